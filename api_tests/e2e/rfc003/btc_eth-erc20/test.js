@@ -74,6 +74,7 @@ describe("RFC003: Bitcoin for ERC20", () => {
                 },
                 beta_ledger: {
                     name: "Ethereum",
+                    network: "regtest",
                 },
                 alpha_asset: {
                     name: "Bitcoin",
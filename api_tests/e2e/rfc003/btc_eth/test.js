@@ -126,6 +126,7 @@ describe("RFC003: Bitcoin for Ether", () => {
                 },
                 beta_ledger: {
                     name: "Ethereum",
+                    network: "regtest",
                 },
                 alpha_asset: {
                     name: "Bitcoin",
@@ -123,6 +123,7 @@ describe("RFC003: Ether for Bitcoin", () => {
             .send({
                 alpha_ledger: {
                     name: "Ethereum",
+                    network: "regtest",
                 },
                 beta_ledger: {
                     name: "Bitcoin",
@@ -18,6 +18,21 @@ function sleep(ms) {
     return new Promise(resolve => setTimeout(resolve, ms));
 }
 
+function poll_until_unconfirmed_matches(query_url) {
+    return chai
+        .request(query_url)
+        .get("")
+        .then(res => {
+            res.should.have.status(200);
+            if (res.body.unconfirmed_matches) {
+                return res.body;
+            }
+            return sleep(200).then(() =>
+                poll_until_unconfirmed_matches(query_url)
+            );
+        });
+}
+
 describe("Test Ledger Query Service API", () => {
     before(async function() {
         this.timeout(5000);
@@ -37,6 +52,15 @@ describe("Test Ledger Query Service API", () => {
                     });
             });
 
+            it("LQS should respond not found when subscribing to a non-existent bitcoin transaction query", async function() {
+                return chai
+                    .request(lqs.url())
+                    .get("/queries/bitcoin/transactions/1/events")
+                    .then(res => {
+                        res.should.have.status(404);
+                    });
+            });
+
             const to_address = "bcrt1qcqslz7lfn34dl096t5uwurff9spen5h4v2pmap";
             let location;
             it("LQS should respond with location when creating a valid bitcoin transaction query", async function() {
@@ -98,6 +122,54 @@ describe("Test Ledger Query Service API", () => {
                 });
             });
 
+            it("LQS should backfill the match of a bitcoin transaction query created after the transaction was mined", async function() {
+                this.slow(1000);
+                return bitcoin_rpc_client.getBlockCount().then(block_count => {
+                    return chai
+                        .request(lqs.url())
+                        .post("/queries/bitcoin/transactions")
+                        .send({
+                            to_address: to_address,
+                            from_block_height: block_count - 2,
+                        })
+                        .then(res => {
+                            res.should.have.status(201);
+                            return lqs
+                                .poll_until_matches(chai, res.headers.location)
+                                .then(body => {
+                                    body.matches.should.have.lengthOf(1);
+                                });
+                        });
+                });
+            });
+
+            it("LQS should report an unconfirmed match of a bitcoin transaction query including unconfirmed transactions", async function() {
+                this.slow(1000);
+                return chai
+                    .request(lqs.url())
+                    .post("/queries/bitcoin/transactions")
+                    .send({
+                        to_address: to_address,
+                        include_unconfirmed: true,
+                    })
+                    .then(res => {
+                        res.should.have.status(201);
+                        const query_location = res.headers.location;
+                        return bitcoin_rpc_client
+                            .sendToAddress(to_address, 1)
+                            .then(tx_id => {
+                                return poll_until_unconfirmed_matches(
+                                    query_location
+                                ).then(body => {
+                                    body.matches.should.be.empty;
+                                    body.unconfirmed_matches.should.deep.equal([
+                                        { id: tx_id, confirmations: 0 },
+                                    ]);
+                                });
+                            });
+                    });
+            });
+
             it("LQS should respond with no content when deleting an existing bitcoin transaction query", async function() {
                 return chai
                     .request(location)
@@ -342,5 +414,63 @@ describe("Test Ledger Query Service API", () => {
                     });
             });
         });
+
+        describe("Logs", () => {
+            const token_contract = "0xb97048628db6b661d4c2aa833e95dbe1a905b280";
+            const transfer_topic =
+                "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+            it("LQS should respond with bad request when creating an ethereum log query without conditions", async function() {
+                return chai
+                    .request(lqs.url())
+                    .post("/queries/ethereum/logs")
+                    .send({
+                        topics: [null],
+                    })
+                    .then(res => {
+                        res.should.have.status(400);
+                    });
+            });
+
+            let location;
+            it("LQS should respond with location when creating a valid ethereum log query", async function() {
+                return chai
+                    .request(lqs.url())
+                    .post("/queries/ethereum/logs")
+                    .send({
+                        address: token_contract,
+                        topics: [transfer_topic],
+                    })
+                    .then(res => {
+                        res.should.have.status(201);
+                        location = res.headers.location;
+                        location.should.be.a("string");
+                    });
+            });
+
+            it("LQS should respond with no match when querying an existing ethereum log query", async function() {
+                return chai
+                    .request(location)
+                    .get("")
+                    .then(res => {
+                        res.should.have.status(200);
+                        res.body.query.address.should.equal(token_contract);
+                        res.body.query.topics.should.deep.equal([
+                            transfer_topic,
+                        ]);
+                        res.body.query.confirmations_needed.should.equal(1);
+                        res.body.matches.should.be.empty;
+                    });
+            });
+
+            it("LQS should respond with no content when deleting an existing ethereum log query", async function() {
+                return chai
+                    .request(location)
+                    .delete("")
+                    .then(res => {
+                        res.should.have.status(204);
+                    });
+            });
+        });
     });
 });
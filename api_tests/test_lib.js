@@ -276,6 +276,11 @@ class ComitConf {
         return "http://" + this.host + ":" + this.config.http_api.port;
     }
 
+    comit_listen_address() {
+        const port = this.config.comit.comit_listen.split(":").pop();
+        return this.host + ":" + port;
+    }
+
     poll_comit_node_until(chai, location, state) {
         return new Promise((final_res, rej) => {
             chai.request(this.comit_node_url())
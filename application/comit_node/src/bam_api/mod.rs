@@ -1,6 +1,6 @@
 use crate::{
     bam_api::header::{Error, FromBamHeader, Header, ToBamHeader},
-    swap_protocols::SwapProtocols,
+    swap_protocols::{AssetKind, LedgerKind, SwapProtocols},
 };
 
 pub mod header;
@@ -29,16 +29,18 @@ mod ledger_impls {
     }
 
     impl FromBamHeader for Ethereum {
-        fn from_bam_header(header: Header) -> Result<Self, Error> {
+        fn from_bam_header(mut header: Header) -> Result<Self, Error> {
             header.has_value("Ethereum")?;
 
-            Ok(Ethereum {})
+            Ok(Ethereum {
+                network: header.parameter("network")?,
+            })
         }
     }
 
     impl ToBamHeader for Ethereum {
         fn to_bam_header(&self) -> Result<Header, Error> {
-            Ok(Header::with_value("Ethereum"))
+            Ok(Header::with_value("Ethereum").with_parameter("network", self.network)?)
         }
     }
 }
@@ -100,6 +102,27 @@ mod asset_impls {
     }
 }
 
+impl FromBamHeader for LedgerKind {
+    fn from_bam_header(header: Header) -> Result<Self, Error> {
+        match header.value() {
+            "Bitcoin" => Ok(LedgerKind::Bitcoin),
+            "Ethereum" => Ok(LedgerKind::Ethereum),
+            _ => Err(Error::WrongValue),
+        }
+    }
+}
+
+impl FromBamHeader for AssetKind {
+    fn from_bam_header(header: Header) -> Result<Self, Error> {
+        match header.value() {
+            "Bitcoin" => Ok(AssetKind::Bitcoin),
+            "Ether" => Ok(AssetKind::Ether),
+            "ERC20" => Ok(AssetKind::Erc20),
+            _ => Err(Error::WrongValue),
+        }
+    }
+}
+
 impl FromBamHeader for SwapProtocols {
     fn from_bam_header(header: Header) -> Result<Self, Error> {
         match header.value() {
@@ -1,24 +1,27 @@
 use crate::{
     bam_api::header::FromBamHeader,
-    comit_client::{self, rfc003::RequestBody, SwapReject},
+    comit_client::{self, rfc003::RequestBody, SwapDeclineReason, SwapReject},
     swap_protocols::{
         asset::Asset,
-        ledger::{Bitcoin, Ethereum},
-        rfc003::{self, bob::BobSpawner, state_machine::StateMachineResponse, Ledger},
-        SwapId, SwapProtocols,
+        rfc003::{
+            self, bob::BobSpawner, state_machine::StateMachineResponse, CreateLedgerEvents, Ledger,
+        },
+        LedgerEventDependencies, Metadata, RoleKind, StartHeights, SwapId, SwapProtocols,
     },
 };
 use bam::{
-    config::Config,
+    config::{Config, ResponseFuture},
     json::{Request, Response},
+    noise::PublicKey,
     Status,
 };
-use bitcoin_support::BitcoinQuantity;
-use ethereum_support::{Erc20Quantity, EtherQuantity};
 use futures::future::{self, Future};
 use std::sync::Arc;
 
-pub fn swap_config<B: BobSpawner>(bob_spawner: Arc<B>) -> Config<Request, Response> {
+pub fn swap_config<B: BobSpawner>(
+    bob_spawner: Arc<B>,
+    counterparty: Option<PublicKey>,
+) -> Config<Request, Response> {
     Config::default().on_request(
         "SWAP",
         &[
@@ -34,91 +37,102 @@ pub fn swap_config<B: BobSpawner>(bob_spawner: Arc<B>) -> Config<Request, Respon
                 SwapProtocols::Rfc003 => {
                     let swap_id = SwapId::default();
 
-                    if let Ok(swap_request) =
-                        decode_request::<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>(
-                            &request,
-                        )
-                    {
-                        let response_future = match bob_spawner.spawn(swap_id, swap_request) {
-                            Ok(response_future) => response_future,
-                            Err(e) => {
-                                error!("Unable to spawn Bob: {:?}", e);
-                                return Box::new(future::ok(Response::new(Status::RE(0))));
-                            }
-                        };
-
-                        Box::new(response_future.then(move |result| match result {
-                            Ok(response) => Ok(to_bam_response::<Bitcoin, Ethereum>(response)),
-                            Err(_) => {
-                                warn!(
-                                    "Failed to receive from oneshot channel for swap {}",
-                                    swap_id
-                                );
-                                Ok(Response::new(Status::SE(0)))
-                            }
-                        }))
-                    } else if let Ok(swap_request) =
-                        decode_request::<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>(
-                            &request,
-                        )
-                    {
-                        let response_future = match bob_spawner.spawn(swap_id, swap_request) {
-                            Ok(response_future) => response_future,
-                            Err(e) => {
-                                error!("Unable to spawn Bob: {:?}", e);
-                                return Box::new(future::ok(Response::new(Status::RE(0))));
-                            }
-                        };
-
-                        Box::new(response_future.then(move |result| match result {
-                            Ok(response) => Ok(to_bam_response::<Bitcoin, Ethereum>(response)),
-                            Err(_) => {
-                                warn!(
-                                    "Failed to receive from oneshot channel for swap {}",
-                                    swap_id
-                                );
-                                Ok(Response::new(Status::SE(0)))
-                            }
-                        }))
-                    } else if let Ok(swap_request) =
-                        decode_request::<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>(
-                            &request,
-                        )
-                    {
-                        let response_future = match bob_spawner.spawn(swap_id, swap_request) {
-                            Ok(response_future) => response_future,
-                            Err(e) => {
-                                error!("Unable to spawn Bob: {:?}", e);
-                                return Box::new(future::ok(Response::new(Status::RE(0))));
-                            }
-                        };
-
-                        Box::new(response_future.then(move |result| match result {
-                            Ok(response) => Ok(to_bam_response::<Ethereum, Bitcoin>(response)),
-                            Err(_) => {
-                                warn!(
-                                    "Failed to receive from oneshot channel for swap {}",
-                                    swap_id
-                                );
-                                Ok(Response::new(Status::SE(0)))
+                    let metadata = match decode_metadata(&request) {
+                        Ok(metadata) => metadata,
+                        Err(_) => return unsupported_swap(),
+                    };
+
+                    crate::with_supported_swap_types!(
+                        metadata,
+                        (|| match decode_request::<AL, BL, AA, BA>(&request, counterparty) {
+                            Ok(swap_request) => {
+                                spawn_bob(bob_spawner.as_ref(), swap_id, swap_request)
                             }
-                        }))
-                    } else {
-                        // TODO: Specify and implement response code
-                        Box::new(future::ok(Response::new(Status::SE(0))))
-                    }
+                            Err(Error::UnsupportedLedger) => unsupported_ledger(),
+                            Err(_) => unsupported_swap(),
+                        }),
+                        unsupported_swap()
+                    )
                 }
             }
         },
     )
 }
 
+fn spawn_bob<B: BobSpawner, AL: Ledger, BL: Ledger, AA: Asset, BA: Asset>(
+    bob_spawner: &B,
+    swap_id: SwapId,
+    swap_request: rfc003::bob::SwapRequest<AL, BL, AA, BA>,
+) -> Box<ResponseFuture<Response>>
+where
+    LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
+{
+    let alpha_ledger_confirmations_needed = swap_request.alpha_ledger_confirmations_needed;
+    let beta_ledger_confirmations_needed = swap_request.beta_ledger_confirmations_needed;
+
+    let response_future = match bob_spawner.spawn(swap_id, swap_request) {
+        Ok(response_future) => response_future,
+        Err(rfc003::bob::Error::UnsupportedNetwork) => {
+            warn!(
+                "Declining swap {} on a network we are not connected to",
+                swap_id
+            );
+            return unsupported_ledger();
+        }
+        Err(rfc003::bob::Error::TooFewConfirmations) => {
+            warn!(
+                "Declining swap {} waiting for fewer confirmations than we accept",
+                swap_id
+            );
+            return Box::new(future::ok(to_bam_response::<AL, BL>(
+                Err(SwapReject::Declined {
+                    reason: Some(SwapDeclineReason::TooFewConfirmations),
+                }),
+                alpha_ledger_confirmations_needed,
+                beta_ledger_confirmations_needed,
+            )));
+        }
+        Err(e) => {
+            error!("Unable to spawn Bob: {:?}", e);
+            return Box::new(future::ok(Response::new(Status::RE(0))));
+        }
+    };
+
+    Box::new(response_future.then(move |result| match result {
+        Ok(response) => Ok(to_bam_response::<AL, BL>(
+            response,
+            alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed,
+        )),
+        Err(_) => {
+            warn!(
+                "Failed to receive from oneshot channel for swap {}",
+                swap_id
+            );
+            Ok(Response::new(Status::SE(0)))
+        }
+    }))
+}
+
+fn unsupported_swap() -> Box<ResponseFuture<Response>> {
+    // TODO: Specify and implement response code
+    Box::new(future::ok(Response::new(Status::SE(0))))
+}
+
+fn unsupported_ledger() -> Box<ResponseFuture<Response>> {
+    Box::new(future::ok(
+        Response::new(Status::SE(20)).with_header("REASON", SwapDeclineReason::UnsupportedLedger),
+    ))
+}
+
 #[allow(clippy::type_complexity)]
 fn to_bam_response<AL: Ledger, BL: Ledger>(
     result: Result<
         StateMachineResponse<AL::HtlcIdentity, BL::HtlcIdentity, BL::LockDuration>,
         SwapReject,
     >,
+    alpha_ledger_confirmations_needed: u32,
+    beta_ledger_confirmations_needed: u32,
 ) -> Response {
     match result {
         Ok(response) => {
@@ -129,6 +143,8 @@ fn to_bam_response<AL: Ledger, BL: Ledger>(
                 beta_ledger_refund_identity: response.beta_ledger_refund_identity.into(),
                 alpha_ledger_redeem_identity: response.alpha_ledger_redeem_identity.into(),
                 beta_ledger_lock_duration: response.beta_ledger_lock_duration,
+                alpha_ledger_confirmations_needed: Some(alpha_ledger_confirmations_needed),
+                beta_ledger_confirmations_needed: Some(beta_ledger_confirmations_needed),
             })
         }
         Err(SwapReject::Declined { reason: None }) => Response::new(Status::SE(20)),
@@ -139,8 +155,37 @@ fn to_bam_response<AL: Ledger, BL: Ledger>(
     }
 }
 
+fn decode_metadata(request: &Request) -> Result<Metadata, Error> {
+    Ok(Metadata {
+        alpha_ledger: decode_header(request, "alpha_ledger")?,
+        beta_ledger: decode_header(request, "beta_ledger")?,
+        alpha_asset: decode_header(request, "alpha_asset")?,
+        beta_asset: decode_header(request, "beta_asset")?,
+        role: RoleKind::Bob,
+        counterparty: None,
+        start_heights: StartHeights::default(),
+    })
+}
+
+fn decode_header<H: FromBamHeader>(request: &Request, key: &str) -> Result<H, Error> {
+    H::from_bam_header(
+        request
+            .get_header(key)
+            .ok_or(Error::Missing)?
+            .map_err(|_| Error::Invalid)?,
+    )
+    .map_err(|_| Error::Invalid)
+}
+
+/// A ledger header we cannot read, e.g. an Ethereum one without the `network`
+/// that older nodes leave out, names a ledger we do not support
+fn decode_ledger_header<L: Ledger>(request: &Request, key: &str) -> Result<L, Error> {
+    decode_header(request, key).map_err(|_| Error::UnsupportedLedger)
+}
+
 fn decode_request<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset>(
     request: &Request,
+    counterparty: Option<PublicKey>,
 ) -> Result<rfc003::bob::SwapRequest<AL, BL, AA, BA>, Error> {
     let request_body: RequestBody<AL, BL> = request
         .get_body()
@@ -148,42 +193,59 @@ fn decode_request<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset>(
         .map_err(|_| Error::Invalid)?;
 
     Ok(rfc003::bob::SwapRequest {
-        alpha_asset: AA::from_bam_header(
-            request
-                .get_header("alpha_asset")
-                .ok_or(Error::Missing)?
-                .map_err(|_| Error::Invalid)?,
-        )
-        .map_err(|_| Error::Invalid)?,
-        beta_asset: BA::from_bam_header(
-            request
-                .get_header("beta_asset")
-                .ok_or(Error::Missing)?
-                .map_err(|_| Error::Invalid)?,
-        )
-        .map_err(|_| Error::Invalid)?,
-        alpha_ledger: AL::from_bam_header(
-            request
-                .get_header("alpha_ledger")
-                .ok_or(Error::Missing)?
-                .map_err(|_| Error::Invalid)?,
-        )
-        .map_err(|_| Error::Invalid)?,
-        beta_ledger: BL::from_bam_header(
-            request
-                .get_header("beta_ledger")
-                .ok_or(Error::Missing)?
-                .map_err(|_| Error::Invalid)?,
-        )
-        .map_err(|_| Error::Invalid)?,
+        alpha_asset: decode_header(request, "alpha_asset")?,
+        beta_asset: decode_header(request, "beta_asset")?,
+        alpha_ledger: decode_ledger_header(request, "alpha_ledger")?,
+        beta_ledger: decode_ledger_header(request, "beta_ledger")?,
         alpha_ledger_refund_identity: request_body.alpha_ledger_refund_identity,
         beta_ledger_redeem_identity: request_body.beta_ledger_redeem_identity,
         alpha_ledger_lock_duration: request_body.alpha_ledger_lock_duration,
+        alpha_ledger_confirmations_needed: request_body.alpha_ledger_confirmations_needed,
+        beta_ledger_confirmations_needed: request_body.beta_ledger_confirmations_needed,
         secret_hash: request_body.secret_hash,
+        counterparty,
     })
 }
 
 enum Error {
     Missing,
     Invalid,
+    UnsupportedLedger,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap_protocols::ledger::Ethereum;
+    use std::collections::HashMap;
+
+    fn request_with_beta_ledger(header: serde_json::Value) -> Request {
+        let mut headers = HashMap::new();
+        headers.insert(String::from("beta_ledger"), header);
+        Request::new(String::from("SWAP"), headers, serde_json::Value::Null)
+    }
+
+    #[test]
+    fn ethereum_ledger_without_network_is_unsupported() {
+        let request = request_with_beta_ledger(json!({ "value": "Ethereum" }));
+
+        match decode_ledger_header::<Ethereum>(&request, "beta_ledger") {
+            Err(Error::UnsupportedLedger) => (),
+            Ok(ledger) => panic!("decoded {:?}", ledger),
+            Err(_) => panic!("expected the ledger to be unsupported"),
+        }
+    }
+
+    #[test]
+    fn ethereum_ledger_with_network_is_decoded() {
+        let request = request_with_beta_ledger(json!({
+            "value": "Ethereum",
+            "parameters": { "network": "regtest" }
+        }));
+
+        match decode_ledger_header::<Ethereum>(&request, "beta_ledger") {
+            Ok(ledger) => assert_eq!(ledger, Ethereum::regtest()),
+            Err(_) => panic!("expected the ledger to be decoded"),
+        }
+    }
 }
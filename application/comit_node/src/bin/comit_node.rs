@@ -5,18 +5,39 @@
 extern crate log;
 use directories;
 
+use bam::noise;
 use comit_node::{
     comit_client, comit_server,
     http_api::route_factory,
-    ledger_query_service::DefaultLedgerQueryServiceApiClient,
+    ledger_query_service::{ChainTip, ChainTips, DefaultLedgerQueryServiceApiClient},
     logging,
-    settings::ComitNodeSettings,
+    seed::Seed,
+    settings::{self, ComitNodeSettings, Storage},
     swap_protocols::{
-        rfc003::{alice::AliceSpawner, bob::BobSpawner, state_store::InMemoryStateStore},
-        InMemoryMetadataStore, LedgerEventDependencies, ProtocolDependencies, SwapId,
+        rfc003::{
+            alice::AliceSpawner,
+            auto_execute::{
+                AutoExecutor, BitcoinCoreWallet, DiskExecutionLog, ExecutionLog,
+                InMemoryExecutionLog, Wallets, Web3Wallet,
+            },
+            bob::BobSpawner,
+            recovery,
+            state_store::{DiskStateStore, InMemoryStateStore, StateStore},
+            SafetyMargin,
+        },
+        DiskMetadataStore, InMemoryMetadataStore, LedgerEventDependencies, MetadataStore,
+        ProtocolDependencies, SwapId,
+    },
+};
+use ethereum_support::{
+    web3::{
+        transports::{EventLoopHandle, Http},
+        Web3,
     },
+    *,
 };
-use ethereum_support::*;
+use futures::future;
+use secp256k1_support::KeyPair;
 use std::{env::var, net::SocketAddr, sync::Arc};
 
 // TODO: Make a nice command line interface here (using StructOpt f.e.) see #298
@@ -27,29 +48,79 @@ fn main() -> Result<(), failure::Error> {
     // TODO: Maybe not print settings because of private keys?
     info!("Starting up with {:#?}", settings);
 
-    let metadata_store = Arc::new(InMemoryMetadataStore::default());
-    let state_store = Arc::new(InMemoryStateStore::default());
-    let lqs_client = create_ledger_query_service_api_client(&settings);
-    let comit_client_factory = Arc::new(comit_client::bam::BamClientPool::default());
+    match settings.storage {
+        Storage::InMemory => run(
+            &settings,
+            Arc::new(InMemoryMetadataStore::default()),
+            Arc::new(InMemoryStateStore::default()),
+        ),
+        Storage::Disk { ref path } => run(
+            &settings,
+            Arc::new(DiskMetadataStore::open(path.join("metadata"))?),
+            Arc::new(DiskStateStore::open(path.join("states"))?),
+        ),
+    }
+}
+
+fn run<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
+    settings: &ComitNodeSettings,
+    metadata_store: Arc<T>,
+    state_store: Arc<S>,
+) -> Result<(), failure::Error> {
+    let lqs_client = create_ledger_query_service_api_client(settings);
+    let chain_tips = Arc::new(create_chain_tips(settings, &lqs_client));
+    let node_identity = node_identity(settings.comit.secret_seed);
+    info!("Node identity is {}", node_identity.public_key());
+    let comit_client_factory = Arc::new(comit_client::bam::BamClientPool::new(
+        node_identity.clone(),
+        settings.comit.secure_transport,
+        settings.peers.clone(),
+        settings.comit.request_timeout_secs,
+        settings.comit.frame_format,
+        settings.comit.max_frame_len,
+    ));
     let dependencies = Arc::new(create_dependencies(
-        &settings,
+        settings,
         Arc::clone(&metadata_store),
         Arc::clone(&state_store),
         Arc::clone(&lqs_client),
+        ChainTips::clone(&chain_tips),
         comit_client_factory.clone(),
     ));
 
     let mut runtime = tokio::runtime::Runtime::new()?;
 
+    runtime.spawn(chain_tips.keep_up_to_date());
+
+    let resumed = {
+        let dependencies = Arc::clone(&dependencies);
+        runtime.block_on(future::lazy(move || recovery::resume_swaps(&*dependencies)))?
+    };
+    info!("Resumed {} swaps from a previous run", resumed);
+
+    // Dropping the handle would stop the event loop of the web3 transport
+    let _ethereum_event_loop = match settings.auto_execute {
+        Some(ref auto_execute) => spawn_auto_executor(
+            settings,
+            auto_execute,
+            Arc::clone(&metadata_store),
+            Arc::clone(&state_store),
+            Arc::clone(&chain_tips),
+            &mut runtime,
+        )?,
+        None => None,
+    };
+
     spawn_warp_instance(
-        &settings,
+        settings,
         Arc::clone(&metadata_store),
         Arc::clone(&state_store),
         dependencies.clone(),
+        chain_tips,
         &mut runtime,
     );
 
-    spawn_comit_server(&settings, dependencies.clone(), &mut runtime);
+    spawn_comit_server(settings, node_identity, dependencies.clone(), &mut runtime);
 
     // Block the current thread.
     ::std::thread::park();
@@ -84,11 +155,28 @@ fn create_ledger_query_service_api_client(
     ))
 }
 
+fn create_chain_tips(
+    settings: &ComitNodeSettings,
+    lqs_client: &Arc<DefaultLedgerQueryServiceApiClient>,
+) -> ChainTips {
+    ChainTips {
+        bitcoin: ChainTip::new(
+            Arc::clone(lqs_client),
+            settings.ledger_query_service.bitcoin.poll_interval_secs,
+        ),
+        ethereum: ChainTip::new(
+            Arc::clone(lqs_client),
+            settings.ledger_query_service.ethereum.poll_interval_secs,
+        ),
+    }
+}
+
 fn create_dependencies<T, S, C>(
     settings: &ComitNodeSettings,
     metadata_store: Arc<T>,
     state_store: Arc<S>,
     lqs_client: Arc<DefaultLedgerQueryServiceApiClient>,
+    chain_tips: ChainTips,
     comit_client_factory: Arc<dyn comit_client::ClientFactory<C>>,
 ) -> ProtocolDependencies<T, S, C> {
     ProtocolDependencies {
@@ -96,26 +184,32 @@ fn create_dependencies<T, S, C>(
             lqs_client,
             lqs_bitcoin_poll_interval: settings.ledger_query_service.bitcoin.poll_interval_secs,
             lqs_ethereum_poll_interval: settings.ledger_query_service.ethereum.poll_interval_secs,
+            chain_tips,
         },
         metadata_store,
         state_store,
         comit_client_factory,
         seed: settings.comit.secret_seed,
         remote_comit_node: settings.comit.remote_comit_node_url,
+        networks: settings.networks,
+        min_confirmations: settings.min_confirmations,
     }
 }
 
-fn spawn_warp_instance<S: AliceSpawner>(
+fn spawn_warp_instance<T: MetadataStore<SwapId>, S: StateStore<SwapId>, A: AliceSpawner>(
     settings: &ComitNodeSettings,
-    metadata_store: Arc<InMemoryMetadataStore<SwapId>>,
-    state_store: Arc<InMemoryStateStore<SwapId>>,
-    alice_spawner: Arc<S>,
+    metadata_store: Arc<T>,
+    state_store: Arc<S>,
+    alice_spawner: Arc<A>,
+    chain_tips: Arc<ChainTips>,
     runtime: &mut tokio::runtime::Runtime,
 ) {
     let routes = route_factory::create(
         metadata_store,
         state_store,
         alice_spawner,
+        chain_tips,
+        SafetyMargin::new(settings.safety_margin.min_time_to_alpha_expiry_secs),
         settings.comit.secret_seed,
     );
 
@@ -127,13 +221,84 @@ fn spawn_warp_instance<S: AliceSpawner>(
     runtime.spawn(server);
 }
 
+fn spawn_auto_executor<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
+    settings: &ComitNodeSettings,
+    auto_execute: &settings::AutoExecute,
+    metadata_store: Arc<T>,
+    state_store: Arc<S>,
+    chain_tips: Arc<ChainTips>,
+    runtime: &mut tokio::runtime::Runtime,
+) -> Result<Option<EventLoopHandle>, failure::Error> {
+    let mut wallets = Wallets::default();
+    let mut event_loop = None;
+
+    if let Some(ref bitcoin) = auto_execute.bitcoin {
+        let client = bitcoin_rpc_client::BitcoinCoreClient::new(
+            bitcoin.node_url.as_str(),
+            bitcoin.node_username.as_str(),
+            bitcoin.node_password.as_str(),
+        );
+        wallets.bitcoin = Some(Arc::new(BitcoinCoreWallet::new(
+            client,
+            bitcoin.payout_address.clone(),
+            bitcoin.fee_per_byte,
+        )));
+    }
+
+    if let Some(ref ethereum) = auto_execute.ethereum {
+        let (handle, transport) =
+            Http::new(ethereum.node_url.as_str()).expect("unable to connect to Ethereum node");
+        wallets.ethereum = Some(Arc::new(Web3Wallet::new(
+            Web3::new(transport),
+            ethereum_fee_keypair(settings.comit.secret_seed),
+        )));
+        event_loop = Some(handle);
+    }
+
+    let execution_log: Arc<dyn ExecutionLog> = match settings.storage {
+        Storage::InMemory => Arc::new(InMemoryExecutionLog::default()),
+        Storage::Disk { ref path } => Arc::new(DiskExecutionLog::open(path.join("executions"))?),
+    };
+
+    let executor = AutoExecutor::new(
+        metadata_store,
+        state_store,
+        chain_tips,
+        SafetyMargin::new(settings.safety_margin.min_time_to_alpha_expiry_secs),
+        wallets,
+        execution_log,
+        auto_execute.retry_interval_secs,
+    );
+    runtime.spawn(executor.keep_executing(auto_execute.poll_interval_secs));
+
+    Ok(event_loop)
+}
+
+fn ethereum_fee_keypair(seed: Seed) -> KeyPair {
+    KeyPair::from_secret_key_slice(seed.sha256_with_seed(&[b"ETHEREUM_WALLET"]).as_ref())
+        .expect("The probability of this happening is < 1 in 2^120")
+}
+
+fn node_identity(seed: Seed) -> noise::Keypair {
+    noise::Keypair::from_secret(seed.sha256_with_seed(&[b"NODE_IDENTITY"]))
+}
+
 fn spawn_comit_server<B: BobSpawner>(
     settings: &ComitNodeSettings,
+    node_identity: noise::Keypair,
     bob_spawner: Arc<B>,
     runtime: &mut tokio::runtime::Runtime,
 ) {
     runtime.spawn(
-        comit_server::listen(settings.comit.comit_listen, bob_spawner).map_err(|e| {
+        comit_server::listen(
+            settings.comit.comit_listen,
+            bob_spawner,
+            node_identity,
+            settings.comit.secure_transport,
+            settings.comit.max_frame_len,
+            Arc::new(settings.peers.clone()),
+        )
+        .map_err(|e| {
             error!("ComitServer shutdown: {:?}", e);
         }),
     );
@@ -4,33 +4,53 @@ use crate::{
         rfc003, Client, ClientFactory, ClientFactoryError, SwapDeclineReason, SwapReject,
         SwapResponseError,
     },
+    settings::{Peers, SecureTransport},
     swap_protocols::{self, asset::Asset, SwapProtocols},
 };
-use bam::{self, config::Config, connection::Connection, json, Status};
-use futures::Future;
+use bam::{
+    self,
+    config::Config,
+    connection::{Connection, ConnectionLoop},
+    json, noise, Status,
+};
+use futures::{
+    future::{self, Either},
+    Future,
+};
 use std::{
     collections::HashMap,
     net::SocketAddr,
     sync::{Arc, Mutex, RwLock},
+    time::{Duration, Instant},
 };
-use tokio::{self, net::TcpStream};
+use tokio::{self, net::TcpStream, timer::Delay};
+
+type JsonClient = bam::client::Client<json::Frame, json::Request, json::Response>;
 
 #[derive(Debug)]
 pub struct BamClient {
     comit_node_socket_addr: SocketAddr,
-    bam_client: Arc<Mutex<bam::client::Client<json::Frame, json::Request, json::Response>>>,
+    bam_client: Arc<Mutex<JsonClient>>,
+    counterparty: Option<noise::PublicKey>,
 }
 
 impl BamClient {
     pub fn new(
         comit_node_socket_addr: SocketAddr,
-        bam_client: bam::client::Client<json::Frame, json::Request, json::Response>,
+        bam_client: JsonClient,
+        counterparty: Option<noise::PublicKey>,
     ) -> Self {
         BamClient {
             comit_node_socket_addr,
             bam_client: Arc::new(Mutex::new(bam_client)),
+            counterparty,
         }
     }
+
+    /// Sends all further requests over the connection `bam_client` belongs to
+    fn rebind(&self, bam_client: JsonClient) {
+        *self.bam_client.lock().unwrap() = bam_client;
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -56,6 +76,8 @@ impl Client for BamClient {
         let alpha_ledger_refund_identity = request.alpha_ledger_refund_identity;
         let beta_ledger_redeem_identity = request.beta_ledger_redeem_identity;
         let alpha_ledger_lock_duration = request.alpha_ledger_lock_duration;
+        let alpha_ledger_confirmations_needed = request.alpha_ledger_confirmations_needed;
+        let beta_ledger_confirmations_needed = request.beta_ledger_confirmations_needed;
         let secret_hash = request.secret_hash;
 
         let request = json::Request::new(
@@ -75,6 +97,8 @@ impl Client for BamClient {
                 alpha_ledger_refund_identity,
                 beta_ledger_redeem_identity,
                 alpha_ledger_lock_duration,
+                alpha_ledger_confirmations_needed,
+                beta_ledger_confirmations_needed,
                 secret_hash,
             })
             .expect("should not fail to serialize"),
@@ -129,6 +153,10 @@ impl Client for BamClient {
                         Err(SwapResponseError::InternalError)
                     }
                 },
+                Err(bam::client::Error::Timeout) => {
+                    warn!("{} did not answer swap request in time", socket_addr);
+                    Err(SwapResponseError::Timeout)
+                }
                 Err(transport_error) => {
                     error!(
                         "transport error during request to {:?}:{:?}",
@@ -140,6 +168,10 @@ impl Client for BamClient {
 
         Box::new(response)
     }
+
+    fn counterparty(&self) -> Option<noise::PublicKey> {
+        self.counterparty
+    }
 }
 
 fn to_json_value(
@@ -150,9 +182,304 @@ fn to_json_value(
     serde_json::to_value(header).expect("converting bam-header to json must not fail")
 }
 
-#[derive(Default, Debug)]
+/// Keeps idle connections from being dropped by NATs and firewalls, and makes
+/// a connection whose other end silently went away fail on the next write.
+pub const HEARTBEAT_INTERVAL_SECS: u64 = 30;
+/// Missing three heartbeats in a row means the other end is gone, even if
+/// writing to it still succeeds.
+pub const READ_IDLE_TIMEOUT_SECS: u64 = 3 * HEARTBEAT_INTERVAL_SECS;
+/// Waiting 1, 2, 4, ... seconds in between, this gives up after about a minute.
+const MAX_RECONNECT_ATTEMPTS: u32 = 6;
+/// A connection that closes sooner than this counts as a failed attempt, so
+/// that a peer which accepts and immediately drops us is eventually given up.
+const STABLE_CONNECTION_SECS: u64 = 60;
+
+type Connected = (
+    ConnectionLoop<json::Error>,
+    JsonClient,
+    Option<noise::PublicKey>,
+);
+type Clients = Arc<RwLock<HashMap<SocketAddr, Arc<BamClient>>>>;
+type Reconnect = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Holds one client per COMIT node. A client whose connection closes is
+/// removed and, as long as a swap still holds on to it, reconnected in the
+/// background.
+#[derive(Debug)]
 pub struct BamClientPool {
-    clients: RwLock<HashMap<SocketAddr, Arc<BamClient>>>,
+    clients: Clients,
+    connector: Connector,
+}
+
+impl BamClientPool {
+    pub fn new(
+        node_identity: noise::Keypair,
+        secure_transport: SecureTransport,
+        peers: Peers,
+        request_timeout: Duration,
+        frame_format: json::FrameFormat,
+        max_frame_len: usize,
+    ) -> Self {
+        BamClientPool {
+            clients: Arc::new(RwLock::new(HashMap::new())),
+            connector: Connector {
+                node_identity,
+                secure_transport,
+                peers,
+                request_timeout,
+                frame_format,
+                max_frame_len,
+                reconnect_backoff: Duration::from_secs(1),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Connector {
+    node_identity: noise::Keypair,
+    secure_transport: SecureTransport,
+    peers: Peers,
+    request_timeout: Duration,
+    frame_format: json::FrameFormat,
+    max_frame_len: usize,
+    /// Doubled after every failed attempt
+    reconnect_backoff: Duration,
+}
+
+impl Connector {
+    fn connect(
+        &self,
+        comit_node_socket_addr: SocketAddr,
+    ) -> Box<dyn Future<Item = Connected, Error = ClientFactoryError> + Send> {
+        let pinned_key = self.peers.pinned_key_of(&comit_node_socket_addr);
+
+        if self.secure_transport == SecureTransport::Disabled {
+            if let Some(expected) = pinned_key {
+                return Box::new(future::err(ClientFactoryError::UnexpectedPeer {
+                    expected,
+                    actual: None,
+                }));
+            }
+            if !self.peers.is_allowed(None) {
+                return Box::new(future::err(ClientFactoryError::PeerNotAllowed(None)));
+            }
+        }
+
+        let connector = self.clone();
+
+        Box::new(
+            TcpStream::connect(&comit_node_socket_addr)
+                .from_err::<ClientFactoryError>()
+                .and_then(move |socket| {
+                    info!("Connection to {} established", comit_node_socket_addr);
+                    let codec = json::NegotiatedCodec::offering(connector.frame_format)
+                        .with_max_frame_len(connector.max_frame_len);
+                    let config = Config::<json::Request, json::Response>::default()
+                        .with_request_timeout(connector.request_timeout)
+                        .with_heartbeat_interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS))
+                        .with_read_idle_timeout(Duration::from_secs(READ_IDLE_TIMEOUT_SECS));
+
+                    match connector.secure_transport {
+                        SecureTransport::Disabled => {
+                            let connection = Connection::new(config, codec, socket);
+                            let (connection_future, client) =
+                                connection.start::<json::JsonFrameHandler>();
+                            Either::A(future::ok((connection_future, client, None)))
+                        }
+                        SecureTransport::Enabled | SecureTransport::Required => Either::B(
+                            noise::initiate(socket, connector.node_identity.clone())
+                                .from_err::<ClientFactoryError>()
+                                .and_then(move |(socket, transport)| {
+                                    let counterparty = transport.remote_public_key();
+                                    info!(
+                                        "{} authenticated as {}",
+                                        comit_node_socket_addr, counterparty
+                                    );
+
+                                    if let Some(expected) = pinned_key {
+                                        if counterparty != expected {
+                                            return Err(ClientFactoryError::UnexpectedPeer {
+                                                expected,
+                                                actual: Some(counterparty),
+                                            });
+                                        }
+                                    }
+
+                                    if !connector.peers.is_allowed(Some(&counterparty)) {
+                                        return Err(ClientFactoryError::PeerNotAllowed(Some(
+                                            counterparty,
+                                        )));
+                                    }
+
+                                    let codec = noise::NoiseCodec::new(transport, codec);
+                                    let connection = Connection::new(config, codec, socket);
+                                    let (connection_future, client) =
+                                        connection.start::<json::JsonFrameHandler>();
+                                    Ok((connection_future, client, Some(counterparty)))
+                                }),
+                        ),
+                    }
+                }),
+        )
+    }
+}
+
+fn add_client(
+    clients: Clients,
+    connector: Connector,
+    comit_node_socket_addr: SocketAddr,
+    (connection_future, client, counterparty): Connected,
+) -> Arc<BamClient> {
+    let client = Arc::new(BamClient::new(comit_node_socket_addr, client, counterparty));
+    clients
+        .write()
+        .unwrap()
+        .insert(comit_node_socket_addr, client.clone());
+
+    watch_connection(clients, connector, client.clone(), connection_future, 0);
+
+    client
+}
+
+/// Once the connection of `client` closes, removes `client` from the pool and
+/// tries to reconnect it. `attempt` is the number of reconnect attempts that
+/// preceded this connection.
+fn watch_connection(
+    clients: Clients,
+    connector: Connector,
+    client: Arc<BamClient>,
+    connection_future: ConnectionLoop<json::Error>,
+    attempt: u32,
+) {
+    let comit_node_socket_addr = client.comit_node_socket_addr;
+    let connected_at = Instant::now();
+
+    tokio::spawn(connection_future.then(move |result| {
+        match result {
+            Ok(()) => info!("Connection to {} closed", comit_node_socket_addr),
+            Err(e) => error!(
+                "Connection to {:?} prematurely closed: {:?}",
+                comit_node_socket_addr, e
+            ),
+        }
+
+        {
+            let mut clients = clients.write().unwrap();
+            let is_current = clients
+                .get(&comit_node_socket_addr)
+                .map_or(false, |current| Arc::ptr_eq(current, &client));
+            if is_current {
+                clients.remove(&comit_node_socket_addr);
+            }
+        }
+
+        let attempt = if connected_at.elapsed() >= Duration::from_secs(STABLE_CONNECTION_SECS) {
+            0
+        } else {
+            attempt + 1
+        };
+
+        reconnect(clients, connector, client, attempt)
+    }));
+}
+
+/// Nobody but the caller holds on to `client` anymore, i.e. no swap uses it.
+fn is_unused(client: &Arc<BamClient>) -> bool {
+    Arc::strong_count(client) == 1
+}
+
+fn reconnect(
+    clients: Clients,
+    connector: Connector,
+    client: Arc<BamClient>,
+    attempt: u32,
+) -> Reconnect {
+    let comit_node_socket_addr = client.comit_node_socket_addr;
+
+    if is_unused(&client) {
+        debug!(
+            "Not reconnecting to {} because no swap uses the connection",
+            comit_node_socket_addr
+        );
+        return Box::new(future::ok(()));
+    }
+
+    if attempt >= MAX_RECONNECT_ATTEMPTS {
+        warn!(
+            "Giving up on reconnecting to {} after {} attempts",
+            comit_node_socket_addr, attempt
+        );
+        return Box::new(future::ok(()));
+    }
+
+    let backoff = connector.reconnect_backoff * 2u32.pow(attempt);
+    debug!(
+        "Reconnecting to {} in {:?}",
+        comit_node_socket_addr, backoff
+    );
+
+    Box::new(
+        Delay::new(Instant::now() + backoff)
+            .map_err(|e| error!("Reconnect timer failed: {:?}", e))
+            .and_then(move |_| -> Reconnect {
+                // The swaps may have finished in the meantime
+                if is_unused(&client) {
+                    return Box::new(future::ok(()));
+                }
+
+                Box::new(connector.connect(comit_node_socket_addr).then(
+                    move |result| -> Reconnect {
+                        match result {
+                            Ok((connection_future, bam_client, counterparty)) => {
+                                if counterparty != client.counterparty {
+                                    warn!(
+                                        "Not reconnecting to {} because it authenticated as {:?} instead of {:?}",
+                                        comit_node_socket_addr, counterparty, client.counterparty
+                                    );
+                                    return Box::new(future::ok(()));
+                                }
+
+                                client.rebind(bam_client);
+                                clients
+                                    .write()
+                                    .unwrap()
+                                    .entry(comit_node_socket_addr)
+                                    .or_insert_with(|| client.clone());
+                                info!("Reconnected to {}", comit_node_socket_addr);
+
+                                watch_connection(
+                                    clients,
+                                    connector,
+                                    client,
+                                    connection_future,
+                                    attempt,
+                                );
+                                Box::new(future::ok(()))
+                            }
+                            Err(ClientFactoryError::PeerNotAllowed(peer)) => {
+                                warn!(
+                                    "Not reconnecting to {} because {:?} is not allowed",
+                                    comit_node_socket_addr, peer
+                                );
+                                Box::new(future::ok(()))
+                            }
+                            Err(ClientFactoryError::UnexpectedPeer { expected, actual }) => {
+                                warn!(
+                                    "Not reconnecting to {} because it authenticated as {:?} instead of {}",
+                                    comit_node_socket_addr, actual, expected
+                                );
+                                Box::new(future::ok(()))
+                            }
+                            Err(e) => {
+                                warn!("Failed to reconnect to {}: {:?}", comit_node_socket_addr, e);
+                                reconnect(clients, connector, client, attempt + 1)
+                            }
+                        }
+                    },
+                ))
+            }),
+    )
 }
 
 impl ClientFactory<BamClient> for BamClientPool {
@@ -175,22 +502,13 @@ impl ClientFactory<BamClient> for BamClientPool {
                     "No existing connection to {}. Trying to connect.",
                     comit_node_socket_addr
                 );
-                let socket = TcpStream::connect(&comit_node_socket_addr).wait()?;
-                info!("Connection to {} established", comit_node_socket_addr);
-                let codec = json::JsonFrameCodec::default();
-                let config = Config::<json::Request, json::Response>::default();
-                let connection = Connection::new(config, codec, socket);
-                let (connection_future, client) = connection.start::<json::JsonFrameHandler>();
-                let socket_addr = comit_node_socket_addr;
-                tokio::spawn(connection_future.map_err(move |e| {
-                    error!(
-                        "Connection to {:?} prematurely closed: {:?}",
-                        socket_addr, e
-                    )
-                }));
-                let client = Arc::new(BamClient::new(comit_node_socket_addr, client));
-                let mut clients = self.clients.write().unwrap();
-                clients.insert(comit_node_socket_addr, client.clone());
+                let connected = self.connector.connect(comit_node_socket_addr).wait()?;
+                let client = add_client(
+                    Arc::clone(&self.clients),
+                    self.connector.clone(),
+                    comit_node_socket_addr,
+                    connected,
+                );
                 debug!(
                     "Client for {} created by making a new connection",
                     comit_node_socket_addr
@@ -204,3 +522,63 @@ impl ClientFactory<BamClient> for BamClientPool {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bam::FrameHandler;
+    use spectral::prelude::*;
+    use tokio::runtime::Runtime;
+
+    fn connector() -> Connector {
+        Connector {
+            node_identity: noise::Keypair::new_random().unwrap(),
+            secure_transport: SecureTransport::Disabled,
+            peers: Peers::default(),
+            request_timeout: Duration::from_secs(1),
+            frame_format: json::FrameFormat::NewlineDelimited,
+            max_frame_len: json::DEFAULT_MAX_FRAME_LEN,
+            reconnect_backoff: Duration::from_millis(1),
+        }
+    }
+
+    fn disconnected_client(comit_node_socket_addr: SocketAddr) -> Arc<BamClient> {
+        let (_, response_source) = json::JsonFrameHandler::create(Config::default());
+        let (bam_client, _) = JsonClient::create(response_source, None);
+
+        Arc::new(BamClient::new(comit_node_socket_addr, bam_client, None))
+    }
+
+    fn unreachable_address() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[test]
+    fn given_peer_is_unreachable_reconnect_gives_up() {
+        let mut runtime = Runtime::new().unwrap();
+        let clients: Clients = Arc::new(RwLock::new(HashMap::new()));
+        let client = disconnected_client(unreachable_address());
+        let _swap = client.clone();
+
+        let result = runtime.block_on(reconnect(clients.clone(), connector(), client, 0));
+
+        assert_that(&result).is_ok();
+        assert_that(&clients.read().unwrap().is_empty()).is_true();
+    }
+
+    #[test]
+    fn given_no_swap_uses_the_client_reconnect_does_not_try() {
+        let mut runtime = Runtime::new().unwrap();
+        let clients: Clients = Arc::new(RwLock::new(HashMap::new()));
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let client = disconnected_client(listener.local_addr().unwrap());
+
+        let result = runtime.block_on(reconnect(clients.clone(), connector(), client, 0));
+
+        assert_that(&result).is_ok();
+        assert_that(&listener.accept().is_err()).is_true();
+        assert_that(&clients.read().unwrap().is_empty()).is_true();
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
     },
     swap_protocols::{self, asset::Asset},
 };
+use bam::noise::PublicKey;
 use futures::{
     sync::oneshot::{self, Sender},
     Future,
@@ -72,6 +73,10 @@ impl Client for FakeClient {
                 .to_owned()
         }))
     }
+
+    fn counterparty(&self) -> Option<PublicKey> {
+        None
+    }
 }
 
 #[derive(Debug, Default)]
@@ -3,6 +3,7 @@ pub mod fake;
 pub mod rfc003;
 
 use crate::swap_protocols::{self, asset::Asset};
+use bam::noise;
 use futures::Future;
 use std::{fmt::Debug, io, net::SocketAddr, sync::Arc};
 
@@ -21,13 +22,17 @@ pub trait Client: Send + Sync + 'static {
                 Error = SwapResponseError,
             > + Send,
     >;
+
+    /// The node identity the counterparty authenticated with, if the
+    /// connection uses the secure transport
+    fn counterparty(&self) -> Option<noise::PublicKey>;
 }
 
 pub trait ClientFactory<C>: Send + Sync + Debug {
     fn client_for(&self, comit_node_socket_addr: SocketAddr) -> Result<Arc<C>, ClientFactoryError>;
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SwapReject {
     Declined { reason: Option<SwapDeclineReason> },
     Rejected,
@@ -36,9 +41,11 @@ pub enum SwapReject {
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SwapDeclineReason {
     BadRate,
+    TooFewConfirmations,
+    UnsupportedLedger,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SwapResponseError {
     /// The counterparty had an internal error while processing the request
     InternalError,
@@ -46,11 +53,25 @@ pub enum SwapResponseError {
     TransportError,
     /// The counterparty produced an invalid response to the request
     InvalidResponse,
+    /// The counterparty did not respond within the configured request timeout
+    Timeout,
+    /// We canceled the request before the counterparty responded
+    Canceled,
 }
 
 #[derive(Debug)]
 pub enum ClientFactoryError {
     Connection(io::Error),
+    Handshake(noise::Error),
+    /// The counterparty is not among the allowed peers, `None` if it could
+    /// not be identified
+    PeerNotAllowed(Option<noise::PublicKey>),
+    /// The counterparty did not authenticate with the key pinned for its
+    /// address, `None` if it could not be identified
+    UnexpectedPeer {
+        expected: noise::PublicKey,
+        actual: Option<noise::PublicKey>,
+    },
 }
 
 impl From<io::Error> for ClientFactoryError {
@@ -58,3 +79,9 @@ impl From<io::Error> for ClientFactoryError {
         ClientFactoryError::Connection(e)
     }
 }
+
+impl From<noise::Error> for ClientFactoryError {
+    fn from(e: noise::Error) -> Self {
+        ClientFactoryError::Handshake(e)
+    }
+}
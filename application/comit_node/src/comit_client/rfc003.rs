@@ -9,14 +9,42 @@ pub struct Request<AL: Ledger, BL: Ledger, AA, BA> {
     pub alpha_ledger_refund_identity: AL::Identity,
     pub beta_ledger_redeem_identity: BL::Identity,
     pub alpha_ledger_lock_duration: AL::LockDuration,
+    pub alpha_ledger_confirmations_needed: u32,
+    pub beta_ledger_confirmations_needed: u32,
     pub secret_hash: SecretHash,
 }
 
+/// The block including a transaction counts as its first confirmation, so a
+/// swap that asks for none acts on a transaction as soon as it is mined
+pub fn default_confirmations_needed() -> u32 {
+    1
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct AcceptResponseBody<AL: Ledger, BL: Ledger> {
     pub beta_ledger_refund_identity: BL::Identity,
     pub alpha_ledger_redeem_identity: AL::Identity,
     pub beta_ledger_lock_duration: BL::LockDuration,
+    /// The confirmations Bob waits for, peers that don't send them are taken
+    /// to wait for those of the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alpha_ledger_confirmations_needed: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub beta_ledger_confirmations_needed: Option<u32>,
+}
+
+impl<AL: Ledger, BL: Ledger> AcceptResponseBody<AL, BL> {
+    pub fn waits_for_confirmations_of<AA, BA>(&self, request: &Request<AL, BL, AA, BA>) -> bool {
+        self.alpha_ledger_confirmations_needed
+            .map_or(true, |confirmations| {
+                confirmations == request.alpha_ledger_confirmations_needed
+            })
+            && self
+                .beta_ledger_confirmations_needed
+                .map_or(true, |confirmations| {
+                    confirmations == request.beta_ledger_confirmations_needed
+                })
+    }
 }
 
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
@@ -24,5 +52,11 @@ pub struct RequestBody<AL: Ledger, BL: Ledger> {
     pub alpha_ledger_refund_identity: AL::Identity,
     pub beta_ledger_redeem_identity: BL::Identity,
     pub alpha_ledger_lock_duration: AL::LockDuration,
+    /// Both parties wait for this many confirmations of the transactions on
+    /// the respective ledger, peers that don't send them use the default
+    #[serde(default = "default_confirmations_needed")]
+    pub alpha_ledger_confirmations_needed: u32,
+    #[serde(default = "default_confirmations_needed")]
+    pub beta_ledger_confirmations_needed: u32,
     pub secret_hash: SecretHash,
 }
@@ -1,35 +1,311 @@
-use crate::{bam_api::rfc003::swap_config, swap_protocols::rfc003::bob::BobSpawner};
-use bam::{connection::Connection, json};
-use futures::{Future, Stream};
-use std::{io, net::SocketAddr, sync::Arc};
-use tokio::{self, net::TcpListener};
+use crate::{
+    bam_api::rfc003::swap_config,
+    comit_client::bam::{HEARTBEAT_INTERVAL_SECS, READ_IDLE_TIMEOUT_SECS},
+    settings::{Peers, SecureTransport},
+    swap_protocols::rfc003::bob::BobSpawner,
+};
+use bam::{
+    config::Config,
+    connection::{Connection, ConnectionLoop},
+    json, noise,
+};
+use futures::{future, Async, Future, Stream};
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    self,
+    net::{TcpListener, TcpStream},
+    timer::Timeout,
+};
+
+/// A peer that neither sends a frame nor completes the Noise handshake
+/// within this time is dropped, so idle sockets cannot pile up.
+const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+type AcceptFuture = Box<dyn Future<Item = (), Error = noise::Error> + Send>;
 
 pub fn listen<B: BobSpawner>(
     addr: SocketAddr,
     bob_spawner: Arc<B>,
+    node_identity: noise::Keypair,
+    secure_transport: SecureTransport,
+    max_frame_len: usize,
+    peers: Arc<Peers>,
 ) -> impl Future<Item = (), Error = io::Error> {
     info!("ComitServer listening at {:?}", addr);
     let socket = TcpListener::bind(&addr).unwrap();
 
+    serve(
+        socket,
+        bob_spawner,
+        node_identity,
+        secure_transport,
+        max_frame_len,
+        peers,
+    )
+}
+
+fn serve<B: BobSpawner>(
+    socket: TcpListener,
+    bob_spawner: Arc<B>,
+    node_identity: noise::Keypair,
+    secure_transport: SecureTransport,
+    max_frame_len: usize,
+    peers: Arc<Peers>,
+) -> impl Future<Item = (), Error = io::Error> {
     socket.incoming().for_each(move |connection| {
-        let peer_addr = connection.peer_addr();
-        let codec = json::JsonFrameCodec::default();
+        let peer_addr = match connection.peer_addr() {
+            Ok(peer_addr) => peer_addr,
+            Err(e) => {
+                warn!("Dropping connection without peer address: {:?}", e);
+                return Ok(());
+            }
+        };
+
+        if secure_transport == SecureTransport::Disabled {
+            serve_plaintext(
+                Arc::clone(&bob_spawner),
+                &peers,
+                max_frame_len,
+                connection,
+                peer_addr,
+            );
+            return Ok(());
+        }
+
+        let bob_spawner = Arc::clone(&bob_spawner);
+        let node_identity = node_identity.clone();
+        let peers = Arc::clone(&peers);
+
+        let accept = starts_with_plaintext_frame(connection)
+            .map_err(noise::Error::from)
+            .and_then(move |(connection, is_plaintext)| -> AcceptFuture {
+                if is_plaintext {
+                    if secure_transport == SecureTransport::Required {
+                        warn!("Refusing plaintext connection from {:?}", peer_addr);
+                    } else {
+                        serve_plaintext(bob_spawner, &peers, max_frame_len, connection, peer_addr);
+                    }
+                    return Box::new(future::ok(()));
+                }
+
+                Box::new(noise::respond(connection, node_identity).map(
+                    move |(connection, transport)| {
+                        let counterparty = transport.remote_public_key();
+                        info!(
+                            "Connection with {:?} authenticated as {}",
+                            peer_addr, counterparty
+                        );
 
-        let config = swap_config(Arc::clone(&bob_spawner));
+                        if !peers.is_allowed(Some(&counterparty)) {
+                            warn!("Refusing connection from peer {}", counterparty);
+                            return;
+                        }
 
-        let connection = Connection::new(config, codec, connection);
-        let (close_future, _client) = connection.start::<json::JsonFrameHandler>();
+                        let codec = noise::NoiseCodec::new(
+                            transport,
+                            json::NegotiatedCodec::accepting().with_max_frame_len(max_frame_len),
+                        );
+                        let config = keep_alive(swap_config(bob_spawner, Some(counterparty)));
+                        let (close_future, _client) = Connection::new(config, codec, connection)
+                            .start::<json::JsonFrameHandler>();
 
-        tokio::spawn(close_future.then(move |result| {
-            match result {
-                Ok(()) => info!("Connection with {:?} closed", peer_addr),
-                Err(e) => error!(
-                    "Unexpected error in connection with {:?}: {:?}",
-                    peer_addr, e
-                ),
+                        spawn_connection(close_future, peer_addr);
+                    },
+                ))
+            });
+
+        let accept = Timeout::new(accept, Duration::from_secs(HANDSHAKE_TIMEOUT_SECS));
+
+        tokio::spawn(accept.map_err(move |e| {
+            if e.is_elapsed() {
+                warn!("Handshake with {:?} timed out", peer_addr);
+            } else {
+                error!("Handshake with {:?} failed: {:?}", peer_addr, e);
             }
-            Ok(())
         }));
         Ok(())
     })
 }
+
+/// Plaintext BAM frames start with either a JSON object or the preamble of
+/// their frame format whereas a Noise handshake starts with a length prefix,
+/// hence the first byte that is not ASCII whitespace tells the two apart.
+fn starts_with_plaintext_frame(
+    connection: TcpStream,
+) -> impl Future<Item = (TcpStream, bool), Error = io::Error> {
+    let mut connection = Some(connection);
+
+    future::poll_fn(move || {
+        let mut leading_bytes = [0u8; 16];
+        let read = try_ready!(connection
+            .as_mut()
+            .expect("polled after completion")
+            .poll_peek(&mut leading_bytes));
+
+        let connection = connection.take().expect("polled after completion");
+        Ok(Async::Ready((
+            connection,
+            json::FrameFormat::from_first_byte(&leading_bytes[..read]).is_some(),
+        )))
+    })
+}
+
+fn serve_plaintext<B: BobSpawner>(
+    bob_spawner: Arc<B>,
+    peers: &Peers,
+    max_frame_len: usize,
+    connection: TcpStream,
+    peer_addr: SocketAddr,
+) {
+    if !peers.is_allowed(None) {
+        warn!(
+            "Refusing unauthenticated connection from {:?} because peers are filtered",
+            peer_addr
+        );
+        return;
+    }
+
+    let codec = json::NegotiatedCodec::accepting().with_max_frame_len(max_frame_len);
+    let config = keep_alive(swap_config(bob_spawner, None));
+
+    let (close_future, _client) =
+        Connection::new(config, codec, connection).start::<json::JsonFrameHandler>();
+
+    spawn_connection(close_future, peer_addr);
+}
+
+/// Our clients expect heartbeats just like we expect theirs
+fn keep_alive(
+    config: Config<json::Request, json::Response>,
+) -> Config<json::Request, json::Response> {
+    config
+        .with_heartbeat_interval(Duration::from_secs(HEARTBEAT_INTERVAL_SECS))
+        .with_read_idle_timeout(Duration::from_secs(READ_IDLE_TIMEOUT_SECS))
+}
+
+fn spawn_connection(close_future: ConnectionLoop<json::Error>, peer_addr: SocketAddr) {
+    tokio::spawn(close_future.then(move |result| {
+        match result {
+            Ok(()) => info!("Connection with {:?} closed", peer_addr),
+            Err(e) => error!(
+                "Unexpected error in connection with {:?}: {:?}",
+                peer_addr, e
+            ),
+        }
+        Ok(())
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap_protocols::{
+        asset::Asset,
+        rfc003::{bob, events::ResponseFuture, Bob, CreateLedgerEvents, Ledger},
+        LedgerEventDependencies, SwapId,
+    };
+    use spectral::prelude::*;
+    use std::io::{ErrorKind, Read, Write};
+
+    struct UnusedBobSpawner;
+
+    impl BobSpawner for UnusedBobSpawner {
+        #[allow(clippy::type_complexity)]
+        fn spawn<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset>(
+            &self,
+            _id: SwapId,
+            _swap_request: bob::SwapRequest<AL, BL, AA, BA>,
+        ) -> Result<Box<ResponseFuture<Bob<AL, BL, AA, BA>>>, bob::Error>
+        where
+            LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
+        {
+            unimplemented!()
+        }
+    }
+
+    /// Whether the server still holds a plaintext connection open after the
+    /// first bytes of a frame arrived
+    fn keeps_plaintext_connection(
+        first_bytes: &[u8],
+        secure_transport: SecureTransport,
+        peers: Peers,
+    ) -> bool {
+        let socket = TcpListener::bind(&"127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.spawn(
+            serve(
+                socket,
+                Arc::new(UnusedBobSpawner),
+                noise::Keypair::from_secret([1; 32]),
+                secure_transport,
+                json::DEFAULT_MAX_FRAME_LEN,
+                Arc::new(peers),
+            )
+            .map_err(|_| ()),
+        );
+
+        let mut connection = std::net::TcpStream::connect(addr).unwrap();
+        connection
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        connection.write_all(first_bytes).unwrap();
+
+        let kept = match connection.read(&mut [0u8; 1]) {
+            Ok(0) => false,
+            Err(ref e) if e.kind() == ErrorKind::ConnectionReset => false,
+            Err(ref e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                true
+            }
+            result => panic!("unexpected read result {:?}", result),
+        };
+
+        runtime.shutdown_now().wait().unwrap();
+        kept
+    }
+
+    fn denying_a_peer() -> Peers {
+        Peers {
+            allowed: None,
+            denied: vec![noise::PublicKey::from_bytes([2; 32])],
+            pinned: vec![],
+        }
+    }
+
+    #[test]
+    fn plaintext_connection_is_served_without_peer_filters() {
+        assert_that(&keeps_plaintext_connection(
+            b"{",
+            SecureTransport::Disabled,
+            Peers::default(),
+        ))
+        .is_true();
+    }
+
+    #[test]
+    fn plaintext_connection_is_served_after_leading_whitespace() {
+        assert_that(&keeps_plaintext_connection(
+            b"\r\n{",
+            SecureTransport::Disabled,
+            Peers::default(),
+        ))
+        .is_true();
+    }
+
+    #[test]
+    fn plaintext_connection_is_refused_once_a_peer_is_denied() {
+        assert_that(&keeps_plaintext_connection(
+            b"{",
+            SecureTransport::Disabled,
+            denying_a_peer(),
+        ))
+        .is_false();
+        assert_that(&keeps_plaintext_connection(
+            b"{",
+            SecureTransport::Enabled,
+            denying_a_peer(),
+        ))
+        .is_false();
+    }
+}
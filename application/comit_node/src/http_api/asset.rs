@@ -17,6 +17,10 @@ impl HttpAsset {
         }
     }
 
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn parameter<P: DeserializeOwned>(&mut self, key: &'static str) -> Result<P, Error> {
         let parameter = self
             .parameters
@@ -0,0 +1,31 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HttpExpiry {
+    #[serde(rename = "type")]
+    expiry_type: String,
+    value: u64,
+}
+
+impl HttpExpiry {
+    pub fn with_expiry(name: &'static str, value: u64) -> HttpExpiry {
+        HttpExpiry {
+            expiry_type: String::from(name),
+            value,
+        }
+    }
+}
+
+macro_rules! impl_to_http_expiry {
+    ($block_time:ty, $name:expr) => {
+        impl ToHttpExpiry for $block_time {
+            fn to_http_expiry(&self) -> HttpExpiry {
+                HttpExpiry::with_expiry($name, self.0)
+            }
+        }
+    };
+}
+
+pub trait ToHttpExpiry {
+    fn to_http_expiry(&self) -> HttpExpiry;
+}
@@ -10,6 +10,9 @@ pub mod asset;
 #[macro_use]
 pub mod lock_duration;
 
+#[macro_use]
+pub mod expiry;
+
 mod problem;
 
 pub use self::problem::*;
@@ -18,15 +21,29 @@ pub const PATH: &str = "swaps";
 
 mod ledger_impls {
     use super::ledger::{Error, FromHttpLedger, HttpLedger, ToHttpLedger};
-    use crate::swap_protocols::ledger::{Bitcoin, Ethereum};
+    use crate::swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        LedgerKind,
+    };
 
     impl_http_ledger!(Bitcoin { network });
-    impl_http_ledger!(Ethereum);
+    impl_http_ledger!(Ethereum { network });
+
+    impl FromHttpLedger for LedgerKind {
+        fn from_http_ledger(ledger: HttpLedger) -> Result<Self, Error> {
+            match ledger.name() {
+                "Bitcoin" => Ok(LedgerKind::Bitcoin),
+                "Ethereum" => Ok(LedgerKind::Ethereum),
+                _ => Err(Error::WrongLedger),
+            }
+        }
+    }
 
 }
 
 mod asset_impls {
     use super::asset::{Error, FromHttpAsset, HttpAsset, ToHttpAsset};
+    use crate::swap_protocols::AssetKind;
     use bitcoin_support::BitcoinQuantity;
     use ethereum_support::{
         web3::types::U256, Erc20Quantity, EtherQuantity, FromDecimalStr, ToBigDecimal,
@@ -35,6 +52,17 @@ mod asset_impls {
     impl_http_quantity_asset!(BitcoinQuantity, Bitcoin);
     impl_http_quantity_asset!(EtherQuantity, Ether);
 
+    impl FromHttpAsset for AssetKind {
+        fn from_http_asset(asset: HttpAsset) -> Result<Self, Error> {
+            match asset.name() {
+                "Bitcoin" => Ok(AssetKind::Bitcoin),
+                "Ether" => Ok(AssetKind::Ether),
+                "ERC20" => Ok(AssetKind::Erc20),
+                _ => Err(Error::WrongAsset),
+            }
+        }
+    }
+
     impl FromHttpAsset for Erc20Quantity {
         fn from_http_asset(mut asset: HttpAsset) -> Result<Self, Error> {
             asset.is_asset("ERC20")?;
@@ -66,11 +94,22 @@ mod lock_duration_impls {
     impl_to_http_lock_duration!(Seconds);
 }
 
+mod expiry_impls {
+    use super::expiry::{HttpExpiry, ToHttpExpiry};
+    use crate::swap_protocols::rfc003::{bitcoin::BlockHeight, ethereum::Timestamp};
+
+    impl_to_http_expiry!(BlockHeight, "block_height");
+    impl_to_http_expiry!(Timestamp, "timestamp");
+}
+
 #[cfg(test)]
 mod tests {
 
     use crate::{
-        http_api::{asset::ToHttpAsset, ledger::ToHttpLedger},
+        http_api::{
+            asset::ToHttpAsset,
+            ledger::{FromHttpLedger, HttpLedger, ToHttpLedger},
+        },
         swap_protocols::ledger::{Bitcoin, Ethereum},
     };
     use bitcoin_support::{BitcoinQuantity, Network};
@@ -109,7 +148,9 @@ mod tests {
         let bitcoin = Bitcoin {
             network: Network::Regtest,
         };
-        let ethereum = Ethereum {};
+        let ethereum = Ethereum {
+            network: ethereum_support::Network::Regtest,
+        };
 
         let bitcoin = bitcoin.to_http_ledger().unwrap();
         let ethereum = ethereum.to_http_ledger().unwrap();
@@ -121,7 +162,17 @@ mod tests {
             &bitcoin_serialized,
             r#"{"name":"Bitcoin","network":"regtest"}"#
         );
-        assert_eq!(&ethereum_serialized, r#"{"name":"Ethereum"}"#);
+        assert_eq!(
+            &ethereum_serialized,
+            r#"{"name":"Ethereum","network":"regtest"}"#
+        );
+    }
+
+    #[test]
+    fn http_ledger_without_ethereum_network_is_rejected() {
+        let ledger: HttpLedger = serde_json::from_str(r#"{"name":"Ethereum"}"#).unwrap();
+
+        assert!(Ethereum::from_http_ledger(ledger).is_err());
     }
 
 }
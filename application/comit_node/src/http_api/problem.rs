@@ -1,6 +1,9 @@
-use crate::swap_protocols::{
-    metadata_store,
-    rfc003::{self, state_store},
+use crate::{
+    comit_client::ClientFactoryError,
+    swap_protocols::{
+        metadata_store,
+        rfc003::{self, state_store, UnsafeAction},
+    },
 };
 use http::StatusCode;
 use http_api_problem::{HttpApiProblem, HttpStatusCode};
@@ -66,6 +69,26 @@ pub fn action_already_taken() -> HttpApiProblem {
     HttpApiProblem::new("action-already-taken").set_status(400)
 }
 
+impl From<UnsafeAction> for HttpApiProblem {
+    fn from(e: UnsafeAction) -> Self {
+        let detail = match e.time_left {
+            Some(time_left) => format!(
+                "The alpha HTLC expires in about {}s but this action needs at least {}s to be safe",
+                time_left.as_secs(),
+                e.time_needed.as_secs()
+            ),
+            None => format!(
+                "It is unknown when the alpha HTLC expires but this action needs at least {}s to be safe",
+                e.time_needed.as_secs()
+            ),
+        };
+
+        HttpApiProblem::new("unsafe-action")
+            .set_status(400)
+            .set_detail(detail)
+    }
+}
+
 impl From<state_store::Error> for HttpApiProblem {
     fn from(_e: state_store::Error) -> Self {
         HttpApiProblem::with_title_and_type_from_status(500).set_detail("Storage layer failure")
@@ -78,6 +101,22 @@ impl From<metadata_store::Error> for HttpApiProblem {
     }
 }
 
+impl From<ClientFactoryError> for HttpApiProblem {
+    fn from(e: ClientFactoryError) -> Self {
+        error!("Could not connect to the counterparty: {:?}", e);
+        match e {
+            ClientFactoryError::PeerNotAllowed(_) => HttpApiProblem::new("peer-not-allowed")
+                .set_status(403)
+                .set_detail("The counterparty is not among the allowed peers"),
+            ClientFactoryError::UnexpectedPeer { .. } => HttpApiProblem::new("unexpected-peer")
+                .set_status(403)
+                .set_detail("The counterparty did not authenticate with the pinned key"),
+            _ => HttpApiProblem::with_title_and_type_from_status(502)
+                .set_detail("Could not connect to the counterparty"),
+        }
+    }
+}
+
 impl From<rfc003::state_machine::Error> for HttpApiProblem {
     fn from(e: rfc003::state_machine::Error) -> Self {
         error!("Protocol execution error: {:?}", e);
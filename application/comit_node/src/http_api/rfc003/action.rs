@@ -1,9 +1,12 @@
 use crate::{
     comit_client::SwapDeclineReason,
-    http_api::{problem, HttpApiProblemStdError},
+    http_api::{
+        expiry::{HttpExpiry, ToHttpExpiry},
+        problem, HttpApiProblemStdError,
+    },
+    ledger_query_service::ChainTips,
     swap_protocols::{
         ledger::{Bitcoin, Ethereum},
-        metadata_store::Metadata,
         rfc003::{
             alice, bitcoin,
             bob::{
@@ -13,13 +16,13 @@ use crate::{
             ethereum,
             state_machine::StateMachineResponse,
             state_store::StateStore,
-            Actions, Alice, Bob, Ledger, SecretSource,
+            Alice, Bob, Ledger, SafetyMargin, SecretSource,
         },
-        AssetKind, LedgerKind, MetadataStore, RoleKind, SwapId,
+        MetadataStore, RoleKind, SwapId,
     },
 };
 use bitcoin_support::{self, serialize::serialize_hex, BitcoinQuantity};
-use ethereum_support::{self, Erc20Quantity, EtherQuantity};
+use ethereum_support::{self, EtherQuantity};
 use http_api_problem::HttpApiProblem;
 use std::{str::FromStr, sync::Arc};
 use warp::{self, Rejection, Reply};
@@ -28,6 +31,7 @@ use warp::{self, Rejection, Reply};
 pub enum PostAction {
     Accept,
     Decline,
+    Cancel,
 }
 
 impl FromStr for PostAction {
@@ -37,6 +41,7 @@ impl FromStr for PostAction {
         match s {
             "accept" => Ok(PostAction::Accept),
             "decline" => Ok(PostAction::Decline),
+            "cancel" => Ok(PostAction::Cancel),
             _ => Err(()),
         }
     }
@@ -166,9 +171,18 @@ pub enum ActionResponseBody {
         data: ethereum_support::Bytes,
         value: EtherQuantity,
         gas_limit: ethereum_support::U256,
+        network: ethereum_support::Network,
     },
 }
 
+#[derive(Debug, Serialize)]
+struct ActionResource {
+    #[serde(flatten)]
+    body: ActionResponseBody,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<HttpExpiry>,
+}
+
 pub trait IntoResponseBody {
     fn into_response_body(
         self,
@@ -269,6 +283,7 @@ impl IntoResponseBody for ethereum::ContractDeploy {
             data,
             value,
             gas_limit,
+            network,
         } = self;
         match query_params {
             GetActionQueryParams::None {} => Ok(ActionResponseBody::SendEthereumTransaction {
@@ -276,6 +291,7 @@ impl IntoResponseBody for ethereum::ContractDeploy {
                 data,
                 value,
                 gas_limit,
+                network,
             }),
             _ => {
                 error!("Unexpected GET parameters for an ethereum::ContractDeploy action type. Expected: None.");
@@ -296,6 +312,7 @@ impl IntoResponseBody for ethereum::SendTransaction {
             data,
             value,
             gas_limit,
+            network,
         } = self;
         match query_params {
             GetActionQueryParams::None {} => Ok(ActionResponseBody::SendEthereumTransaction {
@@ -303,6 +320,7 @@ impl IntoResponseBody for ethereum::SendTransaction {
                 data,
                 value,
                 gas_limit,
+                network,
             }),
             _ => {
                 error!("Unexpected GET parameters for an ethereum::SendTransaction action. Expected: None.");
@@ -323,8 +341,8 @@ impl IntoResponseBody for () {
     }
 }
 
-impl<Deploy, Fund, Redeem, Refund> IntoResponseBody
-    for alice::ActionKind<Deploy, Fund, Redeem, Refund>
+impl<Cancel, Deploy, Fund, Redeem, Refund> IntoResponseBody
+    for alice::ActionKind<Cancel, Deploy, Fund, Redeem, Refund>
 where
     Deploy: IntoResponseBody,
     Fund: IntoResponseBody,
@@ -340,6 +358,10 @@ where
             alice::ActionKind::Fund(payload) => payload.into_response_body(query_params),
             alice::ActionKind::Redeem(payload) => payload.into_response_body(query_params),
             alice::ActionKind::Refund(payload) => payload.into_response_body(query_params),
+            alice::ActionKind::Cancel(_) => {
+                error!("IntoResponseBody is not implemented for Cancel");
+                Err(HttpApiProblem::with_title_and_type_from_status(500))
+            }
         }
     }
 }
@@ -400,6 +422,8 @@ struct DeclineSwapRequestHttpBody {
 pub fn post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     metadata_store: Arc<T>,
     state_store: Arc<S>,
+    chain_tips: Arc<ChainTips>,
+    safety_margin: SafetyMargin,
     secret_source: Arc<dyn SecretSource>,
     id: SwapId,
     action: PostAction,
@@ -408,6 +432,8 @@ pub fn post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     handle_post(
         metadata_store.as_ref(),
         state_store.as_ref(),
+        chain_tips.as_ref(),
+        &safety_margin,
         secret_source.as_ref(),
         id,
         action,
@@ -422,16 +448,42 @@ pub fn post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
 pub fn handle_post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     metadata_store: &T,
     state_store: &S,
+    chain_tips: &ChainTips,
+    safety_margin: &SafetyMargin,
     secret_source: &dyn SecretSource,
     id: SwapId,
     action: PostAction,
     body: serde_json::Value,
 ) -> Result<(), HttpApiProblem> {
-    use crate::swap_protocols::{AssetKind, LedgerKind, Metadata, RoleKind};
     trace!("accept action requested on {:?}", id);
     let metadata = metadata_store
         .get(&id)?
         .ok_or_else(problem::swap_not_found)?;
+    let latest_blocks = chain_tips.latest_known();
+
+    if let PostAction::Cancel = action {
+        return with_swap_types_alice!(
+            &metadata,
+            (|| {
+                let state = state_store
+                    .get::<Role>(&id)?
+                    .ok_or_else(problem::state_store)?;
+
+                let cancel_action = safety_margin
+                    .safe_actions(&state, &latest_blocks)
+                    .into_iter()
+                    .find_map(move |action| match action {
+                        alice::ActionKind::Cancel(cancel) => Some(Ok(cancel)),
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| Err(HttpApiProblem::with_title_and_type_from_status(404)))?;
+
+                cancel_action
+                    .cancel()
+                    .map_err(|_| problem::action_already_taken())
+            })
+        );
+    }
 
     with_swap_types_bob!(
         &metadata,
@@ -450,8 +502,8 @@ pub fn handle_post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
                         .ok_or_else(problem::state_store)?;
 
                     let accept_action = {
-                        state
-                            .actions()
+                        safety_margin
+                            .safe_actions(&state, &latest_blocks)
                             .into_iter()
                             .find_map(move |action| match action {
                                 bob::ActionKind::Accept(accept) => Some(Ok(accept)),
@@ -479,8 +531,8 @@ pub fn handle_post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
                             .ok_or_else(problem::state_store)?;
 
                         let decline_action = {
-                            state
-                                .actions()
+                            safety_margin
+                                .safe_actions(&state, &latest_blocks)
                                 .into_iter()
                                 .find_map(move |action| match action {
                                     bob::ActionKind::Decline(decline) => Some(Ok(decline)),
@@ -496,6 +548,7 @@ pub fn handle_post<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
                         ExecuteDecline::execute(&decline_action, reason)
                     })
             }
+            PostAction::Cancel => unreachable!("Cancel is handled above as it is Alice's action"),
         })
     )
 }
@@ -512,15 +565,16 @@ trait MatchesAction<A> {
     fn matches(self, action: &A) -> bool;
 }
 
-impl<Deploy, Fund, Redeem, Refund> MatchesAction<alice::ActionKind<Deploy, Fund, Redeem, Refund>>
-    for GetAction
+impl<Cancel, Deploy, Fund, Redeem, Refund>
+    MatchesAction<alice::ActionKind<Cancel, Deploy, Fund, Redeem, Refund>> for GetAction
 {
-    fn matches(self, other: &alice::ActionKind<Deploy, Fund, Redeem, Refund>) -> bool {
+    fn matches(self, other: &alice::ActionKind<Cancel, Deploy, Fund, Redeem, Refund>) -> bool {
         match other {
             alice::ActionKind::Deploy(_) => self == GetAction::Deploy,
             alice::ActionKind::Fund(_) => self == GetAction::Fund,
             alice::ActionKind::Redeem(_) => self == GetAction::Redeem,
             alice::ActionKind::Refund(_) => self == GetAction::Refund,
+            alice::ActionKind::Cancel(_) => false,
         }
     }
 }
@@ -541,6 +595,46 @@ impl<Accept, Decline, Deploy, Fund, Redeem, Refund>
     }
 }
 
+/// The HTLC whose expiry bounds when an action is still safe to take,
+/// i.e. the one that can be refunded once it expired.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ActionHtlc {
+    Alpha,
+    Beta,
+}
+
+trait TargetsHtlc {
+    fn htlc(&self) -> Option<ActionHtlc>;
+}
+
+impl<Cancel, Deploy, Fund, Redeem, Refund> TargetsHtlc
+    for alice::ActionKind<Cancel, Deploy, Fund, Redeem, Refund>
+{
+    fn htlc(&self) -> Option<ActionHtlc> {
+        match self {
+            alice::ActionKind::Deploy(_)
+            | alice::ActionKind::Fund(_)
+            | alice::ActionKind::Refund(_) => Some(ActionHtlc::Alpha),
+            alice::ActionKind::Redeem(_) => Some(ActionHtlc::Beta),
+            alice::ActionKind::Cancel(_) => None,
+        }
+    }
+}
+
+impl<Accept, Decline, Deploy, Fund, Redeem, Refund> TargetsHtlc
+    for bob::ActionKind<Accept, Decline, Deploy, Fund, Redeem, Refund>
+{
+    fn htlc(&self) -> Option<ActionHtlc> {
+        match self {
+            bob::ActionKind::Deploy(_) | bob::ActionKind::Fund(_) | bob::ActionKind::Refund(_) => {
+                Some(ActionHtlc::Beta)
+            }
+            bob::ActionKind::Redeem(_) => Some(ActionHtlc::Alpha),
+            bob::ActionKind::Accept(_) | bob::ActionKind::Decline(_) => None,
+        }
+    }
+}
+
 impl FromStr for GetAction {
     type Err = ();
 
@@ -559,6 +653,8 @@ impl FromStr for GetAction {
 pub fn get<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     metadata_store: Arc<T>,
     state_store: Arc<S>,
+    chain_tips: Arc<ChainTips>,
+    safety_margin: SafetyMargin,
     id: SwapId,
     action: GetAction,
     query_params: GetActionQueryParams,
@@ -566,6 +662,8 @@ pub fn get<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     handle_get(
         metadata_store.as_ref(),
         state_store,
+        chain_tips.as_ref(),
+        &safety_margin,
         &id,
         action,
         &query_params,
@@ -577,6 +675,8 @@ pub fn get<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
 fn handle_get<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     metadata_store: &T,
     state_store: Arc<S>,
+    chain_tips: &ChainTips,
+    safety_margin: &SafetyMargin,
     id: &SwapId,
     action: GetAction,
     query_params: &GetActionQueryParams,
@@ -592,19 +692,32 @@ fn handle_get<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
                 .get::<Role>(id)?
                 .ok_or_else(problem::state_store)?;
             trace!("Retrieved state for {}: {:?}", id, state);
-
-            state
-                .actions()
-                .iter()
-                .find_map(|state_action| {
-                    if action.matches(state_action) {
+            let latest_blocks = chain_tips.latest_known();
+
+            safety_margin
+                .actions(&state, &latest_blocks)
+                .into_iter()
+                .find_map(|(state_action, safety)| {
+                    if action.matches(&state_action) {
+                        if let Err(e) = safety {
+                            warn!("Swap {}: Withholding {:?} because {:?}", id, action, e);
+                            return Some(Err(HttpApiProblem::from(e)));
+                        }
+                        let expires_at = match state_action.htlc() {
+                            Some(ActionHtlc::Alpha) => state
+                                .alpha_htlc_expiry()
+                                .map(|expiry| expiry.to_http_expiry()),
+                            Some(ActionHtlc::Beta) => state
+                                .beta_htlc_expiry()
+                                .map(|expiry| expiry.to_http_expiry()),
+                            None => None,
+                        };
                         Some(
                             state_action
-                                .clone()
                                 .into_response_body(query_params.clone())
                                 .map(|body| {
                                     trace!("Swap {}: Returning {:?} for {:?}", id, body, action);
-                                    warp::reply::json(&body)
+                                    warp::reply::json(&ActionResource { body, expires_at })
                                 }),
                         )
                     } else {
@@ -644,4 +757,34 @@ mod test {
             })
         );
     }
+
+    #[test]
+    fn action_resource_serializes_expiry_next_to_the_body() {
+        let resource = ActionResource {
+            body: ActionResponseBody::BroadcastSignedBitcoinTransaction {
+                hex: String::from("0100"),
+            },
+            expires_at: Some(HttpExpiry::with_expiry("block_height", 244)),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&resource).unwrap(),
+            r#"{"hex":"0100","expires_at":{"type":"block_height","value":244}}"#
+        );
+    }
+
+    #[test]
+    fn action_resource_without_expiry_omits_it() {
+        let resource = ActionResource {
+            body: ActionResponseBody::BroadcastSignedBitcoinTransaction {
+                hex: String::from("0100"),
+            },
+            expires_at: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&resource).unwrap(),
+            r#"{"hex":"0100"}"#
+        );
+    }
 }
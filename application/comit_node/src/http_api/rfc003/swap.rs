@@ -1,11 +1,14 @@
 use crate::{
+    comit_client,
     http_api::{
         self,
-        asset::{HttpAsset, ToHttpAsset},
-        ledger::{HttpLedger, ToHttpLedger},
+        asset::{FromHttpAsset, HttpAsset, ToHttpAsset},
+        expiry::{HttpExpiry, ToHttpExpiry},
+        ledger::{FromHttpLedger, HttpLedger, ToHttpLedger},
         lock_duration::{HttpLockDuration, ToHttpLockDuration},
         problem::{self, HttpApiProblemStdError},
     },
+    ledger_query_service::ChainTips,
     swap_protocols::{
         asset::Asset,
         ledger::{Bitcoin, Ethereum},
@@ -13,38 +16,20 @@ use crate::{
             self,
             alice::{AliceSpawner, SwapRequestIdentities},
             state_store::StateStore,
-            Actions, Alice, Bob, Ledger, SecretSource,
+            Alice, Bob, Ledger, SafetyMargin, SecretSource,
         },
-        AssetKind, LedgerKind, Metadata, MetadataStore, RoleKind, SwapId,
+        AssetKind, LedgerKind, Metadata, MetadataStore, RoleKind, StartHeights, SwapId,
     },
 };
-use bitcoin_support::{self, BitcoinQuantity};
-use ethereum_support::{self, Erc20Quantity, EtherQuantity};
+use bam::noise::PublicKey;
 use http_api_problem::HttpApiProblem;
 use hyper::header;
 use rustic_hal::HalResource;
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use warp::{self, Rejection, Reply};
 
 pub const PROTOCOL_NAME: &str = "rfc003";
 
-#[derive(Clone, Debug, Deserialize, PartialEq)]
-#[serde(untagged)]
-pub enum SwapRequestBodyKind {
-    BitcoinEthereumBitcoinQuantityEtherQuantity(
-        SwapRequestBody<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>,
-    ),
-    BitcoinEthereumBitcoinQuantityErc20Quantity(
-        SwapRequestBody<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>,
-    ),
-    EthereumBitcoinEtherQuantityBitcoinQuantity(
-        SwapRequestBody<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>,
-    ),
-    // It is important that these two come last because untagged enums are tried in order
-    UnsupportedCombination(Box<UnsupportedSwapRequestBody>),
-    MalformedRequest(serde_json::Value),
-}
-
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct SwapRequestBody<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> {
     #[serde(with = "http_api::asset::serde")]
@@ -56,8 +41,14 @@ pub struct SwapRequestBody<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> {
     #[serde(with = "http_api::ledger::serde")]
     beta_ledger: BL,
     alpha_ledger_lock_duration: AL::LockDuration,
+    #[serde(default = "comit_client::rfc003::default_confirmations_needed")]
+    alpha_ledger_confirmations_needed: u32,
+    #[serde(default = "comit_client::rfc003::default_confirmations_needed")]
+    beta_ledger_confirmations_needed: u32,
     #[serde(flatten)]
     identities: SwapRequestBodyIdentities<AL::Identity, BL::Identity>,
+    #[serde(default)]
+    peer: Option<SocketAddr>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq)]
@@ -168,24 +159,40 @@ where
             alpha_ledger: body.alpha_ledger,
             beta_ledger: body.beta_ledger,
             alpha_ledger_lock_duration: body.alpha_ledger_lock_duration,
+            alpha_ledger_confirmations_needed: body.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: body.beta_ledger_confirmations_needed,
             identities: SwapRequestIdentities::from_swap_request_body_identities(
                 body.identities,
                 id,
                 secret_source,
             )?,
+            peer: body.peer,
         })
     }
 }
 
+/// The ledgers and assets of a swap request, which decide what the rest of the
+/// body is deserialized into.
 #[derive(Clone, Debug, Deserialize, PartialEq)]
-pub struct UnsupportedSwapRequestBody {
+pub struct SwapRequestBodyKinds {
     alpha_asset: HttpAsset,
     beta_asset: HttpAsset,
     alpha_ledger: HttpLedger,
     beta_ledger: HttpLedger,
-    alpha_ledger_refund_identity: Option<String>,
-    beta_ledger_redeem_identity: Option<String>,
-    alpha_ledger_lock_duration: i64,
+}
+
+impl SwapRequestBodyKinds {
+    fn metadata(&self) -> Option<Metadata> {
+        Some(Metadata {
+            alpha_ledger: LedgerKind::from_http_ledger(self.alpha_ledger.clone()).ok()?,
+            beta_ledger: LedgerKind::from_http_ledger(self.beta_ledger.clone()).ok()?,
+            alpha_asset: AssetKind::from_http_asset(self.alpha_asset.clone()).ok()?,
+            beta_asset: AssetKind::from_http_asset(self.beta_asset.clone()).ok()?,
+            role: RoleKind::Alice,
+            counterparty: None,
+            start_heights: StartHeights::default(),
+        })
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -198,67 +205,67 @@ fn swap_path(id: SwapId) -> String {
 }
 
 #[allow(clippy::needless_pass_by_value)]
-
 pub fn post_swap<A: AliceSpawner>(
     alice_spawner: Arc<A>,
     secret_source: Arc<dyn SecretSource>,
-    request_body_kind: SwapRequestBodyKind,
+    request_body: serde_json::Value,
 ) -> Result<impl Reply, Rejection> {
-    handle_post_swap(
-        alice_spawner.as_ref(),
-        secret_source.as_ref(),
-        request_body_kind,
-    )
-    .map(|swap_created| {
-        let body = warp::reply::json(&swap_created);
-        let response = warp::reply::with_header(body, header::LOCATION, swap_path(swap_created.id));
-        warp::reply::with_status(response, warp::http::StatusCode::CREATED)
-    })
-    .map_err(|problem| warp::reject::custom(HttpApiProblemStdError::from(problem)))
+    handle_post_swap(alice_spawner.as_ref(), secret_source.as_ref(), request_body)
+        .map(|swap_created| {
+            let body = warp::reply::json(&swap_created);
+            let response =
+                warp::reply::with_header(body, header::LOCATION, swap_path(swap_created.id));
+            warp::reply::with_status(response, warp::http::StatusCode::CREATED)
+        })
+        .map_err(|problem| warp::reject::custom(HttpApiProblemStdError::from(problem)))
 }
 
 fn handle_post_swap<A: AliceSpawner>(
     alice_spawner: &A,
     secret_source: &dyn SecretSource,
-    request_body_kind: SwapRequestBodyKind,
+    request_body: serde_json::Value,
 ) -> Result<SwapCreated, HttpApiProblem> {
     let id = SwapId::default();
 
-    match request_body_kind {
-        SwapRequestBodyKind::BitcoinEthereumBitcoinQuantityEtherQuantity(body) => alice_spawner
-            .spawn(
-                id,
-                rfc003::alice::SwapRequest::from_swap_request_body(body, id, secret_source)?,
-            )?,
-        SwapRequestBodyKind::BitcoinEthereumBitcoinQuantityErc20Quantity(body) => alice_spawner
-            .spawn(
-                id,
-                rfc003::alice::SwapRequest::from_swap_request_body(body, id, secret_source)?,
-            )?,
-        SwapRequestBodyKind::EthereumBitcoinEtherQuantityBitcoinQuantity(body) => alice_spawner
-            .spawn(
+    let kinds = serde_json::from_value::<SwapRequestBodyKinds>(request_body.clone())
+        .map_err(|_| malformed_request(&request_body))?;
+    let unsupported = || {
+        error!(
+            "Swapping {:?} for {:?} from {:?} to {:?} is not supported",
+            kinds.alpha_asset, kinds.beta_asset, kinds.alpha_ledger, kinds.beta_ledger
+        );
+        Err(problem::unsupported())
+    };
+    let metadata = match kinds.metadata() {
+        Some(metadata) => metadata,
+        None => return unsupported(),
+    };
+
+    crate::with_supported_swap_types!(
+        metadata,
+        (|| {
+            let body =
+                serde_json::from_value::<SwapRequestBody<AL, BL, AA, BA>>(request_body.clone())
+                    .map_err(|_| malformed_request(&request_body))?;
+
+            alice_spawner.spawn(
                 id,
                 rfc003::alice::SwapRequest::from_swap_request_body(body, id, secret_source)?,
-            )?,
-        SwapRequestBodyKind::UnsupportedCombination(body) => {
-            error!(
-                "Swapping {:?} for {:?} from {:?} to {:?} is not supported",
-                body.alpha_asset, body.beta_asset, body.alpha_ledger, body.beta_ledger
-            );
-            return Err(problem::unsupported());
-        }
-        SwapRequestBodyKind::MalformedRequest(body) => {
-            error!(
-                "Malformed request body: {}",
-                serde_json::to_string(&body)
-                    .expect("failed to serialize serde_json::Value as string ?!")
-            );
-            return Err(HttpApiProblem::with_title_and_type_from_status(400)
-                .set_detail("The request body was malformed"));
-        }
-    };
+            )?;
+            Ok(SwapCreated { id })
+        }),
+        unsupported()
+    )
+}
 
-    Ok(SwapCreated { id })
+fn malformed_request(request_body: &serde_json::Value) -> HttpApiProblem {
+    error!(
+        "Malformed request body: {}",
+        serde_json::to_string(request_body)
+            .expect("failed to serialize serde_json::Value as string ?!")
+    );
+    HttpApiProblem::with_title_and_type_from_status(400)
+        .set_detail("The request body was malformed")
 }
 
 #[derive(Debug, Serialize)]
@@ -270,6 +277,10 @@ pub struct SwapDescription {
     alpha_lock_duration: HttpLockDuration,
     #[serde(skip_serializing_if = "Option::is_none")]
     beta_lock_duration: Option<HttpLockDuration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    alpha_expires_at: Option<HttpExpiry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    beta_expires_at: Option<HttpExpiry>,
 }
 
 #[derive(Debug, Serialize)]
@@ -277,16 +288,25 @@ struct GetSwapResource {
     pub swap: SwapDescription,
     pub role: String,
     pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<PublicKey>,
 }
 
 #[allow(clippy::needless_pass_by_value)]
 pub fn get_swap<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     metadata_store: Arc<T>,
     state_store: Arc<S>,
+    chain_tips: Arc<ChainTips>,
+    safety_margin: SafetyMargin,
     id: SwapId,
 ) -> Result<impl Reply, Rejection> {
-    let result: Result<(GetSwapResource, Vec<ActionName>), HttpApiProblem> =
-        handle_get_swap(&metadata_store, &state_store, &id);
+    let result: Result<(GetSwapResource, Vec<ActionName>), HttpApiProblem> = handle_get_swap(
+        &metadata_store,
+        &state_store,
+        &chain_tips,
+        &safety_margin,
+        &id,
+    );
 
     match result {
         Ok((swap_resource, actions)) => {
@@ -306,6 +326,8 @@ type ActionName = String;
 fn handle_get_swap<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
     metadata_store: &Arc<T>,
     state_store: &Arc<S>,
+    chain_tips: &Arc<ChainTips>,
+    safety_margin: &SafetyMargin,
     id: &SwapId,
 ) -> Result<(GetSwapResource, Vec<ActionName>), HttpApiProblem> {
     let metadata = metadata_store
@@ -322,8 +344,11 @@ fn handle_get_swap<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
 
             let start_state = state.start_state()?;
 
-            let actions: Vec<ActionName> =
-                state.actions().iter().map(|action| action.name()).collect();
+            let actions: Vec<ActionName> = safety_margin
+                .safe_actions(&state, &chain_tips.latest_known())
+                .iter()
+                .map(|action| action.name())
+                .collect();
             (Ok((
                 GetSwapResource {
                     state: state.name(),
@@ -339,8 +364,15 @@ fn handle_get_swap<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
                         beta_lock_duration: state
                             .beta_ledger_lock_duration()
                             .map(|lock| lock.to_http_lock_duration().unwrap()),
+                        alpha_expires_at: state
+                            .alpha_htlc_expiry()
+                            .map(|expiry| expiry.to_http_expiry()),
+                        beta_expires_at: state
+                            .beta_htlc_expiry()
+                            .map(|expiry| expiry.to_http_expiry()),
                     },
                     role: format!("{}", metadata.role),
+                    counterparty: metadata.counterparty,
                 },
                 actions,
             )))
@@ -409,6 +441,8 @@ fn handle_get_swaps<T: MetadataStore<SwapId>, S: StateStore<SwapId>>(
 mod tests {
 
     use super::*;
+    use bitcoin_support::BitcoinQuantity;
+    use ethereum_support::{Erc20Quantity, EtherQuantity};
     use spectral::prelude::*;
 
     #[test]
@@ -419,7 +453,8 @@ mod tests {
                     "network": "regtest"
                 },
                 "beta_ledger": {
-                    "name": "Ethereum"
+                    "name": "Ethereum",
+                    "network": "regtest"
                 },
                 "alpha_asset": {
                     "name": "Bitcoin",
@@ -440,14 +475,168 @@ mod tests {
             alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
             beta_asset: EtherQuantity::from_eth(10.0),
             alpha_ledger: Bitcoin::regtest(),
-            beta_ledger: Ethereum::default(),
+            beta_ledger: Ethereum::regtest(),
             alpha_ledger_lock_duration: bitcoin_support::Blocks::new(144),
+            alpha_ledger_confirmations_needed: 1,
+            beta_ledger_confirmations_needed: 1,
             identities: SwapRequestBodyIdentities::OnlyRedeem {
                 beta_ledger_redeem_identity: ethereum_support::Address::from(
                     "0x00a329c0648769a73afac7f9381e08fb43dbea72",
                 ),
             },
+            peer: None,
         })
     }
 
+    #[test]
+    fn can_deserialize_swap_request_body_with_peer() {
+        let body = r#"{
+                "alpha_ledger": {
+                    "name": "Bitcoin",
+                    "network": "regtest"
+                },
+                "beta_ledger": {
+                    "name": "Ethereum",
+                    "network": "regtest"
+                },
+                "alpha_asset": {
+                    "name": "Bitcoin",
+                    "quantity": "100000000"
+                },
+                "beta_asset": {
+                    "name": "Ether",
+                    "quantity": "10000000000000000000"
+                },
+                "beta_ledger_redeem_identity": "0x00a329c0648769a73afac7f9381e08fb43dbea72",
+                "alpha_ledger_lock_duration": 144,
+                "peer": "127.0.0.1:8011"
+            }"#;
+
+        let body: Result<SwapRequestBody<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>, _> =
+            serde_json::from_str(body);
+
+        assert_that(&body)
+            .is_ok()
+            .map(|body| &body.peer)
+            .is_equal_to(&Some("127.0.0.1:8011".parse().unwrap()));
+    }
+
+    #[test]
+    fn can_deserialize_swap_request_body_with_confirmations_needed() {
+        let body = r#"{
+                "alpha_ledger": {
+                    "name": "Bitcoin",
+                    "network": "regtest"
+                },
+                "beta_ledger": {
+                    "name": "Ethereum",
+                    "network": "regtest"
+                },
+                "alpha_asset": {
+                    "name": "Bitcoin",
+                    "quantity": "100000000"
+                },
+                "beta_asset": {
+                    "name": "Ether",
+                    "quantity": "10000000000000000000"
+                },
+                "beta_ledger_redeem_identity": "0x00a329c0648769a73afac7f9381e08fb43dbea72",
+                "alpha_ledger_lock_duration": 144,
+                "alpha_ledger_confirmations_needed": 3,
+                "beta_ledger_confirmations_needed": 12
+            }"#;
+
+        let body: SwapRequestBody<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity> =
+            serde_json::from_str(body).unwrap();
+
+        assert_that(&body.alpha_ledger_confirmations_needed).is_equal_to(3);
+        assert_that(&body.beta_ledger_confirmations_needed).is_equal_to(12);
+    }
+
+    #[test]
+    fn can_deserialize_erc20_for_bitcoin_swap_request_body() {
+        let body = r#"{
+                "alpha_ledger": {
+                    "name": "Ethereum",
+                    "network": "regtest"
+                },
+                "beta_ledger": {
+                    "name": "Bitcoin",
+                    "network": "regtest"
+                },
+                "alpha_asset": {
+                    "name": "ERC20",
+                    "quantity": "1000",
+                    "token_contract": "0xb97048628db6b661d4c2aa833e95dbe1a905b280"
+                },
+                "beta_asset": {
+                    "name": "Bitcoin",
+                    "quantity": "100000000"
+                },
+                "alpha_ledger_refund_identity": "0x00a329c0648769a73afac7f9381e08fb43dbea72",
+                "beta_ledger_redeem_identity": null,
+                "alpha_ledger_lock_duration": 86400
+            }"#;
+
+        let body: Result<SwapRequestBody<Ethereum, Bitcoin, Erc20Quantity, BitcoinQuantity>, _> =
+            serde_json::from_str(body);
+
+        assert_that(&body).is_ok();
+    }
+
+    #[test]
+    fn swap_request_body_kinds_resolve_to_a_supported_swap() {
+        let body = r#"{
+                "alpha_ledger": {
+                    "name": "Ethereum",
+                    "network": "regtest"
+                },
+                "beta_ledger": {
+                    "name": "Bitcoin",
+                    "network": "regtest"
+                },
+                "alpha_asset": {
+                    "name": "ERC20",
+                    "quantity": "1000",
+                    "token_contract": "0xb97048628db6b661d4c2aa833e95dbe1a905b280"
+                },
+                "beta_asset": {
+                    "name": "Bitcoin",
+                    "quantity": "100000000"
+                }
+            }"#;
+
+        let kinds: SwapRequestBodyKinds = serde_json::from_str(body).unwrap();
+        let metadata = kinds.metadata().unwrap();
+
+        assert!(crate::with_supported_swap_types!(
+            metadata,
+            (|| true),
+            false
+        ));
+    }
+
+    #[test]
+    fn swap_request_body_kinds_of_unknown_ledgers_have_no_metadata() {
+        let body = r#"{
+                "alpha_ledger": {
+                    "name": "Thomas' wallet"
+                },
+                "beta_ledger": {
+                    "name": "Higher-Dimension"
+                },
+                "alpha_asset": {
+                    "name": "AUD",
+                    "quantity": "3.5"
+                },
+                "beta_asset": {
+                    "name": "Espresso"
+                }
+            }"#;
+
+        let kinds: SwapRequestBodyKinds = serde_json::from_str(body).unwrap();
+
+        assert_that(&kinds.metadata()).is_none();
+    }
+
 }
@@ -19,67 +19,14 @@ macro_rules! _match_role {
 #[macro_export]
 macro_rules! with_swap_types {
     ($metadata:expr, $fn:tt) => {{
-        use bitcoin_support::BitcoinQuantity;
-        use ethereum_support::EtherQuantity;
         let metadata = $metadata;
+        let role = metadata.role;
 
-        match metadata {
-            Metadata {
-                alpha_ledger: LedgerKind::Bitcoin,
-                beta_ledger: LedgerKind::Ethereum,
-                alpha_asset: AssetKind::Bitcoin,
-                beta_asset: AssetKind::Ether,
-                role,
-            } => {
-                #[allow(dead_code)]
-                type AL = Bitcoin;
-                #[allow(dead_code)]
-                type BL = Ethereum;
-                #[allow(dead_code)]
-                type AA = BitcoinQuantity;
-                #[allow(dead_code)]
-                type BA = EtherQuantity;
-
-                _match_role!(role, $fn)
-            }
-            Metadata {
-                alpha_ledger: LedgerKind::Bitcoin,
-                beta_ledger: LedgerKind::Ethereum,
-                alpha_asset: AssetKind::Bitcoin,
-                beta_asset: AssetKind::Erc20,
-                role,
-            } => {
-                #[allow(dead_code)]
-                type AL = Bitcoin;
-                #[allow(dead_code)]
-                type BL = Ethereum;
-                #[allow(dead_code)]
-                type AA = BitcoinQuantity;
-                #[allow(dead_code)]
-                type BA = Erc20Quantity;
-
-                _match_role!(role, $fn)
-            }
-            Metadata {
-                alpha_ledger: LedgerKind::Ethereum,
-                beta_ledger: LedgerKind::Bitcoin,
-                alpha_asset: AssetKind::Ether,
-                beta_asset: AssetKind::Bitcoin,
-                role,
-            } => {
-                #[allow(dead_code)]
-                type AL = Ethereum;
-                #[allow(dead_code)]
-                type BL = Bitcoin;
-                #[allow(dead_code)]
-                type AA = EtherQuantity;
-                #[allow(dead_code)]
-                type BA = BitcoinQuantity;
-
-                _match_role!(role, $fn)
-            }
-            _ => unimplemented!(),
-        }
+        $crate::with_supported_swap_types!(
+            metadata,
+            (|| _match_role!(role, $fn)),
+            Err($crate::http_api::unsupported())
+        )
     }};
 }
 
@@ -101,66 +48,42 @@ macro_rules! _match_role_bob {
 #[macro_export]
 macro_rules! with_swap_types_bob {
     ($metadata:expr, $fn:tt) => {{
-        use bitcoin_support::BitcoinQuantity;
-        use ethereum_support::EtherQuantity;
         let metadata = $metadata;
+        let role = metadata.role;
 
-        match metadata {
-            Metadata {
-                alpha_ledger: LedgerKind::Bitcoin,
-                beta_ledger: LedgerKind::Ethereum,
-                alpha_asset: AssetKind::Bitcoin,
-                beta_asset: AssetKind::Ether,
-                role,
-            } => {
-                #[allow(dead_code)]
-                type AL = Bitcoin;
-                #[allow(dead_code)]
-                type BL = Ethereum;
-                #[allow(dead_code)]
-                type AA = BitcoinQuantity;
-                #[allow(dead_code)]
-                type BA = EtherQuantity;
-
-                _match_role_bob!(role, $fn)
-            }
-            Metadata {
-                alpha_ledger: LedgerKind::Bitcoin,
-                beta_ledger: LedgerKind::Ethereum,
-                alpha_asset: AssetKind::Bitcoin,
-                beta_asset: AssetKind::Erc20,
-                role,
-            } => {
-                #[allow(dead_code)]
-                type AL = Bitcoin;
-                #[allow(dead_code)]
-                type BL = Ethereum;
-                #[allow(dead_code)]
-                type AA = BitcoinQuantity;
-                #[allow(dead_code)]
-                type BA = Erc20Quantity;
+        $crate::with_supported_swap_types!(
+            metadata,
+            (|| _match_role_bob!(role, $fn)),
+            Err($crate::http_api::unsupported())
+        )
+    }};
+}
 
-                _match_role_bob!(role, $fn)
-            }
-            Metadata {
-                alpha_ledger: LedgerKind::Ethereum,
-                beta_ledger: LedgerKind::Bitcoin,
-                alpha_asset: AssetKind::Ether,
-                beta_asset: AssetKind::Bitcoin,
-                role,
-            } => {
-                #[allow(dead_code)]
-                type AL = Ethereum;
-                #[allow(dead_code)]
-                type BL = Bitcoin;
-                #[allow(dead_code)]
-                type AA = EtherQuantity;
+macro_rules! _match_role_alice {
+    ($role:ident, $fn:tt) => {
+        #[allow(clippy::redundant_closure_call)]
+        match $role {
+            RoleKind::Alice => {
                 #[allow(dead_code)]
-                type BA = BitcoinQuantity;
-
-                _match_role_bob!(role, $fn)
+                type Role = Alice<AL, BL, AA, BA>;
+                $fn()
             }
-            _ => unimplemented!(),
+            _ => Err(HttpApiProblem::with_title_and_type_from_status(400)
+                .set_detail("Requested action is not supported for this role")),
         }
+    };
+}
+
+#[macro_export]
+macro_rules! with_swap_types_alice {
+    ($metadata:expr, $fn:tt) => {{
+        let metadata = $metadata;
+        let role = metadata.role;
+
+        $crate::with_supported_swap_types!(
+            metadata,
+            (|| _match_role_alice!(role, $fn)),
+            Err($crate::http_api::unsupported())
+        )
     }};
 }
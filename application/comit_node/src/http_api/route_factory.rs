@@ -1,8 +1,9 @@
 use crate::{
     http_api::{self, rfc003::action::GetActionQueryParams},
+    ledger_query_service::ChainTips,
     seed::Seed,
     swap_protocols::{
-        rfc003::{alice::AliceSpawner, state_store, SecretSource},
+        rfc003::{alice::AliceSpawner, state_store, SafetyMargin, SecretSource},
         MetadataStore, SwapId,
     },
 };
@@ -13,6 +14,8 @@ pub fn create<T: MetadataStore<SwapId>, S: state_store::StateStore<SwapId>, A: A
     metadata_store: Arc<T>,
     state_store: Arc<S>,
     alice_spawner: Arc<A>,
+    chain_tips: Arc<ChainTips>,
+    safety_margin: SafetyMargin,
     seed: Seed,
 ) -> BoxedFilter<(impl Reply,)> {
     let seed = Arc::new(seed);
@@ -23,6 +26,8 @@ pub fn create<T: MetadataStore<SwapId>, S: state_store::StateStore<SwapId>, A: A
     let state_store = warp::any().map(move || state_store.clone());
     let empty_json_body = warp::any().map(|| json!({}));
     let alice_spawner = warp::any().map(move || alice_spawner.clone());
+    let chain_tips = warp::any().map(move || chain_tips.clone());
+    let safety_margin = warp::any().map(move || safety_margin);
 
     let rfc003_post_swap = rfc003
         .and(warp::path::end())
@@ -36,6 +41,8 @@ pub fn create<T: MetadataStore<SwapId>, S: state_store::StateStore<SwapId>, A: A
         .and(warp::get2())
         .and(metadata_store.clone())
         .and(state_store.clone())
+        .and(chain_tips.clone())
+        .and(safety_margin.clone())
         .and(warp::path::param())
         .and(warp::path::end())
         .and_then(http_api::rfc003::swap::get_swap);
@@ -50,6 +57,8 @@ pub fn create<T: MetadataStore<SwapId>, S: state_store::StateStore<SwapId>, A: A
     let rfc003_post_action = rfc003
         .and(metadata_store.clone())
         .and(state_store.clone())
+        .and(chain_tips.clone())
+        .and(safety_margin.clone())
         .and(rfc003_secret_gen.clone())
         .and(warp::path::param::<SwapId>())
         .and(warp::path::param::<http_api::rfc003::action::PostAction>())
@@ -61,6 +70,8 @@ pub fn create<T: MetadataStore<SwapId>, S: state_store::StateStore<SwapId>, A: A
     let rfc003_get_action = rfc003
         .and(metadata_store.clone())
         .and(state_store.clone())
+        .and(chain_tips.clone())
+        .and(safety_margin)
         .and(warp::path::param::<SwapId>())
         .and(warp::path::param::<http_api::rfc003::action::GetAction>())
         .and(warp::query::<GetActionQueryParams>())
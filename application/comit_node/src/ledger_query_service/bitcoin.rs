@@ -8,13 +8,43 @@ pub enum BitcoinQuery {
         to_address: Option<bitcoin_support::Address>,
         from_outpoint: Option<bitcoin_support::OutPoint>,
         unlock_script: Option<Vec<Vec<u8>>>,
+        confirmations_needed: u32,
+        /// Also matches transactions mined before the query was created,
+        /// starting at this height
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from_block_height: Option<u64>,
+        /// The LQS deletes the query once it reaches this height
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at_block: Option<u64>,
     },
     Block {
         min_height: Option<u32>,
     },
 }
 
-impl Query for BitcoinQuery {}
+impl Query for BitcoinQuery {
+    fn with_from_block_height(mut self, height: u64) -> Self {
+        if let BitcoinQuery::Transaction {
+            ref mut from_block_height,
+            ..
+        } = self
+        {
+            *from_block_height = Some(height);
+        }
+        self
+    }
+
+    fn with_expires_at_block(mut self, height: u64) -> Self {
+        if let BitcoinQuery::Transaction {
+            ref mut expires_at_block,
+            ..
+        } = self
+        {
+            *expires_at_block = Some(height);
+        }
+        self
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -32,11 +62,14 @@ mod tests {
             to_address,
             from_outpoint,
             unlock_script,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
         assert_eq!(
             query,
-            r#"{"to_address":"bcrt1qcqslz7lfn34dl096t5uwurff9spen5h4v2pmap","from_outpoint":null,"unlock_script":null}"#
+            r#"{"to_address":"bcrt1qcqslz7lfn34dl096t5uwurff9spen5h4v2pmap","from_outpoint":null,"unlock_script":null,"confirmations_needed":1}"#
         )
     }
 
@@ -49,11 +82,50 @@ mod tests {
             to_address,
             from_outpoint,
             unlock_script,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
         assert_eq!(
             query,
-            r#"{"to_address":null,"from_outpoint":null,"unlock_script":null}"#
+            r#"{"to_address":null,"from_outpoint":null,"unlock_script":null,"confirmations_needed":1}"#
+        )
+    }
+
+    #[test]
+    fn given_a_bitcoin_transaction_query_from_a_block_height_it_serializes_ok() {
+        let query = BitcoinQuery::Transaction {
+            to_address: None,
+            from_outpoint: None,
+            unlock_script: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
+        }
+        .with_from_block_height(42);
+        let query = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            query,
+            r#"{"to_address":null,"from_outpoint":null,"unlock_script":null,"confirmations_needed":1,"from_block_height":42}"#
+        )
+    }
+
+    #[test]
+    fn given_a_bitcoin_transaction_query_that_expires_it_serializes_ok() {
+        let query = BitcoinQuery::Transaction {
+            to_address: None,
+            from_outpoint: None,
+            unlock_script: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
+        }
+        .with_expires_at_block(1000);
+        let query = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            query,
+            r#"{"to_address":null,"from_outpoint":null,"unlock_script":null,"confirmations_needed":1,"expires_at_block":1000}"#
         )
     }
 
@@ -82,11 +154,14 @@ mod tests {
             to_address,
             from_outpoint,
             unlock_script,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
         assert_eq!(
             query,
-            r#"{"to_address":null,"from_outpoint":{"txid":"02b082113e35d5386285094c2829e7e2963fa0b5369fb7f4b79c4c90877dcd3d","vout":0},"unlock_script":null}"#
+            r#"{"to_address":null,"from_outpoint":{"txid":"02b082113e35d5386285094c2829e7e2963fa0b5369fb7f4b79c4c90877dcd3d","vout":0},"unlock_script":null,"confirmations_needed":1}"#
         )
     }
 
@@ -102,11 +177,14 @@ mod tests {
             to_address,
             from_outpoint,
             unlock_script,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
         assert_eq!(
             query,
-            r#"{"to_address":null,"from_outpoint":null,"unlock_script":[[1,2,3,4,5],[5,4,3,2,1]]}"#
+            r#"{"to_address":null,"from_outpoint":null,"unlock_script":[[1,2,3,4,5],[5,4,3,2,1]],"confirmations_needed":1}"#
         )
     }
 }
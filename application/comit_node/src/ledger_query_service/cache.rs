@@ -66,7 +66,15 @@ mod tests {
         criteria: u32,
     }
 
-    impl Query for SomeQuery {}
+    impl Query for SomeQuery {
+        fn with_from_block_height(self, _: u64) -> Self {
+            self
+        }
+
+        fn with_expires_at_block(self, _: u64) -> Self {
+            self
+        }
+    }
 
     impl CreateQuery<Bitcoin, SomeQuery> for CountInvocations {
         fn create_query(
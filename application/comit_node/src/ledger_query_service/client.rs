@@ -1,13 +1,23 @@
 use crate::{
     ledger_query_service::{
         bitcoin::BitcoinQuery, ethereum::EthereumQuery, CreateQuery, Error, FetchFullQueryResults,
-        FetchQueryResults, LedgerQueryServiceApiClient, Query, QueryId,
+        FetchLatestBlock, FetchQueryResults, LatestBlock, LedgerQueryServiceApiClient, Query,
+        QueryId, QueryResultEvent, SubscribeToQueryResults,
     },
     swap_protocols::ledger::{Bitcoin, Ethereum, Ledger},
 };
-use futures::{stream::Stream, Async};
-use reqwest::{header::LOCATION, r#async::Client, StatusCode, Url};
+use futures::{
+    future::{self, Either},
+    stream::{self, Stream},
+    Async,
+};
+use reqwest::{
+    header::{ACCEPT, LOCATION},
+    r#async::Client,
+    StatusCode, Url,
+};
 use serde::Deserialize;
+use std::iter;
 use tokio::prelude::future::Future;
 
 #[derive(Debug)]
@@ -17,11 +27,24 @@ pub struct DefaultLedgerQueryServiceApiClient {
     create_bitcoin_block_query_endpoint: Url,
     create_ethereum_transaction_query_endpoint: Url,
     create_ethereum_block_query_endpoint: Url,
+    create_ethereum_log_query_endpoint: Url,
+    bitcoin_latest_block_endpoint: Url,
+    ethereum_latest_block_endpoint: Url,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct QueryResponse<T> {
     matches: Vec<T>,
+    #[serde(default)]
+    mined_in: Vec<Option<LatestBlock>>,
+}
+
+impl<T> QueryResponse<T> {
+    fn matches_with_blocks(self) -> Vec<(T, Option<LatestBlock>)> {
+        let mined_in = self.mined_in.into_iter().chain(iter::repeat(None));
+
+        self.matches.into_iter().zip(mined_in).collect()
+    }
 }
 
 impl DefaultLedgerQueryServiceApiClient {
@@ -40,6 +63,15 @@ impl DefaultLedgerQueryServiceApiClient {
             create_ethereum_block_query_endpoint: endpoint
                 .join("queries/ethereum/blocks")
                 .expect("invalid url"),
+            create_ethereum_log_query_endpoint: endpoint
+                .join("queries/ethereum/logs")
+                .expect("invalid url"),
+            bitcoin_latest_block_endpoint: endpoint
+                .join("blocks/bitcoin/latest")
+                .expect("invalid url"),
+            ethereum_latest_block_endpoint: endpoint
+                .join("blocks/ethereum/latest")
+                .expect("invalid url"),
         }
     }
 
@@ -129,7 +161,8 @@ impl DefaultLedgerQueryServiceApiClient {
     fn fetch_full_results<L: Ledger>(
         &self,
         query: &QueryId<L>,
-    ) -> Box<dyn Future<Item = Vec<L::Transaction>, Error = Error> + Send> {
+    ) -> Box<dyn Future<Item = Vec<(L::Transaction, Option<LatestBlock>)>, Error = Error> + Send>
+    {
         let mut url = query.as_ref().clone();
         url.set_query(Some("expand_results=true"));
 
@@ -144,11 +177,86 @@ impl DefaultLedgerQueryServiceApiClient {
                     url, e
                 ))
             })
-            .map(|response| response.matches);
+            .map(QueryResponse::matches_with_blocks);
 
         Box::new(transactions)
     }
 
+    fn fetch_latest(
+        &self,
+        url: Url,
+    ) -> Box<dyn Future<Item = Option<LatestBlock>, Error = Error> + Send> {
+        let latest_block = self
+            .client
+            .get(url.clone())
+            .send()
+            .and_then(|mut response| {
+                if response.status() == StatusCode::NOT_FOUND {
+                    Either::A(future::ok(None))
+                } else {
+                    Either::B(response.json::<LatestBlock>().map(Some))
+                }
+            })
+            .map_err(move |e| {
+                Error::FailedRequest(format!(
+                    "Failed to fetch the latest block from {:?} because {:?}",
+                    url, e
+                ))
+            });
+
+        Box::new(latest_block)
+    }
+
+    fn subscribe<L: Ledger>(
+        &self,
+        query: &QueryId<L>,
+    ) -> Box<dyn Stream<Item = QueryResultEvent<L::TxId>, Error = Error> + Send> {
+        let mut url = query.as_ref().clone();
+        url.path_segments_mut()
+            .expect("query urls have a path")
+            .push("events");
+
+        let mut buffer = String::new();
+        let request_url = url.clone();
+
+        let events = self
+            .client
+            .get(url.clone())
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map(|response| response.into_body())
+            .flatten_stream()
+            .map_err(move |e| {
+                Error::FailedRequest(format!("Failed to subscribe to {:?} because {:?}", url, e))
+            })
+            .map(move |chunk| stream::iter_ok(take_events(&mut buffer, &chunk)))
+            .flatten()
+            .and_then(move |(name, data)| {
+                serde_json::from_str(&data)
+                    .map_err(|e| {
+                        Error::MalformedResponse(format!(
+                            "Unable to parse {} {} pushed by {:?}: {:?}",
+                            name, data, request_url, e
+                        ))
+                    })
+                    .map(|tx_id| match name.as_str() {
+                        "match" => Some(QueryResultEvent::Match(tx_id)),
+                        "removal" => Some(QueryResultEvent::Removal(tx_id)),
+                        _ => {
+                            warn!(
+                                "Ignoring unknown event {} pushed by {:?}",
+                                name, request_url
+                            );
+                            None
+                        }
+                    })
+            })
+            .filter_map(|event| event);
+
+        Box::new(events)
+    }
+
     fn _delete<L: Ledger>(
         &self,
         query: &QueryId<L>,
@@ -193,11 +301,34 @@ impl FetchFullQueryResults<Bitcoin> for DefaultLedgerQueryServiceApiClient {
     fn fetch_full_query_results(
         &self,
         query: &QueryId<Bitcoin>,
-    ) -> Box<dyn Future<Item = Vec<<Bitcoin as Ledger>::Transaction>, Error = Error> + Send> {
+    ) -> Box<
+        dyn Future<
+                Item = Vec<(<Bitcoin as Ledger>::Transaction, Option<LatestBlock>)>,
+                Error = Error,
+            > + Send,
+    > {
         self.fetch_full_results(query)
     }
 }
 
+impl SubscribeToQueryResults<Bitcoin> for DefaultLedgerQueryServiceApiClient {
+    fn subscribe_to_query_results(
+        &self,
+        query: &QueryId<Bitcoin>,
+    ) -> Box<dyn Stream<Item = QueryResultEvent<<Bitcoin as Ledger>::TxId>, Error = Error> + Send>
+    {
+        self.subscribe(query)
+    }
+}
+
+impl FetchLatestBlock<Bitcoin> for DefaultLedgerQueryServiceApiClient {
+    fn fetch_latest_block(
+        &self,
+    ) -> Box<dyn Future<Item = Option<LatestBlock>, Error = Error> + Send> {
+        self.fetch_latest(self.bitcoin_latest_block_endpoint.clone())
+    }
+}
+
 impl LedgerQueryServiceApiClient<Bitcoin, BitcoinQuery> for DefaultLedgerQueryServiceApiClient {
     fn delete(&self, query: &QueryId<Bitcoin>) -> Box<dyn Future<Item = (), Error = Error> + Send> {
         self._delete(&query)
@@ -214,6 +345,7 @@ impl CreateQuery<Ethereum, EthereumQuery> for DefaultLedgerQueryServiceApiClient
                 self.create_ethereum_transaction_query_endpoint.clone()
             }
             EthereumQuery::Block { .. } => self.create_ethereum_block_query_endpoint.clone(),
+            EthereumQuery::Log { .. } => self.create_ethereum_log_query_endpoint.clone(),
         };
         self._create(endpoint, query)
     }
@@ -232,11 +364,34 @@ impl FetchFullQueryResults<Ethereum> for DefaultLedgerQueryServiceApiClient {
     fn fetch_full_query_results(
         &self,
         query: &QueryId<Ethereum>,
-    ) -> Box<dyn Future<Item = Vec<<Ethereum as Ledger>::Transaction>, Error = Error> + Send> {
+    ) -> Box<
+        dyn Future<
+                Item = Vec<(<Ethereum as Ledger>::Transaction, Option<LatestBlock>)>,
+                Error = Error,
+            > + Send,
+    > {
         self.fetch_full_results(query)
     }
 }
 
+impl SubscribeToQueryResults<Ethereum> for DefaultLedgerQueryServiceApiClient {
+    fn subscribe_to_query_results(
+        &self,
+        query: &QueryId<Ethereum>,
+    ) -> Box<dyn Stream<Item = QueryResultEvent<<Ethereum as Ledger>::TxId>, Error = Error> + Send>
+    {
+        self.subscribe(query)
+    }
+}
+
+impl FetchLatestBlock<Ethereum> for DefaultLedgerQueryServiceApiClient {
+    fn fetch_latest_block(
+        &self,
+    ) -> Box<dyn Future<Item = Option<LatestBlock>, Error = Error> + Send> {
+        self.fetch_latest(self.ethereum_latest_block_endpoint.clone())
+    }
+}
+
 impl LedgerQueryServiceApiClient<Ethereum, EthereumQuery> for DefaultLedgerQueryServiceApiClient {
     fn delete(
         &self,
@@ -246,6 +401,30 @@ impl LedgerQueryServiceApiClient<Ethereum, EthereumQuery> for DefaultLedgerQuery
     }
 }
 
+/// Appends `chunk` to `buffer` and drains the name and data of every
+/// Server-Sent Event that is complete by now. Partial events stay in the
+/// buffer for the next chunk.
+fn take_events(buffer: &mut String, chunk: &[u8]) -> Vec<(String, String)> {
+    buffer.push_str(&String::from_utf8_lossy(chunk));
+
+    let mut events = Vec::new();
+    while let Some(end) = buffer.find("\n\n") {
+        let event: String = buffer.drain(..end + 2).collect();
+        // Events without a name are `message`s according to the spec
+        let mut name = String::from("message");
+
+        for line in event.lines() {
+            if line.starts_with("event:") {
+                name = line["event:".len()..].trim().to_string();
+            } else if line.starts_with("data:") {
+                events.push((name.clone(), line["data:".len()..].trim().to_string()));
+            }
+        }
+    }
+
+    events
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -257,4 +436,45 @@ mod test {
 
         let _: QueryResponse<TransactionId> = serde_json::from_str(json).unwrap();
     }
+
+    #[test]
+    fn given_mined_in_blocks_pairs_them_with_the_matches() {
+        let json = r#"{"query":{"to_address":"bcrt1qtfd0gvmdhx2uz267a8a3rpm4v55t8nuzgka2f5xzm4e06tg2d2dqxugdz7","confirmations_needed":1},"matches":["b29cb185d467b3a5faeb7a3f312175e336dbfcc8e9fecc8ad86e9106031315c2","c29cb185d467b3a5faeb7a3f312175e336dbfcc8e9fecc8ad86e9106031315c2"],"mined_in":[{"height":42,"timestamp":1546300800}]}"#;
+
+        let response: QueryResponse<TransactionId> = serde_json::from_str(json).unwrap();
+        let mined_in: Vec<_> = response
+            .matches_with_blocks()
+            .into_iter()
+            .map(|(_, mined_in)| mined_in)
+            .collect();
+
+        assert_eq!(
+            mined_in,
+            vec![
+                Some(LatestBlock {
+                    height: 42,
+                    timestamp: 1_546_300_800,
+                }),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn given_event_split_across_chunks_data_is_taken_once_complete() {
+        let mut buffer = String::new();
+
+        assert_eq!(
+            take_events(&mut buffer, b"event: match\ndata: \"ab"),
+            Vec::<(String, String)>::new()
+        );
+        assert_eq!(
+            take_events(&mut buffer, b"cd\"\n\nevent: removal\ndata: \"ef\"\n\n"),
+            vec![
+                (String::from("match"), String::from("\"abcd\"")),
+                (String::from("removal"), String::from("\"ef\""))
+            ]
+        );
+        assert!(buffer.is_empty());
+    }
 }
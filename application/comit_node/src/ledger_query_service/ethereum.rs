@@ -1,5 +1,5 @@
 use crate::ledger_query_service::Query;
-use ethereum_support::web3::types::{Address, Bytes};
+use ethereum_support::web3::types::{Address, Bytes, H256};
 use serde::Serialize;
 
 #[derive(Debug, Clone, Serialize, Eq, Hash, PartialEq)]
@@ -11,13 +11,67 @@ pub enum EthereumQuery {
         is_contract_creation: Option<bool>,
         transaction_data: Option<Bytes>,
         transaction_data_length: Option<usize>,
+        confirmations_needed: u32,
+        /// Also matches transactions mined before the query was created,
+        /// starting at this height
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from_block_height: Option<u64>,
+        /// The LQS deletes the query once it reaches this height
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at_block: Option<u64>,
     },
     Block {
         min_timestamp_secs: Option<u32>,
     },
+    /// Transactions emitting a log from `address` whose topics match
+    /// position by position, `None` matching any topic, and whose data is
+    /// exactly `data` if given
+    Log {
+        address: Option<Address>,
+        topics: Vec<Option<H256>>,
+        data: Option<Bytes>,
+        confirmations_needed: u32,
+        /// Also matches transactions mined before the query was created,
+        /// starting at this height
+        #[serde(skip_serializing_if = "Option::is_none")]
+        from_block_height: Option<u64>,
+        /// The LQS deletes the query once it reaches this height
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_at_block: Option<u64>,
+    },
 }
 
-impl Query for EthereumQuery {}
+impl Query for EthereumQuery {
+    fn with_from_block_height(mut self, height: u64) -> Self {
+        match self {
+            EthereumQuery::Transaction {
+                ref mut from_block_height,
+                ..
+            }
+            | EthereumQuery::Log {
+                ref mut from_block_height,
+                ..
+            } => *from_block_height = Some(height),
+            EthereumQuery::Block { .. } => {}
+        }
+        self
+    }
+
+    fn with_expires_at_block(mut self, height: u64) -> Self {
+        match self {
+            EthereumQuery::Transaction {
+                ref mut expires_at_block,
+                ..
+            }
+            | EthereumQuery::Log {
+                ref mut expires_at_block,
+                ..
+            } => *expires_at_block = Some(height),
+            EthereumQuery::Block { .. } => {}
+        }
+        self
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -35,11 +89,14 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
         assert_eq!(
             query,
-            r#"{"from_address":null,"to_address":"0x8457037fcd80a8650c4692d7fcfc1d0a96b92867","is_contract_creation":null,"transaction_data":null,"transaction_data_length":null}"#
+            r#"{"from_address":null,"to_address":"0x8457037fcd80a8650c4692d7fcfc1d0a96b92867","is_contract_creation":null,"transaction_data":null,"transaction_data_length":null,"confirmations_needed":1}"#
         )
     }
 
@@ -52,11 +109,14 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
         assert_eq!(
             query,
-            r#"{"from_address":null,"to_address":null,"is_contract_creation":null,"transaction_data":null,"transaction_data_length":null}"#
+            r#"{"from_address":null,"to_address":null,"is_contract_creation":null,"transaction_data":null,"transaction_data_length":null,"confirmations_needed":1}"#
         )
     }
 
@@ -69,6 +129,49 @@ mod tests {
         assert_eq!(query, r#"{"min_timestamp_secs":10}"#)
     }
 
+    #[test]
+    fn given_an_ethereum_log_query_it_serializes_ok() {
+        let query = EthereumQuery::Log {
+            address: Some(Address::from_str("8457037fcd80a8650c4692d7fcfc1d0a96b92867").unwrap()),
+            topics: vec![
+                None,
+                Some(
+                    H256::from_str(
+                        "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                    )
+                    .unwrap(),
+                ),
+            ],
+            data: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+        let query = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            query,
+            r#"{"address":"0x8457037fcd80a8650c4692d7fcfc1d0a96b92867","topics":[null,"0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"],"data":null,"confirmations_needed":1}"#
+        )
+    }
+
+    #[test]
+    fn given_an_ethereum_log_query_from_a_block_height_it_serializes_ok() {
+        let query = EthereumQuery::Log {
+            address: None,
+            topics: vec![],
+            data: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
+        }
+        .with_from_block_height(42);
+        let query = serde_json::to_string(&query).unwrap();
+        assert_eq!(
+            query,
+            r#"{"address":null,"topics":[],"data":null,"confirmations_needed":1,"from_block_height":42}"#
+        )
+    }
+
     #[test]
     fn transaction_query_with_data_serializes_correctly() {
         let query = EthereumQuery::Transaction {
@@ -77,8 +180,14 @@ mod tests {
             is_contract_creation: None,
             transaction_data: Some(Bytes::from(b"hello world!".to_vec())),
             transaction_data_length: Some(12),
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
         };
         let query = serde_json::to_string(&query).unwrap();
-        assert_eq!(query, r#"{"from_address":null,"to_address":null,"is_contract_creation":null,"transaction_data":"0x68656c6c6f20776f726c6421","transaction_data_length":12}"#)
+        assert_eq!(
+            query,
+            r#"{"from_address":null,"to_address":null,"is_contract_creation":null,"transaction_data":"0x68656c6c6f20776f726c6421","transaction_data_length":12,"confirmations_needed":1}"#
+        )
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    ledger_query_service::{FetchFullQueryResults, FetchQueryResults, QueryId},
+    ledger_query_service::{FetchFullQueryResults, FetchQueryResults, LatestBlock, QueryId},
     swap_protocols::ledger::Ledger,
 };
 use std::sync::Arc;
@@ -26,7 +26,9 @@ pub trait FetchTransactionStream<L: Ledger> {
         &self,
         ticker: S,
         query_id: QueryId<L>,
-    ) -> Box<dyn Stream<Item = L::Transaction, Error = S::Error> + Send + 'static>;
+    ) -> Box<
+        dyn Stream<Item = (L::Transaction, Option<LatestBlock>), Error = S::Error> + Send + 'static,
+    >;
 }
 
 impl<L: Ledger, C> FetchTransactionIdStream<L> for Arc<C>
@@ -42,29 +44,36 @@ where
         ticker: S,
         query_id: QueryId<L>,
     ) -> Box<dyn Stream<Item = <L as Ledger>::TxId, Error = S::Error> + Send + 'static> {
-        let mut emitted_transactions = Vec::new();
+        let mut emitted_transactions: Vec<L::TxId> = Vec::new();
 
         let inner_self = self.clone();
 
         Box::new(
             ticker
                 .and_then(move |_| {
-                    inner_self.fetch_query_results(&query_id).or_else(|e| {
-                        warn!("Falling back to empty list of transactions because {:?}", e);
-                        Ok(Vec::new())
-                    })
+                    inner_self
+                        .fetch_query_results(&query_id)
+                        .map(Some)
+                        .or_else(|e| {
+                            warn!("Skipping this round of results because {:?}", e);
+                            Ok(None)
+                        })
                 })
-                .map(iter_ok)
-                .flatten()
-                .filter(move |transaction| {
-                    let is_new_transaction = !emitted_transactions.contains(transaction);
-
-                    if is_new_transaction {
-                        emitted_transactions.push(transaction.clone());
-                    }
-
-                    is_new_transaction
-                }),
+                .filter_map(|transactions| transactions)
+                .map(move |transactions| {
+                    // A transaction that is no longer among the results was
+                    // retracted by a reorg and is new again should it return
+                    emitted_transactions.retain(|emitted| transactions.contains(emitted));
+
+                    let new_transactions: Vec<_> = transactions
+                        .into_iter()
+                        .filter(|transaction| !emitted_transactions.contains(transaction))
+                        .collect();
+                    emitted_transactions.extend(new_transactions.iter().cloned());
+
+                    iter_ok(new_transactions)
+                })
+                .flatten(),
         )
     }
 }
@@ -78,30 +87,60 @@ impl<L: Ledger> FetchTransactionStream<L> for Arc<dyn FetchFullQueryResults<L>>
         &self,
         ticker: S,
         query_id: QueryId<L>,
-    ) -> Box<dyn Stream<Item = <L as Ledger>::Transaction, Error = S::Error> + Send + 'static> {
-        let mut emitted_transactions = Vec::new();
+    ) -> Box<
+        dyn Stream<Item = (<L as Ledger>::Transaction, Option<LatestBlock>), Error = S::Error>
+            + Send
+            + 'static,
+    > {
+        // A transaction emitted before the LQS knew its block is emitted again
+        // once it does
+        let mut emitted_transactions: Vec<(L::Transaction, bool)> = Vec::new();
 
         let inner_self = self.clone();
 
         Box::new(
             ticker
                 .and_then(move |_| {
-                    inner_self.fetch_full_query_results(&query_id).or_else(|e| {
-                        warn!("Falling back to empty list of transactions because {:?}", e);
-                        Ok(Vec::new())
-                    })
+                    inner_self
+                        .fetch_full_query_results(&query_id)
+                        .map(Some)
+                        .or_else(|e| {
+                            warn!("Skipping this round of results because {:?}", e);
+                            Ok(None)
+                        })
                 })
-                .map(iter_ok)
-                .flatten()
-                .filter(move |transaction| {
-                    let is_new_transaction = !emitted_transactions.contains(transaction);
-
-                    if is_new_transaction {
-                        emitted_transactions.push(transaction.clone());
-                    }
-
-                    is_new_transaction
-                }),
+                .filter_map(|transactions| transactions)
+                .map(move |transactions| {
+                    // A transaction that is no longer among the results was
+                    // retracted by a reorg and is new again should it return
+                    emitted_transactions.retain(|(emitted, _)| {
+                        transactions
+                            .iter()
+                            .any(|(transaction, _)| transaction == emitted)
+                    });
+
+                    let new_transactions: Vec<_> = transactions
+                        .into_iter()
+                        .filter(|(transaction, mined_in)| {
+                            !emitted_transactions.iter().any(|(emitted, with_block)| {
+                                emitted == transaction && (*with_block || mined_in.is_none())
+                            })
+                        })
+                        .collect();
+                    emitted_transactions.retain(|(emitted, _)| {
+                        !new_transactions
+                            .iter()
+                            .any(|(transaction, _)| transaction == emitted)
+                    });
+                    emitted_transactions.extend(
+                        new_transactions.iter().map(|(transaction, mined_in)| {
+                            (transaction.clone(), mined_in.is_some())
+                        }),
+                    );
+
+                    iter_ok(new_transactions)
+                })
+                .flatten(),
         )
     }
 }
@@ -115,7 +154,10 @@ mod tests {
     };
     use bitcoin_support::TransactionId;
     use futures::sync::mpsc;
-    use std::time::{Duration, Instant};
+    use std::{
+        sync::Mutex,
+        time::{Duration, Instant},
+    };
     use tokio::{prelude::future::Either, runtime::Runtime, timer::Delay};
 
     #[test]
@@ -268,6 +310,123 @@ mod tests {
         );
     }
 
+    #[test]
+    fn given_transaction_is_retracted_and_matches_again_should_emit_it_again() {
+        let _ = pretty_env_logger::try_init();
+
+        let mut runtime = Runtime::new().unwrap();
+
+        let (sender, receiver) = mpsc::unbounded();
+        let ledger_query_service =
+            Arc::new(LedgerQueryServiceMock::<Bitcoin, BitcoinQuery>::default());
+        let transaction_id = TransactionId::from_hex(
+            "0000000000000000000000000000000000000000000000000000000000000001",
+        )
+        .unwrap();
+
+        let stream = ledger_query_service.fetch_transaction_id_stream(
+            receiver,
+            QueryId::new("http://localhost/results/1".parse().unwrap()),
+        );
+
+        ledger_query_service.set_next_result(Box::new(future::ok(vec![transaction_id])));
+        sender.unbounded_send(()).unwrap();
+        let (result, stream) = runtime
+            .block_on(stream.into_future())
+            .map_err(|_| ())
+            .unwrap();
+        assert_eq!(result, Some(transaction_id));
+
+        // The mock has no result set, so the transaction is gone
+        sender.unbounded_send(()).unwrap();
+        let stream = match runtime.block_on(
+            stream
+                .into_future()
+                .select2(Delay::new(Instant::now() + Duration::from_millis(100))),
+        ) {
+            Ok(Either::B((_, pending))) => pending.into_inner().unwrap(),
+            _ => panic!("should not emit a transaction that was retracted"),
+        };
+
+        ledger_query_service.set_next_result(Box::new(future::ok(vec![transaction_id])));
+        sender.unbounded_send(()).unwrap();
+        let (result, _) = runtime
+            .block_on(stream.into_future())
+            .map_err(|_| ())
+            .unwrap();
+        assert_eq!(result, Some(transaction_id));
+    }
+
+    #[derive(Debug, Default)]
+    struct FullQueryResultsMock {
+        results: Mutex<Vec<(bitcoin_support::Transaction, Option<LatestBlock>)>>,
+    }
+
+    impl FetchFullQueryResults<Bitcoin> for FullQueryResultsMock {
+        fn fetch_full_query_results(
+            &self,
+            _query: &QueryId<Bitcoin>,
+        ) -> Box<
+            dyn Future<
+                    Item = Vec<(bitcoin_support::Transaction, Option<LatestBlock>)>,
+                    Error = crate::ledger_query_service::Error,
+                > + Send,
+        > {
+            Box::new(future::ok(self.results.lock().unwrap().clone()))
+        }
+    }
+
+    #[test]
+    fn given_block_of_emitted_transaction_becomes_known_should_emit_it_again() {
+        let _ = pretty_env_logger::try_init();
+
+        let mut runtime = Runtime::new().unwrap();
+
+        let (sender, receiver) = mpsc::unbounded();
+        let full_query_results = Arc::new(FullQueryResultsMock::default());
+        let transaction = bitcoin_support::Transaction {
+            version: 1,
+            lock_time: 42,
+            input: vec![],
+            output: vec![],
+        };
+        let block = LatestBlock {
+            height: 100,
+            timestamp: 1_546_300_800,
+        };
+
+        let stream = (Arc::clone(&full_query_results) as Arc<dyn FetchFullQueryResults<Bitcoin>>)
+            .fetch_transaction_stream(
+                receiver,
+                QueryId::new("http://localhost/results/1".parse().unwrap()),
+            );
+
+        *full_query_results.results.lock().unwrap() = vec![(transaction.clone(), None)];
+        sender.unbounded_send(()).unwrap();
+        let (result, stream) = runtime
+            .block_on(stream.into_future())
+            .map_err(|_| ())
+            .unwrap();
+        assert_eq!(result, Some((transaction.clone(), None)));
+
+        *full_query_results.results.lock().unwrap() = vec![(transaction.clone(), Some(block))];
+        sender.unbounded_send(()).unwrap();
+        let (result, stream) = runtime
+            .block_on(stream.into_future())
+            .map_err(|_| ())
+            .unwrap();
+        assert_eq!(result, Some((transaction.clone(), Some(block))));
+
+        sender.unbounded_send(()).unwrap();
+        if let Ok(Either::A(_)) = runtime.block_on(
+            stream
+                .into_future()
+                .select2(Delay::new(Instant::now() + Duration::from_millis(100))),
+        ) {
+            panic!("should not emit a transaction again once its block was emitted")
+        }
+    }
+
     #[test]
     fn given_no_results_should_not_emit_anything() {
         let _ = pretty_env_logger::try_init();
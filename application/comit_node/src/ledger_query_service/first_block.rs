@@ -0,0 +1,81 @@
+use crate::{
+    ledger_query_service::{FetchQueryResults, QueryId},
+    swap_protocols::ledger::Ledger,
+};
+use futures::{stream::Stream, Future};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::timer::{self, Interval};
+
+/// Waits for the LQS to report a block matching a block query.
+#[derive(Debug, Clone)]
+pub struct FirstBlock<L: Ledger> {
+    fetch_results: Arc<dyn FetchQueryResults<L>>,
+    poll_interval: Duration,
+}
+
+impl<L: Ledger> FirstBlock<L> {
+    pub fn new<F: FetchQueryResults<L>>(
+        fetch_query_results: Arc<F>,
+        poll_interval: Duration,
+    ) -> Self {
+        Self {
+            fetch_results: fetch_query_results as Arc<dyn FetchQueryResults<L>>,
+            poll_interval,
+        }
+    }
+
+    /// Unlike `FirstMatch::first_match_of`, this doesn't panic if the timer
+    /// fails but hands the error to the caller.
+    pub fn first_block_of(
+        &self,
+        query_id: QueryId<L>,
+    ) -> impl Future<Item = (), Error = timer::Error> {
+        let fetch_results = Arc::clone(&self.fetch_results);
+
+        Interval::new(Instant::now(), self.poll_interval)
+            .and_then(move |_| {
+                fetch_results.fetch_query_results(&query_id).or_else(|e| {
+                    warn!("Falling back to no matching blocks because {:?}", e);
+                    Ok(Vec::new())
+                })
+            })
+            .filter(|blocks| !blocks.is_empty())
+            .take(1)
+            .into_future()
+            .map(|_| ())
+            .map_err(|(e, _)| e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ledger_query_service::fake_query_service::InvocationCountFakeLedgerQueryService,
+        swap_protocols::ledger::Bitcoin,
+    };
+    use bitcoin_support::Sha256dHash;
+    use std::sync::Mutex;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn first_block_of_resolves_once_the_lqs_matched_a_block() {
+        let lqs = Arc::new(InvocationCountFakeLedgerQueryService::<Bitcoin> {
+            number_of_invocations_before_result: 3,
+            invocations: Mutex::new(0),
+            results: vec![Sha256dHash::from_data(b"block")],
+        });
+        let first_block = FirstBlock::new(Arc::clone(&lqs), Duration::from_millis(10));
+
+        let mut runtime = Runtime::new().unwrap();
+        let result = runtime.block_on(
+            first_block.first_block_of(QueryId::new("http://localhost/results/1".parse().unwrap())),
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(*lqs.invocations.lock().unwrap(), 3);
+    }
+}
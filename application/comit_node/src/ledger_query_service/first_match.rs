@@ -1,6 +1,7 @@
 use crate::{
     ledger_query_service::{
-        fetch_transaction_stream::FetchTransactionStream, FetchFullQueryResults, QueryId,
+        fetch_transaction_stream::FetchTransactionStream, FetchFullQueryResults, LatestBlock,
+        QueryId, SubscribeToQueryResults,
     },
     swap_protocols::ledger::Ledger,
 };
@@ -9,36 +10,83 @@ use std::{
     sync::Arc,
     time::{Duration, Instant},
 };
-use tokio::timer::Interval;
+use tokio::timer::{self, Interval};
 
 #[derive(Debug, Clone)]
 pub struct FirstMatch<L: Ledger> {
     fetch_results: Arc<dyn FetchFullQueryResults<L>>,
+    subscriptions: Arc<dyn SubscribeToQueryResults<L>>,
     poll_interval: Duration,
 }
 
 impl<L: Ledger> FirstMatch<L> {
-    pub fn new<F: FetchFullQueryResults<L>>(
-        fetch_full_query_results: Arc<F>,
+    pub fn new<F: FetchFullQueryResults<L> + SubscribeToQueryResults<L>>(
+        lqs_client: Arc<F>,
         poll_interval: Duration,
     ) -> Self {
         Self {
-            fetch_results: fetch_full_query_results as Arc<dyn FetchFullQueryResults<L>>,
+            fetch_results: Arc::clone(&lqs_client) as Arc<dyn FetchFullQueryResults<L>>,
+            subscriptions: lqs_client as Arc<dyn SubscribeToQueryResults<L>>,
             poll_interval,
         }
     }
 }
 
 impl<L: Ledger> FirstMatch<L> {
+    /// Fetches the results whenever the LQS pushes a match or a removal.
+    /// Should the subscription fail or be closed, it falls back to polling
+    /// every `poll_interval`. A pushed match is not taken at its word but
+    /// only once the fetched results still contain it, so a match the LQS
+    /// retracted in the meantime is never acted upon.
     pub fn first_match_of<E>(
         &self,
         query_id: QueryId<L>,
     ) -> impl Future<Item = L::Transaction, Error = E> {
-        self.fetch_results
-            .fetch_transaction_stream(Interval::new(Instant::now(), self.poll_interval), query_id)
+        self.matches_of(query_id)
+            .map(|(transaction, _)| transaction)
+            .take(1)
+            .into_future()
+            .map(|(first_match, _)| first_match.expect("ticker stream should never terminate"))
+            .map_err(|(e, _)| panic!("timer should never error but {:?}", e))
+    }
+
+    /// Like `first_match_of` but also yields the block the match was mined
+    /// in. A match is only taken once the LQS knows that block.
+    pub fn first_match_in_block_of<E>(
+        &self,
+        query_id: QueryId<L>,
+    ) -> impl Future<Item = (L::Transaction, LatestBlock), Error = E> {
+        self.matches_of(query_id)
+            .filter_map(|(transaction, mined_in)| {
+                if mined_in.is_none() {
+                    debug!("Waiting for the LQS to know the block of {:?}", transaction);
+                }
+                mined_in.map(|block| (transaction, block))
+            })
             .take(1)
             .into_future()
-            .map(|(txid, _)| txid.expect("ticker stream should never terminate"))
+            .map(|(first_match, _)| first_match.expect("ticker stream should never terminate"))
             .map_err(|(e, _)| panic!("timer should never error but {:?}", e))
     }
+
+    fn matches_of(
+        &self,
+        query_id: QueryId<L>,
+    ) -> impl Stream<Item = (L::Transaction, Option<LatestBlock>), Error = timer::Error> {
+        let pushed_events = self
+            .subscriptions
+            .subscribe_to_query_results(&query_id)
+            .then(|result| {
+                if let Err(e) = &result {
+                    warn!("Falling back to polling because {:?}", e);
+                }
+                Ok::<_, timer::Error>(result.is_ok())
+            })
+            .take_while(|is_subscribed| Ok(*is_subscribed))
+            .map(|_| ());
+        let polling = Interval::new(Instant::now(), self.poll_interval).map(|_| ());
+
+        self.fetch_results
+            .fetch_transaction_stream(pushed_events.chain(polling), query_id)
+    }
 }
@@ -0,0 +1,100 @@
+use crate::{
+    ledger_query_service::Error,
+    swap_protocols::ledger::{Bitcoin, Ethereum, Ledger},
+};
+use futures::{Future, Stream};
+use std::{
+    fmt::Debug,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::timer::Interval;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct LatestBlock {
+    pub height: u64,
+    pub timestamp: u64,
+}
+
+pub trait FetchLatestBlock<L: Ledger>: 'static + Send + Sync + Debug {
+    /// Resolves to `None` as long as the LQS hasn't processed a block since it
+    /// was started.
+    fn fetch_latest_block(
+        &self,
+    ) -> Box<dyn Future<Item = Option<LatestBlock>, Error = Error> + Send>;
+}
+
+/// Keeps track of the most recent block the LQS has processed for a ledger.
+#[derive(Debug, Clone)]
+pub struct ChainTip<L: Ledger> {
+    fetch_latest_block: Arc<dyn FetchLatestBlock<L>>,
+    latest_block: Arc<RwLock<Option<LatestBlock>>>,
+    poll_interval: Duration,
+}
+
+impl<L: Ledger> ChainTip<L> {
+    pub fn new<F: FetchLatestBlock<L>>(
+        fetch_latest_block: Arc<F>,
+        poll_interval: Duration,
+    ) -> Self {
+        Self {
+            fetch_latest_block: fetch_latest_block as Arc<dyn FetchLatestBlock<L>>,
+            latest_block: Arc::new(RwLock::new(None)),
+            poll_interval,
+        }
+    }
+
+    /// The block seen by the last poll. Lags behind the LQS by up to one poll
+    /// interval, so it is only ever too old, never too new.
+    pub fn latest_known(&self) -> Option<LatestBlock> {
+        *self.latest_block.read().unwrap()
+    }
+
+    pub fn keep_up_to_date(&self) -> impl Future<Item = (), Error = ()> + Send {
+        let fetch_latest_block = Arc::clone(&self.fetch_latest_block);
+        let latest_block = Arc::clone(&self.latest_block);
+
+        Interval::new(Instant::now(), self.poll_interval)
+            .map_err(|e| error!("Stopped polling for the latest block because {:?}", e))
+            .for_each(move |_| {
+                let latest_block = Arc::clone(&latest_block);
+
+                fetch_latest_block.fetch_latest_block().then(move |result| {
+                    match result {
+                        Ok(Some(block)) => *latest_block.write().unwrap() = Some(block),
+                        Ok(None) => trace!("LQS hasn't seen a block yet"),
+                        Err(e) => warn!("Failed to fetch the latest block because {:?}", e),
+                    };
+                    Ok(())
+                })
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LatestBlocks {
+    pub bitcoin: Option<LatestBlock>,
+    pub ethereum: Option<LatestBlock>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChainTips {
+    pub bitcoin: ChainTip<Bitcoin>,
+    pub ethereum: ChainTip<Ethereum>,
+}
+
+impl ChainTips {
+    pub fn latest_known(&self) -> LatestBlocks {
+        LatestBlocks {
+            bitcoin: self.bitcoin.latest_known(),
+            ethereum: self.ethereum.latest_known(),
+        }
+    }
+
+    pub fn keep_up_to_date(&self) -> impl Future<Item = (), Error = ()> + Send {
+        self.bitcoin
+            .keep_up_to_date()
+            .join(self.ethereum.keep_up_to_date())
+            .map(|_| ())
+    }
+}
@@ -1,9 +1,11 @@
-pub use self::{bitcoin::*, cache::*, client::*, ethereum::*, first_match::*};
+pub use self::{
+    bitcoin::*, cache::*, client::*, ethereum::*, first_block::*, first_match::*, latest_block::*,
+};
 use crate::swap_protocols::ledger::Ledger;
 use reqwest::Url;
 use serde::Serialize;
 use std::{fmt::Debug, hash::Hash, marker::PhantomData};
-use tokio::prelude::Future;
+use tokio::prelude::{Future, Stream};
 
 mod bitcoin;
 mod cache;
@@ -11,7 +13,9 @@ mod client;
 mod ethereum;
 pub mod fake_query_service;
 pub mod fetch_transaction_stream;
+mod first_block;
 mod first_match;
+mod latest_block;
 
 #[derive(Clone, Debug, PartialOrd, PartialEq)]
 pub struct QueryId<L: Ledger> {
@@ -34,7 +38,7 @@ impl<L: Ledger> QueryId<L> {
     }
 }
 
-#[derive(Fail, Debug, PartialEq, Clone)]
+#[derive(Fail, Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Error {
     #[fail(display = "The request failed to send.")]
     FailedRequest(String),
@@ -42,7 +46,17 @@ pub enum Error {
     MalformedResponse(String),
 }
 
-pub trait Query: Sized + Clone + Debug + Send + Sync + Eq + Hash + Serialize + 'static {}
+pub trait Query: Sized + Clone + Debug + Send + Sync + Eq + Hash + Serialize + 'static {
+    /// Asks the LQS to also look at the blocks from `height` on, which may
+    /// have been mined before the query is created. Queries that match blocks
+    /// rather than transactions stay as they are.
+    fn with_from_block_height(self, height: u64) -> Self;
+
+    /// Asks the LQS to delete the query on its own once it reaches block
+    /// `height`, should it never be deleted otherwise. Leaves queries that
+    /// match blocks as they are.
+    fn with_expires_at_block(self, height: u64) -> Self;
+}
 
 pub trait LedgerQueryServiceApiClient<L: Ledger, Q: Query>:
     'static + Send + Sync + CreateQuery<L, Q> + FetchQueryResults<L>
@@ -57,7 +71,7 @@ pub trait CreateQuery<L: Ledger, Q: Query>: 'static + Send + Sync + Debug {
     ) -> Box<dyn Future<Item = QueryId<L>, Error = Error> + Send + 'static>;
 }
 
-pub trait FetchQueryResults<L: Ledger>: 'static + Send + Sync {
+pub trait FetchQueryResults<L: Ledger>: 'static + Send + Sync + Debug {
     fn fetch_query_results(
         &self,
         query: &QueryId<L>,
@@ -65,8 +79,27 @@ pub trait FetchQueryResults<L: Ledger>: 'static + Send + Sync {
 }
 
 pub trait FetchFullQueryResults<L: Ledger>: 'static + Send + Sync + Debug {
+    /// Fetches the matched transactions along with the block each of them was
+    /// mined in, if the LQS knows it.
     fn fetch_full_query_results(
         &self,
         query: &QueryId<L>,
-    ) -> Box<dyn Future<Item = Vec<L::Transaction>, Error = Error> + Send>;
+    ) -> Box<dyn Future<Item = Vec<(L::Transaction, Option<LatestBlock>)>, Error = Error> + Send>;
+}
+
+/// A change to the results of a query that the LQS pushed
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryResultEvent<T> {
+    Match(T),
+    /// The LQS retracted the match because its block got orphaned
+    Removal(T),
+}
+
+pub trait SubscribeToQueryResults<L: Ledger>: 'static + Send + Sync + Debug {
+    /// Yields the matches found so far and then every match or removal the
+    /// LQS pushes, for as long as the connection stays open.
+    fn subscribe_to_query_results(
+        &self,
+        query: &QueryId<L>,
+    ) -> Box<dyn Stream<Item = QueryResultEvent<L::TxId>, Error = Error> + Send>;
 }
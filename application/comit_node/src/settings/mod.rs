@@ -1,12 +1,16 @@
 mod serde;
 
-use crate::seed::Seed;
+use crate::{
+    seed::Seed,
+    swap_protocols::ledger::{MinConfirmations, Networks},
+};
 use ::serde::Deserialize;
+use bam::{json, noise::PublicKey};
 use config::{Config, ConfigError, File};
 use std::{
     ffi::OsStr,
     net::{IpAddr, SocketAddr},
-    path::Path,
+    path::{Path, PathBuf},
     time::Duration,
 };
 
@@ -15,6 +19,17 @@ pub struct ComitNodeSettings {
     pub comit: Comit,
     pub http_api: HttpApi,
     pub ledger_query_service: LedgerQueryService,
+    #[serde(default)]
+    pub storage: Storage,
+    #[serde(default)]
+    pub safety_margin: SafetyMargin,
+    #[serde(default)]
+    pub auto_execute: Option<AutoExecute>,
+    #[serde(default)]
+    pub peers: Peers,
+    pub networks: Networks,
+    #[serde(default)]
+    pub min_confirmations: MinConfirmations,
 }
 
 #[derive(Debug, Deserialize)]
@@ -24,6 +39,86 @@ pub struct Comit {
     #[serde(with = "serde::socket_addr")]
     pub comit_listen: SocketAddr,
     pub secret_seed: Seed,
+    #[serde(default)]
+    pub secure_transport: SecureTransport,
+    /// How long Alice waits for Bob to answer a swap request before the swap
+    /// fails with a timeout
+    #[serde(with = "serde::duration", default = "default_request_timeout")]
+    pub request_timeout_secs: Duration,
+    /// How BAM frames are delimited on connections this node opens. Other
+    /// formats than `newline_delimited_json` are only used if the peer
+    /// accepts them.
+    #[serde(default)]
+    pub frame_format: json::FrameFormat,
+    /// Connections over which the peer sends a larger frame are closed
+    #[serde(default = "default_max_frame_len")]
+    pub max_frame_len: usize,
+}
+
+fn default_request_timeout() -> Duration {
+    Duration::from_secs(60 * 60)
+}
+
+fn default_max_frame_len() -> usize {
+    json::DEFAULT_MAX_FRAME_LEN
+}
+
+/// Whether COMIT connections are encrypted and authenticated with the node
+/// identity key derived from `secret_seed`. `enabled` still accepts plaintext
+/// connections from peers that do not support it, `required` refuses them.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SecureTransport {
+    Disabled,
+    Enabled,
+    Required,
+}
+
+impl Default for SecureTransport {
+    fn default() -> Self {
+        SecureTransport::Disabled
+    }
+}
+
+/// Which COMIT nodes we talk to, identified by the public key they
+/// authenticate with over the secure transport. Peers on a plaintext
+/// connection cannot be identified and are refused once `allowed` or `denied`
+/// is set, otherwise a denied peer could just leave out the secure transport.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Peers {
+    #[serde(default)]
+    pub allowed: Option<Vec<PublicKey>>,
+    #[serde(default)]
+    pub denied: Vec<PublicKey>,
+    #[serde(default)]
+    pub pinned: Vec<PinnedPeer>,
+}
+
+/// A COMIT node we connect to has to authenticate with `public_key`, so that
+/// whoever answers at `address` cannot impersonate it.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PinnedPeer {
+    #[serde(with = "serde::socket_addr")]
+    pub address: SocketAddr,
+    pub public_key: PublicKey,
+}
+
+impl Peers {
+    pub fn pinned_key_of(&self, address: &SocketAddr) -> Option<PublicKey> {
+        self.pinned
+            .iter()
+            .find(|pinned| pinned.address == *address)
+            .map(|pinned| pinned.public_key)
+    }
+
+    pub fn is_allowed(&self, peer: Option<&PublicKey>) -> bool {
+        match (peer, &self.allowed) {
+            (Some(peer), _) if self.denied.contains(peer) => false,
+            (Some(peer), Some(allowed)) => allowed.contains(peer),
+            (Some(_), None) => true,
+            (None, allowed) => allowed.is_none() && self.denied.is_empty(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -47,6 +142,72 @@ pub struct PollParameters {
     pub poll_interval_secs: Duration,
 }
 
+/// Where the node keeps swap metadata and states. Only `disk` survives a
+/// restart.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Storage {
+    InMemory,
+    Disk { path: PathBuf },
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage::InMemory
+    }
+}
+
+/// How much time has to be left on the alpha HTLC for the node to still
+/// offer redeeming beta (as Alice) or funding beta (as Bob).
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SafetyMargin {
+    #[serde(with = "serde::duration")]
+    pub min_time_to_alpha_expiry_secs: Duration,
+}
+
+impl Default for SafetyMargin {
+    fn default() -> Self {
+        SafetyMargin {
+            min_time_to_alpha_expiry_secs: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Lets the node redeem and refund by itself. Ledgers without a wallet are
+/// still left to the user.
+#[derive(Debug, Deserialize)]
+pub struct AutoExecute {
+    #[serde(with = "serde::duration")]
+    pub poll_interval_secs: Duration,
+    /// How long a broadcast transaction may take to show up on the ledger
+    /// before it is broadcast again
+    #[serde(with = "serde::duration", default = "default_retry_interval")]
+    pub retry_interval_secs: Duration,
+    pub bitcoin: Option<BitcoinWallet>,
+    pub ethereum: Option<EthereumWallet>,
+}
+
+fn default_retry_interval() -> Duration {
+    Duration::from_secs(30 * 60)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BitcoinWallet {
+    #[serde(with = "serde::url")]
+    pub node_url: url::Url,
+    pub node_username: String,
+    pub node_password: String,
+    #[serde(with = "serde::bitcoin_address")]
+    pub payout_address: bitcoin_support::Address,
+    pub fee_per_byte: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EthereumWallet {
+    #[serde(with = "serde::url")]
+    pub node_url: url::Url,
+}
+
 impl ComitNodeSettings {
     pub fn create<D: AsRef<OsStr>, R: AsRef<OsStr>>(
         default_config: D,
@@ -109,4 +270,120 @@ mod tests {
         .is_equal_to(&Duration::from_secs(20));
     }
 
+    #[test]
+    fn ethereum_network_is_read_by_name() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().networks.ethereum)
+            .is_equal_to(&ethereum_support::Network::Regtest);
+    }
+
+    #[test]
+    fn storage_defaults_to_in_memory() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().storage).is_equal_to(&Storage::InMemory);
+    }
+
+    #[test]
+    fn safety_margin_is_read_in_seconds() {
+        let settings = comit_settings();
+
+        assert_that(
+            &settings
+                .unwrap()
+                .safety_margin
+                .min_time_to_alpha_expiry_secs,
+        )
+        .is_equal_to(&Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn auto_execute_is_off_by_default() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().auto_execute.is_none()).is_true();
+    }
+
+    #[test]
+    fn secure_transport_is_disabled_by_default() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().comit.secure_transport)
+            .is_equal_to(SecureTransport::Disabled);
+    }
+
+    #[test]
+    fn frame_format_is_newline_delimited_json_by_default() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().comit.frame_format)
+            .is_equal_to(json::FrameFormat::NewlineDelimited);
+    }
+
+    #[test]
+    fn max_frame_len_defaults_to_the_one_of_bam() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().comit.max_frame_len)
+            .is_equal_to(json::DEFAULT_MAX_FRAME_LEN);
+    }
+
+    #[test]
+    fn all_peers_are_allowed_by_default() {
+        let settings = comit_settings();
+
+        assert_that(&settings.unwrap().peers).is_equal_to(Peers::default());
+    }
+
+    #[test]
+    fn denied_peer_is_refused_even_if_allowed() {
+        let alice = PublicKey::from_bytes([1; 32]);
+        let bob = PublicKey::from_bytes([2; 32]);
+
+        let peers = Peers {
+            allowed: Some(vec![alice, bob]),
+            denied: vec![bob],
+            pinned: vec![],
+        };
+
+        assert_that(&peers.is_allowed(Some(&alice))).is_true();
+        assert_that(&peers.is_allowed(Some(&bob))).is_false();
+        assert_that(&peers.is_allowed(None)).is_false();
+    }
+
+    #[test]
+    fn unidentified_peer_is_refused_once_a_peer_is_denied() {
+        let alice = PublicKey::from_bytes([1; 32]);
+        let bob = PublicKey::from_bytes([2; 32]);
+
+        let peers = Peers {
+            allowed: None,
+            denied: vec![bob],
+            pinned: vec![],
+        };
+
+        assert_that(&peers.is_allowed(Some(&alice))).is_true();
+        assert_that(&peers.is_allowed(None)).is_false();
+    }
+
+    #[test]
+    fn pinned_key_is_looked_up_by_address() {
+        let alice = PublicKey::from_bytes([1; 32]);
+        let address = "127.0.0.1:8011".parse().unwrap();
+
+        let peers = Peers {
+            allowed: None,
+            denied: vec![],
+            pinned: vec![PinnedPeer {
+                address,
+                public_key: alice,
+            }],
+        };
+
+        assert_that(&peers.pinned_key_of(&address))
+            .is_some()
+            .is_equal_to(alice);
+        assert_that(&peers.pinned_key_of(&"127.0.0.1:8012".parse().unwrap())).is_none();
+    }
 }
@@ -1,3 +1,4 @@
+pub mod bitcoin_address;
 pub mod duration;
 pub mod socket_addr;
 pub mod url;
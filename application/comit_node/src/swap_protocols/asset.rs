@@ -1,9 +1,11 @@
 use crate::{
     bam_api::header::{FromBamHeader, ToBamHeader},
     http_api::asset::{FromHttpAsset, ToHttpAsset},
+    swap_protocols::metadata_store::AssetKind,
 };
 use bitcoin_support::BitcoinQuantity;
 use ethereum_support::{Erc20Quantity, EtherQuantity};
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
     fmt::{Debug, Display},
     hash::Hash,
@@ -19,13 +21,34 @@ pub trait Asset:
     + PartialEq
     + Eq
     + Hash
+    + Serialize
+    + DeserializeOwned
     + FromHttpAsset
     + ToHttpAsset
     + FromBamHeader
     + ToBamHeader
+    + Into<AssetKind>
 {
 }
 
 impl Asset for BitcoinQuantity {}
 impl Asset for EtherQuantity {}
 impl Asset for Erc20Quantity {}
+
+impl From<BitcoinQuantity> for AssetKind {
+    fn from(_: BitcoinQuantity) -> Self {
+        AssetKind::Bitcoin
+    }
+}
+
+impl From<EtherQuantity> for AssetKind {
+    fn from(_: EtherQuantity) -> Self {
+        AssetKind::Ether
+    }
+}
+
+impl From<Erc20Quantity> for AssetKind {
+    fn from(_: Erc20Quantity) -> Self {
+        AssetKind::Erc20
+    }
+}
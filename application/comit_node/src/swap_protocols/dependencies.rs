@@ -1,6 +1,11 @@
 use crate::{
-    comit_client::ClientFactory, ledger_query_service::DefaultLedgerQueryServiceApiClient,
+    comit_client::ClientFactory,
+    ledger_query_service::{ChainTips, DefaultLedgerQueryServiceApiClient},
     seed::Seed,
+    swap_protocols::{
+        ledger::{MinConfirmations, Networks},
+        metadata_store::StartHeights,
+    },
 };
 use std::{net::SocketAddr, sync::Arc, time::Duration};
 
@@ -13,7 +18,12 @@ pub struct ProtocolDependencies<T, S, C> {
     pub state_store: Arc<S>,
     pub comit_client_factory: Arc<dyn ClientFactory<C>>,
     pub seed: Seed,
+    /// Used for swaps that don't name a peer themselves
     pub remote_comit_node: SocketAddr,
+    /// Swaps on ledgers of any other network are refused
+    pub networks: Networks,
+    /// Swaps asking for fewer confirmations are refused
+    pub min_confirmations: MinConfirmations,
 }
 
 #[derive(Debug)]
@@ -21,4 +31,17 @@ pub struct LedgerEventDependencies {
     pub lqs_client: Arc<DefaultLedgerQueryServiceApiClient>,
     pub lqs_bitcoin_poll_interval: Duration,
     pub lqs_ethereum_poll_interval: Duration,
+    pub chain_tips: ChainTips,
+}
+
+impl LedgerEventDependencies {
+    /// To be recorded when a swap is started, see [`StartHeights`]
+    pub fn start_heights(&self) -> StartHeights {
+        let latest_blocks = self.chain_tips.latest_known();
+
+        StartHeights {
+            bitcoin: latest_blocks.bitcoin.map(|block| block.height),
+            ethereum: latest_blocks.ethereum.map(|block| block.height),
+        }
+    }
 }
@@ -0,0 +1,101 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to access {:?}", _0)]
+    Io(PathBuf, #[cause] io::Error),
+    #[fail(display = "Contents of {:?} could not be (de)serialized", _0)]
+    Serialization(PathBuf, #[cause] serde_json::Error),
+}
+
+/// A directory holding one JSON document per key.
+#[derive(Debug, Clone)]
+pub struct JsonDirectory {
+    path: PathBuf,
+}
+
+impl JsonDirectory {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path).map_err(|e| Error::Io(path.clone(), e))?;
+
+        Ok(JsonDirectory { path })
+    }
+
+    pub fn file_for<K: ToString>(&self, key: &K) -> PathBuf {
+        self.path.join(format!("{}.json", key.to_string()))
+    }
+
+    pub fn contains<K: ToString>(&self, key: &K) -> bool {
+        self.file_for(key).exists()
+    }
+
+    pub fn keys(&self) -> Result<Vec<String>, Error> {
+        let entries = fs::read_dir(&self.path).map_err(|e| Error::Io(self.path.clone(), e))?;
+
+        let mut keys = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| Error::Io(self.path.clone(), e))?.path();
+
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+
+            if let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) {
+                keys.push(key.to_string());
+            }
+        }
+
+        Ok(keys)
+    }
+
+    pub fn read<K: ToString, T: DeserializeOwned>(&self, key: &K) -> Result<Option<T>, Error> {
+        read_json(&self.file_for(key))
+    }
+
+    pub fn write<K: ToString, T: Serialize>(&self, key: &K, value: &T) -> Result<(), Error> {
+        write_json(&self.file_for(key), value)
+    }
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Io(path.to_path_buf(), e)),
+    };
+
+    serde_json::from_reader(file)
+        .map(Some)
+        .map_err(|e| Error::Serialization(path.to_path_buf(), e))
+}
+
+/// Writes to a temporary file first and moves it into place afterwards so
+/// that a crash halfway through never leaves a truncated document behind.
+/// The directory is synced as well, otherwise the rename may not survive a
+/// crash even though the file's contents do.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let bytes =
+        serde_json::to_vec(value).map_err(|e| Error::Serialization(path.to_path_buf(), e))?;
+
+    let tmp_path = path.with_extension("json.tmp");
+
+    let mut file = File::create(&tmp_path).map_err(|e| Error::Io(tmp_path.clone(), e))?;
+    file.write_all(&bytes)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| Error::Io(tmp_path.clone(), e))?;
+
+    fs::rename(&tmp_path, path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+
+    match path.parent() {
+        Some(directory) if directory != Path::new("") => File::open(directory)
+            .and_then(|directory| directory.sync_all())
+            .map_err(|e| Error::Io(directory.to_path_buf(), e)),
+        _ => Ok(()),
+    }
+}
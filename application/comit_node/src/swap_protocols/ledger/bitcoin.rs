@@ -1,10 +1,13 @@
-use crate::swap_protocols::ledger::Ledger;
+use crate::swap_protocols::{
+    ledger::{Ledger, MinConfirmations, Networks},
+    metadata_store::LedgerKind,
+};
 use bitcoin_support::{
     Address, BitcoinQuantity, IntoP2wpkhAddress, Network, PubkeyHash, Transaction, TransactionId,
 };
 use secp256k1_support::PublicKey;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Bitcoin {
     pub network: Network,
 }
@@ -30,6 +33,12 @@ impl Default for Bitcoin {
     }
 }
 
+impl From<Bitcoin> for LedgerKind {
+    fn from(_: Bitcoin) -> Self {
+        LedgerKind::Bitcoin
+    }
+}
+
 impl Ledger for Bitcoin {
     type Quantity = BitcoinQuantity;
     type TxId = TransactionId;
@@ -41,4 +50,13 @@ impl Ledger for Bitcoin {
     fn address_for_identity(&self, pubkeyhash: PubkeyHash) -> Address {
         pubkeyhash.into_p2wpkh_address(self.network)
     }
+
+    // There is no configured Bitcoin network yet
+    fn is_on(&self, _: &Networks) -> bool {
+        true
+    }
+
+    fn min_confirmations(min_confirmations: &MinConfirmations) -> u32 {
+        min_confirmations.bitcoin
+    }
 }
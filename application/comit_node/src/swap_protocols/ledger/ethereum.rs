@@ -1,9 +1,36 @@
-use crate::swap_protocols::ledger::Ledger;
-use ethereum_support::{Address, EtherQuantity, Transaction, H256};
+use crate::swap_protocols::{
+    ledger::{Ledger, MinConfirmations, Networks},
+    metadata_store::LedgerKind,
+};
+use ethereum_support::{Address, EtherQuantity, Network, Transaction, H256};
 use secp256k1_support::PublicKey;
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
-pub struct Ethereum {}
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Ethereum {
+    pub network: Network,
+}
+
+impl Ethereum {
+    pub fn new(network: Network) -> Self {
+        Ethereum { network }
+    }
+
+    pub fn regtest() -> Self {
+        Ethereum {
+            network: Network::Regtest,
+        }
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.network.chain_id()
+    }
+}
+
+impl From<Ethereum> for LedgerKind {
+    fn from(_: Ethereum) -> Self {
+        LedgerKind::Ethereum
+    }
+}
 
 impl Ledger for Ethereum {
     type Quantity = EtherQuantity;
@@ -16,4 +43,29 @@ impl Ledger for Ethereum {
     fn address_for_identity(&self, address: Address) -> Address {
         address
     }
+
+    fn is_on(&self, networks: &Networks) -> bool {
+        self.network == networks.ethereum
+    }
+
+    fn min_confirmations(min_confirmations: &MinConfirmations) -> u32 {
+        min_confirmations.ethereum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn ethereum_on_another_network_than_the_node_is_not_on_it() {
+        let networks = Networks {
+            ethereum: Network::Regtest,
+        };
+
+        assert_that(&Ethereum::regtest().is_on(&networks)).is_true();
+        assert_that(&Ethereum::new(Network::Mainnet).is_on(&networks)).is_false();
+    }
+
 }
@@ -8,6 +8,7 @@ pub use self::{bitcoin::Bitcoin, ethereum::Ethereum};
 use crate::{
     bam_api::header::{FromBamHeader, ToBamHeader},
     http_api::ledger::{FromHttpLedger, ToHttpLedger},
+    swap_protocols::metadata_store::LedgerKind,
 };
 use std::hash::Hash;
 
@@ -17,7 +18,8 @@ pub trait Ledger:
     + Send
     + Sync
     + 'static
-    + Default
+    + Serialize
+    + DeserializeOwned
     + PartialEq
     + Eq
     + Hash
@@ -25,6 +27,7 @@ pub trait Ledger:
     + ToHttpLedger
     + FromBamHeader
     + ToBamHeader
+    + Into<LedgerKind>
 {
     type Quantity: Debug + Copy + DeserializeOwned + Serialize + Send + Sync + 'static;
     type TxId: Debug + Clone + DeserializeOwned + Serialize + Send + Sync + PartialEq + 'static;
@@ -51,4 +54,36 @@ pub trait Ledger:
         + 'static;
 
     fn address_for_identity(&self, identity: Self::Identity) -> Self::Address;
+
+    /// Whether the node's connection to this kind of ledger is on the network
+    /// of `self`. Swaps on other networks can neither be observed nor acted on.
+    fn is_on(&self, networks: &Networks) -> bool;
+
+    /// The fewest confirmations of a transaction on this kind of ledger the
+    /// node accepts a swap to wait for
+    fn min_confirmations(min_confirmations: &MinConfirmations) -> u32;
+}
+
+/// The networks of the chains the ledger query service and the wallets are
+/// connected to. Swaps on any other network are refused.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct Networks {
+    pub ethereum: ethereum_support::Network,
+}
+
+/// Swaps asking for fewer confirmations are refused, as the counterparty
+/// could otherwise have us act on a transaction it can still reorg out
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct MinConfirmations {
+    pub bitcoin: u32,
+    pub ethereum: u32,
+}
+
+impl Default for MinConfirmations {
+    fn default() -> Self {
+        MinConfirmations {
+            bitcoin: 1,
+            ethereum: 1,
+        }
+    }
 }
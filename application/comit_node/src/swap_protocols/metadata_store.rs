@@ -1,42 +1,70 @@
+use crate::swap_protocols::disk_store::{self, JsonDirectory};
+use bam::noise::PublicKey;
 use std::{
     collections::HashMap,
     fmt::{Debug, Display},
     hash::Hash,
+    marker::PhantomData,
+    path::Path,
+    str::FromStr,
     sync::Mutex,
 };
 
-#[derive(Clone, Copy, Debug, Display)]
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize)]
 pub enum RoleKind {
     Alice,
     Bob,
 }
 
-#[derive(Clone, Copy, Debug, Display)]
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize)]
 pub enum LedgerKind {
     Bitcoin,
     Ethereum,
 }
 
-#[derive(Clone, Copy, Debug, Display)]
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize)]
 pub enum AssetKind {
     Bitcoin,
     Ether,
     Erc20,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Metadata {
     pub alpha_ledger: LedgerKind,
     pub beta_ledger: LedgerKind,
     pub alpha_asset: AssetKind,
     pub beta_asset: AssetKind,
     pub role: RoleKind,
+    /// The node identity of the other party, known if the COMIT connection
+    /// was authenticated
+    #[serde(default)]
+    pub counterparty: Option<PublicKey>,
+    #[serde(default)]
+    pub start_heights: StartHeights,
+}
+
+/// The heights the LQS had processed when the swap was started. None of the
+/// swap's transactions can be mined in an earlier block, so the LQS only needs
+/// to look back that far for queries created later on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StartHeights {
+    pub bitcoin: Option<u64>,
+    pub ethereum: Option<u64>,
 }
 
 #[derive(Debug, Fail)]
 pub enum Error {
     #[fail(display = "Metadata already exists")]
     DuplicateKey,
+    #[fail(display = "Metadata could not be read from or written to disk")]
+    Disk(#[cause] disk_store::Error),
+}
+
+impl From<disk_store::Error> for Error {
+    fn from(e: disk_store::Error) -> Self {
+        Error::Disk(e)
+    }
 }
 
 pub trait MetadataStore<K>: Send + Sync + 'static {
@@ -79,3 +107,110 @@ impl<K: Debug + Display + Hash + Eq + Clone + Send + Sync + 'static> MetadataSto
             .collect())
     }
 }
+
+#[derive(Debug)]
+pub struct DiskMetadataStore<K> {
+    directory: JsonDirectory,
+    write_lock: Mutex<()>,
+    key_type: PhantomData<K>,
+}
+
+impl<K> DiskMetadataStore<K> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(DiskMetadataStore {
+            directory: JsonDirectory::open(path)?,
+            write_lock: Mutex::new(()),
+            key_type: PhantomData,
+        })
+    }
+}
+
+impl<K: Debug + Display + FromStr + Send + Sync + 'static> MetadataStore<K>
+    for DiskMetadataStore<K>
+{
+    fn get(&self, key: &K) -> Result<Option<Metadata>, Error> {
+        let metadata = self.directory.read(key)?;
+        trace!("Fetched metadata of swap with id {}: {:?}", key, metadata);
+
+        Ok(metadata)
+    }
+
+    fn insert<M: Into<Metadata>>(&self, key: K, value: M) -> Result<(), Error> {
+        let _guard = self.write_lock.lock().unwrap();
+
+        if self.directory.contains(&key) {
+            return Err(Error::DuplicateKey);
+        }
+
+        self.directory.write(&key, &value.into())?;
+        Ok(())
+    }
+
+    fn all(&self) -> Result<Vec<(K, Metadata)>, Error> {
+        let mut all = Vec::new();
+
+        for name in self.directory.keys()? {
+            let key = match K::from_str(&name) {
+                Ok(key) => key,
+                Err(_) => {
+                    warn!("Ignoring unexpected file {}.json in metadata store", name);
+                    continue;
+                }
+            };
+
+            if let Some(metadata) = self.directory.read(&key)? {
+                all.push((key, metadata));
+            }
+        }
+
+        Ok(all)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap_protocols::SwapId;
+    use spectral::prelude::*;
+
+    fn metadata() -> Metadata {
+        Metadata {
+            alpha_ledger: LedgerKind::Bitcoin,
+            beta_ledger: LedgerKind::Ethereum,
+            alpha_asset: AssetKind::Bitcoin,
+            beta_asset: AssetKind::Ether,
+            role: RoleKind::Alice,
+            counterparty: None,
+            start_heights: StartHeights::default(),
+        }
+    }
+
+    #[test]
+    fn disk_metadata_store_survives_reopening() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let id = SwapId::default();
+
+        {
+            let store = DiskMetadataStore::open(path).unwrap();
+            store.insert(id, metadata()).unwrap();
+
+            assert_that(&store.insert(id, metadata())).is_err();
+        }
+
+        let store = DiskMetadataStore::<SwapId>::open(path).unwrap();
+
+        assert_that(&store.get(&id).unwrap()).is_some();
+        assert_that(&store.all().unwrap()).has_length(1);
+    }
+
+    #[test]
+    fn metadata_stored_before_start_heights_were_recorded_can_be_read() {
+        let metadata = serde_json::from_str::<Metadata>(
+            r#"{"alpha_ledger":"Bitcoin","beta_ledger":"Ethereum","alpha_asset":"Bitcoin","beta_asset":"Ether","role":"Alice"}"#,
+        )
+        .unwrap();
+
+        assert_that(&metadata.start_heights).is_equal_to(StartHeights::default());
+    }
+}
@@ -1,5 +1,6 @@
 pub mod asset;
 mod dependencies;
+pub mod disk_store;
 pub mod ledger;
 pub mod rfc003;
 
@@ -9,7 +10,8 @@ pub use self::{
     dependencies::*,
     ledger::Ledger,
     metadata_store::{
-        AssetKind, InMemoryMetadataStore, LedgerKind, Metadata, MetadataStore, RoleKind,
+        AssetKind, DiskMetadataStore, InMemoryMetadataStore, LedgerKind, Metadata, MetadataStore,
+        RoleKind, StartHeights,
     },
 };
 
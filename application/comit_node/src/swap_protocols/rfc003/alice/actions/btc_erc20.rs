@@ -1,10 +1,13 @@
-use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    rfc003::{
-        alice, bitcoin,
-        ethereum::{self, Erc20Htlc},
-        state_machine::*,
-        Actions, Alice,
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            alice, bitcoin,
+            ethereum::{self, Erc20Htlc},
+            state_machine::*,
+            Actions, Alice,
+        },
     },
 };
 use bitcoin_support::{BitcoinQuantity, OutPoint};
@@ -29,6 +32,7 @@ impl OngoingSwap<Alice<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
                 bitcoin::Htlc::from(self.alpha_htlc_params())
                     .unlock_after_timeout(self.alpha_ledger_refund_identity),
             ),
+            network: self.alpha_ledger.network,
         }
     }
 
@@ -44,25 +48,49 @@ impl OngoingSwap<Alice<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
             data,
             gas_limit,
             value: EtherQuantity::zero(),
+            network: self.beta_ledger.network,
         }
     }
 }
 
 impl Actions for SwapStates<Alice<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
     type ActionKind = alice::ActionKind<
+        alice::Cancel,
         (),
         bitcoin::SendToAddress,
         ethereum::SendTransaction,
         bitcoin::SpendOutput,
     >;
 
-    fn actions(&self) -> Vec<Self::ActionKind> {
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
         use self::SwapStates as SS;
         match *self {
+            SS::Start(Start { ref role, .. }) => role
+                .cancel_action()
+                .map(alice::ActionKind::Cancel)
+                .into_iter()
+                .collect(),
             SS::Accepted(Accepted { ref swap, .. }) => {
                 vec![alice::ActionKind::Fund(swap.fund_action())]
             }
             SS::BothFunded(BothFunded {
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => {
+                let mut actions = vec![alice::ActionKind::Redeem(
+                    swap.redeem_action(*beta_htlc_location),
+                )];
+                if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) {
+                    actions.push(alice::ActionKind::Refund(
+                        swap.refund_action(*alpha_htlc_location),
+                    ));
+                }
+                actions
+            }
+            SS::AlphaExpired(AlphaExpired {
                 ref alpha_htlc_location,
                 ref beta_htlc_location,
                 ref swap,
@@ -71,18 +99,35 @@ impl Actions for SwapStates<Alice<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quant
                 alice::ActionKind::Redeem(swap.redeem_action(*beta_htlc_location)),
                 alice::ActionKind::Refund(swap.refund_action(*alpha_htlc_location)),
             ],
+            SS::BothExpired(BothExpired {
+                ref alpha_htlc_location,
+                ref swap,
+                ..
+            }) => vec![alice::ActionKind::Refund(
+                swap.refund_action(*alpha_htlc_location),
+            )],
             SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded {
                 ref swap,
                 ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BetaExpired(BetaExpired {
+                ref swap,
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
                 ..
             })
             | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
                 ref swap,
                 ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
                 ..
-            }) => vec![alice::ActionKind::Refund(
-                swap.refund_action(*alpha_htlc_location),
-            )],
+            }) if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) => {
+                vec![alice::ActionKind::Refund(
+                    swap.refund_action(*alpha_htlc_location),
+                )]
+            }
             SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
                 ref beta_htlc_location,
                 ref swap,
@@ -1,10 +1,13 @@
-use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    rfc003::{
-        alice, bitcoin,
-        ethereum::{self, EtherHtlc},
-        state_machine::*,
-        Actions, Alice,
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            alice, bitcoin,
+            ethereum::{self, EtherHtlc},
+            state_machine::*,
+            Actions, Alice,
+        },
     },
 };
 use bitcoin_support::{BitcoinQuantity, OutPoint};
@@ -27,6 +30,7 @@ impl OngoingSwap<Alice<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> {
                 bitcoin::Htlc::from(self.alpha_htlc_params())
                     .unlock_after_timeout(self.alpha_ledger_refund_identity),
             ),
+            network: self.alpha_ledger.network,
         }
     }
 
@@ -42,25 +46,49 @@ impl OngoingSwap<Alice<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> {
             data,
             gas_limit,
             value: EtherQuantity::from_wei(U256::zero()),
+            network: self.beta_ledger.network,
         }
     }
 }
 
 impl Actions for SwapStates<Alice<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> {
     type ActionKind = alice::ActionKind<
+        alice::Cancel,
         (),
         bitcoin::SendToAddress,
         ethereum::SendTransaction,
         bitcoin::SpendOutput,
     >;
 
-    fn actions(&self) -> Vec<Self::ActionKind> {
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
         use self::SwapStates as SS;
         match *self {
+            SS::Start(Start { ref role, .. }) => role
+                .cancel_action()
+                .map(alice::ActionKind::Cancel)
+                .into_iter()
+                .collect(),
             SS::Accepted(Accepted { ref swap, .. }) => {
                 vec![alice::ActionKind::Fund(swap.fund_action())]
             }
             SS::BothFunded(BothFunded {
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => {
+                let mut actions = vec![alice::ActionKind::Redeem(
+                    swap.redeem_action(*beta_htlc_location),
+                )];
+                if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) {
+                    actions.push(alice::ActionKind::Refund(
+                        swap.refund_action(*alpha_htlc_location),
+                    ));
+                }
+                actions
+            }
+            SS::AlphaExpired(AlphaExpired {
                 ref alpha_htlc_location,
                 ref beta_htlc_location,
                 ref swap,
@@ -69,18 +97,35 @@ impl Actions for SwapStates<Alice<Bitcoin, Ethereum, BitcoinQuantity, EtherQuant
                 alice::ActionKind::Redeem(swap.redeem_action(*beta_htlc_location)),
                 alice::ActionKind::Refund(swap.refund_action(*alpha_htlc_location)),
             ],
+            SS::BothExpired(BothExpired {
+                ref alpha_htlc_location,
+                ref swap,
+                ..
+            }) => vec![alice::ActionKind::Refund(
+                swap.refund_action(*alpha_htlc_location),
+            )],
             SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded {
                 ref swap,
                 ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BetaExpired(BetaExpired {
+                ref swap,
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
                 ..
             })
             | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
                 ref swap,
                 ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
                 ..
-            }) => vec![alice::ActionKind::Refund(
-                swap.refund_action(*alpha_htlc_location),
-            )],
+            }) if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) => {
+                vec![alice::ActionKind::Refund(
+                    swap.refund_action(*alpha_htlc_location),
+                )]
+            }
             SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
                 ref beta_htlc_location,
                 ref swap,
@@ -97,3 +142,55 @@ impl Actions for SwapStates<Alice<Bitcoin, Ethereum, BitcoinQuantity, EtherQuant
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::swap_protocols::rfc003::{role::test::Alisha, Secret};
+    use std::str::FromStr;
+
+    fn start_state(alisha: Alisha) -> Start<Alisha> {
+        Start {
+            alpha_ledger_refund_identity: secp256k1_support::KeyPair::from_secret_key_slice(
+                &hex::decode("18e14a7b6a307f426a94f8114701e7c8e774e7f9a47e2c2035db29a206321725")
+                    .unwrap(),
+            )
+            .unwrap(),
+            beta_ledger_redeem_identity: ethereum_support::Address::from_str(
+                "8457037fcd80a8650c4692d7fcfc1d0a96b92867",
+            )
+            .unwrap(),
+            alpha_ledger: Bitcoin::default(),
+            beta_ledger: Ethereum::regtest(),
+            alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
+            beta_asset: EtherQuantity::from_eth(10.0),
+            alpha_ledger_lock_duration: bitcoin_support::Blocks::from(144),
+            alpha_ledger_confirmations_needed: 1,
+            beta_ledger_confirmations_needed: 1,
+            secret: Secret::from(*b"hello world, you are beautiful!!"),
+            role: alisha,
+        }
+    }
+
+    #[test]
+    fn given_start_state_should_offer_cancel_until_alice_canceled() {
+        let (alisha, _canceled) = Alisha::create();
+        let swap_state = SwapStates::from(start_state(alisha.clone()));
+
+        let actions = swap_state.actions(&LatestBlocks::default());
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].name(), "cancel");
+
+        alisha.cancel_action().unwrap().cancel().unwrap();
+
+        assert!(swap_state.actions(&LatestBlocks::default()).is_empty());
+    }
+
+    #[test]
+    fn given_alice_restored_from_storage_should_not_offer_cancel() {
+        let swap_state = SwapStates::from(start_state(Alisha::default()));
+
+        assert!(swap_state.actions(&LatestBlocks::default()).is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            alice, bitcoin,
+            ethereum::{self, Erc20Htlc, Htlc},
+            state_machine::*,
+            Actions, Alice,
+        },
+    },
+};
+use bitcoin_support::{BitcoinQuantity, OutPoint};
+use bitcoin_witness::PrimedInput;
+use ethereum_support::{Bytes, Erc20Quantity, EtherQuantity};
+
+impl OngoingSwap<Alice<Ethereum, Bitcoin, Erc20Quantity, BitcoinQuantity>> {
+    pub fn deploy_action(&self) -> ethereum::ContractDeploy {
+        let htlc = Erc20Htlc::from(self.alpha_htlc_params());
+        let data = htlc.compile_to_hex().into();
+        let gas_limit = htlc.deployment_gas_limit();
+
+        ethereum::ContractDeploy {
+            data,
+            value: EtherQuantity::zero(),
+            gas_limit,
+            network: self.alpha_ledger.network,
+        }
+    }
+
+    pub fn fund_action(
+        &self,
+        alpha_htlc_location: ethereum_support::Address,
+    ) -> ethereum::SendTransaction {
+        let htlc = Erc20Htlc::from(self.alpha_htlc_params());
+        let gas_limit = Erc20Htlc::fund_tx_gas_limit();
+
+        ethereum::SendTransaction {
+            to: self.alpha_asset.token_contract(),
+            data: htlc.funding_tx_payload(alpha_htlc_location),
+            gas_limit,
+            value: EtherQuantity::zero(),
+            network: self.alpha_ledger.network,
+        }
+    }
+
+    pub fn refund_action(
+        &self,
+        alpha_htlc_location: ethereum_support::Address,
+    ) -> ethereum::SendTransaction {
+        let data = Bytes::default();
+        let gas_limit = Erc20Htlc::tx_gas_limit();
+
+        ethereum::SendTransaction {
+            to: alpha_htlc_location,
+            data,
+            gas_limit,
+            value: EtherQuantity::zero(),
+            network: self.alpha_ledger.network,
+        }
+    }
+
+    pub fn redeem_action(&self, beta_htlc_location: OutPoint) -> bitcoin::SpendOutput {
+        let htlc: bitcoin::Htlc = self.beta_htlc_params().into();
+
+        bitcoin::SpendOutput {
+            output: PrimedInput::new(
+                beta_htlc_location,
+                self.beta_asset,
+                htlc.unlock_with_secret(self.beta_ledger_redeem_identity, &self.secret),
+            ),
+            network: self.beta_ledger.network,
+        }
+    }
+}
+
+impl Actions for SwapStates<Alice<Ethereum, Bitcoin, Erc20Quantity, BitcoinQuantity>> {
+    type ActionKind = alice::ActionKind<
+        alice::Cancel,
+        ethereum::ContractDeploy,
+        ethereum::SendTransaction,
+        bitcoin::SpendOutput,
+        ethereum::SendTransaction,
+    >;
+
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
+        use self::SwapStates as SS;
+        match *self {
+            SS::Start(Start { ref role, .. }) => role
+                .cancel_action()
+                .map(alice::ActionKind::Cancel)
+                .into_iter()
+                .collect(),
+            SS::Accepted(Accepted { ref swap, .. }) => {
+                vec![alice::ActionKind::Deploy(swap.deploy_action())]
+            }
+            SS::AlphaDeployed(AlphaDeployed {
+                ref swap,
+                ref alpha_htlc_location,
+                ..
+            }) => vec![alice::ActionKind::Fund(
+                swap.fund_action(*alpha_htlc_location),
+            )],
+            SS::BothFunded(BothFunded {
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => {
+                let mut actions = vec![alice::ActionKind::Redeem(
+                    swap.redeem_action(*beta_htlc_location),
+                )];
+                if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) {
+                    actions.push(alice::ActionKind::Refund(
+                        swap.refund_action(*alpha_htlc_location),
+                    ));
+                }
+                actions
+            }
+            SS::AlphaExpired(AlphaExpired {
+                ref alpha_htlc_location,
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => vec![
+                alice::ActionKind::Redeem(swap.redeem_action(*beta_htlc_location)),
+                alice::ActionKind::Refund(swap.refund_action(*alpha_htlc_location)),
+            ],
+            SS::BothExpired(BothExpired {
+                ref alpha_htlc_location,
+                ref swap,
+                ..
+            }) => vec![alice::ActionKind::Refund(
+                swap.refund_action(*alpha_htlc_location),
+            )],
+            SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded {
+                ref swap,
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BetaExpired(BetaExpired {
+                ref swap,
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
+                ref swap,
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ..
+            }) if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) => {
+                vec![alice::ActionKind::Refund(
+                    swap.refund_action(*alpha_htlc_location),
+                )]
+            }
+            SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            })
+            | SS::AlphaRedeemedBetaFunded(AlphaRedeemedBetaFunded {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => vec![alice::ActionKind::Redeem(
+                swap.redeem_action(*beta_htlc_location),
+            )],
+            _ => vec![],
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    rfc003::{
-        alice, bitcoin,
-        ethereum::{self, EtherHtlc, Htlc},
-        state_machine::*,
-        Actions, Alice,
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            alice, bitcoin,
+            ethereum::{self, EtherHtlc, Htlc},
+            state_machine::*,
+            Actions, Alice,
+        },
     },
 };
 use bitcoin_support::{BitcoinQuantity, OutPoint};
@@ -21,6 +24,7 @@ impl OngoingSwap<Alice<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> {
             data,
             value: self.alpha_asset,
             gas_limit,
+            network: self.alpha_ledger.network,
         }
     }
 
@@ -36,6 +40,7 @@ impl OngoingSwap<Alice<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> {
             data,
             gas_limit,
             value: EtherQuantity::zero(),
+            network: self.alpha_ledger.network,
         }
     }
 
@@ -48,25 +53,49 @@ impl OngoingSwap<Alice<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> {
                 self.beta_asset,
                 htlc.unlock_with_secret(self.beta_ledger_redeem_identity, &self.secret),
             ),
+            network: self.beta_ledger.network,
         }
     }
 }
 
 impl Actions for SwapStates<Alice<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> {
     type ActionKind = alice::ActionKind<
+        alice::Cancel,
         (),
         ethereum::ContractDeploy,
         bitcoin::SpendOutput,
         ethereum::SendTransaction,
     >;
 
-    fn actions(&self) -> Vec<Self::ActionKind> {
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
         use self::SwapStates as SS;
         match *self {
+            SS::Start(Start { ref role, .. }) => role
+                .cancel_action()
+                .map(alice::ActionKind::Cancel)
+                .into_iter()
+                .collect(),
             SS::Accepted(Accepted { ref swap, .. }) => {
                 vec![alice::ActionKind::Fund(swap.fund_action())]
             }
             SS::BothFunded(BothFunded {
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => {
+                let mut actions = vec![alice::ActionKind::Redeem(
+                    swap.redeem_action(*beta_htlc_location),
+                )];
+                if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) {
+                    actions.push(alice::ActionKind::Refund(
+                        swap.refund_action(*alpha_htlc_location),
+                    ));
+                }
+                actions
+            }
+            SS::AlphaExpired(AlphaExpired {
                 ref alpha_htlc_location,
                 ref beta_htlc_location,
                 ref swap,
@@ -75,18 +104,35 @@ impl Actions for SwapStates<Alice<Ethereum, Bitcoin, EtherQuantity, BitcoinQuant
                 alice::ActionKind::Redeem(swap.redeem_action(*beta_htlc_location)),
                 alice::ActionKind::Refund(swap.refund_action(*alpha_htlc_location)),
             ],
+            SS::BothExpired(BothExpired {
+                ref alpha_htlc_location,
+                ref swap,
+                ..
+            }) => vec![alice::ActionKind::Refund(
+                swap.refund_action(*alpha_htlc_location),
+            )],
             SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded {
                 ref swap,
                 ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BetaExpired(BetaExpired {
+                ref swap,
+                ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
                 ..
             })
             | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
                 ref swap,
                 ref alpha_htlc_location,
+                alpha_htlc_deployed_at,
                 ..
-            }) => vec![alice::ActionKind::Refund(
-                swap.refund_action(*alpha_htlc_location),
-            )],
+            }) if swap.alpha_htlc_expired(alpha_htlc_deployed_at, latest_blocks) => {
+                vec![alice::ActionKind::Refund(
+                    swap.refund_action(*alpha_htlc_location),
+                )]
+            }
             SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
                 ref beta_htlc_location,
                 ref swap,
@@ -1,19 +1,25 @@
 mod btc_erc20;
 mod btc_eth;
+mod erc20_btc;
 mod eth_btc;
 
+use futures::sync::oneshot;
+use std::sync::{Arc, Mutex};
+
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
-pub enum ActionKind<Deploy, Fund, Redeem, Refund> {
+pub enum ActionKind<Cancel, Deploy, Fund, Redeem, Refund> {
+    Cancel(Cancel),
     Deploy(Deploy),
     Fund(Fund),
     Redeem(Redeem),
     Refund(Refund),
 }
 
-impl<Deploy, Fund, Redeem, Refund> ActionKind<Deploy, Fund, Redeem, Refund> {
+impl<Cancel, Deploy, Fund, Redeem, Refund> ActionKind<Cancel, Deploy, Fund, Redeem, Refund> {
     pub fn name(&self) -> String {
         use self::ActionKind::*;
         match *self {
+            Cancel(_) => String::from("cancel"),
             Deploy(_) => String::from("deploy"),
             Fund(_) => String::from("fund"),
             Redeem(_) => String::from("redeem"),
@@ -21,3 +27,23 @@ impl<Deploy, Fund, Redeem, Refund> ActionKind<Deploy, Fund, Redeem, Refund> {
         }
     }
 }
+
+/// Stops waiting for Bob to respond to the swap request.
+#[derive(Debug, Clone)]
+pub struct Cancel {
+    sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+}
+
+impl Cancel {
+    pub fn new(sender: Arc<Mutex<Option<oneshot::Sender<()>>>>) -> Self {
+        Self { sender }
+    }
+
+    pub fn cancel(&self) -> Result<(), ()> {
+        let mut sender = self.sender.lock().unwrap();
+        match sender.take() {
+            Some(sender) => sender.send(()),
+            None => Err(()),
+        }
+    }
+}
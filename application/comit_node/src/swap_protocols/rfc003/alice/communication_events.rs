@@ -1,5 +1,5 @@
 use crate::{
-    comit_client,
+    comit_client::{self, SwapResponseError},
     swap_protocols::{
         asset::Asset,
         rfc003::{
@@ -10,7 +10,7 @@ use crate::{
         },
     },
 };
-use futures::Future;
+use futures::{future, sync::oneshot, Future};
 use std::sync::Arc;
 
 #[allow(missing_debug_implementations)]
@@ -19,13 +19,15 @@ pub struct AliceToBob<C, AL: Ledger, BL: Ledger> {
     response_future:
         Option<Box<StateMachineResponseFuture<AL::Identity, BL::Identity, BL::LockDuration>>>,
     client: Arc<C>,
+    canceled: Option<oneshot::Receiver<()>>,
 }
 
 impl<C, AL: Ledger, BL: Ledger> AliceToBob<C, AL, BL> {
-    pub fn new(client: Arc<C>) -> Self {
+    pub fn new(client: Arc<C>, canceled: oneshot::Receiver<()>) -> Self {
         AliceToBob {
             client,
             response_future: None,
+            canceled: Some(canceled),
         }
     }
 }
@@ -38,13 +40,138 @@ impl<C: comit_client::Client, AL: Ledger, BL: Ledger, AA: Asset, BA: Asset>
         request: &comit_client::rfc003::Request<AL, BL, AA, BA>,
     ) -> &mut ResponseFuture<Alice<AL, BL, AA, BA>> {
         let client = Arc::clone(&self.client);
+        let canceled = &mut self.canceled;
         self.response_future.get_or_insert_with(|| {
+            let sent_request = request.clone();
+            let response = client
+                .send_swap_request(request.clone())
+                .and_then(move |result| match result {
+                    Ok(accept) if !accept.waits_for_confirmations_of(&sent_request) => {
+                        warn!(
+                            "Bob accepted with other confirmations than requested: {:?}",
+                            accept
+                        );
+                        Err(SwapResponseError::InvalidResponse)
+                    }
+                    result => Ok(result.map(Into::into)),
+                })
+                .map_err(rfc003::Error::SwapResponse);
+
+            // Dropping the cancel sender together with Alice must not cancel
+            // the request, only taking the cancel action does
+            let canceled = canceled
+                .take()
+                .expect("the response future is only created once")
+                .or_else(|_| future::empty::<(), ()>())
+                .then(|_| Err(rfc003::Error::SwapResponse(SwapResponseError::Canceled)));
+
             Box::new(
-                client
-                    .send_swap_request(request.clone())
-                    .map_err(rfc003::Error::SwapResponse)
-                    .map(|result| result.map(Into::into)),
+                response
+                    .select(canceled)
+                    .map(|(response, _)| response)
+                    .map_err(|(e, _)| e),
             )
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        comit_client::{
+            fake::FakeClient,
+            rfc003::{AcceptResponseBody, Request},
+        },
+        swap_protocols::{
+            ledger::{Bitcoin, Ethereum},
+            rfc003::{ethereum::Seconds, state_machine::StateMachineResponse, Secret},
+        },
+    };
+    use bitcoin_support::{BitcoinQuantity, Blocks, PubkeyHash};
+    use ethereum_support::{Address, EtherQuantity};
+    use hex::FromHex;
+    use spectral::prelude::*;
+    use std::str::FromStr;
+
+    fn request() -> Request<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity> {
+        Request {
+            alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
+            beta_asset: EtherQuantity::from_eth(10.0),
+            alpha_ledger: Bitcoin::regtest(),
+            beta_ledger: Ethereum::regtest(),
+            alpha_ledger_refund_identity: PubkeyHash::from_hex(
+                "d38e554430c4035f2877a579a07a99886153f071",
+            )
+            .unwrap(),
+            beta_ledger_redeem_identity: Address::from_str(
+                "8457037fcd80a8650c4692d7fcfc1d0a96b92867",
+            )
+            .unwrap(),
+            alpha_ledger_lock_duration: Blocks::from(144),
+            alpha_ledger_confirmations_needed: 3,
+            beta_ledger_confirmations_needed: 12,
+            secret_hash: Secret::from(*b"hello world, you are beautiful!!").hash(),
+        }
+    }
+
+    fn accept_response(
+        alpha_ledger_confirmations_needed: Option<u32>,
+        beta_ledger_confirmations_needed: Option<u32>,
+    ) -> AcceptResponseBody<Bitcoin, Ethereum> {
+        AcceptResponseBody {
+            beta_ledger_refund_identity: Address::from_str(
+                "71b9f69dcabb340a3fe229c3f94f1662ad85e5e8",
+            )
+            .unwrap(),
+            alpha_ledger_redeem_identity: PubkeyHash::from_hex(
+                "d38e554430c4035f2877a579a07a99886153f071",
+            )
+            .unwrap(),
+            beta_ledger_lock_duration: Seconds(42),
+            alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed,
+        }
+    }
+
+    fn respond(
+        response: AcceptResponseBody<Bitcoin, Ethereum>,
+    ) -> Result<
+        Result<StateMachineResponse<PubkeyHash, Address, Seconds>, comit_client::SwapReject>,
+        rfc003::Error,
+    > {
+        let client = Arc::new(FakeClient::default());
+        let (_cancel, canceled) = oneshot::channel();
+        let mut alice_to_bob = AliceToBob::new(Arc::clone(&client), canceled);
+
+        let response_future = alice_to_bob.request_responded(&request());
+        client.resolve_request(Ok(response));
+
+        response_future.wait()
+    }
+
+    #[test]
+    fn accept_waiting_for_the_requested_confirmations_is_taken() {
+        let response = accept_response(Some(3), Some(12));
+
+        assert_that(&respond(response.clone()))
+            .is_ok()
+            .is_ok_containing(StateMachineResponse::from(response));
+    }
+
+    #[test]
+    fn accept_without_confirmations_is_taken_to_wait_for_the_requested_ones() {
+        let response = accept_response(None, None);
+
+        assert_that(&respond(response.clone()))
+            .is_ok()
+            .is_ok_containing(StateMachineResponse::from(response));
+    }
+
+    #[test]
+    fn accept_waiting_for_fewer_confirmations_than_requested_is_invalid() {
+        assert_that(&respond(accept_response(Some(3), Some(0)))).is_err_containing(
+            rfc003::Error::SwapResponse(SwapResponseError::InvalidResponse),
+        );
+    }
+}
@@ -11,7 +11,7 @@ pub use self::{
 };
 
 use crate::{
-    comit_client::{self, ClientFactory},
+    comit_client,
     swap_protocols::{
         asset::Asset,
         rfc003::{
@@ -25,12 +25,21 @@ use crate::{
         },
     },
 };
-use futures::{future, Future};
-use std::{marker::PhantomData, net::SocketAddr, sync::Arc};
+use futures::{future, sync::oneshot, Future};
+use std::{
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
 
-#[derive(Clone, Debug)]
+/// Like Bob's response sender, the cancel sender is not persisted. An `Alice`
+/// restored from storage can no longer cancel her swap request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Alice<AL, BL, AA, BA> {
+    #[serde(skip)]
     phantom_data: PhantomData<(AL, BL, AA, BA)>,
+    #[serde(skip)]
+    cancel_sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
 }
 
 impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> Alice<AL, BL, AA, BA> {
@@ -38,10 +47,11 @@ impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> Alice<AL, BL, AA, BA> {
         initiation: Initiation<Self>,
         alpha_ledger_events: Box<dyn LedgerEvents<AL, AA>>,
         beta_ledger_events: Box<dyn LedgerEvents<BL, BA>>,
-        comit_client_factory: Arc<dyn ClientFactory<C>>,
-        comit_node_addr: SocketAddr,
+        comit_client: Arc<C>,
         save_state: Arc<dyn SaveState<Self>>,
     ) -> Box<dyn Future<Item = SwapOutcome<Self>, Error = rfc003::Error> + Send> {
+        let (alice, canceled) = Self::create();
+
         let start_state = Start {
             alpha_ledger: initiation.alpha_ledger,
             beta_ledger: initiation.beta_ledger,
@@ -50,27 +60,45 @@ impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> Alice<AL, BL, AA, BA> {
             alpha_ledger_refund_identity: initiation.alpha_ledger_refund_identity,
             beta_ledger_redeem_identity: initiation.beta_ledger_redeem_identity,
             alpha_ledger_lock_duration: initiation.alpha_ledger_lock_duration,
+            alpha_ledger_confirmations_needed: initiation.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: initiation.beta_ledger_confirmations_needed,
             secret: initiation.secret,
-            role: Alice::default(),
-        };
-        save_state.save(start_state.clone().into());
-        let comit_client = match comit_client_factory.client_for(comit_node_addr) {
-            Ok(comit_client) => comit_client,
-            // This mess will go away with #319
-            Err(e) => {
-                return Box::new(future::err(rfc003::Error::Internal(format!("{:?}", e))));
-            }
+            role: alice,
         };
+        if let Err(e) = save_state.save(start_state.clone().into()) {
+            return Box::new(future::err(e));
+        }
 
         let context = Context {
             alpha_ledger_events,
             beta_ledger_events,
-            communication_events: Box::new(AliceToBob::new(Arc::clone(&comit_client))),
+            communication_events: Box::new(AliceToBob::new(comit_client, canceled)),
             state_repo: save_state,
         };
 
         Box::new(Swap::start_in(start_state, context))
     }
+
+    pub fn create() -> (Self, oneshot::Receiver<()>) {
+        let (sender, receiver) = oneshot::channel();
+        (
+            Alice {
+                phantom_data: PhantomData,
+                cancel_sender: Arc::new(Mutex::new(Some(sender))),
+            },
+            receiver,
+        )
+    }
+
+    /// `None` once the swap request was canceled and for an `Alice` restored
+    /// from storage, as there is no request left she could cancel
+    pub fn cancel_action(&self) -> Option<Cancel> {
+        if self.cancel_sender.lock().unwrap().is_none() {
+            return None;
+        }
+
+        Some(Cancel::new(self.cancel_sender.clone()))
+    }
 }
 
 impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> Role for Alice<AL, BL, AA, BA> {
@@ -89,6 +117,7 @@ impl<AL, BL, AA, BA> Default for Alice<AL, BL, AA, BA> {
     fn default() -> Self {
         Self {
             phantom_data: PhantomData,
+            cancel_sender: Arc::new(Mutex::new(None)),
         }
     }
 }
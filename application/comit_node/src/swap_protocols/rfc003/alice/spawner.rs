@@ -1,5 +1,5 @@
 use crate::{
-    comit_client,
+    comit_client::{self, ClientFactoryError},
     swap_protocols::{
         asset::Asset,
         dependencies::{LedgerEventDependencies, ProtocolDependencies},
@@ -21,6 +21,12 @@ use std::sync::Arc;
 pub enum Error {
     Storage(state_store::Error),
     Metadata(metadata_store::Error),
+    Connection(ClientFactoryError),
+    /// One of the ledgers is on a network the node is not connected to
+    UnsupportedNetwork,
+    /// The swap asks to wait for fewer confirmations on one of the ledgers
+    /// than the node accepts
+    TooFewConfirmations,
 }
 
 impl From<Error> for HttpApiProblem {
@@ -29,6 +35,13 @@ impl From<Error> for HttpApiProblem {
         match e {
             Storage(e) => e.into(),
             Metadata(e) => e.into(),
+            Connection(e) => e.into(),
+            UnsupportedNetwork => HttpApiProblem::new("network-not-supported")
+                .set_status(400)
+                .set_detail("The node is not connected to the network of one of the ledgers."),
+            TooFewConfirmations => HttpApiProblem::new("too-few-confirmations")
+                .set_status(400)
+                .set_detail("The swap waits for fewer confirmations than the node accepts."),
         }
     }
 }
@@ -40,8 +53,7 @@ pub trait AliceSpawner: Send + Sync + 'static {
         swap_request: SwapRequest<AL, BL, AA, BA>,
     ) -> Result<(), Error>
     where
-        LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
-        SwapRequest<AL, BL, AA, BA>: Into<Metadata>;
+        LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>;
 }
 
 impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client> AliceSpawner
@@ -54,14 +66,42 @@ impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client> A
     ) -> Result<(), Error>
     where
         LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
-        SwapRequest<AL, BL, AA, BA>: Into<Metadata>,
     {
+        // Only the Ethereum network is checked, there is no setting for the
+        // Bitcoin network yet and Bitcoin ledgers are taken to be on it
+        if !swap_request.alpha_ledger.is_on(&self.networks)
+            || !swap_request.beta_ledger.is_on(&self.networks)
+        {
+            return Err(Error::UnsupportedNetwork);
+        }
+
+        if swap_request.alpha_ledger_confirmations_needed
+            < AL::min_confirmations(&self.min_confirmations)
+            || swap_request.beta_ledger_confirmations_needed
+                < BL::min_confirmations(&self.min_confirmations)
+        {
+            return Err(Error::TooFewConfirmations);
+        }
+
+        let comit_client = self
+            .comit_client_factory
+            .client_for(swap_request.peer.unwrap_or(self.remote_comit_node))
+            .map_err(Error::Connection)?;
+
+        let start_heights = self.ledger_events.start_heights();
         let save_state = self
             .state_store
             .new_save_state(id)
             .map_err(Error::Storage)?;
         self.metadata_store
-            .insert(id, swap_request.clone())
+            .insert(
+                id,
+                Metadata {
+                    counterparty: comit_client.counterparty(),
+                    start_heights,
+                    ..swap_request.clone().into()
+                },
+            )
             .map_err(Error::Metadata)?;
 
         let initiation = Initiation {
@@ -72,15 +112,16 @@ impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client> A
             beta_ledger_redeem_identity: swap_request.identities.beta_ledger_redeem_identity,
             alpha_ledger_refund_identity: swap_request.identities.alpha_ledger_refund_identity,
             alpha_ledger_lock_duration: swap_request.alpha_ledger_lock_duration,
+            alpha_ledger_confirmations_needed: swap_request.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: swap_request.beta_ledger_confirmations_needed,
             secret: self.seed.new_secret(id),
         };
 
         let state_machine_future = Alice::<AL, BL, AA, BA>::new_state_machine(
             initiation,
-            self.ledger_events.create_ledger_events(),
-            self.ledger_events.create_ledger_events(),
-            Arc::clone(&self.comit_client_factory),
-            self.remote_comit_node,
+            self.ledger_events.create_ledger_events(start_heights),
+            self.ledger_events.create_ledger_events(start_heights),
+            comit_client,
             save_state,
         );
 
@@ -1,10 +1,9 @@
 use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    metadata_store::{AssetKind, LedgerKind, Metadata, RoleKind},
+    asset::Asset,
+    metadata_store::{Metadata, RoleKind, StartHeights},
     rfc003::Ledger,
 };
-use bitcoin_support::BitcoinQuantity;
-use ethereum_support::{Erc20Quantity, EtherQuantity};
+use std::net::SocketAddr;
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct SwapRequest<AL: Ledger, BL: Ledger, AA, BA> {
@@ -13,7 +12,11 @@ pub struct SwapRequest<AL: Ledger, BL: Ledger, AA, BA> {
     pub alpha_ledger: AL,
     pub beta_ledger: BL,
     pub alpha_ledger_lock_duration: AL::LockDuration,
+    pub alpha_ledger_confirmations_needed: u32,
+    pub beta_ledger_confirmations_needed: u32,
     pub identities: SwapRequestIdentities<AL, BL>,
+    /// The COMIT node to send the request to instead of the default one
+    pub peer: Option<SocketAddr>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -22,38 +25,18 @@ pub struct SwapRequestIdentities<AL: Ledger, BL: Ledger> {
     pub beta_ledger_redeem_identity: BL::HtlcIdentity,
 }
 
-impl From<SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> for Metadata {
-    fn from(_: SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>) -> Self {
+impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> From<SwapRequest<AL, BL, AA, BA>>
+    for Metadata
+{
+    fn from(request: SwapRequest<AL, BL, AA, BA>) -> Self {
         Self {
-            alpha_ledger: LedgerKind::Bitcoin,
-            beta_ledger: LedgerKind::Ethereum,
-            alpha_asset: AssetKind::Bitcoin,
-            beta_asset: AssetKind::Ether,
-            role: RoleKind::Alice,
-        }
-    }
-}
-
-impl From<SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> for Metadata {
-    fn from(_: SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>) -> Self {
-        Self {
-            alpha_ledger: LedgerKind::Bitcoin,
-            beta_ledger: LedgerKind::Ethereum,
-            alpha_asset: AssetKind::Bitcoin,
-            beta_asset: AssetKind::Erc20,
-            role: RoleKind::Alice,
-        }
-    }
-}
-
-impl From<SwapRequest<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> for Metadata {
-    fn from(_: SwapRequest<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>) -> Self {
-        Self {
-            alpha_ledger: LedgerKind::Ethereum,
-            beta_ledger: LedgerKind::Bitcoin,
-            alpha_asset: AssetKind::Ether,
-            beta_asset: AssetKind::Bitcoin,
+            alpha_ledger: request.alpha_ledger.into(),
+            beta_ledger: request.beta_ledger.into(),
+            alpha_asset: request.alpha_asset.into(),
+            beta_asset: request.beta_asset.into(),
             role: RoleKind::Alice,
+            counterparty: None,
+            start_heights: StartHeights::default(),
         }
     }
 }
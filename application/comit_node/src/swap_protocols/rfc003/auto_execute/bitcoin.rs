@@ -0,0 +1,71 @@
+use crate::swap_protocols::{
+    metadata_store::LedgerKind,
+    rfc003::{
+        auto_execute::{Error, Execute, Wallets},
+        bitcoin::SpendOutput,
+    },
+};
+use bitcoin_rpc_client::{
+    rpc::SerializedRawTransaction, BitcoinCoreClient, BitcoinRpcApi, TransactionId,
+};
+use bitcoin_support::{serialize::serialize_hex, Address};
+use std::fmt::Debug;
+
+pub trait BitcoinWallet: Send + Sync + Debug {
+    fn spend(&self, action: SpendOutput) -> Result<TransactionId, Error>;
+}
+
+impl Execute for SpendOutput {
+    fn execute(self, wallets: &Wallets) -> Result<(), Error> {
+        let wallet = wallets
+            .bitcoin
+            .as_ref()
+            .ok_or(Error::MissingWallet(LedgerKind::Bitcoin))?;
+        let txid = wallet.spend(self)?;
+
+        info!("Broadcast bitcoin transaction {}", txid);
+        Ok(())
+    }
+}
+
+/// Signs with the keys that are part of the action and sends the funds to a
+/// fixed payout address. Actions of swaps on a different network than the
+/// payout address are refused.
+#[derive(DebugStub)]
+pub struct BitcoinCoreWallet {
+    #[debug_stub = "BitcoinCoreClient"]
+    client: BitcoinCoreClient,
+    payout_address: Address,
+    fee_per_byte: f64,
+}
+
+impl BitcoinCoreWallet {
+    pub fn new(client: BitcoinCoreClient, payout_address: Address, fee_per_byte: f64) -> Self {
+        BitcoinCoreWallet {
+            client,
+            payout_address,
+            fee_per_byte,
+        }
+    }
+}
+
+impl BitcoinWallet for BitcoinCoreWallet {
+    fn spend(&self, action: SpendOutput) -> Result<TransactionId, Error> {
+        if self.payout_address.network != action.network {
+            return Err(Error::PayoutAddressOnWrongNetwork(
+                self.payout_address.clone(),
+                action.network,
+            ));
+        }
+
+        let transaction = action
+            .spend_to(self.payout_address.clone())
+            .sign_with_rate(self.fee_per_byte);
+        let hex = serialize_hex(&transaction).map_err(Error::BitcoinSerialization)?;
+
+        self.client
+            .send_raw_transaction(SerializedRawTransaction(hex))
+            .map_err(Error::BitcoinRpcConnection)?
+            .map_err(Error::BitcoinRpcResponse)
+    }
+}
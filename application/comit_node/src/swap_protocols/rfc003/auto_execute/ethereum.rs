@@ -0,0 +1,92 @@
+use crate::swap_protocols::{
+    metadata_store::LedgerKind,
+    rfc003::{
+        auto_execute::{Error, Execute, Wallets},
+        ethereum::SendTransaction,
+    },
+};
+use ethereum_support::{
+    web3::{transports::Http, Web3},
+    Address, BlockNumber, Bytes, Future, InMemoryWallet, ToEthereumAddress, UnsignedTransaction,
+    Wallet, H256,
+};
+use secp256k1_support::KeyPair;
+use std::fmt::Debug;
+
+pub trait EthereumWallet: Send + Sync + Debug {
+    fn send(&self, action: SendTransaction) -> Result<H256, Error>;
+}
+
+impl Execute for SendTransaction {
+    fn execute(self, wallets: &Wallets) -> Result<(), Error> {
+        let wallet = wallets
+            .ethereum
+            .as_ref()
+            .ok_or(Error::MissingWallet(LedgerKind::Ethereum))?;
+        let tx_id = wallet.send(self)?;
+
+        info!("Broadcast ethereum transaction {:x}", tx_id);
+        Ok(())
+    }
+}
+
+/// Pays for gas from a single account whose key is derived from the node's
+/// seed. Redeeming and refunding an HTLC doesn't depend on who sends the
+/// transaction, so this account only ever needs to hold some ether for fees.
+///
+/// Transactions are signed for the network of the swap they belong to and
+/// refused unless the connected node reports that network's id.
+#[derive(DebugStub)]
+pub struct Web3Wallet {
+    #[debug_stub = "Web3"]
+    web3: Web3<Http>,
+    keypair: KeyPair,
+    address: Address,
+}
+
+impl Web3Wallet {
+    pub fn new(web3: Web3<Http>, keypair: KeyPair) -> Self {
+        let address = keypair.public_key().to_ethereum_address();
+        info!("Paying ethereum fees from {:x}", address);
+
+        Web3Wallet {
+            web3,
+            keypair,
+            address,
+        }
+    }
+}
+
+impl EthereumWallet for Web3Wallet {
+    fn send(&self, action: SendTransaction) -> Result<H256, Error> {
+        let network_id = self.web3.net().version().wait().map_err(Error::Web3)?;
+        if network_id != action.network.chain_id().to_string() {
+            return Err(Error::EthereumNodeOnWrongNetwork(
+                network_id,
+                action.network,
+            ));
+        }
+
+        let eth = self.web3.eth();
+        let nonce = eth
+            .transaction_count(self.address, Some(BlockNumber::Pending))
+            .wait()
+            .map_err(Error::Web3)?;
+        let gas_price = eth.gas_price().wait().map_err(Error::Web3)?;
+
+        let transaction = UnsignedTransaction {
+            nonce,
+            gas_price,
+            gas_limit: action.gas_limit,
+            to: Some(action.to),
+            value: action.value.wei(),
+            data: Some(action.data),
+        };
+        let wallet = InMemoryWallet::new(self.keypair.clone(), action.network.chain_id());
+        let signed_transaction = wallet.sign(&transaction);
+
+        eth.send_raw_transaction(Bytes::from(signed_transaction))
+            .wait()
+            .map_err(Error::Web3)
+    }
+}
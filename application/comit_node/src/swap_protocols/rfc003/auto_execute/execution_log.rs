@@ -0,0 +1,121 @@
+use crate::swap_protocols::{
+    disk_store::{self, JsonDirectory},
+    SwapId,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Remembers when the auto-executor last broadcast an action of a swap, so it
+/// neither broadcasts the same transaction on every poll nor forgets about it
+/// across restarts.
+pub trait ExecutionLog: Send + Sync + 'static {
+    fn last_executed(
+        &self,
+        id: &SwapId,
+        action: &str,
+    ) -> Result<Option<SystemTime>, disk_store::Error>;
+    fn record(&self, id: SwapId, action: &str, at: SystemTime) -> Result<(), disk_store::Error>;
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryExecutionLog {
+    executions: Mutex<HashMap<(SwapId, String), SystemTime>>,
+}
+
+impl ExecutionLog for InMemoryExecutionLog {
+    fn last_executed(
+        &self,
+        id: &SwapId,
+        action: &str,
+    ) -> Result<Option<SystemTime>, disk_store::Error> {
+        let executions = self.executions.lock().unwrap();
+
+        Ok(executions.get(&(*id, action.to_string())).cloned())
+    }
+
+    fn record(&self, id: SwapId, action: &str, at: SystemTime) -> Result<(), disk_store::Error> {
+        let mut executions = self.executions.lock().unwrap();
+
+        executions.insert((id, action.to_string()), at);
+        Ok(())
+    }
+}
+
+/// Keeps one JSON document per swap that maps the name of each broadcast
+/// action to the seconds since the UNIX epoch at which it was broadcast.
+#[derive(Debug)]
+pub struct DiskExecutionLog {
+    directory: JsonDirectory,
+    write_lock: Mutex<()>,
+}
+
+impl DiskExecutionLog {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, disk_store::Error> {
+        Ok(DiskExecutionLog {
+            directory: JsonDirectory::open(path)?,
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    fn executions_of(&self, id: &SwapId) -> Result<BTreeMap<String, u64>, disk_store::Error> {
+        Ok(self.directory.read(id)?.unwrap_or_default())
+    }
+}
+
+impl ExecutionLog for DiskExecutionLog {
+    fn last_executed(
+        &self,
+        id: &SwapId,
+        action: &str,
+    ) -> Result<Option<SystemTime>, disk_store::Error> {
+        let executions = self.executions_of(id)?;
+
+        Ok(executions
+            .get(action)
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(*secs)))
+    }
+
+    fn record(&self, id: SwapId, action: &str, at: SystemTime) -> Result<(), disk_store::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+
+        let mut executions = self.executions_of(&id)?;
+        let secs = at
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or(0);
+        executions.insert(action.to_string(), secs);
+
+        self.directory.write(&id, &executions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn disk_execution_log_survives_reopening() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let id = SwapId::default();
+        let at = UNIX_EPOCH + Duration::from_secs(1_546_300_800);
+
+        {
+            let log = DiskExecutionLog::open(path).unwrap();
+            log.record(id, "redeem", at).unwrap();
+        }
+
+        let log = DiskExecutionLog::open(path).unwrap();
+
+        assert_that(&log.last_executed(&id, "redeem").unwrap())
+            .is_some()
+            .is_equal_to(&at);
+        assert_that(&log.last_executed(&id, "refund").unwrap()).is_none();
+    }
+
+}
@@ -0,0 +1,356 @@
+mod bitcoin;
+mod ethereum;
+mod execution_log;
+
+pub use self::{
+    bitcoin::{BitcoinCoreWallet, BitcoinWallet},
+    ethereum::{EthereumWallet, Web3Wallet},
+    execution_log::{DiskExecutionLog, ExecutionLog, InMemoryExecutionLog},
+};
+
+use crate::{
+    ledger_query_service::ChainTips,
+    swap_protocols::{
+        asset::Asset,
+        disk_store,
+        metadata_store::{self, LedgerKind, Metadata, MetadataStore, RoleKind},
+        rfc003::{
+            alice, bob,
+            state_machine::SwapStates,
+            state_store::{self, StateStore},
+            Actions, Alice, Bob, CheckSafety, Ledger, Role, SafetyMargin,
+        },
+        SwapId,
+    },
+};
+use ethereum_support::web3;
+use futures::{future, Future, Stream};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::timer::Interval;
+use tokio_threadpool::blocking;
+
+#[derive(Debug)]
+pub enum Error {
+    MissingWallet(LedgerKind),
+    BitcoinRpcConnection(bitcoin_rpc_client::ClientError),
+    BitcoinRpcResponse(bitcoin_rpc_client::RpcError),
+    BitcoinSerialization(bitcoin_support::Error),
+    PayoutAddressOnWrongNetwork(bitcoin_support::Address, bitcoin_support::Network),
+    EthereumNodeOnWrongNetwork(String, ethereum_support::Network),
+    Web3(web3::Error),
+    Storage(state_store::Error),
+    Metadata(metadata_store::Error),
+    ExecutionLog(disk_store::Error),
+}
+
+/// The wallets the node broadcasts with. A swap touching a ledger without a
+/// wallet is left to the user.
+#[derive(Debug, Default)]
+pub struct Wallets {
+    pub bitcoin: Option<Arc<dyn BitcoinWallet>>,
+    pub ethereum: Option<Arc<dyn EthereumWallet>>,
+}
+
+/// An action payload the node knows how to sign and broadcast by itself.
+pub trait Execute {
+    fn execute(self, wallets: &Wallets) -> Result<(), Error>;
+}
+
+/// Only redeeming and refunding is done automatically. Both just move funds
+/// out of an HTLC into an address we control, whereas funding requires the
+/// user to decide where the money comes from.
+pub trait AutoExecute {
+    fn name(&self) -> String;
+    fn is_auto_executable(&self) -> bool;
+    /// Does nothing for actions that are not auto-executable.
+    fn auto_execute(self, wallets: &Wallets) -> Result<(), Error>;
+}
+
+impl<Cancel, Deploy, Fund, Redeem: Execute, Refund: Execute> AutoExecute
+    for alice::ActionKind<Cancel, Deploy, Fund, Redeem, Refund>
+{
+    fn name(&self) -> String {
+        alice::ActionKind::name(self)
+    }
+
+    fn is_auto_executable(&self) -> bool {
+        match self {
+            alice::ActionKind::Redeem(_) | alice::ActionKind::Refund(_) => true,
+            _ => false,
+        }
+    }
+
+    fn auto_execute(self, wallets: &Wallets) -> Result<(), Error> {
+        match self {
+            alice::ActionKind::Redeem(redeem) => redeem.execute(wallets),
+            alice::ActionKind::Refund(refund) => refund.execute(wallets),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<Accept, Decline, Deploy, Fund, Redeem: Execute, Refund: Execute> AutoExecute
+    for bob::ActionKind<Accept, Decline, Deploy, Fund, Redeem, Refund>
+{
+    fn name(&self) -> String {
+        bob::ActionKind::name(self)
+    }
+
+    fn is_auto_executable(&self) -> bool {
+        match self {
+            bob::ActionKind::Redeem(_) | bob::ActionKind::Refund(_) => true,
+            _ => false,
+        }
+    }
+
+    fn auto_execute(self, wallets: &Wallets) -> Result<(), Error> {
+        match self {
+            bob::ActionKind::Redeem(redeem) => redeem.execute(wallets),
+            bob::ActionKind::Refund(refund) => refund.execute(wallets),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Periodically looks at the actions of every swap and takes the redeem and
+/// refund actions on behalf of the user.
+///
+/// An action stays on offer until its transaction is seen on the ledger. If
+/// that hasn't happened `retry_interval` after broadcasting it, e.g. because
+/// the transaction got dropped from the mempool, it is broadcast again.
+#[derive(DebugStub)]
+pub struct AutoExecutor<T, S> {
+    metadata_store: Arc<T>,
+    state_store: Arc<S>,
+    chain_tips: Arc<ChainTips>,
+    safety_margin: SafetyMargin,
+    wallets: Wallets,
+    #[debug_stub = "ExecutionLog"]
+    execution_log: Arc<dyn ExecutionLog>,
+    retry_interval: Duration,
+}
+
+impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>> AutoExecutor<T, S> {
+    pub fn new(
+        metadata_store: Arc<T>,
+        state_store: Arc<S>,
+        chain_tips: Arc<ChainTips>,
+        safety_margin: SafetyMargin,
+        wallets: Wallets,
+        execution_log: Arc<dyn ExecutionLog>,
+        retry_interval: Duration,
+    ) -> Self {
+        AutoExecutor {
+            metadata_store,
+            state_store,
+            chain_tips,
+            safety_margin,
+            wallets,
+            execution_log,
+            retry_interval,
+        }
+    }
+
+    /// The wallets talk to their nodes synchronously, so every round runs as
+    /// a blocking section to keep it from stalling the other tasks of the
+    /// runtime.
+    pub fn keep_executing(
+        self,
+        poll_interval: Duration,
+    ) -> impl Future<Item = (), Error = ()> + Send {
+        let auto_executor = Arc::new(self);
+
+        Interval::new(Instant::now(), poll_interval)
+            .map_err(|e| error!("Auto-execute timer failed: {:?}", e))
+            .for_each(move |_| {
+                let auto_executor = Arc::clone(&auto_executor);
+
+                future::poll_fn(move || blocking(|| auto_executor.execute_pending()))
+                    .map_err(|e| error!("Auto-executing requires a thread pool: {:?}", e))
+                    .map(|result| {
+                        if let Err(e) = result {
+                            error!("Failed to auto-execute actions: {:?}", e);
+                        }
+                    })
+            })
+    }
+
+    pub fn execute_pending(&self) -> Result<(), Error> {
+        for (id, metadata) in self.metadata_store.all().map_err(Error::Metadata)? {
+            if let Err(e) = self.execute_swap(id, metadata) {
+                error!("Failed to auto-execute actions of swap {}: {:?}", id, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn execute_swap(&self, id: SwapId, metadata: Metadata) -> Result<(), Error> {
+        with_supported_swap_types!(
+            metadata,
+            (|| self.execute_with_role::<AL, BL, AA, BA>(id, metadata.role)),
+            Ok(())
+        )
+    }
+
+    fn execute_with_role<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset>(
+        &self,
+        id: SwapId,
+        role: RoleKind,
+    ) -> Result<(), Error>
+    where
+        SwapStates<Alice<AL, BL, AA, BA>>: Actions,
+        SwapStates<Bob<AL, BL, AA, BA>>: Actions,
+        <SwapStates<Alice<AL, BL, AA, BA>> as Actions>::ActionKind: AutoExecute + CheckSafety,
+        <SwapStates<Bob<AL, BL, AA, BA>> as Actions>::ActionKind: AutoExecute + CheckSafety,
+    {
+        match role {
+            RoleKind::Alice => self.execute::<Alice<AL, BL, AA, BA>>(id),
+            RoleKind::Bob => self.execute::<Bob<AL, BL, AA, BA>>(id),
+        }
+    }
+
+    fn execute<R: Role>(&self, id: SwapId) -> Result<(), Error>
+    where
+        SwapStates<R>: Actions,
+        <SwapStates<R> as Actions>::ActionKind: AutoExecute + CheckSafety,
+    {
+        let state = match self.state_store.get::<R>(&id).map_err(Error::Storage)? {
+            Some(state) => state,
+            None => return Ok(()),
+        };
+        let latest_blocks = self.chain_tips.latest_known();
+
+        for (action, safety) in self.safety_margin.actions(&state, &latest_blocks) {
+            if !action.is_auto_executable() {
+                continue;
+            }
+
+            let name = action.name();
+            let last_executed = self
+                .execution_log
+                .last_executed(&id, &name)
+                .map_err(Error::ExecutionLog)?;
+            let now = SystemTime::now();
+
+            if !is_due(last_executed, now, self.retry_interval) {
+                continue;
+            }
+
+            if let Err(e) = safety {
+                warn!("Not auto-executing {} of swap {}: {:?}", name, id, e);
+                continue;
+            }
+
+            if last_executed.is_some() {
+                warn!(
+                    "{} of swap {} has not been seen on the ledger since it was broadcast, broadcasting it again",
+                    name, id
+                );
+            }
+
+            match action.auto_execute(&self.wallets) {
+                Ok(()) => {
+                    info!("Auto-executed {} of swap {}", name, id);
+                    self.execution_log
+                        .record(id, &name, now)
+                        .map_err(Error::ExecutionLog)?;
+                }
+                Err(e) => error!("Failed to auto-execute {} of swap {}: {:?}", name, id, e),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn is_due(last_executed: Option<SystemTime>, now: SystemTime, retry_interval: Duration) -> bool {
+    match last_executed {
+        Some(last_executed) => now
+            .duration_since(last_executed)
+            .map(|waited| waited >= retry_interval)
+            .unwrap_or(false),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap_protocols::rfc003::ethereum::SendTransaction;
+    use ethereum_support::{Address, Bytes, EtherQuantity, Network, H256, U256};
+    use std::sync::Mutex;
+
+    #[derive(Debug, Default)]
+    struct FakeEthereumWallet {
+        sent: Mutex<Vec<SendTransaction>>,
+    }
+
+    impl EthereumWallet for FakeEthereumWallet {
+        fn send(&self, action: SendTransaction) -> Result<H256, Error> {
+            self.sent.lock().unwrap().push(action);
+            Ok(H256::zero())
+        }
+    }
+
+    fn send_transaction() -> SendTransaction {
+        SendTransaction {
+            to: Address::zero(),
+            data: Bytes::default(),
+            gas_limit: U256::from(42),
+            value: EtherQuantity::zero(),
+            network: Network::Regtest,
+        }
+    }
+
+    type AliceAction = alice::ActionKind<(), (), (), SendTransaction, SendTransaction>;
+
+    #[test]
+    fn only_redeem_and_refund_are_auto_executed() {
+        let wallet = Arc::new(FakeEthereumWallet::default());
+        let wallets = Wallets {
+            bitcoin: None,
+            ethereum: Some(wallet.clone() as Arc<dyn EthereumWallet>),
+        };
+
+        let fund: AliceAction = alice::ActionKind::Fund(());
+        let redeem: AliceAction = alice::ActionKind::Redeem(send_transaction());
+
+        assert!(!fund.is_auto_executable());
+        assert!(redeem.is_auto_executable());
+        assert!(redeem.auto_execute(&wallets).is_ok());
+        assert_eq!(*wallet.sent.lock().unwrap(), vec![send_transaction()]);
+    }
+
+    #[test]
+    fn fails_without_a_wallet_for_the_ledger() {
+        let refund: AliceAction = alice::ActionKind::Refund(send_transaction());
+
+        match refund.auto_execute(&Wallets::default()) {
+            Err(Error::MissingWallet(LedgerKind::Ethereum)) => {}
+            other => panic!("expected a missing ethereum wallet, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn broadcast_action_is_only_retried_after_the_retry_interval() {
+        let retry_interval = Duration::from_secs(600);
+        let broadcast_at = SystemTime::now();
+
+        assert!(is_due(None, broadcast_at, retry_interval));
+        assert!(!is_due(
+            Some(broadcast_at),
+            broadcast_at + Duration::from_secs(599),
+            retry_interval
+        ));
+        assert!(is_due(
+            Some(broadcast_at),
+            broadcast_at + retry_interval,
+            retry_interval
+        ));
+    }
+
+}
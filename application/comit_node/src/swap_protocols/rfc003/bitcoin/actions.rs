@@ -1,4 +1,4 @@
-use bitcoin_support::{Address, BitcoinQuantity};
+use bitcoin_support::{Address, BitcoinQuantity, Network};
 use bitcoin_witness::{PrimedInput, PrimedTransaction};
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
@@ -12,6 +12,7 @@ pub struct SpendOutput {
     // Remember: One man's input is another man's output!
     // TODO: decide whether we want to serialize this directly
     pub output: PrimedInput,
+    pub network: Network,
 }
 
 impl SpendOutput {
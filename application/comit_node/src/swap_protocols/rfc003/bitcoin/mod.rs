@@ -1,9 +1,13 @@
-use crate::swap_protocols::{
-    ledger::Bitcoin,
-    rfc003::{state_machine::HtlcParams, Ledger},
+use crate::{
+    ledger_query_service::{LatestBlock, LatestBlocks},
+    swap_protocols::{
+        ledger::Bitcoin,
+        rfc003::{state_machine::HtlcParams, Ledger},
+    },
 };
-use bitcoin_support::{Address, BitcoinQuantity, Blocks, OutPoint};
+use bitcoin_support::{Address, BitcoinQuantity, Blocks, OutPoint, BTC_BLOCKS_IN_24H};
 use secp256k1_support::KeyPair;
+use std::{ops::Add, time::Duration};
 
 mod actions;
 mod extract_secret;
@@ -17,10 +21,52 @@ pub use self::{
     queries::*,
 };
 
+/// Bitcoin targets one block every ten minutes
+const EXPECTED_BLOCK_INTERVAL_SECS: u64 = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct BlockHeight(pub u64);
+
+impl From<LatestBlock> for BlockHeight {
+    fn from(latest_block: LatestBlock) -> Self {
+        BlockHeight(latest_block.height)
+    }
+}
+
+impl Add<Blocks> for BlockHeight {
+    type Output = BlockHeight;
+
+    fn add(self, blocks: Blocks) -> BlockHeight {
+        BlockHeight(self.0 + u64::from(blocks.0))
+    }
+}
+
 impl Ledger for Bitcoin {
     type LockDuration = Blocks;
     type HtlcLocation = OutPoint;
     type HtlcIdentity = KeyPair;
+    type BlockTime = BlockHeight;
+
+    fn latest_block_time(latest_blocks: &LatestBlocks) -> Option<BlockHeight> {
+        latest_blocks.bitcoin.map(BlockHeight::from)
+    }
+
+    fn estimated_duration(lock_duration: &Blocks) -> Duration {
+        Duration::from_secs(u64::from(lock_duration.0) * EXPECTED_BLOCK_INTERVAL_SECS)
+    }
+
+    fn estimated_time_until(expiry: BlockHeight, latest_blocks: &LatestBlocks) -> Option<Duration> {
+        latest_blocks.bitcoin.map(|latest_block| {
+            let blocks_left = expiry.0.saturating_sub(latest_block.height);
+            Duration::from_secs(blocks_left * EXPECTED_BLOCK_INTERVAL_SECS)
+        })
+    }
+
+    fn queries_expire_at(latest_block: LatestBlock, lock_duration: &Blocks) -> u64 {
+        // The day of margin also covers the few blocks the LQS may be ahead
+        // of the latest block we know about
+        latest_block.height + u64::from(lock_duration.0) + u64::from(BTC_BLOCKS_IN_24H.0)
+    }
 }
 
 impl From<HtlcParams<Bitcoin, BitcoinQuantity>> for Htlc {
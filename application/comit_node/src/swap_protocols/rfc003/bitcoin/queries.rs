@@ -3,7 +3,10 @@ use crate::{
     swap_protocols::{
         ledger::Bitcoin,
         rfc003::{
-            events::{NewHtlcFundedQuery, NewHtlcRedeemedQuery, NewHtlcRefundedQuery},
+            bitcoin::BlockHeight,
+            events::{
+                NewHtlcExpiredQuery, NewHtlcFundedQuery, NewHtlcRedeemedQuery, NewHtlcRefundedQuery,
+            },
             state_machine::HtlcParams,
         },
     },
@@ -16,32 +19,49 @@ impl NewHtlcFundedQuery<Bitcoin, BitcoinQuantity> for BitcoinQuery {
             to_address: Some(htlc_params.compute_address()),
             from_outpoint: None,
             unlock_script: None,
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 }
 
 impl NewHtlcRefundedQuery<Bitcoin, BitcoinQuantity> for BitcoinQuery {
     fn new_htlc_refunded_query(
-        _htlc_params: &HtlcParams<Bitcoin, BitcoinQuantity>,
+        htlc_params: &HtlcParams<Bitcoin, BitcoinQuantity>,
         htlc_location: &OutPoint,
     ) -> Self {
         BitcoinQuery::Transaction {
             to_address: None,
             from_outpoint: Some(*htlc_location),
             unlock_script: Some(vec![vec![0u8]]),
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 }
 
 impl NewHtlcRedeemedQuery<Bitcoin, BitcoinQuantity> for BitcoinQuery {
     fn new_htlc_redeemed_query(
-        _htlc_params: &HtlcParams<Bitcoin, BitcoinQuantity>,
+        htlc_params: &HtlcParams<Bitcoin, BitcoinQuantity>,
         htlc_location: &OutPoint,
     ) -> Self {
         BitcoinQuery::Transaction {
             to_address: None,
             from_outpoint: Some(*htlc_location),
             unlock_script: Some(vec![vec![1u8]]),
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
+        }
+    }
+}
+
+impl NewHtlcExpiredQuery<Bitcoin> for BitcoinQuery {
+    fn new_htlc_expired_query(expiry: &BlockHeight) -> Self {
+        BitcoinQuery::Block {
+            min_height: Some(expiry.0 as u32),
         }
     }
 }
@@ -50,6 +50,7 @@ mod tests {
             .unwrap(),
             lock_duration: Blocks::from(144),
             secret_hash: Secret::from(*b"hello world, you are beautiful!!").into(),
+            confirmations_needed: 1,
         }
     }
 
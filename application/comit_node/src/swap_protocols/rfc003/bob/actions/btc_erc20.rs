@@ -1,15 +1,18 @@
-use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    rfc003::{
-        bitcoin,
-        bob::{
-            self,
-            actions::{Accept, Decline},
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            bitcoin,
+            bob::{
+                self,
+                actions::{Accept, Decline},
+            },
+            ethereum::{self, Erc20Htlc, Htlc},
+            secret::Secret,
+            state_machine::*,
+            Actions, Bob,
         },
-        ethereum::{self, Erc20Htlc, Htlc},
-        secret::Secret,
-        state_machine::*,
-        Actions, Bob,
     },
 };
 use bitcoin_support::{BitcoinQuantity, OutPoint};
@@ -26,6 +29,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
             data,
             value: EtherQuantity::zero(),
             gas_limit,
+            network: self.beta_ledger.network,
         }
     }
 
@@ -41,6 +45,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
             data,
             gas_limit,
             value: EtherQuantity::zero(),
+            network: self.beta_ledger.network,
         }
     }
 
@@ -56,6 +61,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
             data: htlc.funding_tx_payload(beta_htlc_location),
             gas_limit,
             value: EtherQuantity::zero(),
+            network: self.beta_ledger.network,
         }
     }
 
@@ -71,6 +77,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> {
                 bitcoin::Htlc::from(self.alpha_htlc_params())
                     .unlock_with_secret(self.alpha_ledger_redeem_identity, &secret),
             ),
+            network: self.alpha_ledger.network,
         }
     }
 }
@@ -85,7 +92,7 @@ impl Actions for SwapStates<Bob<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantit
         ethereum::SendTransaction,
     >;
 
-    fn actions(&self) -> Vec<Self::ActionKind> {
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
         use self::SwapStates as SS;
         match *self {
             SS::Start(Start { ref role, .. }) => vec![
@@ -100,23 +107,46 @@ impl Actions for SwapStates<Bob<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantit
                 ref beta_htlc_location,
                 ..
             }) => vec![bob::ActionKind::Fund(swap.fund_action(*beta_htlc_location))],
+            SS::BetaExpired(BetaExpired {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            })
+            | SS::BothExpired(BothExpired {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => vec![bob::ActionKind::Refund(
+                swap.refund_action(*beta_htlc_location),
+            )],
             SS::BothFunded(BothFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
+                ref swap,
+                ..
+            })
+            | SS::AlphaExpired(AlphaExpired {
+                ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
             })
             | SS::AlphaRedeemedBetaFunded(AlphaRedeemedBetaFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
             })
             | SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
-            }) => vec![bob::ActionKind::Refund(
-                swap.refund_action(*beta_htlc_location),
-            )],
+            }) if swap.beta_htlc_expired(beta_htlc_deployed_at, latest_blocks) => {
+                vec![bob::ActionKind::Refund(
+                    swap.refund_action(*beta_htlc_location),
+                )]
+            }
             SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
                 ref swap,
                 ref alpha_htlc_location,
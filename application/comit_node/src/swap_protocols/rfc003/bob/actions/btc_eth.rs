@@ -1,15 +1,18 @@
-use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    rfc003::{
-        bitcoin,
-        bob::{
-            self,
-            actions::{Accept, Decline},
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            bitcoin,
+            bob::{
+                self,
+                actions::{Accept, Decline},
+            },
+            ethereum::{self, EtherHtlc, Htlc},
+            secret::Secret,
+            state_machine::*,
+            Actions, Bob,
         },
-        ethereum::{self, EtherHtlc, Htlc},
-        secret::Secret,
-        state_machine::*,
-        Actions, Bob,
     },
 };
 use bitcoin_support::{BitcoinQuantity, OutPoint};
@@ -26,6 +29,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> {
             data,
             value: self.beta_asset,
             gas_limit,
+            network: self.beta_ledger.network,
         }
     }
 
@@ -41,6 +45,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> {
             data,
             gas_limit,
             value: EtherQuantity::zero(),
+            network: self.beta_ledger.network,
         }
     }
 
@@ -57,6 +62,7 @@ impl OngoingSwap<Bob<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> {
                 self.alpha_asset,
                 htlc.unlock_with_secret(self.alpha_ledger_redeem_identity, &secret),
             ),
+            network: self.alpha_ledger.network,
         }
     }
 }
@@ -71,7 +77,7 @@ impl Actions for SwapStates<Bob<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantit
         ethereum::SendTransaction,
     >;
 
-    fn actions(&self) -> Vec<Self::ActionKind> {
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
         use self::SwapStates as SS;
         match *self {
             SS::Start(Start { ref role, .. }) => vec![
@@ -81,23 +87,46 @@ impl Actions for SwapStates<Bob<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantit
             SS::AlphaFunded(AlphaFunded { ref swap, .. }) => {
                 vec![bob::ActionKind::Fund(swap.fund_action())]
             }
+            SS::BetaExpired(BetaExpired {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            })
+            | SS::BothExpired(BothExpired {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => vec![bob::ActionKind::Refund(
+                swap.refund_action(*beta_htlc_location),
+            )],
             SS::BothFunded(BothFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
+                ref swap,
+                ..
+            })
+            | SS::AlphaExpired(AlphaExpired {
+                ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
             })
             | SS::AlphaRedeemedBetaFunded(AlphaRedeemedBetaFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
             })
             | SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
-            }) => vec![bob::ActionKind::Refund(
-                swap.refund_action(*beta_htlc_location),
-            )],
+            }) if swap.beta_htlc_expired(beta_htlc_deployed_at, latest_blocks) => {
+                vec![bob::ActionKind::Refund(
+                    swap.refund_action(*beta_htlc_location),
+                )]
+            }
             SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
                 ref swap,
                 ref alpha_htlc_location,
@@ -115,13 +144,20 @@ impl Actions for SwapStates<Bob<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantit
 mod tests {
 
     use super::*;
-    use crate::swap_protocols::rfc003::{role::test::Bobisha, Secret};
+    use crate::{
+        ledger_query_service::LatestBlock,
+        swap_protocols::rfc003::{
+            bitcoin::BlockHeight,
+            ethereum::{Seconds, Timestamp},
+            role::test::Bobisha,
+            Secret,
+        },
+    };
+    use bitcoin_support::Sha256dHash;
     use hex::FromHex;
 
-    #[test]
-    fn given_state_instance_when_calling_actions_should_not_need_to_specify_type_arguments() {
-        let (bobisha, _) = Bobisha::create();
-        let swap_state = SwapStates::from(Start::<Bobisha> {
+    fn start_state(bobisha: Bobisha) -> Start<Bobisha> {
+        Start {
             alpha_ledger_refund_identity: bitcoin_support::PubkeyHash::from_hex(
                 "875638cac0b0ae9f826575e190f2788918c354c2",
             )
@@ -130,15 +166,23 @@ mod tests {
                 .parse()
                 .unwrap(),
             alpha_ledger: Bitcoin::default(),
-            beta_ledger: Ethereum::default(),
+            beta_ledger: Ethereum::regtest(),
             alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
             beta_asset: EtherQuantity::from_eth(10.0),
             alpha_ledger_lock_duration: bitcoin_support::Blocks::from(144),
+            alpha_ledger_confirmations_needed: 1,
+            beta_ledger_confirmations_needed: 1,
             secret: Secret::from(*b"hello world, you are beautiful!!").hash(),
             role: bobisha,
-        });
+        }
+    }
 
-        let actions = swap_state.actions();
+    #[test]
+    fn given_state_instance_when_calling_actions_should_not_need_to_specify_type_arguments() {
+        let (bobisha, _) = Bobisha::create();
+        let swap_state = SwapStates::from(start_state(bobisha));
+
+        let actions = swap_state.actions(&LatestBlocks::default());
 
         assert!(actions
             .into_iter()
@@ -150,4 +194,46 @@ mod tests {
             .is_some());
     }
 
+    #[test]
+    fn given_both_funded_should_only_offer_refund_once_beta_htlc_expired() {
+        let (bobisha, _) = Bobisha::create();
+        let response = StateMachineResponse {
+            alpha_ledger_redeem_identity: secp256k1_support::KeyPair::from_secret_key_slice(
+                &hex::decode("18e14a7b6a307f426a94f8114701e7c8e774e7f9a47e2c2035db29a206321725")
+                    .unwrap(),
+            )
+            .unwrap(),
+            beta_ledger_refund_identity: "8457037fcd80a8650c4692d7fcfc1d0a96b92867"
+                .parse()
+                .unwrap(),
+            beta_ledger_lock_duration: Seconds(3600),
+        };
+        let swap_state = SwapStates::from(BothFunded::<Bobisha> {
+            swap: OngoingSwap::new(start_state(bobisha), response),
+            alpha_htlc_location: OutPoint {
+                txid: Sha256dHash::from_data(b"funding"),
+                vout: 0,
+            },
+            alpha_htlc_deployed_at: BlockHeight(100),
+            beta_htlc_location: "a00f2cac7bad9285ecfd59e8860f5b2d8622e099".parse().unwrap(),
+            beta_htlc_deployed_at: Timestamp(1_544_000_000),
+        });
+        let latest_ethereum_block = |timestamp| LatestBlocks {
+            bitcoin: None,
+            ethereum: Some(LatestBlock {
+                height: 0,
+                timestamp,
+            }),
+        };
+
+        assert!(swap_state.actions(&LatestBlocks::default()).is_empty());
+        assert!(swap_state
+            .actions(&latest_ethereum_block(1_544_003_599))
+            .is_empty());
+
+        let actions = swap_state.actions(&latest_ethereum_block(1_544_003_600));
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].name(), "refund");
+    }
+
 }
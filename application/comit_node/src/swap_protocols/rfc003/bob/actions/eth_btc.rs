@@ -1,15 +1,18 @@
-use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    rfc003::{
-        bitcoin,
-        bob::{
-            self,
-            actions::{Accept, Decline},
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        ledger::{Bitcoin, Ethereum},
+        rfc003::{
+            bitcoin,
+            bob::{
+                self,
+                actions::{Accept, Decline},
+            },
+            ethereum::{self, EtherHtlc},
+            secret::Secret,
+            state_machine::*,
+            Actions, Bob,
         },
-        ethereum::{self, EtherHtlc},
-        secret::Secret,
-        state_machine::*,
-        Actions, Bob,
     },
 };
 use bitcoin_support::{BitcoinQuantity, OutPoint};
@@ -32,6 +35,7 @@ impl OngoingSwap<Bob<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> {
                 bitcoin::Htlc::from(self.beta_htlc_params())
                     .unlock_after_timeout(self.beta_ledger_refund_identity),
             ),
+            network: self.beta_ledger.network,
         }
     }
 
@@ -48,6 +52,7 @@ impl OngoingSwap<Bob<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> {
             data,
             gas_limit,
             value: EtherQuantity::from_wei(U256::zero()),
+            network: self.alpha_ledger.network,
         }
     }
 }
@@ -62,7 +67,7 @@ impl Actions for SwapStates<Bob<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantit
         bitcoin::SpendOutput,
     >;
 
-    fn actions(&self) -> Vec<Self::ActionKind> {
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind> {
         use self::SwapStates as SS;
         match *self {
             SS::Start(Start { ref role, .. }) => vec![
@@ -72,23 +77,46 @@ impl Actions for SwapStates<Bob<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantit
             SS::AlphaFunded(AlphaFunded { ref swap, .. }) => {
                 vec![bob::ActionKind::Fund(swap.fund_action())]
             }
+            SS::BetaExpired(BetaExpired {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            })
+            | SS::BothExpired(BothExpired {
+                ref beta_htlc_location,
+                ref swap,
+                ..
+            }) => vec![bob::ActionKind::Refund(
+                swap.refund_action(*beta_htlc_location),
+            )],
             SS::BothFunded(BothFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
+                ref swap,
+                ..
+            })
+            | SS::AlphaExpired(AlphaExpired {
+                ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
             })
             | SS::AlphaRedeemedBetaFunded(AlphaRedeemedBetaFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
             })
             | SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
                 ref beta_htlc_location,
+                beta_htlc_deployed_at,
                 ref swap,
                 ..
-            }) => vec![bob::ActionKind::Refund(
-                swap.refund_action(*beta_htlc_location),
-            )],
+            }) if swap.beta_htlc_expired(beta_htlc_deployed_at, latest_blocks) => {
+                vec![bob::ActionKind::Refund(
+                    swap.refund_action(*beta_htlc_location),
+                )]
+            }
             SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
                 ref swap,
                 ref alpha_htlc_location,
@@ -1,5 +1,6 @@
 mod btc_erc20;
 mod btc_eth;
+mod erc20_btc;
 mod eth_btc;
 
 use crate::{
@@ -22,16 +22,22 @@ use crate::{
     },
 };
 
-use futures::{sync::oneshot, Future};
+use futures::{future, sync::oneshot, Future};
 use std::{
     marker::PhantomData,
     sync::{Arc, Mutex},
 };
 
-#[derive(Debug, Clone)]
+/// The response sender only lives as long as the process that received the
+/// swap request. A `Bob` restored from storage therefore comes back without
+/// one and can no longer accept or decline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Bob<AL: Ledger, BL: Ledger, AA, BA> {
+    #[serde(skip)]
     phantom_data: PhantomData<(AL, BL, AA, BA)>,
     #[allow(clippy::type_complexity)]
+    #[serde(skip)]
     response_sender: Arc<
         Mutex<
             Option<
@@ -68,11 +74,16 @@ impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> Bob<AL, BL, AA, BA> {
             alpha_ledger_refund_identity: initiation.alpha_ledger_refund_identity,
             beta_ledger_redeem_identity: initiation.beta_ledger_redeem_identity,
             alpha_ledger_lock_duration: initiation.alpha_ledger_lock_duration,
+            alpha_ledger_confirmations_needed: initiation.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: initiation.beta_ledger_confirmations_needed,
             secret: initiation.secret,
             role: bob,
         };
 
-        save_state.save(start_state.clone().into());
+        // Dropping the start state drops Bob as well, which cancels the response
+        if let Err(e) = save_state.save(start_state.clone().into()) {
+            return (Box::new(future::err(e)), Box::new(response_for_caller));
+        }
 
         let context = Context {
             alpha_ledger_events,
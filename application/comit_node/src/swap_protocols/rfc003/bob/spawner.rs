@@ -22,6 +22,11 @@ use http_api_problem::HttpApiProblem;
 pub enum Error {
     Storage(state_store::Error),
     Metadata(metadata_store::Error),
+    /// One of the ledgers is on a network the node is not connected to
+    UnsupportedNetwork,
+    /// Alice asked to wait for fewer confirmations on one of the ledgers than
+    /// the node accepts
+    TooFewConfirmations,
 }
 
 impl From<Error> for HttpApiProblem {
@@ -30,6 +35,12 @@ impl From<Error> for HttpApiProblem {
         match e {
             Storage(e) => e.into(),
             Metadata(e) => e.into(),
+            UnsupportedNetwork => HttpApiProblem::new("network-not-supported")
+                .set_status(400)
+                .set_detail("The node is not connected to the network of one of the ledgers."),
+            TooFewConfirmations => HttpApiProblem::new("too-few-confirmations")
+                .set_status(400)
+                .set_detail("The swap waits for fewer confirmations than the node accepts."),
         }
     }
 }
@@ -42,8 +53,7 @@ pub trait BobSpawner: Send + Sync + 'static {
         swap_request: SwapRequest<AL, BL, AA, BA>,
     ) -> Result<Box<ResponseFuture<Bob<AL, BL, AA, BA>>>, Error>
     where
-        LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
-        SwapRequest<AL, BL, AA, BA>: Into<Metadata>;
+        LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>;
 }
 
 impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client> BobSpawner
@@ -57,14 +67,36 @@ impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client> B
     ) -> Result<Box<ResponseFuture<Bob<AL, BL, AA, BA>>>, Error>
     where
         LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
-        SwapRequest<AL, BL, AA, BA>: Into<Metadata>,
     {
+        // Only the Ethereum network is checked, there is no setting for the
+        // Bitcoin network yet and Bitcoin ledgers are taken to be on it
+        if !swap_request.alpha_ledger.is_on(&self.networks)
+            || !swap_request.beta_ledger.is_on(&self.networks)
+        {
+            return Err(Error::UnsupportedNetwork);
+        }
+
+        if swap_request.alpha_ledger_confirmations_needed
+            < AL::min_confirmations(&self.min_confirmations)
+            || swap_request.beta_ledger_confirmations_needed
+                < BL::min_confirmations(&self.min_confirmations)
+        {
+            return Err(Error::TooFewConfirmations);
+        }
+
         let save_state = self
             .state_store
             .new_save_state(id)
             .map_err(Error::Storage)?;
+        let start_heights = self.ledger_events.start_heights();
         self.metadata_store
-            .insert(id, swap_request.clone())
+            .insert(
+                id,
+                Metadata {
+                    start_heights,
+                    ..swap_request.clone().into()
+                },
+            )
             .map_err(Error::Metadata)?;
 
         let initiation = Initiation {
@@ -75,13 +107,15 @@ impl<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client> B
             beta_ledger_redeem_identity: swap_request.beta_ledger_redeem_identity,
             alpha_ledger_refund_identity: swap_request.alpha_ledger_refund_identity,
             alpha_ledger_lock_duration: swap_request.alpha_ledger_lock_duration,
+            alpha_ledger_confirmations_needed: swap_request.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: swap_request.beta_ledger_confirmations_needed,
             secret: swap_request.secret_hash,
         };
 
         let (state_machine_future, response_future) = Bob::new_state_machine(
             initiation,
-            self.ledger_events.create_ledger_events(),
-            self.ledger_events.create_ledger_events(),
+            self.ledger_events.create_ledger_events(start_heights),
+            self.ledger_events.create_ledger_events(start_heights),
             save_state,
         );
 
@@ -1,10 +1,9 @@
 use crate::swap_protocols::{
-    ledger::{Bitcoin, Ethereum},
-    metadata_store::{AssetKind, LedgerKind, Metadata, RoleKind},
+    asset::Asset,
+    metadata_store::{Metadata, RoleKind, StartHeights},
     rfc003::{Ledger, SecretHash},
 };
-use bitcoin_support::BitcoinQuantity;
-use ethereum_support::{Erc20Quantity, EtherQuantity};
+use bam::noise::PublicKey;
 
 #[derive(Clone, Debug, PartialEq, LabelledGeneric)]
 pub struct SwapRequest<AL: Ledger, BL: Ledger, AA, BA> {
@@ -15,41 +14,26 @@ pub struct SwapRequest<AL: Ledger, BL: Ledger, AA, BA> {
     pub alpha_ledger_refund_identity: AL::Identity,
     pub beta_ledger_redeem_identity: BL::Identity,
     pub alpha_ledger_lock_duration: AL::LockDuration,
+    pub alpha_ledger_confirmations_needed: u32,
+    pub beta_ledger_confirmations_needed: u32,
     pub secret_hash: SecretHash,
+    /// The node identity Alice authenticated with, if she used the secure
+    /// transport
+    pub counterparty: Option<PublicKey>,
 }
 
-impl From<SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>> for Metadata {
-    fn from(_: SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, EtherQuantity>) -> Self {
+impl<AL: Ledger, BL: Ledger, AA: Asset, BA: Asset> From<SwapRequest<AL, BL, AA, BA>>
+    for Metadata
+{
+    fn from(request: SwapRequest<AL, BL, AA, BA>) -> Self {
         Self {
-            alpha_ledger: LedgerKind::Bitcoin,
-            beta_ledger: LedgerKind::Ethereum,
-            alpha_asset: AssetKind::Bitcoin,
-            beta_asset: AssetKind::Ether,
-            role: RoleKind::Bob,
-        }
-    }
-}
-
-impl From<SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>> for Metadata {
-    fn from(_: SwapRequest<Bitcoin, Ethereum, BitcoinQuantity, Erc20Quantity>) -> Self {
-        Self {
-            alpha_ledger: LedgerKind::Bitcoin,
-            beta_ledger: LedgerKind::Ethereum,
-            alpha_asset: AssetKind::Bitcoin,
-            beta_asset: AssetKind::Erc20,
-            role: RoleKind::Bob,
-        }
-    }
-}
-
-impl From<SwapRequest<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>> for Metadata {
-    fn from(_: SwapRequest<Ethereum, Bitcoin, EtherQuantity, BitcoinQuantity>) -> Self {
-        Self {
-            alpha_ledger: LedgerKind::Ethereum,
-            beta_ledger: LedgerKind::Bitcoin,
-            alpha_asset: AssetKind::Ether,
-            beta_asset: AssetKind::Bitcoin,
+            alpha_ledger: request.alpha_ledger.into(),
+            beta_ledger: request.beta_ledger.into(),
+            alpha_asset: request.alpha_asset.into(),
+            beta_asset: request.beta_asset.into(),
             role: RoleKind::Bob,
+            counterparty: request.counterparty,
+            start_heights: StartHeights::default(),
         }
     }
 }
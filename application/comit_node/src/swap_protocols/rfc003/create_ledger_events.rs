@@ -1,9 +1,10 @@
 use crate::{
-    ledger_query_service::{FirstMatch, QueryIdCache},
+    ledger_query_service::{FirstBlock, FirstMatch, QueryIdCache},
     swap_protocols::{
         asset::Asset,
         dependencies::LedgerEventDependencies,
         ledger::{Bitcoin, Ethereum},
+        metadata_store::StartHeights,
         rfc003::{
             events::{LedgerEvents, LqsEvents, LqsEventsForErc20},
             Ledger,
@@ -15,38 +16,67 @@ use ethereum_support::{Erc20Quantity, EtherQuantity};
 use std::sync::Arc;
 
 pub trait CreateLedgerEvents<L: Ledger, A: Asset> {
-    fn create_ledger_events(&self) -> Box<dyn LedgerEvents<L, A>>;
+    /// The queries the events create ask the LQS to look for transactions
+    /// from the swap's start height on.
+    fn create_ledger_events(&self, start_heights: StartHeights) -> Box<dyn LedgerEvents<L, A>>;
 }
 
 impl CreateLedgerEvents<Bitcoin, BitcoinQuantity> for LedgerEventDependencies {
-    fn create_ledger_events(&self) -> Box<dyn LedgerEvents<Bitcoin, BitcoinQuantity>> {
+    fn create_ledger_events(
+        &self,
+        start_heights: StartHeights,
+    ) -> Box<dyn LedgerEvents<Bitcoin, BitcoinQuantity>> {
         Box::new(LqsEvents::new(
             QueryIdCache::wrap(Arc::clone(&self.lqs_client)),
             FirstMatch::new(Arc::clone(&self.lqs_client), self.lqs_bitcoin_poll_interval),
+            FirstBlock::new(Arc::clone(&self.lqs_client), self.lqs_bitcoin_poll_interval),
+            self.chain_tips.bitcoin.clone(),
+            start_heights.bitcoin,
+            self.lqs_client.clone(),
         ))
     }
 }
 
 impl CreateLedgerEvents<Ethereum, EtherQuantity> for LedgerEventDependencies {
-    fn create_ledger_events(&self) -> Box<dyn LedgerEvents<Ethereum, EtherQuantity>> {
+    fn create_ledger_events(
+        &self,
+        start_heights: StartHeights,
+    ) -> Box<dyn LedgerEvents<Ethereum, EtherQuantity>> {
         Box::new(LqsEvents::new(
             QueryIdCache::wrap(Arc::clone(&self.lqs_client)),
             FirstMatch::new(
                 Arc::clone(&self.lqs_client),
                 self.lqs_ethereum_poll_interval,
             ),
+            FirstBlock::new(
+                Arc::clone(&self.lqs_client),
+                self.lqs_ethereum_poll_interval,
+            ),
+            self.chain_tips.ethereum.clone(),
+            start_heights.ethereum,
+            self.lqs_client.clone(),
         ))
     }
 }
 
 impl CreateLedgerEvents<Ethereum, Erc20Quantity> for LedgerEventDependencies {
-    fn create_ledger_events(&self) -> Box<dyn LedgerEvents<Ethereum, Erc20Quantity>> {
+    fn create_ledger_events(
+        &self,
+        start_heights: StartHeights,
+    ) -> Box<dyn LedgerEvents<Ethereum, Erc20Quantity>> {
         Box::new(LqsEventsForErc20::new(
             QueryIdCache::wrap(Arc::clone(&self.lqs_client)),
             FirstMatch::new(
                 Arc::clone(&self.lqs_client),
                 self.lqs_ethereum_poll_interval,
             ),
+            FirstBlock::new(
+                Arc::clone(&self.lqs_client),
+                self.lqs_ethereum_poll_interval,
+            ),
+            self.chain_tips.ethereum.clone(),
+            start_heights.ethereum,
+            self.lqs_client.clone(),
         ))
     }
 }
@@ -1,11 +1,13 @@
 use crate::{comit_client::SwapResponseError, ledger_query_service};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Error {
     SwapResponse(SwapResponseError),
     LedgerQueryService(ledger_query_service::Error),
     TimerError,
     InsufficientFunding,
     HtlcDeployment,
+    /// The state of the swap could not be saved, so it must not go on
+    Storage(String),
     Internal(String),
 }
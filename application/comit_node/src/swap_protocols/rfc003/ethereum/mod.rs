@@ -1,9 +1,15 @@
-use crate::swap_protocols::{
-    ledger::Ethereum,
-    rfc003::{state_machine::HtlcParams, Ledger},
+use crate::{
+    ledger_query_service::{LatestBlock, LatestBlocks},
+    swap_protocols::{
+        ledger::Ethereum,
+        rfc003::{state_machine::HtlcParams, Ledger},
+    },
 };
 use ethereum_support::{web3::types::Address, Bytes, Erc20Quantity, EtherQuantity};
-use std::time::Duration;
+use std::{
+    ops::Add,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 mod actions;
 mod erc20_htlc;
@@ -30,6 +36,9 @@ pub trait Htlc {
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Seconds(pub u64);
 
+/// How long the queries about an HTLC outlive its expiry
+const QUERY_EXPIRY_MARGIN: Seconds = Seconds(24 * 60 * 60);
+
 impl From<Duration> for Seconds {
     fn from(duration: Duration) -> Self {
         Seconds(duration.as_secs())
@@ -42,10 +51,57 @@ impl From<Seconds> for Duration {
     }
 }
 
+/// Seconds since the unix epoch, as found in a block header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Timestamp(pub u64);
+
+impl From<LatestBlock> for Timestamp {
+    fn from(latest_block: LatestBlock) -> Self {
+        Timestamp(latest_block.timestamp)
+    }
+}
+
+impl Add<Seconds> for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, seconds: Seconds) -> Timestamp {
+        Timestamp(self.0 + seconds.0)
+    }
+}
+
 impl Ledger for Ethereum {
     type LockDuration = Seconds;
     type HtlcLocation = Address;
     type HtlcIdentity = Address;
+    type BlockTime = Timestamp;
+
+    fn latest_block_time(latest_blocks: &LatestBlocks) -> Option<Timestamp> {
+        latest_blocks.ethereum.map(Timestamp::from)
+    }
+
+    fn estimated_duration(lock_duration: &Seconds) -> Duration {
+        Duration::from(*lock_duration)
+    }
+
+    fn estimated_time_until(expiry: Timestamp, latest_blocks: &LatestBlocks) -> Option<Duration> {
+        latest_blocks.ethereum.map(|latest_block| {
+            Duration::from_secs(expiry.0.saturating_sub(latest_block.timestamp))
+        })
+    }
+
+    fn queries_expire_at(latest_block: LatestBlock, lock_duration: &Seconds) -> u64 {
+        // A block's timestamp is always later than its parent's, so the chain
+        // grows by at most one block per second
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_secs())
+            .unwrap_or(latest_block.timestamp);
+
+        latest_block.height
+            + now.saturating_sub(latest_block.timestamp)
+            + lock_duration.0
+            + QUERY_EXPIRY_MARGIN.0
+    }
 }
 
 impl From<HtlcParams<Ethereum, EtherQuantity>> for EtherHtlc {
@@ -82,7 +138,4 @@ impl HtlcParams<Ethereum, Erc20Quantity> {
     pub fn bytecode(&self) -> Bytes {
         Erc20Htlc::from(self.clone()).compile_to_hex().into()
     }
-    pub fn funding_tx_payload(&self, htlc_location: Address) -> Bytes {
-        Erc20Htlc::from(self.clone()).funding_tx_payload(htlc_location)
-    }
 }
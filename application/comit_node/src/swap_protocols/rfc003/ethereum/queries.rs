@@ -3,13 +3,16 @@ use crate::{
     swap_protocols::{
         ledger::Ethereum,
         rfc003::{
-            events::{NewHtlcFundedQuery, NewHtlcRedeemedQuery, NewHtlcRefundedQuery},
+            ethereum::Timestamp,
+            events::{
+                NewHtlcExpiredQuery, NewHtlcFundedQuery, NewHtlcRedeemedQuery, NewHtlcRefundedQuery,
+            },
             state_machine::HtlcParams,
             Secret,
         },
     },
 };
-use ethereum_support::{web3::types::Address, Bytes, EtherQuantity};
+use ethereum_support::{web3::types::Address, Bytes, EtherQuantity, H256};
 
 impl NewHtlcFundedQuery<Ethereum, EtherQuantity> for EthereumQuery {
     fn new_htlc_funded_query(htlc_params: &HtlcParams<Ethereum, EtherQuantity>) -> Self {
@@ -19,13 +22,16 @@ impl NewHtlcFundedQuery<Ethereum, EtherQuantity> for EthereumQuery {
             is_contract_creation: Some(true),
             transaction_data: Some(htlc_params.bytecode()),
             transaction_data_length: None,
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 }
 
 impl NewHtlcRefundedQuery<Ethereum, EtherQuantity> for EthereumQuery {
     fn new_htlc_refunded_query(
-        _htlc_params: &HtlcParams<Ethereum, EtherQuantity>,
+        htlc_params: &HtlcParams<Ethereum, EtherQuantity>,
         htlc_location: &Address,
     ) -> Self {
         EthereumQuery::Transaction {
@@ -34,13 +40,16 @@ impl NewHtlcRefundedQuery<Ethereum, EtherQuantity> for EthereumQuery {
             is_contract_creation: Some(false),
             transaction_data: Some(Bytes::from(vec![])),
             transaction_data_length: None,
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 }
 
 impl NewHtlcRedeemedQuery<Ethereum, EtherQuantity> for EthereumQuery {
     fn new_htlc_redeemed_query(
-        _htlc_params: &HtlcParams<Ethereum, EtherQuantity>,
+        htlc_params: &HtlcParams<Ethereum, EtherQuantity>,
         htlc_location: &Address,
     ) -> Self {
         EthereumQuery::Transaction {
@@ -49,13 +58,28 @@ impl NewHtlcRedeemedQuery<Ethereum, EtherQuantity> for EthereumQuery {
             is_contract_creation: Some(false),
             transaction_data: None,
             transaction_data_length: Some(Secret::LENGTH),
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
+        }
+    }
+}
+
+impl NewHtlcExpiredQuery<Ethereum> for EthereumQuery {
+    fn new_htlc_expired_query(expiry: &Timestamp) -> Self {
+        EthereumQuery::Block {
+            min_timestamp_secs: Some(expiry.0 as u32),
         }
     }
 }
 
 pub mod erc20 {
     use super::*;
-    use ethereum_support::Erc20Quantity;
+    use ethereum_support::{Erc20Quantity, U256};
+
+    /// keccak256(Transfer(address,address,uint256))
+    const TRANSFER_LOG_MSG: &str =
+        "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
 
     pub fn new_htlc_deployed_query(
         htlc_params: &HtlcParams<Ethereum, Erc20Quantity>,
@@ -66,6 +90,9 @@ pub mod erc20 {
             is_contract_creation: Some(true),
             transaction_data: Some(htlc_params.bytecode()),
             transaction_data_length: None,
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 
@@ -73,32 +100,123 @@ pub mod erc20 {
         htlc_params: &HtlcParams<Ethereum, Erc20Quantity>,
         htlc_location: &Address,
     ) -> EthereumQuery {
-        EthereumQuery::Transaction {
-            from_address: None,
-            to_address: Some(htlc_params.asset.token_contract()),
-            is_contract_creation: None,
-            transaction_data: Some(htlc_params.funding_tx_payload(*htlc_location)),
-            transaction_data_length: None,
+        // Matching the `Transfer` event rather than the `transfer` call finds the
+        // funding no matter which account or contract moved the tokens. The
+        // amount has to match as well, a short transfer doesn't fund the HTLC.
+        EthereumQuery::Log {
+            address: Some(htlc_params.asset.token_contract()),
+            topics: vec![
+                Some(TRANSFER_LOG_MSG.into()),
+                None,
+                Some(address_topic(htlc_location)),
+            ],
+            data: Some(amount_data(htlc_params.asset.quantity())),
+            confirmations_needed: htlc_params.confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 
-    pub fn new_htlc_refunded_query(htlc_location: &Address) -> EthereumQuery {
+    /// The amount of a `Transfer` is its only non-indexed argument, a uint256
+    fn amount_data(quantity: U256) -> Bytes {
+        let mut data = [0u8; 32];
+        quantity.to_big_endian(&mut data);
+        Bytes::from(data.to_vec())
+    }
+
+    /// Indexed address arguments are left-padded to 32 bytes
+    fn address_topic(address: &Address) -> H256 {
+        let mut topic = [0u8; 32];
+        topic[12..].copy_from_slice(address.as_ref());
+        H256::from(topic)
+    }
+
+    pub fn new_htlc_refunded_query(
+        htlc_location: &Address,
+        confirmations_needed: u32,
+    ) -> EthereumQuery {
         EthereumQuery::Transaction {
             from_address: None,
             to_address: Some(*htlc_location),
             is_contract_creation: Some(false),
             transaction_data: Some(Bytes::from(vec![])),
             transaction_data_length: None,
+            confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
         }
     }
 
-    pub fn new_htlc_redeemed_query(htlc_location: &Address) -> EthereumQuery {
+    pub fn new_htlc_redeemed_query(
+        htlc_location: &Address,
+        confirmations_needed: u32,
+    ) -> EthereumQuery {
         EthereumQuery::Transaction {
             from_address: None,
             to_address: Some(*htlc_location),
             is_contract_creation: Some(false),
             transaction_data: None,
             transaction_data_length: Some(Secret::LENGTH),
+            confirmations_needed,
+            from_block_height: None,
+            expires_at_block: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swap_protocols::rfc003::ethereum::Seconds;
+    use ethereum_support::{Erc20Quantity, U256};
+    use spectral::prelude::*;
+
+    fn erc20_htlc_params(quantity: U256) -> HtlcParams<Ethereum, Erc20Quantity> {
+        HtlcParams {
+            asset: Erc20Quantity::new(
+                "b97048628db6b661d4c2aa833e95dbe1a905b280".parse().unwrap(),
+                quantity,
+            ),
+            ledger: Ethereum::regtest(),
+            redeem_identity: "a00f2cac7bad9285ecfd59e8860f5b2d8622e099".parse().unwrap(),
+            refund_identity: "b00f2cac7bad9285ecfd59e8860f5b2d8622e099".parse().unwrap(),
+            lock_duration: Seconds(3600),
+            secret_hash: Secret::from(*b"hello world, you are beautiful!!").into(),
+            confirmations_needed: 1,
         }
     }
+
+    fn data_of(query: EthereumQuery) -> Option<Bytes> {
+        match query {
+            EthereumQuery::Log { data, .. } => data,
+            _ => panic!("expected a log query"),
+        }
+    }
+
+    #[test]
+    fn erc20_funded_query_requires_the_full_amount() {
+        let htlc_location = "c00f2cac7bad9285ecfd59e8860f5b2d8622e099".parse().unwrap();
+
+        let query =
+            erc20::new_htlc_funded_query(&erc20_htlc_params(U256::from(100)), &htlc_location);
+
+        let mut amount = [0u8; 32];
+        amount[31] = 100;
+        assert_that(&data_of(query))
+            .is_some()
+            .is_equal_to(&Bytes::from(amount.to_vec()));
+    }
+
+    #[test]
+    fn short_erc20_transfer_does_not_match_the_funded_query() {
+        let htlc_location = "c00f2cac7bad9285ecfd59e8860f5b2d8622e099".parse().unwrap();
+
+        let full =
+            erc20::new_htlc_funded_query(&erc20_htlc_params(U256::from(100)), &htlc_location);
+        let short =
+            erc20::new_htlc_funded_query(&erc20_htlc_params(U256::from(99)), &htlc_location);
+
+        assert_that(&data_of(full)).is_not_equal_to(data_of(short));
+    }
+
 }
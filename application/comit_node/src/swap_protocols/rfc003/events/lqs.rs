@@ -1,5 +1,8 @@
 use crate::{
-    ledger_query_service::{CreateQuery, EthereumQuery, FirstMatch, Query, QueryIdCache},
+    ledger_query_service::{
+        self, ChainTip, CreateQuery, EthereumQuery, FirstBlock, FirstMatch,
+        LedgerQueryServiceApiClient, Query, QueryId, QueryIdCache,
+    },
     swap_protocols::{
         self,
         asset::Asset,
@@ -8,13 +11,14 @@ use crate::{
             self,
             ethereum::erc20,
             events::{
-                Deployed, Funded, LedgerEvents, NewHtlcFundedQuery, NewHtlcRedeemedQuery,
-                NewHtlcRefundedQuery, RedeemedOrRefunded,
+                Deployed, Expired, Funded, LedgerEvents, NewHtlcExpiredQuery, NewHtlcFundedQuery,
+                NewHtlcRedeemedQuery, NewHtlcRefundedQuery, RedeemedOrRefunded,
             },
             find_htlc_location::FindHtlcLocation,
             secret::SecretHash,
             state_machine::HtlcParams,
-            ExtractSecret, FundTransaction, Ledger, RedeemTransaction, RefundTransaction,
+            Deployment, ExtractSecret, FundTransaction, Ledger, RedeemTransaction,
+            RefundTransaction,
         },
     },
 };
@@ -23,28 +27,106 @@ use futures::{
     future::{self, Either},
     Future,
 };
+use std::{collections::HashSet, sync::Arc};
+use tokio::executor::{DefaultExecutor, Executor};
 
 #[allow(missing_debug_implementations)]
 pub struct LqsEvents<L: Ledger, Q: Query> {
     create_ledger_query: QueryIdCache<L, Q>,
     ledger_first_match: FirstMatch<L>,
+    ledger_first_block: FirstBlock<L>,
+    chain_tip: ChainTip<L>,
+    /// Where the LQS starts looking for the swap's transactions, so a query
+    /// created late still finds those mined before it
+    from_block_height: Option<u64>,
+    lqs_client: Arc<dyn LedgerQueryServiceApiClient<L, Q>>,
+    created_queries: HashSet<Q>,
 
     htlc_deployed_and_funded: Option<Box<Deployed<L>>>,
     htlc_funded: Option<Box<Funded<L>>>,
     htlc_redeemed_or_refunded: Option<Box<RedeemedOrRefunded<L>>>,
+    htlc_expired: Option<Box<Expired>>,
+}
+
+impl<L: Ledger, Q: Query> LqsEvents<L, Q> {
+    /// Deletes every query this swap created. The ids come out of the cache,
+    /// so this does not create them anew.
+    fn delete_created_queries(&mut self) {
+        for query in self.created_queries.drain() {
+            let lqs_client = Arc::clone(&self.lqs_client);
+            let delete = self
+                .create_ledger_query
+                .create_query(query)
+                .and_then(move |query_id| lqs_client.delete(&query_id))
+                .map_err(|e| warn!("Could not delete query from the LQS: {:?}", e));
+
+            // Also runs from `drop`, which may happen after the runtime is gone
+            if let Err(e) = DefaultExecutor::current().spawn(Box::new(delete)) {
+                warn!("Could not delete query from the LQS: {:?}", e);
+            }
+        }
+    }
+}
+
+/// A swap that fails goes to `Error` instead of `Final` and drops its events
+/// without calling `delete_queries`.
+impl<L: Ledger, Q: Query> Drop for LqsEvents<L, Q> {
+    fn drop(&mut self) {
+        self.delete_created_queries()
+    }
 }
 
 impl<L: Ledger, Q: Query> LqsEvents<L, Q>
 where
     L::Transaction: ExtractSecret,
 {
-    pub fn new(create_ledger_query: QueryIdCache<L, Q>, ledger_first_match: FirstMatch<L>) -> Self {
+    pub fn new(
+        create_ledger_query: QueryIdCache<L, Q>,
+        ledger_first_match: FirstMatch<L>,
+        ledger_first_block: FirstBlock<L>,
+        chain_tip: ChainTip<L>,
+        from_block_height: Option<u64>,
+        lqs_client: Arc<dyn LedgerQueryServiceApiClient<L, Q>>,
+    ) -> Self {
         Self {
             create_ledger_query,
             ledger_first_match,
+            ledger_first_block,
+            chain_tip,
+            from_block_height,
+            lqs_client,
+            created_queries: HashSet::new(),
             htlc_deployed_and_funded: None,
             htlc_funded: None,
             htlc_redeemed_or_refunded: None,
+            htlc_expired: None,
+        }
+    }
+
+    fn create_query(
+        &mut self,
+        query: Q,
+    ) -> Box<dyn Future<Item = QueryId<L>, Error = ledger_query_service::Error> + Send> {
+        let query = match self.from_block_height {
+            Some(height) => query.with_from_block_height(height),
+            None => query,
+        };
+        self.created_queries.insert(query.clone());
+        self.create_ledger_query.create_query(query)
+    }
+
+    /// Lets the LQS drop a query about the HTLC on its own once the HTLC is
+    /// long expired, in case this swap never gets to delete it. The expiry
+    /// counts from the latest block, so this is only right for queries about
+    /// an HTLC that is already deployed, i.e. those for its redeem or refund.
+    /// Queries for the deployment are deleted when the swap reaches `Final`
+    /// or its events are dropped.
+    fn expiring(&self, query: Q, lock_duration: &L::LockDuration) -> Q {
+        match self.chain_tip.latest_known() {
+            Some(latest_block) => {
+                query.with_expires_at_block(L::queries_expire_at(latest_block, lock_duration))
+            }
+            None => query,
         }
     }
 
@@ -54,18 +136,23 @@ where
         <L as swap_protocols::Ledger>::Transaction: FindHtlcLocation<L, A>,
     {
         let ledger_first_match = self.ledger_first_match.clone();
-        let query_id = self.create_ledger_query.create_query(query);
+        let query_id = self.create_query(query);
 
         self.htlc_deployed_and_funded.get_or_insert_with(move || {
             let funded_future = query_id
                 .map_err(rfc003::Error::LedgerQueryService)
                 .and_then(move |query_id| {
                     ledger_first_match
-                        .first_match_of(query_id)
-                        .and_then(move |tx| {
+                        .first_match_in_block_of(query_id)
+                        .and_then(move |(tx, mined_in)| {
                             tx.find_htlc_location(&htlc_params)
+                                .map(|location| (location, mined_in))
                                 .map_err(|_| rfc003::Error::InsufficientFunding)
                         })
+                })
+                .map(move |(location, mined_in)| Deployment {
+                    location,
+                    deployed_at: L::BlockTime::from(mined_in),
                 });
 
             Box::new(funded_future)
@@ -79,8 +166,8 @@ where
         secret_hash: SecretHash,
     ) -> &mut RedeemedOrRefunded<L> {
         let ledger_first_match = self.ledger_first_match.clone();
-        let redeemed_query_id = self.create_ledger_query.create_query(redeemed_query);
-        let refunded_query_id = self.create_ledger_query.create_query(refunded_query);
+        let redeemed_query_id = self.create_query(redeemed_query);
+        let refunded_query_id = self.create_query(refunded_query);
 
         self.htlc_redeemed_or_refunded.get_or_insert_with(move || {
             let inner_first_match = ledger_first_match.clone();
@@ -122,13 +209,36 @@ where
             )
         })
     }
+
+    fn htlc_expired(&mut self, query: Q) -> &mut Expired {
+        let ledger_first_block = self.ledger_first_block.clone();
+        let query_id = self.create_query(query);
+
+        self.htlc_expired.get_or_insert_with(move || {
+            let expired_future = query_id
+                .map_err(rfc003::Error::LedgerQueryService)
+                .and_then(move |query_id| {
+                    ledger_first_block.first_block_of(query_id).map_err(|e| {
+                        error!("Stopped waiting for the HTLC to expire because {:?}", e);
+                        rfc003::Error::TimerError
+                    })
+                });
+
+            Box::new(expired_future)
+        })
+    }
 }
+
 impl<L, A, Q> LedgerEvents<L, A> for LqsEvents<L, Q>
 where
     L: Ledger,
     L::Transaction: ExtractSecret,
     A: Asset,
-    Q: Query + NewHtlcRefundedQuery<L, A> + NewHtlcFundedQuery<L, A> + NewHtlcRedeemedQuery<L, A>,
+    Q: Query
+        + NewHtlcRefundedQuery<L, A>
+        + NewHtlcFundedQuery<L, A>
+        + NewHtlcRedeemedQuery<L, A>
+        + NewHtlcExpiredQuery<L>,
     <L as swap_protocols::Ledger>::Transaction: FindHtlcLocation<L, A>,
 {
     fn htlc_deployed(&mut self, htlc_params: HtlcParams<L, A>) -> &mut Deployed<L> {
@@ -150,10 +260,21 @@ where
         htlc_location: &L::HtlcLocation,
     ) -> &mut RedeemedOrRefunded<L> {
         let refunded_query = Q::new_htlc_refunded_query(&htlc_params, htlc_location);
+        let refunded_query = self.expiring(refunded_query, &htlc_params.lock_duration);
         let redeemed_query = Q::new_htlc_redeemed_query(&htlc_params, htlc_location);
+        let redeemed_query = self.expiring(redeemed_query, &htlc_params.lock_duration);
 
         self.htlc_redeemed_or_refunded(redeemed_query, refunded_query, htlc_params.secret_hash)
     }
+
+    fn htlc_expired(&mut self, expiry: L::BlockTime) -> &mut Expired {
+        let query = Q::new_htlc_expired_query(&expiry);
+        self.htlc_expired(query)
+    }
+
+    fn delete_queries(&mut self) {
+        self.delete_created_queries()
+    }
 }
 
 #[allow(missing_debug_implementations)]
@@ -165,14 +286,24 @@ impl LqsEventsForErc20 {
     pub fn new(
         create_ledger_query: QueryIdCache<Ethereum, EthereumQuery>,
         ledger_first_match: FirstMatch<Ethereum>,
+        ledger_first_block: FirstBlock<Ethereum>,
+        chain_tip: ChainTip<Ethereum>,
+        from_block_height: Option<u64>,
+        lqs_client: Arc<dyn LedgerQueryServiceApiClient<Ethereum, EthereumQuery>>,
     ) -> Self {
         Self {
             lqs_events: LqsEvents {
                 create_ledger_query,
                 ledger_first_match,
+                ledger_first_block,
+                chain_tip,
+                from_block_height,
+                lqs_client,
+                created_queries: HashSet::new(),
                 htlc_deployed_and_funded: None,
                 htlc_funded: None,
                 htlc_redeemed_or_refunded: None,
+                htlc_expired: None,
             },
         }
     }
@@ -193,7 +324,7 @@ impl LedgerEvents<Ethereum, Erc20Quantity> for LqsEventsForErc20 {
         htlc_location: &<Ethereum as Ledger>::HtlcLocation,
     ) -> &mut Funded<Ethereum> {
         let query = erc20::new_htlc_funded_query(&htlc_params, htlc_location);
-        let query_id = self.lqs_events.create_ledger_query.create_query(query);
+        let query_id = self.lqs_events.create_query(query);
 
         let ledger_first_match = self.lqs_events.ledger_first_match.clone();
         self.lqs_events.htlc_funded.get_or_insert_with(move || {
@@ -215,8 +346,12 @@ impl LedgerEvents<Ethereum, Erc20Quantity> for LqsEventsForErc20 {
         htlc_params: HtlcParams<Ethereum, Erc20Quantity>,
         htlc_location: &<Ethereum as Ledger>::HtlcLocation,
     ) -> &mut RedeemedOrRefunded<Ethereum> {
-        let refunded_query = erc20::new_htlc_refunded_query(htlc_location);
-        let redeemed_query = erc20::new_htlc_redeemed_query(htlc_location);
+        let confirmations_needed = htlc_params.confirmations_needed;
+        let lock_duration = &htlc_params.lock_duration;
+        let refunded_query = erc20::new_htlc_refunded_query(htlc_location, confirmations_needed);
+        let refunded_query = self.lqs_events.expiring(refunded_query, lock_duration);
+        let redeemed_query = erc20::new_htlc_redeemed_query(htlc_location, confirmations_needed);
+        let redeemed_query = self.lqs_events.expiring(redeemed_query, lock_duration);
 
         self.lqs_events.htlc_redeemed_or_refunded(
             redeemed_query,
@@ -224,4 +359,13 @@ impl LedgerEvents<Ethereum, Erc20Quantity> for LqsEventsForErc20 {
             htlc_params.secret_hash,
         )
     }
+
+    fn htlc_expired(&mut self, expiry: <Ethereum as Ledger>::BlockTime) -> &mut Expired {
+        let query = EthereumQuery::new_htlc_expired_query(&expiry);
+        self.lqs_events.htlc_expired(query)
+    }
+
+    fn delete_queries(&mut self) {
+        self.lqs_events.delete_created_queries()
+    }
 }
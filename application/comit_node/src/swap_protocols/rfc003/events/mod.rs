@@ -11,7 +11,7 @@ use crate::{
             self,
             ledger::Ledger,
             state_machine::{HtlcParams, StateMachineResponse},
-            FundTransaction, RedeemTransaction, RefundTransaction, Role,
+            Deployment, FundTransaction, RedeemTransaction, RefundTransaction, Role,
         },
     },
 };
@@ -33,13 +33,14 @@ pub type ResponseFuture<R: Role> = StateMachineResponseFuture<
     <R::BetaLedger as Ledger>::LockDuration,
 >;
 
-pub type Deployed<L: Ledger> = Future<L::HtlcLocation>;
+pub type Deployed<L: Ledger> = Future<Deployment<L>>;
 pub type Funded<L: Ledger> = Future<Option<FundTransaction<L>>>;
 pub type Refunded<L: Ledger> = Future<L::TxId>;
 pub type Redeemed<L: Ledger> = Future<L::TxId>;
 pub type AlphaRefundedOrBetaFunded<AL: Ledger, BL: Ledger> =
     Future<Either<AL::Transaction, BL::HtlcLocation>>;
 pub type RedeemedOrRefunded<L: Ledger> = Future<Either<RedeemTransaction<L>, RefundTransaction<L>>>;
+pub type Expired = Future<()>;
 
 pub trait LedgerEvents<L: Ledger, A: Asset>: Send {
     fn htlc_deployed(&mut self, htlc_params: HtlcParams<L, A>) -> &mut Deployed<L>;
@@ -55,6 +56,13 @@ pub trait LedgerEvents<L: Ledger, A: Asset>: Send {
         htlc_params: HtlcParams<L, A>,
         htlc_location: &L::HtlcLocation,
     ) -> &mut RedeemedOrRefunded<L>;
+
+    /// Resolves once the ledger has produced a block at or past `expiry`,
+    /// i.e. the HTLC can be refunded from the next block onwards.
+    fn htlc_expired(&mut self, expiry: L::BlockTime) -> &mut Expired;
+
+    /// Called once the swap is over, none of the events will be polled again.
+    fn delete_queries(&mut self) {}
 }
 
 pub trait CommunicationEvents<R: Role>: Send {
@@ -102,3 +110,10 @@ where
         htlc_location: &L::HtlcLocation,
     ) -> Self;
 }
+
+pub trait NewHtlcExpiredQuery<L: Ledger>: Send + Sync
+where
+    Self: Query,
+{
+    fn new_htlc_expired_query(expiry: &L::BlockTime) -> Self;
+}
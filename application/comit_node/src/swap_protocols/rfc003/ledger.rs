@@ -1,9 +1,12 @@
-use crate::swap_protocols::{
-    self,
-    rfc003::secret::{Secret, SecretHash},
+use crate::{
+    ledger_query_service::{LatestBlock, LatestBlocks},
+    swap_protocols::{
+        self,
+        rfc003::secret::{Secret, SecretHash},
+    },
 };
 use serde::{de::DeserializeOwned, Serialize};
-use std::{fmt::Debug, hash::Hash};
+use std::{fmt::Debug, hash::Hash, ops::Add, time::Duration};
 
 pub trait Ledger: swap_protocols::Ledger {
     type LockDuration: PartialEq
@@ -22,7 +25,45 @@ pub trait Ledger: swap_protocols::Ledger {
         + Sync
         + PartialEq
         + Debug
+        + Serialize
+        + DeserializeOwned
         + Into<<Self as swap_protocols::ledger::Ledger>::Identity>;
+    /// The unit in which the ledger measures the HTLC's lock, i.e. adding
+    /// the lock duration to the time of deployment yields the time of expiry.
+    type BlockTime: Copy
+        + Ord
+        + Debug
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned
+        + 'static
+        + From<LatestBlock>
+        + Add<Self::LockDuration, Output = Self::BlockTime>;
+
+    fn latest_block_time(latest_blocks: &LatestBlocks) -> Option<Self::BlockTime>;
+
+    /// Roughly how long it takes the ledger to advance by `lock_duration`.
+    fn estimated_duration(lock_duration: &Self::LockDuration) -> Duration;
+
+    /// Roughly how long it takes until `expiry` is reached, zero if it
+    /// already has been. `None` if we don't know where the ledger is at.
+    fn estimated_time_until(
+        expiry: Self::BlockTime,
+        latest_blocks: &LatestBlocks,
+    ) -> Option<Duration>;
+
+    /// The height at which the LQS can drop the redeem and refund queries
+    /// about an HTLC with `lock_duration` that is deployed by the time of
+    /// `latest_block`. Leaves a margin after the expiry so that late refunds
+    /// are still seen.
+    fn queries_expire_at(latest_block: LatestBlock, lock_duration: &Self::LockDuration) -> u64;
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Deployment<L: Ledger> {
+    pub location: L::HtlcLocation,
+    pub deployed_at: L::BlockTime,
 }
 
 pub trait ExtractSecret {
@@ -1,12 +1,16 @@
 #[macro_use]
 mod transition_save;
+#[macro_use]
+mod swap_types;
 
 pub mod alice;
+pub mod auto_execute;
 pub mod bitcoin;
 pub mod bob;
 pub mod ethereum;
 pub mod events;
 pub mod find_htlc_location;
+pub mod recovery;
 
 pub mod state_machine;
 pub mod state_store;
@@ -15,6 +19,7 @@ mod error;
 
 mod ledger;
 mod role;
+mod safety_margin;
 mod save_state;
 mod secret;
 mod secret_source;
@@ -28,8 +33,11 @@ pub use self::{
     bob::Bob,
     create_ledger_events::CreateLedgerEvents,
     error::Error,
-    ledger::{ExtractSecret, FundTransaction, Ledger, RedeemTransaction, RefundTransaction},
+    ledger::{
+        Deployment, ExtractSecret, FundTransaction, Ledger, RedeemTransaction, RefundTransaction,
+    },
     role::*,
+    safety_margin::{CheckSafety, SafetyMargin, UnsafeAction},
     save_state::SaveState,
     secret::{FromErr, RandomnessSource, Secret, SecretHash},
     secret_source::*,
@@ -0,0 +1,295 @@
+use crate::{
+    comit_client,
+    swap_protocols::{
+        asset::Asset,
+        dependencies::{LedgerEventDependencies, ProtocolDependencies},
+        metadata_store::{self, Metadata, MetadataStore, RoleKind, StartHeights},
+        rfc003::{
+            self,
+            events::{CommunicationEvents, ResponseFuture},
+            state_machine::{Context, Final, Swap, SwapOutcome, SwapStates},
+            state_store::{self, StateStore},
+            Alice, Bob, CreateLedgerEvents, Ledger, Role,
+        },
+        SwapId,
+    },
+};
+use futures::{future, Future};
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to load the states of previous swaps")]
+    Storage(#[cause] state_store::Error),
+    #[fail(display = "Failed to load the metadata of previous swaps")]
+    Metadata(#[cause] metadata_store::Error),
+}
+
+/// Picks up every swap that was still in progress when the node was shut down
+/// and spawns its state machine again, starting from the last saved state.
+/// Swaps that were still waiting for the response to their request are marked
+/// as timed out instead.
+///
+/// Must be called from within a tokio runtime. Returns how many swaps were
+/// resumed.
+pub fn resume_swaps<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client>(
+    dependencies: &ProtocolDependencies<T, S, C>,
+) -> Result<usize, Error> {
+    let mut resumed = 0;
+
+    for (id, metadata) in dependencies.metadata_store.all().map_err(Error::Metadata)? {
+        match resume_swap(dependencies, id, metadata) {
+            Ok(true) => resumed += 1,
+            Ok(false) => {}
+            Err(e) => error!("Failed to resume swap {}: {:?}", id, e),
+        }
+    }
+
+    Ok(resumed)
+}
+
+fn resume_swap<T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client>(
+    dependencies: &ProtocolDependencies<T, S, C>,
+    id: SwapId,
+    metadata: Metadata,
+) -> Result<bool, Error> {
+    with_supported_swap_types!(
+        metadata,
+        (|| resume_with_role::<AL, BL, AA, BA, _, _, _>(dependencies, id, metadata)),
+        {
+            warn!("Cannot resume swap {} with unsupported {:?}", id, metadata);
+            Ok(false)
+        }
+    )
+}
+
+fn resume_with_role<
+    AL: Ledger,
+    BL: Ledger,
+    AA: Asset,
+    BA: Asset,
+    T: MetadataStore<SwapId>,
+    S: StateStore<SwapId>,
+    C: comit_client::Client,
+>(
+    dependencies: &ProtocolDependencies<T, S, C>,
+    id: SwapId,
+    metadata: Metadata,
+) -> Result<bool, Error>
+where
+    LedgerEventDependencies: CreateLedgerEvents<AL, AA> + CreateLedgerEvents<BL, BA>,
+{
+    let start_heights = metadata.start_heights;
+
+    match metadata.role {
+        RoleKind::Alice => {
+            resume::<Alice<AL, BL, AA, BA>, T, S, C>(dependencies, id, start_heights)
+        }
+        RoleKind::Bob => resume::<Bob<AL, BL, AA, BA>, T, S, C>(dependencies, id, start_heights),
+    }
+}
+
+fn resume<R: Role, T: MetadataStore<SwapId>, S: StateStore<SwapId>, C: comit_client::Client>(
+    dependencies: &ProtocolDependencies<T, S, C>,
+    id: SwapId,
+    start_heights: StartHeights,
+) -> Result<bool, Error>
+where
+    LedgerEventDependencies: CreateLedgerEvents<R::AlphaLedger, R::AlphaAsset>
+        + CreateLedgerEvents<R::BetaLedger, R::BetaAsset>,
+{
+    let state = match dependencies
+        .state_store
+        .get::<R>(&id)
+        .map_err(Error::Storage)?
+    {
+        Some(state) => state,
+        None => {
+            warn!("Swap {} has metadata but no state, not resuming it", id);
+            return Ok(false);
+        }
+    };
+
+    let timed_out_state = timed_out(&state);
+
+    if !is_resumable(&state) && timed_out_state.is_none() {
+        trace!("Not resuming swap {} in state {}", id, state.name());
+        return Ok(false);
+    }
+
+    let save_state = match dependencies
+        .state_store
+        .get_save_state::<R>(&id)
+        .map_err(Error::Storage)?
+    {
+        Some(save_state) => save_state,
+        None => return Ok(false),
+    };
+
+    if let Some(timed_out_state) = timed_out_state {
+        info!(
+            "Swap {} never got a response to its request, it timed out",
+            id
+        );
+        if let Err(e) = save_state.save(timed_out_state) {
+            error!("Failed to mark swap {} as timed out: {:?}", id, e);
+        }
+        return Ok(false);
+    }
+
+    info!("Resuming swap {} from state {}", id, state.name());
+
+    let context = Context {
+        alpha_ledger_events: dependencies
+            .ledger_events
+            .create_ledger_events(start_heights),
+        beta_ledger_events: dependencies
+            .ledger_events
+            .create_ledger_events(start_heights),
+        state_repo: save_state,
+        communication_events: Box::new(AlreadyResponded::<R>::default()),
+    };
+
+    tokio::spawn(
+        Swap::start_in(state, context)
+            .map(move |outcome| {
+                info!("Swap {} finished with {:?}", id, outcome);
+            })
+            .map_err(move |e| {
+                error!("Swap {} failed with {:?}", id, e);
+            }),
+    );
+
+    Ok(true)
+}
+
+/// A swap that never got past `Start` depends on a response that was lost
+/// together with the connection to the counterparty, and `Final` and `Error`
+/// swaps have nothing left to watch.
+fn is_resumable<R: Role>(state: &SwapStates<R>) -> bool {
+    match state {
+        SwapStates::Start(_) | SwapStates::Final(_) | SwapStates::Error(_) => false,
+        _ => true,
+    }
+}
+
+/// The state a swap that is still waiting for its response ends up in, so that
+/// it neither stays in `Start` forever nor keeps offering actions.
+fn timed_out<R: Role>(state: &SwapStates<R>) -> Option<SwapStates<R>> {
+    match state {
+        SwapStates::Start(start) => Some(SwapStates::from(Final(SwapOutcome::TimedOut {
+            start: start.clone(),
+        }))),
+        _ => None,
+    }
+}
+
+/// Only `Start` ever waits for the response to the swap request, so a resumed
+/// swap never polls this.
+#[allow(missing_debug_implementations)]
+struct AlreadyResponded<R: Role> {
+    response: Box<ResponseFuture<R>>,
+}
+
+impl<R: Role> Default for AlreadyResponded<R> {
+    fn default() -> Self {
+        AlreadyResponded {
+            response: Box::new(future::err(rfc003::Error::Internal(String::from(
+                "the response to a resumed swap is no longer available",
+            )))),
+        }
+    }
+}
+
+impl<R: Role> CommunicationEvents<R> for AlreadyResponded<R> {
+    fn request_responded(
+        &mut self,
+        _request: &comit_client::rfc003::Request<
+            R::AlphaLedger,
+            R::BetaLedger,
+            R::AlphaAsset,
+            R::BetaAsset,
+        >,
+    ) -> &mut ResponseFuture<R> {
+        &mut self.response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        comit_client::SwapReject,
+        swap_protocols::{
+            ledger::{Bitcoin, Ethereum},
+            rfc003::{
+                ethereum::Seconds,
+                role::test::Alisha,
+                state_machine::{Accepted, OngoingSwap, Start, StateMachineResponse},
+                Secret,
+            },
+        },
+    };
+    use bitcoin_support::{BitcoinQuantity, Blocks, PubkeyHash};
+    use ethereum_support::EtherQuantity;
+    use hex::FromHex;
+
+    fn start_state() -> Start<Alisha> {
+        Start {
+            alpha_ledger_refund_identity: secp256k1_support::KeyPair::from_secret_key_slice(
+                &hex::decode("18e14a7b6a307f426a94f8114701e7c8e774e7f9a47e2c2035db29a206321725")
+                    .unwrap(),
+            )
+            .unwrap(),
+            beta_ledger_redeem_identity: "8457037fcd80a8650c4692d7fcfc1d0a96b92867"
+                .parse()
+                .unwrap(),
+            alpha_ledger: Bitcoin::default(),
+            beta_ledger: Ethereum::regtest(),
+            alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
+            beta_asset: EtherQuantity::from_eth(10.0),
+            alpha_ledger_lock_duration: Blocks::from(144),
+            alpha_ledger_confirmations_needed: 1,
+            beta_ledger_confirmations_needed: 1,
+            secret: Secret::from(*b"hello world, you are beautiful!!"),
+            role: Alisha::default(),
+        }
+    }
+
+    #[test]
+    fn only_swaps_past_start_and_not_finished_are_resumed() {
+        let response = StateMachineResponse {
+            alpha_ledger_redeem_identity: PubkeyHash::from_hex(
+                "d38e554430c4035f2877a579a07a99886153f071",
+            )
+            .unwrap(),
+            beta_ledger_refund_identity: "71b9f69dcabb340a3fe229c3f94f1662ad85e5e8"
+                .parse()
+                .unwrap(),
+            beta_ledger_lock_duration: Seconds(42),
+        };
+
+        let start = SwapStates::from(start_state());
+        let accepted = SwapStates::from(Accepted {
+            swap: OngoingSwap::new(start_state(), response),
+        });
+        let rejected = SwapStates::from(Final(SwapOutcome::Rejected {
+            start: start_state(),
+            rejection_type: SwapReject::Rejected,
+        }));
+
+        assert!(!is_resumable(&start));
+        assert!(is_resumable(&accepted));
+        assert!(!is_resumable(&rejected));
+    }
+
+    #[test]
+    fn swaps_still_in_start_time_out() {
+        let start = SwapStates::from(start_state());
+        let timed_out_start = SwapStates::from(Final(SwapOutcome::TimedOut {
+            start: start_state(),
+        }));
+
+        assert_eq!(timed_out(&start), Some(timed_out_start.clone()));
+        assert_eq!(timed_out(&timed_out_start), None);
+    }
+}
@@ -1,11 +1,15 @@
-use crate::swap_protocols::{
-    self,
-    asset::Asset,
-    rfc003::{ledger::Ledger, SecretHash},
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::{
+        self,
+        asset::Asset,
+        rfc003::{ledger::Ledger, SecretHash},
+    },
 };
+use serde::{de::DeserializeOwned, Serialize};
 use std::fmt::Debug;
 
-pub trait Role: Send + Sync + Debug + Clone + 'static {
+pub trait Role: Send + Sync + Debug + Clone + Serialize + DeserializeOwned + 'static {
     type AlphaLedger: Ledger;
     type BetaLedger: Ledger;
     type AlphaAsset: Asset;
@@ -15,26 +19,41 @@ pub trait Role: Send + Sync + Debug + Clone + 'static {
         + Clone
         + Debug
         + PartialEq
+        + Serialize
+        + DeserializeOwned
         + Into<<Self::AlphaLedger as swap_protocols::Ledger>::Identity>;
     type AlphaRefundHtlcIdentity: Send
         + Sync
         + Clone
         + Debug
         + PartialEq
+        + Serialize
+        + DeserializeOwned
         + Into<<Self::AlphaLedger as swap_protocols::Ledger>::Identity>;
     type BetaRedeemHtlcIdentity: Send
         + Sync
         + Clone
         + Debug
         + PartialEq
+        + Serialize
+        + DeserializeOwned
         + Into<<Self::BetaLedger as swap_protocols::Ledger>::Identity>;
     type BetaRefundHtlcIdentity: Send
         + Sync
         + Clone
         + Debug
         + PartialEq
+        + Serialize
+        + DeserializeOwned
         + Into<<Self::BetaLedger as swap_protocols::Ledger>::Identity>;
-    type Secret: Send + Sync + Clone + Into<SecretHash> + Debug + PartialEq;
+    type Secret: Send
+        + Sync
+        + Clone
+        + Into<SecretHash>
+        + Debug
+        + PartialEq
+        + Serialize
+        + DeserializeOwned;
 }
 
 #[derive(Debug)]
@@ -46,13 +65,17 @@ pub struct Initiation<R: Role> {
     pub alpha_asset: R::AlphaAsset,
     pub beta_asset: R::BetaAsset,
     pub alpha_ledger_lock_duration: <R::AlphaLedger as Ledger>::LockDuration,
+    pub alpha_ledger_confirmations_needed: u32,
+    pub beta_ledger_confirmations_needed: u32,
     pub secret: R::Secret,
 }
 
 pub trait Actions {
     type ActionKind;
 
-    fn actions(&self) -> Vec<Self::ActionKind>;
+    /// Refund actions are only returned once the respective HTLC has expired
+    /// according to `latest_blocks`.
+    fn actions(&self, latest_blocks: &LatestBlocks) -> Vec<Self::ActionKind>;
 }
 
 #[cfg(test)]
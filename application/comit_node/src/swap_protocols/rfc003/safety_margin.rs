@@ -0,0 +1,296 @@
+use crate::{
+    ledger_query_service::LatestBlocks,
+    swap_protocols::rfc003::{alice, bob, state_machine::SwapStates, Actions, Ledger, Role},
+};
+use std::time::Duration;
+
+/// How much time the node wants to have left on the alpha HTLC before
+/// revealing the secret on beta (as Alice) or locking up funds in the beta
+/// HTLC (as Bob). Otherwise Bob might not get to redeem alpha before Alice
+/// can refund it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SafetyMargin {
+    min_time_to_alpha_expiry: Duration,
+}
+
+/// `time_left` is `None` if we cannot tell when the alpha HTLC expires, e.g.
+/// because the LQS hasn't told us about any block yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnsafeAction {
+    pub time_left: Option<Duration>,
+    pub time_needed: Duration,
+}
+
+impl SafetyMargin {
+    pub fn new(min_time_to_alpha_expiry: Duration) -> Self {
+        Self {
+            min_time_to_alpha_expiry,
+        }
+    }
+
+    /// Every action of `state` along with whether it is safe to take it right
+    /// now. The HTTP API and the auto-executor get their actions from here,
+    /// so an unsafe action is withheld no matter who asks for it.
+    pub fn actions<R: Role>(
+        &self,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Vec<(
+        <SwapStates<R> as Actions>::ActionKind,
+        Result<(), UnsafeAction>,
+    )>
+    where
+        SwapStates<R>: Actions,
+        <SwapStates<R> as Actions>::ActionKind: CheckSafety,
+    {
+        state
+            .actions(latest_blocks)
+            .into_iter()
+            .map(|action| {
+                let safety = action.check_safety(self, state, latest_blocks);
+                (action, safety)
+            })
+            .collect()
+    }
+
+    /// Only the actions of `state` that are safe to take right now.
+    pub fn safe_actions<R: Role>(
+        &self,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Vec<<SwapStates<R> as Actions>::ActionKind>
+    where
+        SwapStates<R>: Actions,
+        <SwapStates<R> as Actions>::ActionKind: CheckSafety,
+    {
+        self.actions(state, latest_blocks)
+            .into_iter()
+            .filter_map(|(action, safety)| safety.ok().map(|()| action))
+            .collect()
+    }
+
+    /// As long as we cannot tell when the alpha HTLC expires, e.g. because
+    /// the LQS hasn't told us about any block yet, the action is unsafe.
+    pub fn check_redeem_beta<R: Role>(
+        &self,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Result<(), UnsafeAction> {
+        self.check(state, latest_blocks, self.min_time_to_alpha_expiry)
+    }
+
+    /// Once beta is funded, Alice may redeem it as late as its expiry, after
+    /// which Bob still has to redeem alpha. The beta lock duration therefore
+    /// counts towards the time we need to have left on alpha.
+    pub fn check_fund_beta<R: Role>(
+        &self,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Result<(), UnsafeAction> {
+        let beta_lock = match state.beta_ledger_lock_duration() {
+            Some(beta_lock) => R::BetaLedger::estimated_duration(&beta_lock),
+            None => return Ok(()),
+        };
+
+        self.check(
+            state,
+            latest_blocks,
+            beta_lock + self.min_time_to_alpha_expiry,
+        )
+    }
+
+    fn check<R: Role>(
+        &self,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+        time_needed: Duration,
+    ) -> Result<(), UnsafeAction> {
+        let time_left = state
+            .alpha_htlc_expiry()
+            .and_then(|expiry| R::AlphaLedger::estimated_time_until(expiry, latest_blocks));
+
+        match time_left {
+            Some(time_left) if time_left >= time_needed => Ok(()),
+            time_left => Err(UnsafeAction {
+                time_left,
+                time_needed,
+            }),
+        }
+    }
+}
+
+/// Redeeming beta as Alice and funding beta as Bob are only safe while
+/// there is enough time left on the alpha HTLC.
+pub trait CheckSafety {
+    fn check_safety<R: Role>(
+        &self,
+        safety_margin: &SafetyMargin,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Result<(), UnsafeAction>;
+}
+
+impl<Cancel, Deploy, Fund, Redeem, Refund> CheckSafety
+    for alice::ActionKind<Cancel, Deploy, Fund, Redeem, Refund>
+{
+    fn check_safety<R: Role>(
+        &self,
+        safety_margin: &SafetyMargin,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Result<(), UnsafeAction> {
+        match self {
+            alice::ActionKind::Redeem(_) => safety_margin.check_redeem_beta(state, latest_blocks),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<Accept, Decline, Deploy, Fund, Redeem, Refund> CheckSafety
+    for bob::ActionKind<Accept, Decline, Deploy, Fund, Redeem, Refund>
+{
+    fn check_safety<R: Role>(
+        &self,
+        safety_margin: &SafetyMargin,
+        state: &SwapStates<R>,
+        latest_blocks: &LatestBlocks,
+    ) -> Result<(), UnsafeAction> {
+        match self {
+            bob::ActionKind::Deploy(_) | bob::ActionKind::Fund(_) => {
+                safety_margin.check_fund_beta(state, latest_blocks)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ledger_query_service::LatestBlock,
+        swap_protocols::{
+            ledger::{Bitcoin, Ethereum},
+            rfc003::{
+                bitcoin::BlockHeight, ethereum::Seconds, role::test::Bobisha, state_machine::*,
+                Secret,
+            },
+        },
+    };
+    use bitcoin_support::{BitcoinQuantity, Blocks, OutPoint, Sha256dHash};
+    use ethereum_support::EtherQuantity;
+    use hex::FromHex;
+    use std::str::FromStr;
+
+    fn alpha_funded(beta_ledger_lock_duration: Seconds) -> SwapStates<Bobisha> {
+        let (bobisha, _) = Bobisha::create();
+        let start = Start {
+            alpha_ledger_refund_identity: bitcoin_support::PubkeyHash::from_hex(
+                "875638cac0b0ae9f826575e190f2788918c354c2",
+            )
+            .unwrap(),
+            beta_ledger_redeem_identity: ethereum_support::Address::from_str(
+                "8457037fcd80a8650c4692d7fcfc1d0a96b92867",
+            )
+            .unwrap(),
+            alpha_ledger: Bitcoin::regtest(),
+            beta_ledger: Ethereum::regtest(),
+            alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
+            beta_asset: EtherQuantity::from_eth(10.0),
+            alpha_ledger_lock_duration: Blocks::from(144),
+            alpha_ledger_confirmations_needed: 1,
+            beta_ledger_confirmations_needed: 1,
+            secret: Secret::from(*b"hello world, you are beautiful!!").hash(),
+            role: bobisha,
+        };
+        let response = StateMachineResponse {
+            alpha_ledger_redeem_identity: secp256k1_support::KeyPair::from_secret_key_slice(
+                &hex::decode("18e14a7b6a307f426a94f8114701e7c8e774e7f9a47e2c2035db29a206321725")
+                    .unwrap(),
+            )
+            .unwrap(),
+            beta_ledger_refund_identity: ethereum_support::Address::from_str(
+                "8457037fcd80a8650c4692d7fcfc1d0a96b92867",
+            )
+            .unwrap(),
+            beta_ledger_lock_duration,
+        };
+
+        SwapStates::from(AlphaFunded {
+            swap: OngoingSwap::new(start, response.into()),
+            alpha_htlc_location: OutPoint {
+                txid: Sha256dHash::from_data(b"funding"),
+                vout: 0,
+            },
+            alpha_htlc_deployed_at: BlockHeight(100),
+            alpha_htlc_expired: false,
+        })
+    }
+
+    fn at_bitcoin_height(height: u64) -> LatestBlocks {
+        LatestBlocks {
+            bitcoin: Some(LatestBlock {
+                height,
+                timestamp: 0,
+            }),
+            ethereum: None,
+        }
+    }
+
+    #[test]
+    fn fund_beta_is_unsafe_if_beta_lock_eats_up_the_margin() {
+        let margin = SafetyMargin::new(Duration::from_secs(3600));
+        // alpha expires at 244, i.e. 24 blocks or roughly four hours from now
+        let latest_blocks = at_bitcoin_height(220);
+
+        assert_eq!(
+            margin.check_fund_beta(&alpha_funded(Seconds(3600)), &latest_blocks),
+            Ok(())
+        );
+        assert_eq!(
+            margin.check_fund_beta(&alpha_funded(Seconds(4 * 3600)), &latest_blocks),
+            Err(UnsafeAction {
+                time_left: Some(Duration::from_secs(4 * 3600)),
+                time_needed: Duration::from_secs(5 * 3600),
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_latest_block_makes_the_action_unsafe() {
+        let margin = SafetyMargin::new(Duration::from_secs(3600));
+
+        assert_eq!(
+            margin.check_redeem_beta(&alpha_funded(Seconds(3600)), &LatestBlocks::default()),
+            Err(UnsafeAction {
+                time_left: None,
+                time_needed: Duration::from_secs(3600),
+            })
+        );
+    }
+
+    #[test]
+    fn unsafe_fund_is_withheld_from_the_safe_actions() {
+        let margin = SafetyMargin::new(Duration::from_secs(3600));
+        let state = alpha_funded(Seconds(4 * 3600));
+
+        let is_fund = |action: &bob::ActionKind<_, _, _, _, _, _>| match action {
+            bob::ActionKind::Fund(_) => true,
+            _ => false,
+        };
+
+        assert!(margin
+            .safe_actions(&state, &at_bitcoin_height(100))
+            .iter()
+            .any(is_fund));
+        assert!(!margin
+            .safe_actions(&state, &at_bitcoin_height(220))
+            .iter()
+            .any(is_fund));
+        assert!(!margin
+            .safe_actions(&state, &LatestBlocks::default())
+            .iter()
+            .any(is_fund));
+    }
+
+}
@@ -1,21 +1,25 @@
-use crate::swap_protocols::rfc003::{state_machine::SwapStates, Role};
+use crate::swap_protocols::rfc003::{self, state_machine::SwapStates, Role};
 use futures::sync::mpsc;
 use std::sync::RwLock;
 
 pub trait SaveState<R: Role>: Send + Sync {
-    fn save(&self, state: SwapStates<R>);
+    /// A swap whose state could not be saved must not act on it, as it would
+    /// be resumed from an earlier state after a restart.
+    fn save(&self, state: SwapStates<R>) -> Result<(), rfc003::Error>;
 }
 
 impl<R: Role + Sync> SaveState<R> for RwLock<Option<SwapStates<R>>> {
-    fn save(&self, state: SwapStates<R>) {
+    fn save(&self, state: SwapStates<R>) -> Result<(), rfc003::Error> {
         let _self = &mut *self.write().unwrap();
         *_self = Some(state);
+        Ok(())
     }
 }
 
 impl<R: Role> SaveState<R> for mpsc::UnboundedSender<SwapStates<R>> {
-    fn save(&self, state: SwapStates<R>) {
+    fn save(&self, state: SwapStates<R>) -> Result<(), rfc003::Error> {
         // ignore error the subscriber is no longer interested in state updates
         let _ = self.unbounded_send(state);
+        Ok(())
     }
 }
@@ -1,7 +1,8 @@
 #![allow(clippy::too_many_arguments)] // TODO: Figure out how to properly place this on the state_machine_future derive so that is is forwarded to the generated structs and impl
 
 use crate::{
-    comit_client::{self, SwapReject},
+    comit_client::{self, SwapReject, SwapResponseError},
+    ledger_query_service::LatestBlocks,
     swap_protocols::{
         asset::Asset,
         rfc003::{self, events, ledger::Ledger, RedeemTransaction, Role, SaveState, SecretHash},
@@ -38,9 +39,13 @@ pub struct HtlcParams<L: Ledger, A: Asset> {
     pub refund_identity: L::Identity,
     pub lock_duration: L::LockDuration,
     pub secret_hash: SecretHash,
+    /// How deep the transactions of this HTLC have to be buried before the
+    /// swap acts on them
+    pub confirmations_needed: u32,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct OngoingSwap<R: Role> {
     pub alpha_ledger: R::AlphaLedger,
     pub beta_ledger: R::BetaLedger,
@@ -52,6 +57,8 @@ pub struct OngoingSwap<R: Role> {
     pub beta_ledger_refund_identity: R::BetaRefundHtlcIdentity,
     pub alpha_ledger_lock_duration: <R::AlphaLedger as Ledger>::LockDuration,
     pub beta_ledger_lock_duration: <R::BetaLedger as Ledger>::LockDuration,
+    pub alpha_ledger_confirmations_needed: u32,
+    pub beta_ledger_confirmations_needed: u32,
     pub secret: R::Secret,
     pub role: R,
 }
@@ -76,6 +83,8 @@ impl<R: Role> OngoingSwap<R> {
             beta_ledger_refund_identity: response.beta_ledger_refund_identity,
             alpha_ledger_lock_duration: start.alpha_ledger_lock_duration,
             beta_ledger_lock_duration: response.beta_ledger_lock_duration,
+            alpha_ledger_confirmations_needed: start.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: start.beta_ledger_confirmations_needed,
             secret: start.secret,
             role: start.role,
         }
@@ -89,6 +98,7 @@ impl<R: Role> OngoingSwap<R> {
             refund_identity: self.alpha_ledger_refund_identity.clone().into(),
             lock_duration: self.alpha_ledger_lock_duration.clone(),
             secret_hash: self.secret.clone().into(),
+            confirmations_needed: self.alpha_ledger_confirmations_needed,
         }
     }
 
@@ -100,16 +110,62 @@ impl<R: Role> OngoingSwap<R> {
             refund_identity: self.beta_ledger_refund_identity.clone().into(),
             lock_duration: self.beta_ledger_lock_duration.clone(),
             secret_hash: self.secret.clone().into(),
+            confirmations_needed: self.beta_ledger_confirmations_needed,
         }
     }
+
+    pub fn alpha_htlc_expiry(
+        &self,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+    ) -> <R::AlphaLedger as Ledger>::BlockTime {
+        alpha_htlc_deployed_at + self.alpha_ledger_lock_duration.clone()
+    }
+
+    pub fn beta_htlc_expiry(
+        &self,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
+    ) -> <R::BetaLedger as Ledger>::BlockTime {
+        beta_htlc_deployed_at + self.beta_ledger_lock_duration.clone()
+    }
+
+    /// Whether the alpha HTLC can be refunded in the next block. Without a
+    /// known latest block we cannot tell and assume it can't.
+    pub fn alpha_htlc_expired(
+        &self,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        latest_blocks: &LatestBlocks,
+    ) -> bool {
+        R::AlphaLedger::latest_block_time(latest_blocks)
+            .map(|now| now >= self.alpha_htlc_expiry(alpha_htlc_deployed_at))
+            .unwrap_or(false)
+    }
+
+    /// Whether the beta HTLC can be refunded in the next block. Without a
+    /// known latest block we cannot tell and assume it can't.
+    pub fn beta_htlc_expired(
+        &self,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
+        latest_blocks: &LatestBlocks,
+    ) -> bool {
+        R::BetaLedger::latest_block_time(latest_blocks)
+            .map(|now| now >= self.beta_htlc_expiry(beta_htlc_deployed_at))
+            .unwrap_or(false)
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub enum SwapOutcome<R: Role> {
     Rejected {
         start: Start<R>,
         rejection_type: SwapReject,
     },
+    TimedOut {
+        start: Start<R>,
+    },
+    Canceled {
+        start: Start<R>,
+    },
     AlphaRefunded {
         swap: OngoingSwap<R>,
     },
@@ -140,7 +196,10 @@ pub struct Context<R: Role> {
 }
 
 #[derive(StateMachineFuture)]
-#[state_machine_future(context = "Context", derive(Clone, Debug, PartialEq))]
+#[state_machine_future(
+    context = "Context",
+    derive(Clone, Debug, PartialEq, Serialize, Deserialize)
+)]
 #[allow(missing_debug_implementations)]
 pub enum Swap<R: Role> {
     #[state_machine_future(start, transitions(Accepted, Final))]
@@ -152,6 +211,8 @@ pub enum Swap<R: Role> {
         alpha_asset: R::AlphaAsset,
         beta_asset: R::BetaAsset,
         alpha_ledger_lock_duration: <R::AlphaLedger as Ledger>::LockDuration,
+        alpha_ledger_confirmations_needed: u32,
+        beta_ledger_confirmations_needed: u32,
         secret: R::Secret,
         role: R,
     },
@@ -163,19 +224,25 @@ pub enum Swap<R: Role> {
     AlphaDeployed {
         swap: OngoingSwap<R>,
         alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
     },
 
-    #[state_machine_future(transitions(AlphaFundedBetaDeployed, Final))]
+    #[state_machine_future(transitions(AlphaFunded, AlphaFundedBetaDeployed, Final))]
     AlphaFunded {
         swap: OngoingSwap<R>,
         alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        alpha_htlc_expired: bool,
     },
 
-    #[state_machine_future(transitions(BothFunded, Final))]
+    #[state_machine_future(transitions(AlphaFundedBetaDeployed, BothFunded, AlphaExpired, Final))]
     AlphaFundedBetaDeployed {
         swap: OngoingSwap<R>,
         alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        alpha_htlc_expired: bool,
         beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
     },
 
     #[state_machine_future(transitions(
@@ -183,36 +250,90 @@ pub enum Swap<R: Role> {
         AlphaFundedBetaRefunded,
         AlphaRefundedBetaFunded,
         AlphaRedeemedBetaFunded,
+        AlphaExpired,
+        BetaExpired,
     ))]
     BothFunded {
         swap: OngoingSwap<R>,
         alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
         beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
     },
 
-    #[state_machine_future(transitions(Final))]
+    #[state_machine_future(transitions(
+        AlphaFundedBetaRedeemed,
+        AlphaFundedBetaRefunded,
+        AlphaRefundedBetaFunded,
+        AlphaRedeemedBetaFunded,
+        BothExpired,
+    ))]
+    AlphaExpired {
+        swap: OngoingSwap<R>,
+        alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
+    },
+
+    #[state_machine_future(transitions(
+        AlphaFundedBetaRedeemed,
+        AlphaFundedBetaRefunded,
+        AlphaRefundedBetaFunded,
+        AlphaRedeemedBetaFunded,
+        BothExpired,
+    ))]
+    BetaExpired {
+        swap: OngoingSwap<R>,
+        alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
+    },
+
+    #[state_machine_future(transitions(
+        AlphaFundedBetaRedeemed,
+        AlphaFundedBetaRefunded,
+        AlphaRefundedBetaFunded,
+        AlphaRedeemedBetaFunded,
+    ))]
+    BothExpired {
+        swap: OngoingSwap<R>,
+        alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
+    },
+
+    #[state_machine_future(transitions(AlphaFundedBetaRefunded, Final))]
     AlphaFundedBetaRefunded {
         swap: OngoingSwap<R>,
         alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        alpha_htlc_expired: bool,
     },
 
     #[state_machine_future(transitions(Final))]
     AlphaRefundedBetaFunded {
         swap: OngoingSwap<R>,
         beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
     },
 
     #[state_machine_future(transitions(Final))]
     AlphaRedeemedBetaFunded {
         swap: OngoingSwap<R>,
         beta_htlc_location: <R::BetaLedger as Ledger>::HtlcLocation,
+        beta_htlc_deployed_at: <R::BetaLedger as Ledger>::BlockTime,
     },
 
-    #[state_machine_future(transitions(Final))]
+    #[state_machine_future(transitions(AlphaFundedBetaRedeemed, Final))]
     AlphaFundedBetaRedeemed {
         swap: OngoingSwap<R>,
         beta_redeemed_tx: RedeemTransaction<R::BetaLedger>,
         alpha_htlc_location: <R::AlphaLedger as Ledger>::HtlcLocation,
+        alpha_htlc_deployed_at: <R::AlphaLedger as Ledger>::BlockTime,
+        alpha_htlc_expired: bool,
     },
 
     #[state_machine_future(ready)]
@@ -222,6 +343,103 @@ pub enum Swap<R: Role> {
     Error(rfc003::Error),
 }
 
+/// Transitions out of a state in which both HTLCs are funded as soon as
+/// either of them has been redeemed or refunded. Whether the HTLCs have
+/// expired in the meantime makes no difference to where we go from there,
+/// only whether the alpha HTLC expired is carried over.
+macro_rules! transition_if_settled {
+    ($state:ident, $context:ident, $alpha_htlc_expired:expr) => {
+        if let Async::Ready(redeemed_or_refunded) = $context
+            .beta_ledger_events
+            .htlc_redeemed_or_refunded($state.swap.beta_htlc_params(), &$state.beta_htlc_location)
+            .poll()?
+        {
+            let state = $state.take();
+            match redeemed_or_refunded {
+                Either::A(beta_redeemed_tx) => transition_save!(
+                    $context.state_repo,
+                    AlphaFundedBetaRedeemed {
+                        swap: state.swap,
+                        beta_redeemed_tx,
+                        alpha_htlc_location: state.alpha_htlc_location,
+                        alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                        alpha_htlc_expired: $alpha_htlc_expired,
+                    }
+                ),
+                Either::B(_beta_refunded_txid) => transition_save!(
+                    $context.state_repo,
+                    AlphaFundedBetaRefunded {
+                        swap: state.swap,
+                        alpha_htlc_location: state.alpha_htlc_location,
+                        alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                        alpha_htlc_expired: $alpha_htlc_expired,
+                    }
+                ),
+            }
+        }
+
+        if let Async::Ready(redeemed_or_refunded) = $context
+            .alpha_ledger_events
+            .htlc_redeemed_or_refunded($state.swap.alpha_htlc_params(), &$state.alpha_htlc_location)
+            .poll()?
+        {
+            let state = $state.take();
+            match redeemed_or_refunded {
+                Either::A(_alpha_redeemed_tx) => transition_save!(
+                    $context.state_repo,
+                    AlphaRedeemedBetaFunded {
+                        swap: state.swap,
+                        beta_htlc_location: state.beta_htlc_location,
+                        beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                    }
+                ),
+                Either::B(_alpha_refunded_txid) => transition_save!(
+                    $context.state_repo,
+                    AlphaRefundedBetaFunded {
+                        swap: state.swap,
+                        beta_htlc_location: state.beta_htlc_location,
+                        beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                    }
+                ),
+            }
+        }
+    };
+}
+
+/// Records the expiry of the alpha HTLC in states that keep waiting for the
+/// HTLC to be redeemed or refunded. Once recorded, the expiry is not polled
+/// again.
+macro_rules! record_alpha_htlc_expiry {
+    ($state:ident, $context:ident, $next_state:ident) => {
+        if !$state.alpha_htlc_expired {
+            if let Async::Ready(()) = $context
+                .alpha_ledger_events
+                .htlc_expired($state.swap.alpha_htlc_expiry($state.alpha_htlc_deployed_at))
+                .poll()?
+            {
+                let state = $state.take();
+                transition_save!(
+                    $context.state_repo,
+                    $next_state {
+                        alpha_htlc_expired: true,
+                        ..state
+                    }
+                )
+            }
+        }
+    };
+}
+
+/// Transitions to `Final`. Nothing is going to wait for the ledger events of
+/// the swap anymore, so the queries behind them are deleted.
+macro_rules! transition_to_final {
+    ($context:ident, $outcome:expr) => {{
+        $context.alpha_ledger_events.delete_queries();
+        $context.beta_ledger_events.delete_queries();
+        transition_save!($context.state_repo, Final($outcome))
+    }};
+}
+
 impl<R: Role> PollSwap<R> for Swap<R> {
     fn poll_start<'s, 'c>(
         state: &'s mut RentToOwn<'s, Start<R>>,
@@ -235,13 +453,34 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             alpha_ledger_refund_identity: state.alpha_ledger_refund_identity.clone().into(),
             beta_ledger_redeem_identity: state.beta_ledger_redeem_identity.clone().into(),
             alpha_ledger_lock_duration: state.alpha_ledger_lock_duration.clone(),
+            alpha_ledger_confirmations_needed: state.alpha_ledger_confirmations_needed,
+            beta_ledger_confirmations_needed: state.beta_ledger_confirmations_needed,
             secret_hash: state.secret.clone().into(),
         };
 
-        let response = try_ready!(context
+        // Without a response there is nothing to wait for on the ledgers, so
+        // a request that was not answered ends the swap like a rejection
+        let response = match context
             .communication_events
             .request_responded(&request)
-            .poll());
+            .poll()
+        {
+            Ok(Async::Ready(response)) => response,
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Err(rfc003::Error::SwapResponse(SwapResponseError::Timeout)) => transition_to_final!(
+                context,
+                SwapOutcome::TimedOut {
+                    start: state.take()
+                }
+            ),
+            Err(rfc003::Error::SwapResponse(SwapResponseError::Canceled)) => transition_to_final!(
+                context,
+                SwapOutcome::Canceled {
+                    start: state.take()
+                }
+            ),
+            Err(e) => return Err(e),
+        };
 
         let state = state.take();
 
@@ -252,12 +491,12 @@ impl<R: Role> PollSwap<R> for Swap<R> {
                     swap: OngoingSwap::new(state, swap_accepted),
                 }
             ),
-            Err(rejection_type) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::Rejected {
+            Err(rejection_type) => transition_to_final!(
+                context,
+                SwapOutcome::Rejected {
                     start: state,
                     rejection_type
-                })
+                }
             ),
         }
     }
@@ -266,7 +505,7 @@ impl<R: Role> PollSwap<R> for Swap<R> {
         state: &'s mut RentToOwn<'s, Accepted<R>>,
         context: &'c mut RentToOwn<'c, Context<R>>,
     ) -> Result<Async<AfterAccepted<R>>, rfc003::Error> {
-        let alpha_htlc_location = try_ready!(context
+        let alpha_deployment = try_ready!(context
             .alpha_ledger_events
             .htlc_deployed(state.swap.alpha_htlc_params())
             .poll());
@@ -275,7 +514,8 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             context.state_repo,
             AlphaDeployed {
                 swap: state.swap,
-                alpha_htlc_location,
+                alpha_htlc_location: alpha_deployment.location,
+                alpha_htlc_deployed_at: alpha_deployment.deployed_at,
             }
         )
     }
@@ -294,6 +534,8 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             AlphaFunded {
                 swap: state.swap,
                 alpha_htlc_location: state.alpha_htlc_location,
+                alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                alpha_htlc_expired: false,
             }
         )
     }
@@ -307,15 +549,17 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             .htlc_redeemed_or_refunded(state.swap.alpha_htlc_params(), &state.alpha_htlc_location)
             .poll()?
         {
-            transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::AlphaRefunded {
+            transition_to_final!(
+                context,
+                SwapOutcome::AlphaRefunded {
                     swap: state.take().swap
-                })
+                }
             )
         }
 
-        let beta_htlc_location = try_ready!(context
+        record_alpha_htlc_expiry!(state, context, AlphaFunded);
+
+        let beta_deployment = try_ready!(context
             .beta_ledger_events
             .htlc_deployed(state.swap.beta_htlc_params())
             .poll());
@@ -325,7 +569,10 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             AlphaFundedBetaDeployed {
                 swap: state.swap,
                 alpha_htlc_location: state.alpha_htlc_location,
-                beta_htlc_location,
+                alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                alpha_htlc_expired: state.alpha_htlc_expired,
+                beta_htlc_location: beta_deployment.location,
+                beta_htlc_deployed_at: beta_deployment.deployed_at,
             }
         )
     }
@@ -339,25 +586,41 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             .htlc_redeemed_or_refunded(state.swap.alpha_htlc_params(), &state.alpha_htlc_location)
             .poll()?
         {
-            transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::AlphaRefunded {
+            transition_to_final!(
+                context,
+                SwapOutcome::AlphaRefunded {
                     swap: state.take().swap
-                })
+                }
             )
         }
 
+        record_alpha_htlc_expiry!(state, context, AlphaFundedBetaDeployed);
+
         let _ = try_ready!(context
             .beta_ledger_events
             .htlc_funded(state.swap.beta_htlc_params(), &state.beta_htlc_location)
             .poll());
         let state = state.take();
+        if state.alpha_htlc_expired {
+            transition_save!(
+                context.state_repo,
+                AlphaExpired {
+                    swap: state.swap,
+                    alpha_htlc_location: state.alpha_htlc_location,
+                    alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                    beta_htlc_location: state.beta_htlc_location,
+                    beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                }
+            )
+        }
         transition_save!(
             context.state_repo,
             BothFunded {
                 swap: state.swap,
                 alpha_htlc_location: state.alpha_htlc_location,
+                alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
                 beta_htlc_location: state.beta_htlc_location,
+                beta_htlc_deployed_at: state.beta_htlc_deployed_at,
             }
         )
     }
@@ -366,81 +629,138 @@ impl<R: Role> PollSwap<R> for Swap<R> {
         state: &'s mut RentToOwn<'s, BothFunded<R>>,
         context: &'c mut RentToOwn<'c, Context<R>>,
     ) -> Result<Async<AfterBothFunded<R>>, rfc003::Error> {
-        if let Async::Ready(redeemed_or_refunded) = context
+        transition_if_settled!(state, context, false);
+
+        if let Async::Ready(()) = context
             .beta_ledger_events
-            .htlc_redeemed_or_refunded(state.swap.beta_htlc_params(), &state.beta_htlc_location)
+            .htlc_expired(state.swap.beta_htlc_expiry(state.beta_htlc_deployed_at))
             .poll()?
         {
             let state = state.take();
-            match redeemed_or_refunded {
-                Either::A(beta_redeemed_tx) => transition_save!(
-                    context.state_repo,
-                    AlphaFundedBetaRedeemed {
-                        swap: state.swap,
-                        beta_redeemed_tx,
-                        alpha_htlc_location: state.alpha_htlc_location,
-                    }
-                ),
-                Either::B(_beta_refunded_txid) => transition_save!(
-                    context.state_repo,
-                    AlphaFundedBetaRefunded {
-                        swap: state.swap,
-                        alpha_htlc_location: state.alpha_htlc_location,
-                    }
-                ),
-            }
+            transition_save!(
+                context.state_repo,
+                BetaExpired {
+                    swap: state.swap,
+                    alpha_htlc_location: state.alpha_htlc_location,
+                    alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                    beta_htlc_location: state.beta_htlc_location,
+                    beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                }
+            )
         }
 
-        match try_ready!(context
+        if let Async::Ready(()) = context
             .alpha_ledger_events
-            .htlc_redeemed_or_refunded(state.swap.alpha_htlc_params(), &state.alpha_htlc_location)
-            .poll())
+            .htlc_expired(state.swap.alpha_htlc_expiry(state.alpha_htlc_deployed_at))
+            .poll()?
         {
-            Either::A(_alpha_redeemed_tx) => {
-                let state = state.take();
-                transition_save!(
-                    context.state_repo,
-                    AlphaRedeemedBetaFunded {
-                        swap: state.swap,
-                        beta_htlc_location: state.beta_htlc_location,
-                    }
-                )
-            }
-            Either::B(_alpha_refunded_txid) => {
-                let state = state.take();
-                transition_save!(
-                    context.state_repo,
-                    AlphaRefundedBetaFunded {
-                        swap: state.swap,
-                        beta_htlc_location: state.beta_htlc_location,
-                    }
-                )
-            }
+            let state = state.take();
+            transition_save!(
+                context.state_repo,
+                AlphaExpired {
+                    swap: state.swap,
+                    alpha_htlc_location: state.alpha_htlc_location,
+                    alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                    beta_htlc_location: state.beta_htlc_location,
+                    beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                }
+            )
         }
+
+        Ok(Async::NotReady)
+    }
+
+    fn poll_alpha_expired<'s, 'c>(
+        state: &'s mut RentToOwn<'s, AlphaExpired<R>>,
+        context: &'c mut RentToOwn<'c, Context<R>>,
+    ) -> Result<Async<AfterAlphaExpired<R>>, rfc003::Error> {
+        transition_if_settled!(state, context, true);
+
+        if let Async::Ready(()) = context
+            .beta_ledger_events
+            .htlc_expired(state.swap.beta_htlc_expiry(state.beta_htlc_deployed_at))
+            .poll()?
+        {
+            let state = state.take();
+            transition_save!(
+                context.state_repo,
+                BothExpired {
+                    swap: state.swap,
+                    alpha_htlc_location: state.alpha_htlc_location,
+                    alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                    beta_htlc_location: state.beta_htlc_location,
+                    beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                }
+            )
+        }
+
+        Ok(Async::NotReady)
+    }
+
+    fn poll_beta_expired<'s, 'c>(
+        state: &'s mut RentToOwn<'s, BetaExpired<R>>,
+        context: &'c mut RentToOwn<'c, Context<R>>,
+    ) -> Result<Async<AfterBetaExpired<R>>, rfc003::Error> {
+        transition_if_settled!(state, context, false);
+
+        if let Async::Ready(()) = context
+            .alpha_ledger_events
+            .htlc_expired(state.swap.alpha_htlc_expiry(state.alpha_htlc_deployed_at))
+            .poll()?
+        {
+            let state = state.take();
+            transition_save!(
+                context.state_repo,
+                BothExpired {
+                    swap: state.swap,
+                    alpha_htlc_location: state.alpha_htlc_location,
+                    alpha_htlc_deployed_at: state.alpha_htlc_deployed_at,
+                    beta_htlc_location: state.beta_htlc_location,
+                    beta_htlc_deployed_at: state.beta_htlc_deployed_at,
+                }
+            )
+        }
+
+        Ok(Async::NotReady)
+    }
+
+    fn poll_both_expired<'s, 'c>(
+        state: &'s mut RentToOwn<'s, BothExpired<R>>,
+        context: &'c mut RentToOwn<'c, Context<R>>,
+    ) -> Result<Async<AfterBothExpired<R>>, rfc003::Error> {
+        transition_if_settled!(state, context, true);
+
+        Ok(Async::NotReady)
     }
 
     fn poll_alpha_funded_beta_refunded<'s, 'c>(
         state: &'s mut RentToOwn<'s, AlphaFundedBetaRefunded<R>>,
         context: &'c mut RentToOwn<'c, Context<R>>,
     ) -> Result<Async<AfterAlphaFundedBetaRefunded<R>>, rfc003::Error> {
-        match try_ready!(context
+        if let Async::Ready(alpha_redeemed_or_refunded) = context
             .alpha_ledger_events
             .htlc_redeemed_or_refunded(state.swap.alpha_htlc_params(), &state.alpha_htlc_location)
-            .poll())
+            .poll()?
         {
-            Either::A(_alpha_redeemed_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::AlphaRedeemedBetaRefunded {
-                    swap: state.take().swap
-                })
-            ),
-            Either::B(_alpha_refunded_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::BothRefunded {
-                    swap: state.take().swap
-                })
-            ),
+            match alpha_redeemed_or_refunded {
+                Either::A(_alpha_redeemed_txid) => transition_to_final!(
+                    context,
+                    SwapOutcome::AlphaRedeemedBetaRefunded {
+                        swap: state.take().swap
+                    }
+                ),
+                Either::B(_alpha_refunded_txid) => transition_to_final!(
+                    context,
+                    SwapOutcome::BothRefunded {
+                        swap: state.take().swap
+                    }
+                ),
+            }
         }
+
+        record_alpha_htlc_expiry!(state, context, AlphaFundedBetaRefunded);
+
+        Ok(Async::NotReady)
     }
 
     fn poll_alpha_refunded_beta_funded<'s, 'c>(
@@ -452,17 +772,17 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             .htlc_redeemed_or_refunded(state.swap.beta_htlc_params(), &state.beta_htlc_location)
             .poll())
         {
-            Either::A(_beta_redeemed_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::AlphaRefundedBetaRedeemed {
+            Either::A(_beta_redeemed_txid) => transition_to_final!(
+                context,
+                SwapOutcome::AlphaRefundedBetaRedeemed {
                     swap: state.take().swap
-                })
+                }
             ),
-            Either::B(_beta_refunded_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::BothRefunded {
+            Either::B(_beta_refunded_txid) => transition_to_final!(
+                context,
+                SwapOutcome::BothRefunded {
                     swap: state.take().swap
-                })
+                }
             ),
         }
     }
@@ -476,17 +796,17 @@ impl<R: Role> PollSwap<R> for Swap<R> {
             .htlc_redeemed_or_refunded(state.swap.beta_htlc_params(), &state.beta_htlc_location)
             .poll())
         {
-            Either::A(_beta_redeemed_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::BothRedeemed {
+            Either::A(_beta_redeemed_txid) => transition_to_final!(
+                context,
+                SwapOutcome::BothRedeemed {
                     swap: state.take().swap
-                })
+                }
             ),
-            Either::B(_beta_refunded_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::AlphaRedeemedBetaRefunded {
+            Either::B(_beta_refunded_txid) => transition_to_final!(
+                context,
+                SwapOutcome::AlphaRedeemedBetaRefunded {
                     swap: state.take().swap
-                })
+                }
             ),
         }
     }
@@ -495,24 +815,30 @@ impl<R: Role> PollSwap<R> for Swap<R> {
         state: &'s mut RentToOwn<'s, AlphaFundedBetaRedeemed<R>>,
         context: &'c mut RentToOwn<'c, Context<R>>,
     ) -> Result<Async<AfterAlphaFundedBetaRedeemed<R>>, rfc003::Error> {
-        match try_ready!(context
+        if let Async::Ready(alpha_redeemed_or_refunded) = context
             .alpha_ledger_events
             .htlc_redeemed_or_refunded(state.swap.alpha_htlc_params(), &state.alpha_htlc_location)
-            .poll())
+            .poll()?
         {
-            Either::A(_beta_redeemed_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::BothRedeemed {
-                    swap: state.take().swap
-                })
-            ),
-            Either::B(_beta_refunded_txid) => transition_save!(
-                context.state_repo,
-                Final(SwapOutcome::AlphaRefundedBetaRedeemed {
-                    swap: state.take().swap
-                })
-            ),
+            match alpha_redeemed_or_refunded {
+                Either::A(_beta_redeemed_txid) => transition_to_final!(
+                    context,
+                    SwapOutcome::BothRedeemed {
+                        swap: state.take().swap
+                    }
+                ),
+                Either::B(_beta_refunded_txid) => transition_to_final!(
+                    context,
+                    SwapOutcome::AlphaRefundedBetaRedeemed {
+                        swap: state.take().swap
+                    }
+                ),
+            }
         }
+
+        record_alpha_htlc_expiry!(state, context, AlphaFundedBetaRedeemed);
+
+        Ok(Async::NotReady)
     }
 }
 
@@ -532,6 +858,9 @@ impl_display!(AlphaDeployed);
 impl_display!(AlphaFunded);
 impl_display!(AlphaFundedBetaDeployed);
 impl_display!(BothFunded);
+impl_display!(AlphaExpired);
+impl_display!(BetaExpired);
+impl_display!(BothExpired);
 impl_display!(AlphaFundedBetaRefunded);
 impl_display!(AlphaRefundedBetaFunded);
 impl_display!(AlphaFundedBetaRedeemed);
@@ -548,11 +877,16 @@ impl<R: Role> SwapStates<R> {
             SS::AlphaFunded { .. } => String::from("AlphaFunded"),
             SS::AlphaFundedBetaDeployed { .. } => String::from("AlphaFundedBetaDeployed"),
             SS::BothFunded { .. } => String::from("BothFunded"),
+            SS::AlphaExpired { .. } => String::from("AlphaExpired"),
+            SS::BetaExpired { .. } => String::from("BetaExpired"),
+            SS::BothExpired { .. } => String::from("BothExpired"),
             SS::AlphaFundedBetaRefunded { .. } => String::from("AlphaFundedBetaRefunded"),
             SS::AlphaRefundedBetaFunded { .. } => String::from("AlphaRefundedBetaFunded"),
             SS::AlphaFundedBetaRedeemed { .. } => String::from("AlphaFundedBetaRedeemed"),
             SS::AlphaRedeemedBetaFunded { .. } => String::from("AlphaRedeemedBetaFunded"),
             SS::Final(Final(SwapOutcome::Rejected { .. })) => String::from("Rejected"),
+            SS::Final(Final(SwapOutcome::TimedOut { .. })) => String::from("TimedOut"),
+            SS::Final(Final(SwapOutcome::Canceled { .. })) => String::from("Canceled"),
             SS::Final(Final(SwapOutcome::AlphaRefunded { .. })) => String::from("AlphaRefunded"),
             SS::Final(Final(SwapOutcome::BothRefunded { .. })) => String::from("BothRefunded"),
             SS::Final(Final(SwapOutcome::BothRedeemed { .. })) => String::from("BothRedeemed"),
@@ -569,14 +903,18 @@ impl<R: Role> SwapStates<R> {
     pub fn start_state(&self) -> Result<Start<R>, Error> {
         use self::SwapStates as SS;
         match *self {
-            SS::Start(ref start) | SS::Final(Final(SwapOutcome::Rejected { ref start, .. })) => {
-                Ok(start.clone())
-            }
+            SS::Start(ref start)
+            | SS::Final(Final(SwapOutcome::Rejected { ref start, .. }))
+            | SS::Final(Final(SwapOutcome::TimedOut { ref start }))
+            | SS::Final(Final(SwapOutcome::Canceled { ref start })) => Ok(start.clone()),
             SS::Accepted(Accepted { ref swap, .. })
             | SS::AlphaDeployed(AlphaDeployed { ref swap, .. })
             | SS::AlphaFunded(AlphaFunded { ref swap, .. })
             | SS::AlphaFundedBetaDeployed(AlphaFundedBetaDeployed { ref swap, .. })
             | SS::BothFunded(BothFunded { ref swap, .. })
+            | SS::AlphaExpired(AlphaExpired { ref swap, .. })
+            | SS::BetaExpired(BetaExpired { ref swap, .. })
+            | SS::BothExpired(BothExpired { ref swap, .. })
             | SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded { ref swap, .. })
             | SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded { ref swap, .. })
             | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed { ref swap, .. })
@@ -593,6 +931,8 @@ impl<R: Role> SwapStates<R> {
                 alpha_ledger_refund_identity: swap.alpha_ledger_refund_identity.clone(),
                 beta_ledger_redeem_identity: swap.beta_ledger_redeem_identity.clone(),
                 alpha_ledger_lock_duration: swap.alpha_ledger_lock_duration.clone(),
+                alpha_ledger_confirmations_needed: swap.alpha_ledger_confirmations_needed,
+                beta_ledger_confirmations_needed: swap.beta_ledger_confirmations_needed,
                 secret: swap.secret.clone(),
                 role: swap.role.clone(),
             }),
@@ -608,6 +948,9 @@ impl<R: Role> SwapStates<R> {
             | SS::AlphaFunded(AlphaFunded { ref swap, .. })
             | SS::AlphaFundedBetaDeployed(AlphaFundedBetaDeployed { ref swap, .. })
             | SS::BothFunded(BothFunded { ref swap, .. })
+            | SS::AlphaExpired(AlphaExpired { ref swap, .. })
+            | SS::BetaExpired(BetaExpired { ref swap, .. })
+            | SS::BothExpired(BothExpired { ref swap, .. })
             | SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded { ref swap, .. })
             | SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded { ref swap, .. })
             | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed { ref swap, .. })
@@ -622,4 +965,98 @@ impl<R: Role> SwapStates<R> {
             SS::Start(_) | SS::Final(_) | SS::Error(_) => None,
         }
     }
+
+    pub fn alpha_htlc_expiry(&self) -> Option<<R::AlphaLedger as Ledger>::BlockTime> {
+        use self::SwapStates as SS;
+        match *self {
+            SS::AlphaDeployed(AlphaDeployed {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaFunded(AlphaFunded {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaFundedBetaDeployed(AlphaFundedBetaDeployed {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BothFunded(BothFunded {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaExpired(AlphaExpired {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BetaExpired(BetaExpired {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::BothExpired(BothExpired {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaFundedBetaRefunded(AlphaFundedBetaRefunded {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaFundedBetaRedeemed(AlphaFundedBetaRedeemed {
+                ref swap,
+                alpha_htlc_deployed_at,
+                ..
+            }) => Some(swap.alpha_htlc_expiry(alpha_htlc_deployed_at)),
+            _ => None,
+        }
+    }
+
+    pub fn beta_htlc_expiry(&self) -> Option<<R::BetaLedger as Ledger>::BlockTime> {
+        use self::SwapStates as SS;
+        match *self {
+            SS::AlphaFundedBetaDeployed(AlphaFundedBetaDeployed {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            })
+            | SS::BothFunded(BothFunded {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaExpired(AlphaExpired {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            })
+            | SS::BetaExpired(BetaExpired {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            })
+            | SS::BothExpired(BothExpired {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaRefundedBetaFunded(AlphaRefundedBetaFunded {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            })
+            | SS::AlphaRedeemedBetaFunded(AlphaRedeemedBetaFunded {
+                ref swap,
+                beta_htlc_deployed_at,
+                ..
+            }) => Some(swap.beta_htlc_expiry(beta_htlc_deployed_at)),
+            _ => None,
+        }
+    }
 }
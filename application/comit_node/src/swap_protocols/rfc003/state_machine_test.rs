@@ -1,13 +1,15 @@
 use crate::{
-    comit_client::SwapReject,
+    comit_client::{fake::FakeClient, SwapReject, SwapResponseError},
     swap_protocols::{
         ledger::{Bitcoin, Ethereum},
         rfc003::{
-            ethereum::Seconds,
+            alice::AliceToBob,
+            bitcoin::BlockHeight,
+            ethereum::{Seconds, Timestamp},
             events::{self, LedgerEvents},
             role::test::{Alisha, Bobisha, FakeCommunicationEvents},
             state_machine::*,
-            Ledger, RedeemTransaction, Secret,
+            Deployment, Error, Ledger, RedeemTransaction, Secret,
         },
     },
 };
@@ -16,16 +18,28 @@ use ethereum_support::EtherQuantity;
 use futures::{
     future::{self, Either},
     sync::mpsc,
-    Stream,
+    Future, Stream,
 };
 use hex::FromHex;
 use std::{str::FromStr, sync::Arc};
 
-#[derive(Default)]
 struct FakeLedgerEvents<L: Ledger> {
     pub htlc_deployed: Option<Box<events::Deployed<L>>>,
     pub htlc_funded: Option<Box<events::Funded<L>>>,
     pub htlc_redeemed_or_refunded: Option<Box<events::RedeemedOrRefunded<L>>>,
+    pub htlc_expired: Option<Box<events::Expired>>,
+}
+
+// Not derived, as that would require the ledger to implement `Default`
+impl<L: Ledger> Default for FakeLedgerEvents<L> {
+    fn default() -> Self {
+        FakeLedgerEvents {
+            htlc_deployed: None,
+            htlc_funded: None,
+            htlc_redeemed_or_refunded: None,
+            htlc_expired: None,
+        }
+    }
 }
 
 impl LedgerEvents<Bitcoin, BitcoinQuantity> for FakeLedgerEvents<Bitcoin> {
@@ -51,6 +65,10 @@ impl LedgerEvents<Bitcoin, BitcoinQuantity> for FakeLedgerEvents<Bitcoin> {
     ) -> &mut events::RedeemedOrRefunded<Bitcoin> {
         self.htlc_redeemed_or_refunded.as_mut().unwrap()
     }
+
+    fn htlc_expired(&mut self, _expiry: BlockHeight) -> &mut events::Expired {
+        self.htlc_expired.as_mut().unwrap()
+    }
 }
 
 impl LedgerEvents<Ethereum, EtherQuantity> for FakeLedgerEvents<Ethereum> {
@@ -74,7 +92,11 @@ impl LedgerEvents<Ethereum, EtherQuantity> for FakeLedgerEvents<Ethereum> {
         _htlc_params: HtlcParams<Ethereum, EtherQuantity>,
         _htlc_location: &ethereum_support::Address,
     ) -> &mut events::RedeemedOrRefunded<Ethereum> {
-        unimplemented!()
+        self.htlc_redeemed_or_refunded.as_mut().unwrap()
+    }
+
+    fn htlc_expired(&mut self, _expiry: Timestamp) -> &mut events::Expired {
+        self.htlc_expired.as_mut().unwrap()
     }
 }
 
@@ -90,10 +112,12 @@ fn gen_start_state() -> Start<Alisha> {
         )
         .unwrap(),
         alpha_ledger: Bitcoin::regtest(),
-        beta_ledger: Ethereum::default(),
+        beta_ledger: Ethereum::regtest(),
         alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
         beta_asset: EtherQuantity::from_eth(10.0),
         alpha_ledger_lock_duration: Blocks::from(144),
+        alpha_ledger_confirmations_needed: 1,
+        beta_ledger_confirmations_needed: 1,
         secret: Secret::from(*b"hello world, you are beautiful!!"),
         role: Alisha::default(),
     }
@@ -164,6 +188,60 @@ fn when_swap_is_rejected_go_to_final_reject() {
     run_state_machine!(state_machine, states);
 }
 
+#[test]
+fn when_swap_request_times_out_go_to_final_timed_out() {
+    let start = gen_start_state();
+
+    let (state_machine, states) = init!(
+        Alisha,
+        FakeCommunicationEvents::<Alisha> {
+            response: Some(Box::new(future::err(Error::SwapResponse(
+                SwapResponseError::Timeout
+            )))),
+        },
+        start.clone().into(),
+        FakeLedgerEvents {
+            ..Default::default()
+        },
+        FakeLedgerEvents {
+            ..Default::default()
+        }
+    );
+
+    run_state_machine!(state_machine, states);
+}
+
+#[test]
+fn when_swap_request_is_canceled_go_to_final_canceled() {
+    let (alisha, canceled) = Alisha::create();
+    let start = Start {
+        role: alisha.clone(),
+        ..gen_start_state()
+    };
+
+    let (state_machine, states) = init!(
+        Alisha,
+        AliceToBob::new(Arc::new(FakeClient::default()), canceled),
+        start.clone().into(),
+        FakeLedgerEvents {
+            ..Default::default()
+        },
+        FakeLedgerEvents {
+            ..Default::default()
+        }
+    );
+
+    alisha
+        .cancel_action()
+        .expect("a request that was just sent can be canceled")
+        .cancel()
+        .unwrap();
+
+    run_state_machine!(state_machine, states);
+
+    assert!(alisha.cancel_action().is_none());
+}
+
 #[test]
 fn alpha_refunded() {
     let bob_response = StateMachineResponse {
@@ -187,9 +265,12 @@ fn alpha_refunded() {
         },
         start.clone().into(),
         FakeLedgerEvents::<Bitcoin> {
-            htlc_deployed: Some(Box::new(future::ok(OutPoint {
-                txid: Sha256dHash::from_data(b"funding"),
-                vout: 0,
+            htlc_deployed: Some(Box::new(future::ok(Deployment {
+                location: OutPoint {
+                    txid: Sha256dHash::from_data(b"funding"),
+                    vout: 0,
+                },
+                deployed_at: BlockHeight(100),
             }))),
             htlc_funded: Some(Box::new(future::ok(None))),
             htlc_redeemed_or_refunded: Some(Box::new(future::ok(Either::A(RedeemTransaction {
@@ -219,14 +300,17 @@ fn alpha_refunded() {
             alpha_htlc_location: OutPoint {
                 txid: Sha256dHash::from_data(b"funding"),
                 vout: 0
-            }
+            },
+            alpha_htlc_deployed_at: BlockHeight(100),
         },
         AlphaFunded {
             swap: OngoingSwap::new(start.clone(), bob_response.clone().into()),
             alpha_htlc_location: OutPoint {
                 txid: Sha256dHash::from_data(b"funding"),
                 vout: 0
-            }
+            },
+            alpha_htlc_deployed_at: BlockHeight(100),
+            alpha_htlc_expired: false,
         }
     );
 }
@@ -244,10 +328,12 @@ fn bob_transition_alpha_refunded() {
         )
         .unwrap(),
         alpha_ledger: Bitcoin::regtest(),
-        beta_ledger: Ethereum::default(),
+        beta_ledger: Ethereum::regtest(),
         alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
         beta_asset: EtherQuantity::from_eth(10.0),
         alpha_ledger_lock_duration: Blocks::from(144),
+        alpha_ledger_confirmations_needed: 1,
+        beta_ledger_confirmations_needed: 1,
         secret: Secret::from(*b"hello world, you are beautiful!!").hash(),
         role: bobisha,
     };
@@ -272,9 +358,12 @@ fn bob_transition_alpha_refunded() {
         },
         start.clone().into(),
         FakeLedgerEvents::<Bitcoin> {
-            htlc_deployed: Some(Box::new(future::ok(OutPoint {
-                txid: Sha256dHash::from_data(b"funding"),
-                vout: 0,
+            htlc_deployed: Some(Box::new(future::ok(Deployment {
+                location: OutPoint {
+                    txid: Sha256dHash::from_data(b"funding"),
+                    vout: 0,
+                },
+                deployed_at: BlockHeight(100),
             }))),
             htlc_funded: Some(Box::new(future::ok(None))),
             htlc_redeemed_or_refunded: Some(Box::new(future::ok(Either::A(RedeemTransaction {
@@ -304,14 +393,168 @@ fn bob_transition_alpha_refunded() {
             alpha_htlc_location: OutPoint {
                 txid: Sha256dHash::from_data(b"funding"),
                 vout: 0
-            }
+            },
+            alpha_htlc_deployed_at: BlockHeight(100),
         },
         AlphaFunded {
             swap: OngoingSwap::new(start.clone(), response.clone().into()),
             alpha_htlc_location: OutPoint {
                 txid: Sha256dHash::from_data(b"funding"),
                 vout: 0
-            }
+            },
+            alpha_htlc_deployed_at: BlockHeight(100),
+            alpha_htlc_expired: false,
+        }
+    );
+}
+
+#[test]
+fn both_funded_records_expiry_of_either_htlc() {
+    let bob_response = StateMachineResponse {
+        beta_ledger_refund_identity: ethereum_support::Address::from_str(
+            "71b9f69dcabb340a3fe229c3f94f1662ad85e5e8",
+        )
+        .unwrap(),
+        alpha_ledger_redeem_identity: bitcoin_support::PubkeyHash::from_hex(
+            "d38e554430c4035f2877a579a07a99886153f071",
+        )
+        .unwrap(),
+        beta_ledger_lock_duration: Seconds(42),
+    };
+
+    let start = gen_start_state();
+    let swap = OngoingSwap::new(start.clone(), bob_response.clone().into());
+    let alpha_htlc_location = OutPoint {
+        txid: Sha256dHash::from_data(b"funding"),
+        vout: 0,
+    };
+    let beta_htlc_location =
+        ethereum_support::Address::from_str("0a81e8be41b21f651a71aab1a85c6813b8bbccf8").unwrap();
+
+    let (state_machine, states) = init!(
+        Alisha,
+        FakeCommunicationEvents::<Alisha> { response: None },
+        BothFunded {
+            swap: swap.clone(),
+            alpha_htlc_location,
+            alpha_htlc_deployed_at: BlockHeight(100),
+            beta_htlc_location,
+            beta_htlc_deployed_at: Timestamp(1_000),
+        }
+        .into(),
+        FakeLedgerEvents::<Bitcoin> {
+            htlc_redeemed_or_refunded: Some(Box::new(future::empty())),
+            htlc_expired: Some(Box::new(future::ok(()))),
+            ..Default::default()
+        },
+        FakeLedgerEvents::<Ethereum> {
+            htlc_redeemed_or_refunded: Some(Box::new(future::empty())),
+            htlc_expired: Some(Box::new(future::ok(()))),
+            ..Default::default()
         }
     );
+
+    // Neither HTLC is ever redeemed or refunded, so the swap never finishes
+    // and we only look at the states it went through.
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(state_machine.map(|_| ()).map_err(|_| ()));
+    let actual_states: Vec<SwapStates<Alisha>> =
+        runtime.block_on(states.take(2).collect()).unwrap();
+
+    assert_eq!(
+        actual_states,
+        vec![
+            SwapStates::from(BetaExpired {
+                swap: swap.clone(),
+                alpha_htlc_location,
+                alpha_htlc_deployed_at: BlockHeight(100),
+                beta_htlc_location,
+                beta_htlc_deployed_at: Timestamp(1_000),
+            }),
+            SwapStates::from(BothExpired {
+                swap,
+                alpha_htlc_location,
+                alpha_htlc_deployed_at: BlockHeight(100),
+                beta_htlc_location,
+                beta_htlc_deployed_at: Timestamp(1_000),
+            }),
+        ]
+    );
+}
+
+#[test]
+fn alpha_funded_beta_redeemed_records_expiry_of_alpha_htlc() {
+    let bob_response = StateMachineResponse {
+        beta_ledger_refund_identity: ethereum_support::Address::from_str(
+            "71b9f69dcabb340a3fe229c3f94f1662ad85e5e8",
+        )
+        .unwrap(),
+        alpha_ledger_redeem_identity: bitcoin_support::PubkeyHash::from_hex(
+            "d38e554430c4035f2877a579a07a99886153f071",
+        )
+        .unwrap(),
+        beta_ledger_lock_duration: Seconds(42),
+    };
+
+    let start = gen_start_state();
+    let swap = OngoingSwap::new(start.clone(), bob_response.clone().into());
+    let alpha_htlc_location = OutPoint {
+        txid: Sha256dHash::from_data(b"funding"),
+        vout: 0,
+    };
+    let beta_redeemed_tx = RedeemTransaction {
+        transaction: ethereum_support::Transaction {
+            hash: ethereum_support::H256::from(123),
+            nonce: ethereum_support::U256::from(1),
+            block_hash: None,
+            block_number: None,
+            transaction_index: None,
+            from: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".parse().unwrap(),
+            to: Some("0a81e8be41b21f651a71aab1a85c6813b8bbccf8".parse().unwrap()),
+            value: ethereum_support::U256::from(0),
+            gas_price: ethereum_support::U256::from(0),
+            gas: ethereum_support::U256::from(0),
+            input: ethereum_support::Bytes::from(start.secret.raw_secret().to_vec()),
+        },
+        secret: start.secret,
+    };
+
+    let (state_machine, states) = init!(
+        Alisha,
+        FakeCommunicationEvents::<Alisha> { response: None },
+        AlphaFundedBetaRedeemed {
+            swap: swap.clone(),
+            beta_redeemed_tx: beta_redeemed_tx.clone(),
+            alpha_htlc_location,
+            alpha_htlc_deployed_at: BlockHeight(100),
+            alpha_htlc_expired: false,
+        }
+        .into(),
+        FakeLedgerEvents::<Bitcoin> {
+            htlc_redeemed_or_refunded: Some(Box::new(future::empty())),
+            htlc_expired: Some(Box::new(future::ok(()))),
+            ..Default::default()
+        },
+        FakeLedgerEvents::<Ethereum> {
+            ..Default::default()
+        }
+    );
+
+    // Bob never redeems the alpha HTLC, so the swap never finishes and we
+    // only look at the state it went to.
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn(state_machine.map(|_| ()).map_err(|_| ()));
+    let actual_states: Vec<SwapStates<Alisha>> =
+        runtime.block_on(states.take(1).collect()).unwrap();
+
+    assert_eq!(
+        actual_states,
+        vec![SwapStates::from(AlphaFundedBetaRedeemed {
+            swap,
+            beta_redeemed_tx,
+            alpha_htlc_location,
+            alpha_htlc_deployed_at: BlockHeight(100),
+            alpha_htlc_expired: true,
+        })]
+    );
 }
@@ -1,8 +1,14 @@
-use crate::swap_protocols::rfc003::{state_machine::SwapStates, Role, SaveState};
+use crate::swap_protocols::{
+    disk_store::{self, JsonDirectory},
+    rfc003::{self, state_machine::SwapStates, Role, SaveState},
+};
 use std::{
     any::Any,
     collections::HashMap,
+    fmt::Display,
     hash::Hash,
+    marker::PhantomData,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex, RwLock},
 };
 
@@ -10,6 +16,14 @@ use std::{
 pub enum Error {
     #[fail(display = "State already exists for given key")]
     DuplicateKey,
+    #[fail(display = "State could not be read from or written to disk")]
+    Disk(#[cause] disk_store::Error),
+}
+
+impl From<disk_store::Error> for Error {
+    fn from(e: disk_store::Error) -> Self {
+        Error::Disk(e)
+    }
 }
 
 pub trait StateStore<K>: Send + Sync + 'static {
@@ -65,21 +79,87 @@ impl<K: Hash + Eq + Clone + Send + Sync + 'static> StateStore<K> for InMemorySta
     }
 }
 
+/// Keeps the latest state of every swap in its own JSON document. A freshly
+/// registered swap is written as `null` until the state machine saves its
+/// first state.
+#[derive(Debug)]
+pub struct DiskStateStore<K> {
+    directory: JsonDirectory,
+    write_lock: Mutex<()>,
+    key_type: PhantomData<K>,
+}
+
+impl<K> DiskStateStore<K> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(DiskStateStore {
+            directory: JsonDirectory::open(path)?,
+            write_lock: Mutex::new(()),
+            key_type: PhantomData,
+        })
+    }
+}
+
+impl<K: Display + Send + Sync + 'static> StateStore<K> for DiskStateStore<K> {
+    fn new_save_state<R: Role>(&self, key: K) -> Result<Arc<dyn SaveState<R>>, Error> {
+        let _guard = self.write_lock.lock().unwrap();
+
+        if self.directory.contains(&key) {
+            return Err(Error::DuplicateKey);
+        }
+
+        let no_state: Option<SwapStates<R>> = None;
+        self.directory.write(&key, &no_state)?;
+
+        Ok(Arc::new(DiskSaveState {
+            path: self.directory.file_for(&key),
+        }))
+    }
+
+    fn get<R: Role>(&self, key: &K) -> Result<Option<SwapStates<R>>, Error> {
+        let state: Option<Option<SwapStates<R>>> = self.directory.read(key)?;
+
+        Ok(state.and_then(|state| state))
+    }
+
+    fn get_save_state<R: Role>(&self, key: &K) -> Result<Option<Arc<dyn SaveState<R>>>, Error> {
+        if !self.directory.contains(key) {
+            return Ok(None);
+        }
+
+        Ok(Some(Arc::new(DiskSaveState {
+            path: self.directory.file_for(key),
+        })))
+    }
+}
+
+#[derive(Debug)]
+struct DiskSaveState {
+    path: PathBuf,
+}
+
+impl<R: Role> SaveState<R> for DiskSaveState {
+    fn save(&self, state: SwapStates<R>) -> Result<(), rfc003::Error> {
+        disk_store::write_json(&self.path, &Some(state)).map_err(|e| {
+            error!("Failed to persist swap state: {}", e);
+            rfc003::Error::Storage(e.to_string())
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::swap_protocols::{
         ledger::{Bitcoin, Ethereum},
         rfc003::{role::test::Alisha, state_machine::Start, Secret},
+        SwapId,
     };
     use bitcoin_support::{BitcoinQuantity, Blocks};
     use ethereum_support::EtherQuantity;
     use spectral::prelude::*;
 
-    #[test]
-    fn store_get_and_save_state() {
-        let state_store = InMemoryStateStore::default();
-        let start_state = Start::<Alisha> {
+    fn start_state() -> Start<Alisha> {
+        Start::<Alisha> {
             alpha_ledger_refund_identity: secp256k1_support::KeyPair::from_secret_key_slice(
                 &hex::decode("18e14a7b6a307f426a94f8114701e7c8e774e7f9a47e2c2035db29a206321725")
                     .unwrap(),
@@ -89,22 +169,26 @@ mod tests {
                 .parse()
                 .unwrap(),
             alpha_ledger: Bitcoin::default(),
-            beta_ledger: Ethereum::default(),
+            beta_ledger: Ethereum::regtest(),
             alpha_asset: BitcoinQuantity::from_bitcoin(1.0),
             beta_asset: EtherQuantity::from_eth(10.0),
             alpha_ledger_lock_duration: Blocks::from(144),
+            alpha_ledger_confirmations_needed: 1,
+            beta_ledger_confirmations_needed: 1,
             secret: Secret::from(*b"hello world, you are beautiful!!"),
             role: Alisha::default(),
-        };
+        }
+    }
 
+    fn assert_get_and_save_state<K: Clone, S: StateStore<K>>(state_store: &S, id: K) {
+        let start_state = start_state();
         let state = SwapStates::from(start_state.clone());
-        let id = 1;
 
-        let res = state_store.new_save_state(id);
+        let res = state_store.new_save_state(id.clone());
         assert!(res.is_ok());
 
         let save_state = res.unwrap();
-        save_state.save(start_state.clone().into());
+        save_state.save(start_state.clone().into()).unwrap();
 
         let res = state_store.get(&id).unwrap();
         assert_that(&res).contains_value(state);
@@ -116,9 +200,57 @@ mod tests {
             ..start_state
         });
 
-        save_state.save(second_state.clone());
+        save_state.save(second_state.clone()).unwrap();
 
         let res = state_store.get(&id).unwrap();
         assert_that(&res).contains_value(second_state)
     }
+
+    #[test]
+    fn store_get_and_save_state() {
+        let state_store = InMemoryStateStore::default();
+
+        assert_get_and_save_state(&state_store, 1);
+    }
+
+    #[test]
+    fn disk_store_get_and_save_state() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let state_store = DiskStateStore::open(path).unwrap();
+
+        assert_get_and_save_state(&state_store, SwapId::default());
+    }
+
+    #[test]
+    fn disk_store_keeps_state_across_reopening() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path();
+        let id = SwapId::default();
+        let state = SwapStates::from(start_state());
+
+        {
+            let state_store = DiskStateStore::open(path).unwrap();
+            let save_state = state_store.new_save_state::<Alisha>(id).unwrap();
+            save_state.save(state.clone()).unwrap();
+        }
+
+        let state_store = DiskStateStore::open(path).unwrap();
+
+        assert!(state_store.new_save_state::<Alisha>(id).is_err());
+        assert_that(&state_store.get::<Alisha>(&id).unwrap()).contains_value(state);
+    }
+
+    #[test]
+    fn disk_save_state_fails_once_the_state_cannot_be_written() {
+        let directory = tempfile::tempdir().unwrap();
+        let state_store = DiskStateStore::open(directory.path()).unwrap();
+        let save_state = state_store
+            .new_save_state::<Alisha>(SwapId::default())
+            .unwrap();
+
+        std::fs::remove_dir_all(directory.path()).unwrap();
+
+        assert_that(&save_state.save(SwapStates::from(start_state()))).is_err();
+    }
 }
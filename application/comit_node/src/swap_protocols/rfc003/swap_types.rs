@@ -0,0 +1,81 @@
+/// The ledger and asset combinations this node can swap.
+///
+/// Resolves the kinds recorded in a `Metadata` to their types and calls `$fn`
+/// with `AL`, `BL`, `AA` and `BA` in scope. Any other combination evaluates to
+/// `$unsupported`. The HTTP API, the BAM server, recovery and auto-execution
+/// all dispatch through this.
+///
+/// This is only a table of arms, not a registry. Adding an arm makes the
+/// callers compile the pair, but the pair still needs hand-written `Actions`
+/// impls for both `Alice` and `Bob` (see `alice::actions` and `bob::actions`),
+/// plus whatever per-ledger impls its ledgers don't have yet.
+#[macro_export]
+macro_rules! with_supported_swap_types {
+    ($metadata:expr, $fn:tt, $unsupported:expr) => {{
+        use bitcoin_support::BitcoinQuantity;
+        use ethereum_support::{Erc20Quantity, EtherQuantity};
+        use $crate::swap_protocols::{
+            ledger::{Bitcoin, Ethereum},
+            AssetKind, LedgerKind,
+        };
+        let metadata = $metadata;
+
+        #[allow(clippy::redundant_closure_call)]
+        match (
+            metadata.alpha_ledger,
+            metadata.beta_ledger,
+            metadata.alpha_asset,
+            metadata.beta_asset,
+        ) {
+            (LedgerKind::Bitcoin, LedgerKind::Ethereum, AssetKind::Bitcoin, AssetKind::Ether) => {
+                #[allow(dead_code)]
+                type AL = Bitcoin;
+                #[allow(dead_code)]
+                type BL = Ethereum;
+                #[allow(dead_code)]
+                type AA = BitcoinQuantity;
+                #[allow(dead_code)]
+                type BA = EtherQuantity;
+
+                $fn()
+            }
+            (LedgerKind::Bitcoin, LedgerKind::Ethereum, AssetKind::Bitcoin, AssetKind::Erc20) => {
+                #[allow(dead_code)]
+                type AL = Bitcoin;
+                #[allow(dead_code)]
+                type BL = Ethereum;
+                #[allow(dead_code)]
+                type AA = BitcoinQuantity;
+                #[allow(dead_code)]
+                type BA = Erc20Quantity;
+
+                $fn()
+            }
+            (LedgerKind::Ethereum, LedgerKind::Bitcoin, AssetKind::Ether, AssetKind::Bitcoin) => {
+                #[allow(dead_code)]
+                type AL = Ethereum;
+                #[allow(dead_code)]
+                type BL = Bitcoin;
+                #[allow(dead_code)]
+                type AA = EtherQuantity;
+                #[allow(dead_code)]
+                type BA = BitcoinQuantity;
+
+                $fn()
+            }
+            (LedgerKind::Ethereum, LedgerKind::Bitcoin, AssetKind::Erc20, AssetKind::Bitcoin) => {
+                #[allow(dead_code)]
+                type AL = Ethereum;
+                #[allow(dead_code)]
+                type BL = Bitcoin;
+                #[allow(dead_code)]
+                type AA = Erc20Quantity;
+                #[allow(dead_code)]
+                type BA = BitcoinQuantity;
+
+                $fn()
+            }
+            _ => $unsupported,
+        }
+    }};
+}
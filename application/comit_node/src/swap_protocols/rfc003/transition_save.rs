@@ -2,7 +2,7 @@
 macro_rules! transition_save {
     ($repo:expr, $new_state:expr) => {{
         let save_state = $new_state;
-        $repo.save(save_state.clone().into());
+        $repo.save(save_state.clone().into())?;
 
         debug!("Transitioning to {}", save_state);
 
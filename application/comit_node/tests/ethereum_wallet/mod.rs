@@ -1,7 +0,0 @@
-pub mod transaction;
-pub mod wallet;
-
-pub use self::{
-    transaction::*,
-    wallet::{InMemoryWallet, Wallet},
-};
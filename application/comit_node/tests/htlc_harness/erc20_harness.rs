@@ -1,5 +1,4 @@
 use crate::{
-    ethereum_wallet::InMemoryWallet,
     htlc_harness::{new_account, HTLC_TIMEOUT, SECRET},
     parity_client::ParityClient,
 };
@@ -12,7 +11,7 @@ use ethereum_support::{
         transports::EventLoopHandle,
         types::{Address, U256},
     },
-    EtherQuantity,
+    EtherQuantity, InMemoryWallet,
 };
 use std::{sync::Arc, time::Duration};
 use tc_web3_client;
@@ -1,5 +1,4 @@
 use crate::{
-    ethereum_wallet::InMemoryWallet,
     htlc_harness::{new_account, HTLC_TIMEOUT, SECRET},
     parity_client::ParityClient,
 };
@@ -9,7 +8,7 @@ use comit_node::swap_protocols::rfc003::{
 };
 use ethereum_support::{
     web3::{transports::EventLoopHandle, types::Address},
-    EtherQuantity,
+    EtherQuantity, InMemoryWallet,
 };
 use std::{sync::Arc, time::Duration};
 use tc_web3_client;
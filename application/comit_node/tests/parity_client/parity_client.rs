@@ -1,4 +1,3 @@
-use crate::ethereum_wallet::{UnsignedTransaction, Wallet};
 use comit_node::swap_protocols::rfc003::{
     ethereum::{Htlc, Seconds},
     SecretHash,
@@ -6,7 +5,7 @@ use comit_node::swap_protocols::rfc003::{
 use ethereum_support::{
     web3::{transports::Http, Web3},
     Address, Bytes, CallRequest, EtherQuantity, Future, TransactionReceipt, TransactionRequest,
-    H256, U256,
+    UnsignedTransaction, Wallet, H256, U256,
 };
 use std::{
     ops::DerefMut,
@@ -0,0 +1,426 @@
+use crate::{
+    block_processor::{
+        record_confirmed_match, Block, MatchedUpTo, PendingTransaction, PendingTransactions, Query,
+        QueryMatchResult, RecentBlocks, Transaction,
+    },
+    latest_block_repository::LatestBlockRepository,
+    query_repository::{Error, QueryRepository},
+    query_result_repository::QueryResultRepository,
+};
+use futures::{future::join_all, Future, Stream};
+use std::sync::Arc;
+
+/// How far back a query can ask to be matched. Scanning further would keep
+/// the ledger's node busy for too long.
+pub const MAX_BACKFILL_BLOCKS: u64 = 10_000;
+
+/// Gives access to blocks that are already part of the chain.
+pub trait BlockSource<B>: Send + Sync + 'static {
+    /// The blocks from height `from` to `to`, both included, oldest first.
+    fn blocks_between(&self, from: u64, to: u64) -> Box<dyn Stream<Item = B, Error = ()> + Send>;
+}
+
+/// Decorates a query repository so that saving a query which names a
+/// `from_block_height` also matches it against the blocks from that height up
+/// to the last one the block processor matched without it.
+#[derive(DebugStub)]
+pub struct BackfillingQueryRepository<B, Q> {
+    #[debug_stub = "Queries"]
+    queries: Arc<dyn QueryRepository<Q>>,
+    #[debug_stub = "Results"]
+    results: Arc<dyn QueryResultRepository<Q>>,
+    pending_transactions: PendingTransactions,
+    recent_blocks: RecentBlocks,
+    matched_up_to: MatchedUpTo,
+    #[debug_stub = "LatestBlockRepository"]
+    latest_block_repository: Arc<dyn LatestBlockRepository>,
+    #[debug_stub = "BlockSource"]
+    blocks: Arc<dyn BlockSource<B>>,
+}
+
+impl<B, Q> BackfillingQueryRepository<B, Q> {
+    /// `pending_transactions`, `recent_blocks` and `matched_up_to` are those of
+    /// the block processor that serves the queries.
+    pub fn new(
+        queries: Arc<dyn QueryRepository<Q>>,
+        results: Arc<dyn QueryResultRepository<Q>>,
+        pending_transactions: PendingTransactions,
+        recent_blocks: RecentBlocks,
+        matched_up_to: MatchedUpTo,
+        latest_block_repository: Arc<dyn LatestBlockRepository>,
+        blocks: Arc<dyn BlockSource<B>>,
+    ) -> Self {
+        BackfillingQueryRepository {
+            queries,
+            results,
+            pending_transactions,
+            recent_blocks,
+            matched_up_to,
+            latest_block_repository,
+            blocks,
+        }
+    }
+}
+
+impl<B, Q> BackfillingQueryRepository<B, Q>
+where
+    B: Block + Send,
+    Q: Query<B::Transaction> + Send + Sync + Clone,
+{
+    fn backfill(
+        &self,
+        id: u32,
+        query: Q,
+        matched_without_query_up_to: Option<u64>,
+    ) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        let from_block_height = match query.from_block_height() {
+            Some(from_block_height) => from_block_height,
+            None => return Box::new(futures::future::ok(())),
+        };
+
+        // Until the block processor gets to its first block, the last one it
+        // processed before the restart is the last one it matched
+        let to_block_height = match matched_without_query_up_to
+            .or_else(|| self.latest_block_repository.get().map(|block| block.height))
+        {
+            Some(height) => height,
+            None => {
+                warn!(
+                    "Cannot backfill query {} before the first block has been processed",
+                    id
+                );
+                return Box::new(futures::future::ok(()));
+            }
+        };
+
+        if from_block_height > to_block_height {
+            return Box::new(futures::future::ok(()));
+        }
+
+        let from_block_height = if to_block_height - from_block_height >= MAX_BACKFILL_BLOCKS {
+            let capped = to_block_height - MAX_BACKFILL_BLOCKS + 1;
+            warn!(
+                "Backfilling query {} from block {} instead of {}, which is more than {} blocks back",
+                id, capped, from_block_height, MAX_BACKFILL_BLOCKS
+            );
+            capped
+        } else {
+            from_block_height
+        };
+
+        debug!(
+            "Backfilling query {} from block {} to {}",
+            id, from_block_height, to_block_height
+        );
+
+        let results = Arc::clone(&self.results);
+        let pending_transactions = Arc::clone(&self.pending_transactions);
+        let recent_blocks = Arc::clone(&self.recent_blocks);
+        let matched_up_to = Arc::clone(&self.matched_up_to);
+
+        Box::new(
+            self.blocks
+                .blocks_between(from_block_height, to_block_height)
+                .for_each(move |block| {
+                    let height = block.height();
+                    let blockhash = block.blockhash();
+                    let results = Arc::clone(&results);
+                    let pending_transactions = Arc::clone(&pending_transactions);
+                    let recent_blocks = Arc::clone(&recent_blocks);
+                    let matched_up_to = Arc::clone(&matched_up_to);
+
+                    let matches = block.transactions().iter().map(|transaction| {
+                        let tx_id = transaction.transaction_id();
+                        query
+                            .matches(transaction)
+                            .map(move |result| (tx_id, result))
+                    });
+
+                    join_all(matches).map(move |matches| {
+                        // The block processor may have moved on in the meantime,
+                        // every block it processed since counts as a confirmation
+                        // already. Holding the lock keeps it from moving on while
+                        // the matches are filed.
+                        let matched_up_to = matched_up_to.lock().unwrap();
+                        let confirmations = matched_up_to
+                            .unwrap_or(to_block_height)
+                            .saturating_sub(height)
+                            + 1;
+
+                        for (tx_id, result) in matches {
+                            match result {
+                                QueryMatchResult::Yes {
+                                    confirmations_needed,
+                                } if u64::from(confirmations_needed) <= confirmations => {
+                                    results.add_result(id, tx_id.clone());
+                                    record_confirmed_match(
+                                        &recent_blocks,
+                                        &blockhash,
+                                        confirmations_needed,
+                                        (id, tx_id),
+                                    );
+                                }
+                                QueryMatchResult::Yes {
+                                    confirmations_needed,
+                                } => {
+                                    let pending_confirmations =
+                                        confirmations_needed - confirmations as u32;
+                                    let mut pending_transactions =
+                                        pending_transactions.lock().unwrap();
+                                    pending_transactions.push(PendingTransaction::new(
+                                        id,
+                                        tx_id,
+                                        blockhash.clone(),
+                                        pending_confirmations,
+                                    ));
+                                }
+                                QueryMatchResult::No => {}
+                            }
+                        }
+                    })
+                }),
+        )
+    }
+}
+
+impl<B, Q> QueryRepository<Q> for BackfillingQueryRepository<B, Q>
+where
+    B: Block + Send,
+    Q: Query<B::Transaction> + Send + Sync + Clone,
+{
+    fn all(&self) -> Box<dyn Iterator<Item = (u32, Q)>> {
+        self.queries.all()
+    }
+
+    fn get(&self, id: u32) -> Option<Q> {
+        self.queries.get(id)
+    }
+
+    fn save(&self, entity: Q) -> Result<u32, Error<Q>> {
+        // The block processor cannot start on another block while the query is
+        // saved, so the blocks it matched without the query end exactly at
+        // `matched_up_to`
+        let (id, matched_without_query_up_to) = {
+            let matched_up_to = self.matched_up_to.lock().unwrap();
+            (self.queries.save(entity.clone())?, *matched_up_to)
+        };
+
+        tokio::spawn(self.backfill(id, entity, matched_without_query_up_to));
+
+        Ok(id)
+    }
+
+    fn delete(&self, id: u32) {
+        self.queries.delete(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        in_memory_latest_block_repository::InMemoryLatestBlockRepository,
+        in_memory_query_repository::InMemoryQueryRepository,
+        in_memory_query_result_repository::InMemoryQueryResultRepository,
+        latest_block_repository::LatestBlock,
+    };
+    use futures::stream;
+    use spectral::prelude::*;
+    use std::{collections::VecDeque, sync::Mutex};
+
+    #[derive(Clone, Debug)]
+    struct TransactionQuery {
+        transaction_id: u8,
+        confirmations_needed: u32,
+        from_block_height: Option<u64>,
+    }
+
+    impl Query<u8> for TransactionQuery {
+        fn matches(
+            &self,
+            transaction: &u8,
+        ) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send> {
+            if self.transaction_id == *transaction {
+                Box::new(futures::future::ok(
+                    QueryMatchResult::yes_with_confirmations(self.confirmations_needed),
+                ))
+            } else {
+                Box::new(futures::future::ok(QueryMatchResult::no()))
+            }
+        }
+
+        fn is_empty(&self) -> bool {
+            false
+        }
+
+        fn from_block_height(&self) -> Option<u64> {
+            self.from_block_height
+        }
+    }
+
+    impl Transaction for u8 {
+        fn transaction_id(&self) -> String {
+            self.to_string()
+        }
+    }
+
+    /// Block `n` holds a single transaction with id `n`.
+    #[derive(Clone, Debug)]
+    struct TestBlock(Vec<u8>);
+
+    impl Block for TestBlock {
+        type Transaction = u8;
+
+        fn blockhash(&self) -> String {
+            format!("block-{}", self.0[0])
+        }
+        fn prev_blockhash(&self) -> String {
+            format!("block-{}", self.0[0] - 1)
+        }
+        fn height(&self) -> u64 {
+            u64::from(self.0[0])
+        }
+        fn transactions(&self) -> &[u8] {
+            self.0.as_slice()
+        }
+    }
+
+    /// Records which heights were asked for
+    #[derive(Default)]
+    struct TestChain(Arc<Mutex<Vec<u64>>>);
+
+    impl BlockSource<TestBlock> for TestChain {
+        fn blocks_between(
+            &self,
+            from: u64,
+            to: u64,
+        ) -> Box<dyn Stream<Item = TestBlock, Error = ()> + Send> {
+            self.0.lock().unwrap().extend(from..=to);
+            Box::new(stream::iter_ok(
+                (from..=to).map(|height| TestBlock(vec![height as u8])),
+            ))
+        }
+    }
+
+    struct Setup {
+        repository: BackfillingQueryRepository<TestBlock, TransactionQuery>,
+        results: Arc<InMemoryQueryResultRepository<TransactionQuery>>,
+        pending_transactions: PendingTransactions,
+        scanned_heights: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl Setup {
+        fn with_latest_block(height: u64) -> Self {
+            let results = Arc::new(InMemoryQueryResultRepository::default());
+            let pending_transactions = Arc::new(Mutex::new(Vec::new()));
+            let latest_block_repository = Arc::new(InMemoryLatestBlockRepository::default());
+            latest_block_repository.set(LatestBlock {
+                height,
+                timestamp: 0,
+            });
+            let chain = TestChain::default();
+            let scanned_heights = Arc::clone(&chain.0);
+
+            let repository = BackfillingQueryRepository::new(
+                Arc::new(InMemoryQueryRepository::default()),
+                results.clone(),
+                Arc::clone(&pending_transactions),
+                Arc::new(Mutex::new(VecDeque::new())),
+                Arc::new(Mutex::new(None)),
+                latest_block_repository,
+                Arc::new(chain),
+            );
+
+            Setup {
+                repository,
+                results,
+                pending_transactions,
+                scanned_heights,
+            }
+        }
+
+        fn backfill(&self, query: TransactionQuery) -> u32 {
+            let id = self.repository.queries.save(query.clone()).unwrap();
+            let matched_up_to = *self.repository.matched_up_to.lock().unwrap();
+            self.repository
+                .backfill(id, query, matched_up_to)
+                .wait()
+                .unwrap();
+            id
+        }
+    }
+
+    #[test]
+    fn given_query_from_earlier_height_matches_already_mined_transaction() {
+        let setup = Setup::with_latest_block(10);
+
+        let id = setup.backfill(TransactionQuery {
+            transaction_id: 5,
+            confirmations_needed: 1,
+            from_block_height: Some(3),
+        });
+
+        assert_that(&setup.results.get(id).map(|result| result.0))
+            .is_some()
+            .is_equal_to(vec![String::from("5")]);
+    }
+
+    #[test]
+    fn given_query_from_later_height_ignores_older_transaction() {
+        let setup = Setup::with_latest_block(10);
+
+        let id = setup.backfill(TransactionQuery {
+            transaction_id: 5,
+            confirmations_needed: 1,
+            from_block_height: Some(6),
+        });
+
+        assert_that(&setup.results.get(id)).is_none();
+    }
+
+    #[test]
+    fn given_too_few_confirmations_transaction_is_left_pending() {
+        let setup = Setup::with_latest_block(10);
+
+        let id = setup.backfill(TransactionQuery {
+            transaction_id: 9,
+            confirmations_needed: 5,
+            from_block_height: Some(0),
+        });
+
+        assert_that(&setup.results.get(id)).is_none();
+        assert_that(&*setup.pending_transactions.lock().unwrap()).has_length(1);
+    }
+
+    #[test]
+    fn given_block_processor_matched_a_block_the_latest_block_does_not_know_about_yet_backfill_includes_it(
+    ) {
+        let setup = Setup::with_latest_block(10);
+        *setup.repository.matched_up_to.lock().unwrap() = Some(11);
+
+        let id = setup.backfill(TransactionQuery {
+            transaction_id: 11,
+            confirmations_needed: 1,
+            from_block_height: Some(3),
+        });
+
+        assert_that(&setup.results.get(id).map(|result| result.0))
+            .is_some()
+            .is_equal_to(vec![String::from("11")]);
+    }
+
+    #[test]
+    fn given_query_from_too_far_back_only_the_latest_blocks_are_scanned() {
+        let setup = Setup::with_latest_block(MAX_BACKFILL_BLOCKS + 9);
+
+        setup.backfill(TransactionQuery {
+            transaction_id: 5,
+            confirmations_needed: 1,
+            from_block_height: Some(0),
+        });
+
+        let scanned_heights = setup.scanned_heights.lock().unwrap();
+        assert_that(&scanned_heights.first()).is_equal_to(Some(&10));
+        assert_that(&*scanned_heights).has_length(MAX_BACKFILL_BLOCKS as usize);
+    }
+}
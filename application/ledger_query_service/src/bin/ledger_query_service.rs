@@ -4,23 +4,45 @@
 #[macro_use]
 extern crate log;
 
+use bitcoin_support::MinedBlock;
 use config::ConfigError;
-use ethereum_support::web3::{
-    transports::{EventLoopHandle, Http},
-    Web3,
+use ethereum_support::{
+    web3::{
+        transports::{EventLoopHandle, Http},
+        Web3,
+    },
+    Block as EthereumBlock, Transaction as EthereumTransaction,
+};
+use futures::{
+    future::{self, Either},
+    stream::Stream,
+    Future,
 };
-use futures::stream::Stream;
 use ledger_query_service::{
     bitcoin::{BitcoinBlockQuery, BitcoinTransactionQuery},
-    ethereum::{EthereumBlockQuery, EthereumTransactionQuery},
-    settings::{self, Settings},
-    BlockProcessor, DefaultBlockProcessor, InMemoryQueryRepository, InMemoryQueryResultRepository,
-    QueryResultRepository, RouteFactory,
+    delete_expired_queries, disk_store,
+    ethereum::{
+        EthereumBlockQuery, EthereumBlockWithReceipts, EthereumTransactionLogQuery,
+        EthereumTransactionQuery,
+    },
+    settings::{self, BitcoinBlockSource, Settings, Storage},
+    BackfillingQueryRepository, BitcoindRpcBlockSource, BlockProcessor, BlockSource,
+    DefaultBlockProcessor, DiskLatestBlockRepository, DiskQueryRepository,
+    DiskQueryResultRepository, InMemoryLatestBlockRepository, InMemoryQueryRepository,
+    InMemoryQueryResultRepository, LatestBlock, LatestBlockRepository,
+    NotifyingQueryResultRepository, QueryRepository, QueryResultRepository, RouteFactory,
+    UnconfirmedMatches,
 };
-use std::{env::var, sync::Arc};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{env::var, sync::Arc, time::Duration};
 use tokio::runtime::Runtime;
 use warp::{self, filters::BoxedFilter, Filter, Reply};
 
+/// How many blocks a transaction may sit in the mempool before its match is
+/// dropped, about a day on either ledger
+const UNCONFIRMED_MATCH_MAX_AGE_BITCOIN: u64 = 144;
+const UNCONFIRMED_MATCH_MAX_AGE_ETHEREUM: u64 = 5760;
+
 fn main() -> Result<(), failure::Error> {
     let _ = pretty_env_logger::try_init();
 
@@ -31,10 +53,19 @@ fn main() -> Result<(), failure::Error> {
 
     let route_factory = RouteFactory::new(settings.http_api.external_url);
 
-    let bitcoin_routes = create_bitcoin_routes(&mut runtime, &route_factory, settings.bitcoin);
+    let bitcoin_routes = create_bitcoin_routes(
+        &mut runtime,
+        &route_factory,
+        &settings.storage,
+        settings.bitcoin,
+    )?;
 
-    let (ethereum_routes, _event_loop_handle) =
-        create_ethereum_routes(&mut runtime, &route_factory, settings.ethereum);
+    let (ethereum_routes, _event_loop_handle) = create_ethereum_routes(
+        &mut runtime,
+        &route_factory,
+        &settings.storage,
+        settings.ethereum,
+    )?;
 
     let routes = bitcoin_routes.or(ethereum_routes);
 
@@ -45,113 +76,337 @@ fn main() -> Result<(), failure::Error> {
 fn create_bitcoin_routes(
     runtime: &mut Runtime,
     route_factory: &RouteFactory,
+    storage: &Storage,
     settings: settings::Bitcoin,
-) -> BoxedFilter<(impl Reply,)> {
+) -> Result<BoxedFilter<(impl Reply,)>, failure::Error> {
     let transaction_query_repository =
-        Arc::new(InMemoryQueryRepository::<BitcoinTransactionQuery>::default());
-    let block_query_repository = Arc::new(InMemoryQueryRepository::<BitcoinBlockQuery>::default());
-    let transaction_query_result_repository = Arc::new(InMemoryQueryResultRepository::default());
-    let block_query_result_repository = Arc::new(InMemoryQueryResultRepository::default());
-
-    let bitcoin_rpc_client = bitcoin_rpc_client::BitcoinCoreClient::new(
+        query_repository::<BitcoinTransactionQuery>(storage, "bitcoin/transaction_queries")?;
+    let block_query_repository =
+        query_repository::<BitcoinBlockQuery>(storage, "bitcoin/block_queries")?;
+    let transaction_query_result_repository = Arc::new(NotifyingQueryResultRepository::new(
+        query_result_repository(storage, "bitcoin/transaction_results")?,
+    ));
+    let block_query_result_repository = Arc::new(NotifyingQueryResultRepository::new(
+        query_result_repository(storage, "bitcoin/block_results")?,
+    ));
+    let latest_block_repository = latest_block_repository(storage, "bitcoin/latest_block")?;
+    let unconfirmed_transaction_matches: Arc<UnconfirmedMatches<BitcoinTransactionQuery>> =
+        Arc::new(UnconfirmedMatches::expiring_after(
+            UNCONFIRMED_MATCH_MAX_AGE_BITCOIN,
+        ));
+
+    let bitcoin_rpc_client = Arc::new(bitcoin_rpc_client::BitcoinCoreClient::new(
         settings.node_url.as_str(),
         settings.node_username.as_str(),
         settings.node_password.as_str(),
-    );
-
-    info!("Connect BitcoinZmqListener to {}", settings.zmq_endpoint);
+    ));
+
+    let bitcoind_rpc_block_source = BitcoindRpcBlockSource::new(Arc::clone(&bitcoin_rpc_client));
+    let historical_blocks: Arc<dyn BlockSource<MinedBlock>> =
+        Arc::new(bitcoind_rpc_block_source.clone());
+    let mut transaction_processor = persisted(
+        DefaultBlockProcessor::new(
+            transaction_query_repository.clone(),
+            block_query_repository.clone(),
+            transaction_query_result_repository.clone(),
+            block_query_result_repository.clone(),
+        )
+        .fetching_ancestors_from(Arc::clone(&historical_blocks)),
+        storage,
+        "bitcoin/block_processor",
+    )?;
 
-    let mut transaction_processor = DefaultBlockProcessor::new(
-        transaction_query_repository.clone(),
-        block_query_repository.clone(),
+    let transaction_query_repository = Arc::new(BackfillingQueryRepository::new(
+        transaction_query_repository,
         transaction_query_result_repository.clone(),
-    );
+        transaction_processor.pending_transactions(),
+        transaction_processor.recent_blocks(),
+        transaction_processor.matched_up_to(),
+        latest_block_repository.clone(),
+        historical_blocks,
+    ));
+
+    if let Some(mempool) = settings.mempool {
+        let transaction_query_repository = transaction_query_repository.clone();
+        let unconfirmed_transaction_matches = unconfirmed_transaction_matches.clone();
+
+        let bitcoin_transactions =
+            ledger_query_service::bitcoin_transaction_listener(mempool.zmq_endpoint.as_str())
+                .expect("Should return a Bitcoind receiver for unconfirmed transactions");
+        let mempool_processor = bitcoin_transactions.for_each(move |transaction| {
+            ledger_query_service::match_unconfirmed_transaction(
+                &*transaction_query_repository,
+                unconfirmed_transaction_matches.clone(),
+                &transaction,
+            )
+        });
+        runtime.spawn(mempool_processor);
+    }
 
     {
+        let transaction_query_repository = transaction_query_repository.clone();
+        let block_query_repository = block_query_repository.clone();
         let transaction_query_result_repository = transaction_query_result_repository.clone();
         let block_query_result_repository = block_query_result_repository.clone();
-
-        let bitcoin_blocks =
-            ledger_query_service::bitcoin_block_listener(settings.zmq_endpoint.as_str())
-                .expect("Should return a Bitcoind received for MinedBlocks");
+        let latest_block_repository = latest_block_repository.clone();
+        let unconfirmed_transaction_matches = unconfirmed_transaction_matches.clone();
+        let mined_transactions = unconfirmed_transaction_matches.clone();
+
+        let bitcoin_blocks = bitcoin_block_stream(
+            runtime,
+            settings.block_source,
+            bitcoind_rpc_block_source,
+            latest_block_repository
+                .get()
+                .map(|latest_block| latest_block.height),
+        )?;
         let bitcoin_processor = bitcoin_blocks
-            .and_then(move |block| transaction_processor.process(block))
-            .for_each(move |(block_results, transaction_results)| {
-                for (id, block_id) in block_results {
-                    block_query_result_repository.add_result(id, block_id);
-                }
-                for (id, tx_id) in transaction_results {
-                    transaction_query_result_repository.add_result(id, tx_id);
-                }
-                Ok(())
-            });
+            .inspect(move |block| mined_transactions.remove_mined(block))
+            .and_then(move |block| {
+                let latest_block = LatestBlock::from(&block);
+                transaction_processor
+                    .process(block)
+                    .map(move |results| (latest_block, results))
+            })
+            .for_each(
+                move |(latest_block, (block_results, transaction_results))| {
+                    for (id, block_id) in block_results {
+                        block_query_result_repository.add_result(id, block_id);
+                    }
+                    for (id, tx_id) in transaction_results {
+                        transaction_query_result_repository.add_result(id, tx_id);
+                    }
+                    let height = latest_block.height;
+                    // Only advance once the matches are stored, otherwise a
+                    // crash in between would lose them for good
+                    latest_block_repository.set(latest_block);
+
+                    for id in delete_expired_queries(
+                        &*transaction_query_repository,
+                        &*transaction_query_result_repository,
+                        height,
+                    ) {
+                        unconfirmed_transaction_matches.delete(id);
+                    }
+                    delete_expired_queries(
+                        &*block_query_repository,
+                        &*block_query_result_repository,
+                        height,
+                    );
+                    Ok(())
+                },
+            );
         runtime.spawn(bitcoin_processor);
     }
 
-    let client = Arc::new(bitcoin_rpc_client);
-
     let ledger_name = "bitcoin";
 
     let transaction_routes = route_factory.create(
         transaction_query_repository,
         transaction_query_result_repository,
-        Some(Arc::clone(&client)),
+        unconfirmed_transaction_matches,
+        Some(bitcoin_rpc_client),
         ledger_name,
     );
 
     let block_routes = route_factory.create(
         block_query_repository,
         block_query_result_repository,
+        Arc::new(UnconfirmedMatches::default()),
         None,
         ledger_name,
     );
 
-    transaction_routes.or(block_routes).boxed()
+    let latest_block_route =
+        route_factory.create_latest_block_route(latest_block_repository, ledger_name);
+
+    Ok(transaction_routes
+        .or(block_routes)
+        .or(latest_block_route)
+        .boxed())
 }
 
 fn create_ethereum_routes(
     runtime: &mut Runtime,
     route_factory: &RouteFactory,
+    storage: &Storage,
     settings: settings::Ethereum,
-) -> (BoxedFilter<(impl Reply,)>, EventLoopHandle) {
+) -> Result<(BoxedFilter<(impl Reply,)>, EventLoopHandle), disk_store::Error> {
     let transaction_query_repository =
-        Arc::new(InMemoryQueryRepository::<EthereumTransactionQuery>::default());
-    let block_query_repository = Arc::new(InMemoryQueryRepository::<EthereumBlockQuery>::default());
-    let transaction_query_result_repository = Arc::new(InMemoryQueryResultRepository::default());
-    let block_query_result_repository = Arc::new(InMemoryQueryResultRepository::default());
+        query_repository::<EthereumTransactionQuery>(storage, "ethereum/transaction_queries")?;
+    let block_query_repository =
+        query_repository::<EthereumBlockQuery>(storage, "ethereum/block_queries")?;
+    let log_query_repository =
+        query_repository::<EthereumTransactionLogQuery>(storage, "ethereum/log_queries")?;
+    let transaction_query_result_repository = Arc::new(NotifyingQueryResultRepository::new(
+        query_result_repository(storage, "ethereum/transaction_results")?,
+    ));
+    let block_query_result_repository = Arc::new(NotifyingQueryResultRepository::new(
+        query_result_repository(storage, "ethereum/block_results")?,
+    ));
+    let log_query_result_repository = Arc::new(NotifyingQueryResultRepository::new(
+        query_result_repository(storage, "ethereum/log_results")?,
+    ));
+    let latest_block_repository = latest_block_repository(storage, "ethereum/latest_block")?;
+    let unconfirmed_transaction_matches: Arc<UnconfirmedMatches<EthereumTransactionQuery>> =
+        Arc::new(UnconfirmedMatches::expiring_after(
+            UNCONFIRMED_MATCH_MAX_AGE_ETHEREUM,
+        ));
 
     info!("Starting EthereumSimpleListener on {}", settings.node_url);
 
-    let mut transaction_processor = DefaultBlockProcessor::new(
-        transaction_query_repository.clone(),
-        block_query_repository.clone(),
-        transaction_query_result_repository.clone(),
-    );
-
     let (event_loop, transport) =
         Http::new(settings.node_url.as_str()).expect("unable to connect to Ethereum node");
     let web3_client = Arc::new(Web3::new(transport));
 
+    let historical_blocks: Arc<dyn BlockSource<EthereumBlock<EthereumTransaction>>> =
+        web3_client.clone();
+    let historical_blocks_with_receipts: Arc<dyn BlockSource<EthereumBlockWithReceipts>> =
+        web3_client.clone();
+
+    let mut transaction_processor = persisted(
+        DefaultBlockProcessor::new(
+            transaction_query_repository.clone(),
+            block_query_repository.clone(),
+            transaction_query_result_repository.clone(),
+            block_query_result_repository.clone(),
+        )
+        .fetching_ancestors_from(Arc::clone(&historical_blocks)),
+        storage,
+        "ethereum/block_processor",
+    )?;
+
+    // Block queries are served by the transaction processor, this one never
+    // gets any
+    let mut log_processor = persisted(
+        DefaultBlockProcessor::new(
+            log_query_repository.clone(),
+            Arc::new(InMemoryQueryRepository::<EthereumBlockQuery>::default()),
+            log_query_result_repository.clone(),
+            Arc::new(InMemoryQueryResultRepository::default()),
+        )
+        .fetching_ancestors_from(Arc::clone(&historical_blocks_with_receipts)),
+        storage,
+        "ethereum/log_processor",
+    )?;
+
+    let transaction_query_repository = Arc::new(BackfillingQueryRepository::new(
+        transaction_query_repository,
+        transaction_query_result_repository.clone(),
+        transaction_processor.pending_transactions(),
+        transaction_processor.recent_blocks(),
+        transaction_processor.matched_up_to(),
+        latest_block_repository.clone(),
+        historical_blocks,
+    ));
+
+    let log_query_repository = Arc::new(BackfillingQueryRepository::new(
+        log_query_repository,
+        log_query_result_repository.clone(),
+        log_processor.pending_transactions(),
+        log_processor.recent_blocks(),
+        log_processor.matched_up_to(),
+        latest_block_repository.clone(),
+        historical_blocks_with_receipts,
+    ));
+
+    if settings.poll_mempool {
+        let transaction_query_repository = transaction_query_repository.clone();
+        let unconfirmed_transaction_matches = unconfirmed_transaction_matches.clone();
+
+        let pending_transactions = ledger_query_service::ethereum_pending_transaction_listener(
+            web3_client.clone(),
+            settings.poll_interval_secs,
+        )
+        .expect("Should return a Web3 pending transaction poller");
+        let mempool_processor = pending_transactions.for_each(move |transaction| {
+            ledger_query_service::match_unconfirmed_transaction(
+                &*transaction_query_repository,
+                unconfirmed_transaction_matches.clone(),
+                &transaction,
+            )
+        });
+        runtime.spawn(mempool_processor);
+    }
+
     {
+        let transaction_query_repository = transaction_query_repository.clone();
+        let block_query_repository = block_query_repository.clone();
         let transaction_query_result_repository = transaction_query_result_repository.clone();
         let block_query_result_repository = block_query_result_repository.clone();
+        let latest_block_repository = latest_block_repository.clone();
+        let unconfirmed_transaction_matches = unconfirmed_transaction_matches.clone();
+        let mined_transactions = unconfirmed_transaction_matches.clone();
 
         let web3_blocks = ledger_query_service::ethereum_block_listener(
             web3_client.clone(),
             settings.poll_interval_secs,
+            latest_block_repository
+                .get()
+                .map(|latest_block| latest_block.height),
         )
         .expect("Should return a Web3 block poller");
+        let log_query_result_repository = log_query_result_repository.clone();
+        let log_query_repository = log_query_repository.clone();
+        let expiring_log_query_repository = log_query_repository.clone();
+        let web3_client = web3_client.clone();
+
         let web3_processor = web3_blocks
-            .and_then(move |block| transaction_processor.process(block))
-            .for_each(move |(block_results, transaction_results)| {
-                for (id, block_id) in block_results {
-                    block_query_result_repository.add_result(id, block_id);
-                }
-                for (id, tx_id) in transaction_results {
-                    transaction_query_result_repository.add_result(id, tx_id);
+            .inspect(move |block| mined_transactions.remove_mined(block))
+            .and_then(move |block| {
+                if log_query_repository.all().next().is_some() {
+                    Either::A(ledger_query_service::ethereum_transaction_receipts(
+                        &web3_client,
+                        block,
+                    ))
+                } else {
+                    Either::B(future::ok(EthereumBlockWithReceipts {
+                        block,
+                        receipts: Vec::new(),
+                    }))
                 }
-                Ok(())
-            });
+            })
+            .and_then(move |block_with_receipts| {
+                let latest_block = LatestBlock::from(&block_with_receipts.block);
+                transaction_processor
+                    .process(block_with_receipts.block.clone())
+                    .join(log_processor.process(block_with_receipts))
+                    .map(move |results| (latest_block, results))
+            })
+            .for_each(
+                move |(latest_block, ((block_results, transaction_results), (_, log_results)))| {
+                    for (id, block_id) in block_results {
+                        block_query_result_repository.add_result(id, block_id);
+                    }
+                    for (id, tx_id) in transaction_results {
+                        transaction_query_result_repository.add_result(id, tx_id);
+                    }
+                    for (id, tx_id) in log_results {
+                        log_query_result_repository.add_result(id, tx_id);
+                    }
+                    let height = latest_block.height;
+                    latest_block_repository.set(latest_block);
+
+                    for id in delete_expired_queries(
+                        &*transaction_query_repository,
+                        &*transaction_query_result_repository,
+                        height,
+                    ) {
+                        unconfirmed_transaction_matches.delete(id);
+                    }
+                    delete_expired_queries::<EthereumBlock<EthereumTransaction>, _>(
+                        &*block_query_repository,
+                        &*block_query_result_repository,
+                        height,
+                    );
+                    delete_expired_queries(
+                        &*expiring_log_query_repository,
+                        &*log_query_result_repository,
+                        height,
+                    );
+                    Ok(())
+                },
+            );
         runtime.spawn(web3_processor);
     }
 
@@ -160,18 +415,141 @@ fn create_ethereum_routes(
     let transaction_routes = route_factory.create(
         transaction_query_repository,
         transaction_query_result_repository,
+        unconfirmed_transaction_matches,
         Some(Arc::clone(&web3_client)),
         ledger_name,
     );
 
-    let block_routes = route_factory.create(
+    let block_routes = route_factory.create::<EthereumBlock<EthereumTransaction>, _, _>(
         block_query_repository,
         block_query_result_repository,
+        Arc::new(UnconfirmedMatches::default()),
         None,
         ledger_name,
     );
 
-    (transaction_routes.or(block_routes).boxed(), event_loop)
+    // Receipts, and therefore logs, only exist once a transaction is mined
+    let log_routes = route_factory.create(
+        log_query_repository,
+        log_query_result_repository,
+        Arc::new(UnconfirmedMatches::default()),
+        Some(Arc::clone(&web3_client)),
+        ledger_name,
+    );
+
+    let latest_block_route =
+        route_factory.create_latest_block_route(latest_block_repository, ledger_name);
+
+    Ok((
+        transaction_routes
+            .or(block_routes)
+            .or(log_routes)
+            .or(latest_block_route)
+            .boxed(),
+        event_loop,
+    ))
+}
+
+fn bitcoin_block_stream(
+    runtime: &mut Runtime,
+    block_source: BitcoinBlockSource,
+    source: BitcoindRpcBlockSource,
+    resume_after: Option<u64>,
+) -> Result<Box<dyn Stream<Item = MinedBlock, Error = ()> + Send>, failure::Error> {
+    match block_source {
+        BitcoinBlockSource::Zmq { endpoint } => {
+            info!("Connect BitcoinZmqListener to {}", endpoint);
+            // Subscribing before asking for the tip makes sure that blocks
+            // mined in between arrive over ZeroMQ
+            let new_blocks = ledger_query_service::bitcoin_block_listener(endpoint.as_str())
+                .expect("Should return a Bitcoind received for MinedBlocks");
+
+            let height = match resume_after {
+                Some(height) => height,
+                None => return Ok(Box::new(new_blocks)),
+            };
+
+            // ZeroMQ only delivers what is mined while subscribed, the blocks
+            // mined since the last run are fetched over RPC
+            let header_source = source.clone();
+            let tip = source
+                .best_block_hash()
+                .and_then(move |blockhash| header_source.block_header(&blockhash))
+                .map(|tip| tip.height);
+            let tip = runtime.block_on(tip).map_err(|e| {
+                failure::err_msg(format!(
+                    "Cannot replay the Bitcoin blocks mined after {} because bitcoind does not tell its height: {:?}",
+                    height, e
+                ))
+            })?;
+            let missed_blocks = ledger_query_service::bitcoin_blocks_between(
+                source,
+                height + 1,
+                tip,
+                Duration::from_secs(1),
+            );
+
+            Ok(Box::new(missed_blocks.chain(
+                new_blocks.filter(move |block| u64::from(block.height) > tip),
+            )))
+        }
+        BitcoinBlockSource::RpcPolling { poll_interval_secs } => Ok(
+            ledger_query_service::bitcoin_block_poller(source, poll_interval_secs, resume_after),
+        ),
+    }
+}
+
+fn persisted<T, B, TQ, BQ>(
+    block_processor: DefaultBlockProcessor<T, B, TQ, BQ>,
+    storage: &Storage,
+    name: &str,
+) -> Result<DefaultBlockProcessor<T, B, TQ, BQ>, disk_store::Error> {
+    match storage {
+        Storage::InMemory => Ok(block_processor),
+        Storage::Disk { path } => block_processor.persisted_at(path.join(format!("{}.json", name))),
+    }
+}
+
+fn query_repository<Q: Serialize + DeserializeOwned + Send + Sync + Clone + 'static>(
+    storage: &Storage,
+    name: &str,
+) -> Result<Arc<dyn QueryRepository<Q>>, disk_store::Error> {
+    let repository: Arc<dyn QueryRepository<Q>> = match storage {
+        Storage::InMemory => Arc::new(InMemoryQueryRepository::default()),
+        Storage::Disk { path } => Arc::new(DiskQueryRepository::open(
+            path.join(format!("{}.json", name)),
+        )?),
+    };
+
+    Ok(repository)
+}
+
+fn query_result_repository<Q: Send + Sync + Clone + 'static>(
+    storage: &Storage,
+    name: &str,
+) -> Result<Arc<dyn QueryResultRepository<Q>>, disk_store::Error> {
+    let repository: Arc<dyn QueryResultRepository<Q>> = match storage {
+        Storage::InMemory => Arc::new(InMemoryQueryResultRepository::default()),
+        Storage::Disk { path } => Arc::new(DiskQueryResultRepository::open(
+            path.join(format!("{}.jsonl", name)),
+        )?),
+    };
+
+    Ok(repository)
+}
+
+fn latest_block_repository(
+    storage: &Storage,
+    name: &str,
+) -> Result<Arc<dyn LatestBlockRepository>, disk_store::Error> {
+    let repository: Arc<dyn LatestBlockRepository> = match storage {
+        Storage::InMemory => Arc::new(InMemoryLatestBlockRepository::default()),
+        Storage::Disk { path } => Arc::new(DiskLatestBlockRepository::open(
+            path.join(format!("{}.json", name)),
+        )?),
+    };
+
+    Ok(repository)
 }
 
 fn load_settings() -> Result<Settings, ConfigError> {
@@ -1,8 +1,19 @@
-use crate::{query_repository::QueryRepository, query_result_repository::QueryResultRepository};
-use futures::{future::join_all, Future};
+use crate::{
+    backfill::BlockSource,
+    disk_store::{self, read_json, write_json},
+    query_index::QueryIndex,
+    query_repository::QueryRepository,
+    query_result_repository::QueryResultRepository,
+};
+use futures::{
+    future::{self, join_all, Either, Loop},
+    stream, Future, Stream,
+};
 use std::{
+    collections::VecDeque,
     fmt::Debug,
     marker::PhantomData,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
 };
 use tokio;
@@ -18,6 +29,12 @@ pub trait BlockProcessor<B> {
 
 pub trait Transaction: Debug + 'static + Clone {
     fn transaction_id(&self) -> String;
+
+    /// The keys under which queries that could match this transaction are
+    /// filed, see `Query::index_key`.
+    fn index_keys(&self) -> Vec<String> {
+        Vec::new()
+    }
 }
 
 pub trait Block: Debug + 'static + Clone {
@@ -25,12 +42,38 @@ pub trait Block: Debug + 'static + Clone {
 
     fn blockhash(&self) -> String;
     fn prev_blockhash(&self) -> String;
+    fn height(&self) -> u64;
     fn transactions(&self) -> &[Self::Transaction];
 }
 
 pub trait Query<O>: Debug + 'static {
     fn matches(&self, object: &O) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send>;
     fn is_empty(&self) -> bool;
+
+    /// The height to scan already mined blocks from when the query is
+    /// created. Without one, only blocks processed afterwards are matched.
+    fn from_block_height(&self) -> Option<u64> {
+        None
+    }
+
+    /// Whether matching transactions are also reported while they are still
+    /// waiting in the mempool.
+    fn include_unconfirmed(&self) -> bool {
+        false
+    }
+
+    /// The height of the block after which the query is deleted along with
+    /// its results. Without one, the query lives until it is deleted through
+    /// the API.
+    fn expires_at_block(&self) -> Option<u64> {
+        None
+    }
+
+    /// A key that every matching transaction lists among its `index_keys`.
+    /// Queries without one are matched against every transaction.
+    fn index_key(&self) -> Option<String> {
+        None
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -55,13 +98,107 @@ impl QueryMatchResult {
     }
 }
 
-#[derive(Debug)]
+/// How many of the most recently processed blocks are remembered. A reorg that
+/// goes deeper than this cannot be rolled back.
+pub const MAX_REORG_DEPTH: usize = 100;
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PendingTransaction {
     matching_query_id: u32,
     tx_id: String,
+    blockhash: String,
     pending_confirmations: u32,
 }
 
+impl PendingTransaction {
+    pub fn new(
+        matching_query_id: u32,
+        tx_id: String,
+        blockhash: String,
+        pending_confirmations: u32,
+    ) -> Self {
+        PendingTransaction {
+            matching_query_id,
+            tx_id,
+            blockhash,
+            pending_confirmations,
+        }
+    }
+}
+
+pub type PendingTransactions = Arc<Mutex<Vec<PendingTransaction>>>;
+
+/// Everything that processing a block added to the query results, so it can be
+/// taken back if the block gets orphaned.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcessedBlock {
+    blockhash: String,
+    block_matches: Vec<QueryMatch>,
+    transaction_matches: Vec<QueryMatch>,
+    confirmed_transactions: Vec<PendingTransaction>,
+}
+
+impl ProcessedBlock {
+    fn new(blockhash: String) -> Self {
+        ProcessedBlock {
+            blockhash,
+            block_matches: Vec::new(),
+            transaction_matches: Vec::new(),
+            confirmed_transactions: Vec::new(),
+        }
+    }
+}
+
+/// The most recently processed blocks, oldest first.
+pub type RecentBlocks = Arc<Mutex<VecDeque<ProcessedBlock>>>;
+
+/// The height of the last block whose transactions were matched against the
+/// queries known at the time. A query saved while holding the lock is matched
+/// by the block processor against every later block.
+pub type MatchedUpTo = Arc<Mutex<Option<u64>>>;
+
+/// Files a match that was found in the already processed block `blockhash`
+/// but not by processing it, so that a reorg retracts it like the others.
+/// Matches that waited for confirmations are filed with the block that
+/// confirmed them. Blocks that are no longer remembered cannot be rolled back
+/// anyway.
+pub fn record_confirmed_match(
+    recent_blocks: &RecentBlocks,
+    blockhash: &str,
+    confirmations_needed: u32,
+    (query_id, tx_id): QueryMatch,
+) {
+    let mut recent_blocks = recent_blocks.lock().unwrap();
+
+    let position = match recent_blocks
+        .iter()
+        .rposition(|processed_block| processed_block.blockhash == blockhash)
+    {
+        Some(position) => position,
+        None => return,
+    };
+
+    if confirmations_needed <= 1 {
+        recent_blocks[position]
+            .transaction_matches
+            .push((query_id, tx_id));
+    } else if let Some(confirming_block) =
+        recent_blocks.get_mut(position + confirmations_needed as usize - 1)
+    {
+        confirming_block
+            .confirmed_transactions
+            .push(PendingTransaction {
+                matching_query_id: query_id,
+                tx_id,
+                blockhash: blockhash.to_string(),
+                pending_confirmations: 0,
+            });
+    }
+}
+
+/// What a `DefaultBlockProcessor` needs to carry on after a restart.
+type PersistedState = (Vec<PendingTransaction>, VecDeque<ProcessedBlock>);
+
 type ArcQueryRepository<Q> = Arc<dyn QueryRepository<Q>>;
 type ArcQueryResultRepository<Q> = Arc<dyn QueryResultRepository<Q>>;
 
@@ -73,44 +210,103 @@ pub struct DefaultBlockProcessor<T, B, TQ, BQ> {
     block_queries: ArcQueryRepository<BQ>,
     #[debug_stub = "Results"]
     transaction_results: ArcQueryResultRepository<TQ>,
-    pending_transactions: Arc<Mutex<Vec<PendingTransaction>>>,
-    blockhashes: Vec<String>,
+    #[debug_stub = "Results"]
+    block_results: ArcQueryResultRepository<BQ>,
+    pending_transactions: PendingTransactions,
+    recent_blocks: RecentBlocks,
+    matched_up_to: MatchedUpTo,
+    state_path: Option<PathBuf>,
+    #[debug_stub = "BlockSource"]
+    ancestors: Option<Arc<dyn BlockSource<B>>>,
     tx_type: PhantomData<T>,
     block_type: PhantomData<B>,
 }
 
-impl<T: Transaction, B: Block<Transaction = T>, TQ: Query<T> + 'static, BQ: Query<B> + 'static>
-    BlockProcessor<B> for DefaultBlockProcessor<T, B, TQ, BQ>
+impl<T, B, TQ, BQ> Clone for DefaultBlockProcessor<T, B, TQ, BQ> {
+    fn clone(&self) -> Self {
+        Self {
+            transaction_queries: Arc::clone(&self.transaction_queries),
+            block_queries: Arc::clone(&self.block_queries),
+            transaction_results: Arc::clone(&self.transaction_results),
+            block_results: Arc::clone(&self.block_results),
+            pending_transactions: Arc::clone(&self.pending_transactions),
+            recent_blocks: Arc::clone(&self.recent_blocks),
+            matched_up_to: Arc::clone(&self.matched_up_to),
+            state_path: self.state_path.clone(),
+            ancestors: self.ancestors.clone(),
+            tx_type: PhantomData,
+            block_type: PhantomData,
+        }
+    }
+}
+
+impl<
+        T: Transaction + Send,
+        B: Block<Transaction = T> + Send,
+        TQ: Query<T> + 'static,
+        BQ: Query<B> + 'static,
+    > BlockProcessor<B> for DefaultBlockProcessor<T, B, TQ, BQ>
 {
     fn process(
         &mut self,
         block: B,
     ) -> Box<dyn Future<Item = (Vec<QueryMatch>, Vec<QueryMatch>), Error = ()> + Send> {
         trace!("New block received: {:?}", block);
-        // for now work on the assumption that there is one blockchain, but warn
-        // every time that assumption doesn't hold, by comparing the previous
-        // blockhash to the most recent member of a list of ordered blockhashes
-        if let Some(last_blockhash) = self.blockhashes.last() {
-            if *last_blockhash != block.prev_blockhash() {
-                warn!(
-                    "Block {} lists {} as previous block but last processed block was {}",
-                    block.blockhash(),
-                    block.prev_blockhash(),
-                    last_blockhash
-                );
-            }
+        if self.ancestors.is_none() || !self.is_unknown_parent(&block.prev_blockhash()) {
+            return self.process_block(block);
         }
 
-        self.blockhashes.push(block.blockhash());
-        self.update_pending_transactions();
+        let processor = self.clone();
+        Box::new(
+            self.missing_ancestors_of(&block)
+                .and_then(move |ancestors| {
+                    stream::iter_ok(ancestors.into_iter().chain(Some(block)))
+                        .and_then(move |block| processor.process_block(block))
+                        .fold(
+                            (Vec::new(), Vec::new()),
+                            |(mut all_block_matches, mut all_transaction_matches),
+                             (block_matches, transaction_matches)| {
+                                all_block_matches.extend(block_matches);
+                                all_transaction_matches.extend(transaction_matches);
+                                Ok((all_block_matches, all_transaction_matches))
+                            },
+                        )
+                }),
+        )
+    }
+}
+
+impl<
+        T: Transaction + Send,
+        B: Block<Transaction = T> + Send,
+        TQ: Query<T> + 'static,
+        BQ: Query<B>,
+    > DefaultBlockProcessor<T, B, TQ, BQ>
+{
+    fn process_block(
+        &self,
+        block: B,
+    ) -> Box<dyn Future<Item = (Vec<QueryMatch>, Vec<QueryMatch>), Error = ()> + Send> {
+        self.roll_back_to_parent_of(&block);
+
+        let blockhash = block.blockhash();
+        let mut processed_block = ProcessedBlock::new(blockhash.clone());
+        self.update_pending_transactions(&mut processed_block);
+        self.remember(processed_block);
 
         let block_results = Self::process_new_block(Arc::clone(&self.block_queries), &block);
+        let transaction_queries = {
+            let mut matched_up_to = self.matched_up_to.lock().unwrap();
+            *matched_up_to = Some(block.height());
+            QueryIndex::new(self.transaction_queries.all())
+        };
         let mut tx_result_vecs = vec![];
 
         for tx in block.transactions() {
             tx_result_vecs.push(Self::process_new_transaction(
-                Arc::clone(&self.transaction_queries),
+                &transaction_queries,
                 Arc::clone(&self.pending_transactions),
+                &blockhash,
                 tx,
             ))
         }
@@ -118,13 +314,198 @@ impl<T: Transaction, B: Block<Transaction = T>, TQ: Query<T> + 'static, BQ: Quer
         let tx_results = join_all(tx_result_vecs)
             .map(|tx_result_vec| tx_result_vec.into_iter().flatten().collect());
 
-        Box::new(block_results.join(tx_results))
+        let recent_blocks = Arc::clone(&self.recent_blocks);
+        let pending_transactions = Arc::clone(&self.pending_transactions);
+        let state_path = self.state_path.clone();
+        Box::new(
+            block_results
+                .join(tx_results)
+                .map(move |(block_matches, transaction_matches)| {
+                    let mut recent_blocks = recent_blocks.lock().unwrap();
+                    if let Some(processed_block) = recent_blocks
+                        .iter_mut()
+                        .rev()
+                        .find(|processed_block| processed_block.blockhash == blockhash)
+                    {
+                        // Backfilled matches may already be filed with the block
+                        processed_block
+                            .block_matches
+                            .extend(block_matches.iter().cloned());
+                        processed_block
+                            .transaction_matches
+                            .extend(transaction_matches.iter().cloned());
+                    }
+
+                    // Should the matches not get stored, the block is processed
+                    // again after a restart, which rolls back what is persisted
+                    // here first
+                    if let Some(state_path) = state_path {
+                        let pending_transactions = pending_transactions.lock().unwrap();
+                        if let Err(e) =
+                            write_json(&state_path, &(&*pending_transactions, &*recent_blocks))
+                        {
+                            error!("Could not persist the block processor state: {:?}", e);
+                        }
+                    }
+
+                    (block_matches, transaction_matches)
+                }),
+        )
+    }
+
+    /// Whether `blockhash` is none of the recently processed blocks. Before
+    /// the first block is processed, no parent counts as unknown.
+    fn is_unknown_parent(&self, blockhash: &str) -> bool {
+        let recent_blocks = self.recent_blocks.lock().unwrap();
+
+        !recent_blocks.is_empty()
+            && !recent_blocks
+                .iter()
+                .any(|processed_block| processed_block.blockhash == blockhash)
+    }
+
+    /// The blocks between `block` and the last processed block it builds on,
+    /// oldest first. They were either skipped by the block source or are
+    /// part of a fork that only now became the active chain. Comes up empty
+    /// if the active chain does not lead back to a processed block within
+    /// `MAX_REORG_DEPTH` blocks, in which case `block` is processed as is.
+    fn missing_ancestors_of(&self, block: &B) -> impl Future<Item = Vec<B>, Error = ()> + Send {
+        let processor = self.clone();
+        let ancestors = self
+            .ancestors
+            .clone()
+            .expect("only called with a source of ancestors");
+
+        future::loop_fn(
+            (Vec::new(), block.height(), block.prev_blockhash()),
+            move |(mut missing, height, prev_blockhash): (Vec<B>, u64, String)| {
+                let processor = processor.clone();
+                let parent_height = height
+                    .checked_sub(1)
+                    .filter(|_| missing.len() < MAX_REORG_DEPTH);
+                let parent = match parent_height {
+                    Some(parent_height) => Either::A(
+                        ancestors
+                            .blocks_between(parent_height, parent_height)
+                            .into_future()
+                            .map(|(parent, _)| parent)
+                            .or_else(|_| Ok(None)),
+                    ),
+                    None => Either::B(future::ok(None)),
+                };
+
+                parent.map(move |parent| match parent {
+                    Some(parent) if parent.blockhash() == prev_blockhash => {
+                        let grandparent = parent.prev_blockhash();
+                        missing.push(parent);
+
+                        if processor.is_unknown_parent(&grandparent) {
+                            Loop::Continue((missing, height - 1, grandparent))
+                        } else {
+                            missing.reverse();
+                            Loop::Break(missing)
+                        }
+                    }
+                    _ => {
+                        warn!(
+                            "Could not fetch the unknown ancestor {} at height {:?}",
+                            prev_blockhash, parent_height
+                        );
+                        Loop::Break(Vec::new())
+                    }
+                })
+            },
+        )
+    }
+
+    /// If `block` does not build on the last processed block but on an earlier
+    /// one, every block after its parent was orphaned by a reorg and whatever
+    /// they contributed to the query results is retracted.
+    fn roll_back_to_parent_of(&self, block: &B) {
+        let prev_blockhash = block.prev_blockhash();
+        let orphaned_blocks = {
+            let mut recent_blocks = self.recent_blocks.lock().unwrap();
+
+            let last_blockhash = match recent_blocks.back() {
+                Some(last_block) if last_block.blockhash != prev_blockhash => {
+                    last_block.blockhash.clone()
+                }
+                _ => return,
+            };
+
+            match recent_blocks
+                .iter()
+                .rposition(|processed_block| processed_block.blockhash == prev_blockhash)
+            {
+                Some(parent) => recent_blocks.split_off(parent + 1),
+                None => {
+                    warn!(
+                        "Block {} lists {} as previous block but last processed block was {} and the parent is not among the last {} blocks",
+                        block.blockhash(),
+                        prev_blockhash,
+                        last_blockhash,
+                        MAX_REORG_DEPTH
+                    );
+                    return;
+                }
+            }
+        };
+
+        warn!(
+            "Block {} reorgs the chain back to {}, orphaning {} block(s)",
+            block.blockhash(),
+            prev_blockhash,
+            orphaned_blocks.len()
+        );
+
+        for orphaned_block in orphaned_blocks.into_iter().rev() {
+            self.retract(orphaned_block);
+        }
+    }
+
+    fn retract(&self, orphaned_block: ProcessedBlock) {
+        let ProcessedBlock {
+            blockhash,
+            block_matches,
+            transaction_matches,
+            confirmed_transactions,
+        } = orphaned_block;
+        debug!("Retracting query results of orphaned block {}", blockhash);
+
+        for (query_id, block_id) in block_matches {
+            self.block_results.remove_result(query_id, &block_id);
+        }
+        for (query_id, tx_id) in transaction_matches {
+            self.transaction_results.remove_result(query_id, &tx_id);
+        }
+
+        let mut pending_transactions = self.pending_transactions.lock().unwrap();
+        pending_transactions.retain(|utx| utx.blockhash != blockhash);
+        pending_transactions
+            .iter_mut()
+            .for_each(|utx| utx.pending_confirmations += 1);
+
+        for utx in confirmed_transactions {
+            self.transaction_results
+                .remove_result(utx.matching_query_id, &utx.tx_id);
+            // The block containing the transaction is still part of the chain,
+            // only the confirmation it got from this block is gone
+            pending_transactions.push(PendingTransaction {
+                pending_confirmations: 1,
+                ..utx
+            });
+        }
+    }
+
+    fn remember(&self, processed_block: ProcessedBlock) {
+        let mut recent_blocks = self.recent_blocks.lock().unwrap();
+
+        recent_blocks.push_back(processed_block);
+        while recent_blocks.len() > MAX_REORG_DEPTH {
+            recent_blocks.pop_front();
+        }
     }
-}
 
-impl<T: Transaction, B: Block<Transaction = T>, TQ: Query<T> + 'static, BQ: Query<B>>
-    DefaultBlockProcessor<T, B, TQ, BQ>
-{
     fn process_new_block(
         block_queries: ArcQueryRepository<BQ>,
         block: &B,
@@ -151,37 +532,44 @@ impl<T: Transaction, B: Block<Transaction = T>, TQ: Query<T> + 'static, BQ: Quer
         join_all(query_match_futures).map(|results| results.into_iter().filter_map(|x| x).collect())
     }
 
-    fn update_pending_transactions(&mut self) {
+    fn update_pending_transactions(&self, processed_block: &mut ProcessedBlock) {
         trace!("Updating pending matching transactions");
         let mut pending_transactions = self.pending_transactions.lock().unwrap();
+        // The query may have been deleted or expired while the transaction was
+        // waiting for its confirmations
+        let transaction_queries = &self.transaction_queries;
+        pending_transactions.retain(|utx| transaction_queries.get(utx.matching_query_id).is_some());
         pending_transactions
             .iter_mut()
             .for_each(|utx| utx.pending_confirmations -= 1);
 
-        pending_transactions.iter().for_each(|utx| {
-            if utx.pending_confirmations == 0 {
-                let confirmed_tx_id = &utx.tx_id;
-                trace!(
-                    "Transaction {} now has enough confirmations. Sent to query result repository",
-                    confirmed_tx_id
-                );
-                self.transaction_results
-                    .add_result(utx.matching_query_id, confirmed_tx_id.clone())
-            }
-        });
+        let (confirmed_transactions, still_pending) = pending_transactions
+            .drain(..)
+            .partition(|utx| utx.pending_confirmations == 0);
+        *pending_transactions = still_pending;
 
-        pending_transactions.retain(|ref utx| utx.pending_confirmations > 0);
+        for utx in confirmed_transactions {
+            trace!(
+                "Transaction {} now has enough confirmations. Sent to query result repository",
+                utx.tx_id
+            );
+            self.transaction_results
+                .add_result(utx.matching_query_id, utx.tx_id.clone());
+            processed_block.confirmed_transactions.push(utx);
+        }
     }
 
     fn process_new_transaction(
-        transaction_queries: ArcQueryRepository<TQ>,
-        pending_transactions: Arc<Mutex<Vec<PendingTransaction>>>,
+        transaction_queries: &QueryIndex<T, TQ>,
+        pending_transactions: PendingTransactions,
+        blockhash: &str,
         transaction: &T,
     ) -> impl Future<Item = Vec<QueryMatch>, Error = ()> + Send {
         trace!("Processing {:?}", transaction);
         let mut result_futures = vec![];
 
-        for (query_id, query) in transaction_queries.all() {
+        for (query_id, query) in transaction_queries.candidates(transaction) {
+            let query_id = *query_id;
             trace!(
                 "Matching query {:#?} against transaction {:#?}",
                 query,
@@ -189,6 +577,7 @@ impl<T: Transaction, B: Block<Transaction = T>, TQ: Query<T> + 'static, BQ: Quer
             );
 
             let tx_id = transaction.transaction_id();
+            let blockhash = blockhash.to_string();
             let pending_transactions = Arc::clone(&pending_transactions);
 
             let result_future =
@@ -218,6 +607,7 @@ impl<T: Transaction, B: Block<Transaction = T>, TQ: Query<T> + 'static, BQ: Quer
                             let pending_tx = PendingTransaction {
                                 matching_query_id: query_id,
                                 tx_id,
+                                blockhash,
                                 pending_confirmations: confirmations_needed - 1,
                             };
                             let mut pending_transactions = pending_transactions.lock().unwrap();
@@ -239,17 +629,58 @@ impl<T, B, TQ, BQ> DefaultBlockProcessor<T, B, TQ, BQ> {
         transaction_query_repository: Arc<dyn QueryRepository<TQ>>,
         block_query_repository: Arc<dyn QueryRepository<BQ>>,
         transaction_query_result_repository: Arc<dyn QueryResultRepository<TQ>>,
+        block_query_result_repository: Arc<dyn QueryResultRepository<BQ>>,
     ) -> Self {
         Self {
             transaction_queries: transaction_query_repository,
             block_queries: block_query_repository,
             transaction_results: transaction_query_result_repository,
+            block_results: block_query_result_repository,
             pending_transactions: Arc::new(Mutex::new(Vec::new())),
-            blockhashes: Vec::new(),
+            recent_blocks: Arc::new(Mutex::new(VecDeque::new())),
+            matched_up_to: Arc::new(Mutex::new(None)),
+            state_path: None,
+            ancestors: None,
             tx_type: PhantomData,
             block_type: PhantomData,
         }
     }
+
+    /// Fetches the blocks that a new block builds on from `ancestors` if they
+    /// were never processed, so that a gap or a reorg onto an unseen fork is
+    /// caught up on instead of only warned about.
+    pub fn fetching_ancestors_from(mut self, ancestors: Arc<dyn BlockSource<B>>) -> Self {
+        self.ancestors = Some(ancestors);
+        self
+    }
+
+    /// Restores the pending transactions and recently processed blocks from
+    /// `path` and keeps it up to date with every processed block.
+    pub fn persisted_at<P: AsRef<Path>>(mut self, path: P) -> Result<Self, disk_store::Error> {
+        let path = path.as_ref().to_path_buf();
+
+        if let Some((pending_transactions, recent_blocks)) = read_json::<PersistedState>(&path)? {
+            *self.pending_transactions.lock().unwrap() = pending_transactions;
+            *self.recent_blocks.lock().unwrap() = recent_blocks;
+        }
+        self.state_path = Some(path);
+
+        Ok(self)
+    }
+
+    /// Transactions that matched but still wait for confirmations. Each block
+    /// processed afterwards counts as one.
+    pub fn pending_transactions(&self) -> PendingTransactions {
+        Arc::clone(&self.pending_transactions)
+    }
+
+    pub fn recent_blocks(&self) -> RecentBlocks {
+        Arc::clone(&self.recent_blocks)
+    }
+
+    pub fn matched_up_to(&self) -> MatchedUpTo {
+        Arc::clone(&self.matched_up_to)
+    }
 }
 
 #[cfg(test)]
@@ -260,6 +691,7 @@ mod tests {
         in_memory_query_result_repository::InMemoryQueryResultRepository,
     };
     use spectral::prelude::*;
+    use std::env::temp_dir;
 
     #[derive(Serialize, Deserialize, Clone, Default, Debug, Copy)]
     struct GenericTransactionQuery {
@@ -335,11 +767,29 @@ mod tests {
         fn prev_blockhash(&self) -> String {
             self.parent_id.to_string()
         }
+        fn height(&self) -> u64 {
+            u64::from(self.id)
+        }
         fn transactions(&self) -> &[GenericTransaction] {
             self.transaction_list.as_slice()
         }
     }
 
+    /// The active chain, the block at index `i` has height `i`
+    struct TestChain(Vec<GenericBlock>);
+
+    impl BlockSource<GenericBlock> for TestChain {
+        fn blocks_between(
+            &self,
+            from: u64,
+            to: u64,
+        ) -> Box<dyn Stream<Item = GenericBlock, Error = ()> + Send> {
+            Box::new(stream::iter_ok(
+                self.0[from as usize..=to as usize].to_vec(),
+            ))
+        }
+    }
+
     struct Setup {
         block_processor: DefaultBlockProcessor<
             GenericTransaction,
@@ -347,6 +797,11 @@ mod tests {
             GenericTransactionQuery,
             GenericBlockQuery,
         >,
+        transaction_query_repository: Arc<InMemoryQueryRepository<GenericTransactionQuery>>,
+        block_query_repository: Arc<InMemoryQueryRepository<GenericBlockQuery>>,
+        transaction_query_result_repository:
+            Arc<InMemoryQueryResultRepository<GenericTransactionQuery>>,
+        block_query_result_repository: Arc<InMemoryQueryResultRepository<GenericBlockQuery>>,
         first_block: GenericBlock,
     }
 
@@ -362,11 +817,13 @@ mod tests {
             let transaction_query_result_repository =
                 Arc::new(InMemoryQueryResultRepository::default());
             let block_query_repository = Arc::new(InMemoryQueryRepository::default());
+            let block_query_result_repository = Arc::new(InMemoryQueryResultRepository::default());
 
             let block_processor = DefaultBlockProcessor::new(
                 transaction_query_repository.clone(),
                 block_query_repository.clone(),
                 transaction_query_result_repository.clone(),
+                block_query_result_repository.clone(),
             );
 
             let first_transaction_query = GenericTransactionQuery {
@@ -394,9 +851,61 @@ mod tests {
 
             Self {
                 block_processor,
+                transaction_query_repository,
+                block_query_repository,
+                transaction_query_result_repository,
+                block_query_result_repository,
                 first_block,
             }
         }
+
+        /// Replaces the block processor as if the ledger query service was
+        /// restarted
+        fn restart(&mut self, state_path: &Path) {
+            self.block_processor = DefaultBlockProcessor::new(
+                self.transaction_query_repository.clone(),
+                self.block_query_repository.clone(),
+                self.transaction_query_result_repository.clone(),
+                self.block_query_result_repository.clone(),
+            )
+            .persisted_at(state_path)
+            .unwrap();
+        }
+
+        /// Records the matches the way the ledger query service does
+        fn process_and_store(&mut self, block: GenericBlock) {
+            let (blocks, transactions) = process_results(self.block_processor.process(block));
+
+            for (id, block_id) in blocks {
+                self.block_query_result_repository.add_result(id, block_id);
+            }
+            for (id, tx_id) in transactions {
+                self.transaction_query_result_repository
+                    .add_result(id, tx_id);
+            }
+        }
+
+        fn transaction_results(&self) -> Vec<String> {
+            self.transaction_query_result_repository
+                .get(1)
+                .unwrap_or_default()
+                .0
+        }
+
+        fn block_results(&self) -> Vec<String> {
+            self.block_query_result_repository
+                .get(1)
+                .unwrap_or_default()
+                .0
+        }
+    }
+
+    fn block(id: u8, parent_id: u8) -> GenericBlock {
+        GenericBlock {
+            id,
+            parent_id,
+            ..GenericBlock::default()
+        }
     }
 
     #[test]
@@ -460,6 +969,146 @@ mod tests {
         assert_that(&transactions).named(&"found txs").has_length(0);
     }
 
+    #[test]
+    fn given_reorg_retracts_results_of_orphaned_block() {
+        let mut harness = Setup::new(1, 1, 1, 0, 0);
+        let block_with_transaction = GenericBlock {
+            id: 1,
+            parent_id: 0,
+            ..harness.first_block.clone()
+        };
+
+        harness.process_and_store(block(0, 0));
+        harness.process_and_store(block_with_transaction);
+        assert_that(&harness.transaction_results()).is_equal_to(vec![String::from("1")]);
+
+        harness.process_and_store(block(2, 0));
+
+        assert_that(&harness.transaction_results()).is_empty();
+        assert_that(&harness.block_results())
+            .is_equal_to(vec![String::from("0"), String::from("2")]);
+    }
+
+    #[test]
+    fn given_reorg_revokes_confirmation_of_orphaned_block() {
+        let mut harness = Setup::new(1, 1, 2, 0, 0);
+        let block_with_transaction = GenericBlock {
+            id: 1,
+            parent_id: 0,
+            ..harness.first_block.clone()
+        };
+
+        harness.process_and_store(block(0, 0));
+        harness.process_and_store(block_with_transaction);
+        assert_that(&harness.transaction_results()).is_empty();
+        harness.process_and_store(block(2, 1));
+        assert_that(&harness.transaction_results()).is_equal_to(vec![String::from("1")]);
+
+        harness.process_and_store(block(3, 1));
+        assert_that(&harness.transaction_results()).is_equal_to(vec![String::from("1")]);
+
+        harness.process_and_store(block(4, 0));
+        assert_that(&harness.transaction_results()).is_empty();
+    }
+
+    #[test]
+    fn given_reorg_retracts_match_recorded_for_orphaned_block() {
+        let mut harness = Setup::new(9, 1, 1, 0, 0);
+
+        harness.process_and_store(block(0, 0));
+        harness.process_and_store(block(1, 0));
+        harness
+            .transaction_query_result_repository
+            .add_result(1, String::from("9"));
+        record_confirmed_match(
+            &harness.block_processor.recent_blocks(),
+            "1",
+            1,
+            (1, String::from("9")),
+        );
+
+        harness.process_and_store(block(2, 0));
+
+        assert_that(&harness.transaction_results()).is_empty();
+    }
+
+    #[test]
+    fn given_block_with_unknown_parent_skipped_ancestors_are_fetched_and_processed() {
+        let mut harness = Setup::new(1, 1, 1, 0, 0);
+        let skipped_block_with_transaction = GenericBlock {
+            id: 1,
+            parent_id: 0,
+            ..harness.first_block.clone()
+        };
+        harness.block_processor = DefaultBlockProcessor::new(
+            harness.transaction_query_repository.clone(),
+            harness.block_query_repository.clone(),
+            harness.transaction_query_result_repository.clone(),
+            harness.block_query_result_repository.clone(),
+        )
+        .fetching_ancestors_from(Arc::new(TestChain(vec![
+            block(0, 0),
+            skipped_block_with_transaction,
+            block(2, 1),
+        ])));
+
+        harness.process_and_store(block(0, 0));
+        harness.process_and_store(block(2, 1));
+
+        assert_that(&harness.transaction_results()).is_equal_to(vec![String::from("1")]);
+        assert_that(&harness.block_results()).is_equal_to(vec![
+            String::from("0"),
+            String::from("1"),
+            String::from("2"),
+        ]);
+    }
+
+    #[test]
+    fn given_restart_pending_transaction_still_gets_confirmed() {
+        let state_path = temp_dir().join(format!("lqs-processor-{}.json", rand::random::<u64>()));
+        let mut harness = Setup::new(1, 1, 2, 0, 0);
+        harness.restart(&state_path);
+        let block_with_transaction = GenericBlock {
+            id: 1,
+            parent_id: 0,
+            ..harness.first_block.clone()
+        };
+
+        harness.process_and_store(block(0, 0));
+        harness.process_and_store(block_with_transaction);
+        assert_that(&harness.transaction_results()).is_empty();
+
+        harness.restart(&state_path);
+        harness.process_and_store(block(2, 1));
+
+        assert_that(&harness.transaction_results()).is_equal_to(vec![String::from("1")]);
+
+        std::fs::remove_file(state_path).unwrap();
+    }
+
+    #[test]
+    fn given_restart_reorg_still_retracts_results_of_orphaned_block() {
+        let state_path = temp_dir().join(format!("lqs-processor-{}.json", rand::random::<u64>()));
+        let mut harness = Setup::new(1, 1, 1, 0, 0);
+        harness.restart(&state_path);
+        let block_with_transaction = GenericBlock {
+            id: 1,
+            parent_id: 0,
+            ..harness.first_block.clone()
+        };
+
+        harness.process_and_store(block(0, 0));
+        harness.process_and_store(block_with_transaction);
+        assert_that(&harness.transaction_results()).is_equal_to(vec![String::from("1")]);
+
+        harness.restart(&state_path);
+        harness.process_and_store(block(2, 0));
+
+        assert_that(&harness.transaction_results()).is_empty();
+
+        std::fs::remove_file(state_path).unwrap();
+    }
+
     fn process_results(
         processing_future: Box<
             dyn Future<Item = (Vec<QueryMatch>, Vec<QueryMatch>), Error = ()> + Send,
@@ -0,0 +1,347 @@
+use crate::{
+    block_processor::Block,
+    connectors::bitcoind_rpc_block_source::{BitcoindRpcBlockSource, BlockHeader, Error},
+};
+use bitcoin_support::MinedBlock;
+use futures::{
+    future::{self, Loop},
+    stream, Future, Stream,
+};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::timer::{Delay, Interval};
+
+/// How many of the latest blocks are remembered to find the point a new tip
+/// forks off from. Reorgs reaching deeper than this are not rolled back.
+const MAX_REORG_DEPTH: usize = 100;
+
+/// Heights and hashes of blocks, oldest first.
+type Branch = Vec<(u64, String)>;
+
+/// Streams every new block by asking bitcoind for its best block at each tick.
+/// Blocks that were mined between two ticks, or that replaced others in a
+/// reorg, are fetched by walking back from the new tip. If `resume_after` is
+/// given, all blocks above that height are replayed first.
+pub fn bitcoin_block_poller(
+    source: BitcoindRpcBlockSource,
+    poll_interval: Duration,
+    resume_after: Option<u64>,
+) -> Box<dyn Stream<Item = MinedBlock, Error = ()> + Send> {
+    info!(
+        "Polling bitcoind every {:?} for new Bitcoin blocks",
+        poll_interval
+    );
+
+    let known_blocks = Arc::new(Mutex::new(KnownBlocks::default()));
+    let resume_after = Arc::new(Mutex::new(resume_after));
+
+    Box::new(
+        Interval::new(Instant::now(), poll_interval)
+            .map_err(|e| error!("Could not wait for the next poll of bitcoind: {:?}", e))
+            .map(move |_| {
+                new_blocks(
+                    source.clone(),
+                    Arc::clone(&known_blocks),
+                    Arc::clone(&resume_after),
+                )
+                // A failed poll only ends this tick, the next one picks up
+                // from the last block that made it through
+                .then(|result| match result {
+                    Ok(block) => Ok(Some(block)),
+                    Err(e) => {
+                        warn!("Could not poll bitcoind for new blocks: {:?}", e);
+                        Ok::<_, ()>(None)
+                    }
+                })
+                .take_while(|block| Ok(block.is_some()))
+                .filter_map(|block| block)
+            })
+            .flatten(),
+    )
+}
+
+/// Streams the blocks from `from` to `to`, which lets a source that only
+/// delivers new blocks, like ZeroMQ, pick up where the last run stopped. A
+/// block that cannot be fetched is asked for again until bitcoind delivers it.
+pub fn bitcoin_blocks_between(
+    source: BitcoindRpcBlockSource,
+    from: u64,
+    to: u64,
+    retry_interval: Duration,
+) -> Box<dyn Stream<Item = MinedBlock, Error = ()> + Send> {
+    info!("Replaying Bitcoin blocks {} to {}", from, to);
+
+    let hash_source = source.clone();
+    let blockhashes = retrying(
+        format!("the hashes of Bitcoin blocks {} to {}", from, to),
+        retry_interval,
+        move || hash_source.blockhashes(from, to),
+    );
+
+    Box::new(
+        blockhashes
+            .map(stream::iter_ok)
+            .flatten_stream()
+            .and_then(move |blockhash| {
+                let source = source.clone();
+
+                retrying(
+                    format!("Bitcoin block {}", blockhash),
+                    retry_interval,
+                    move || source.block(&blockhash),
+                )
+            }),
+    )
+}
+
+/// Sends `request` until it succeeds, waiting `retry_interval` after every
+/// failure.
+fn retrying<T, F, R>(
+    what: String,
+    retry_interval: Duration,
+    mut request: F,
+) -> impl Future<Item = T, Error = ()>
+where
+    T: Send + 'static,
+    F: FnMut() -> R,
+    R: Future<Item = T, Error = Error>,
+{
+    future::loop_fn((), move |()| {
+        let what = what.clone();
+
+        request().then(
+            move |result| -> Box<dyn Future<Item = Loop<T, ()>, Error = ()> + Send> {
+                match result {
+                    Ok(item) => Box::new(future::ok(Loop::Break(item))),
+                    Err(e) => {
+                        warn!("Could not fetch {}, retrying: {:?}", what, e);
+                        Box::new(
+                            Delay::new(Instant::now() + retry_interval)
+                                .map(|_| Loop::Continue(()))
+                                .map_err(|e| error!("Could not wait to retry: {:?}", e)),
+                        )
+                    }
+                }
+            },
+        )
+    })
+}
+
+fn new_blocks(
+    source: BitcoindRpcBlockSource,
+    known_blocks: Arc<Mutex<KnownBlocks>>,
+    resume_after: Arc<Mutex<Option<u64>>>,
+) -> Box<dyn Stream<Item = MinedBlock, Error = Error> + Send> {
+    let header_source = source.clone();
+    let block_source = source.clone();
+    let emitted_blocks = Arc::clone(&known_blocks);
+
+    let block_ids = source
+        .best_block_hash()
+        .and_then(move |blockhash| header_source.block_header(&blockhash))
+        .map(
+            move |tip| -> Box<dyn Stream<Item = (u64, String), Error = Error> + Send> {
+                let mut known = known_blocks.lock().unwrap();
+
+                if known.tip() == Some(tip.hash.as_str()) {
+                    return Box::new(stream::empty());
+                }
+
+                if known.is_empty() {
+                    return match resume_after.lock().unwrap().take() {
+                        Some(height) => {
+                            info!("Replaying Bitcoin blocks {} to {}", height + 1, tip.height);
+                            let heights = (height + 1)..=tip.height;
+                            Box::new(
+                                source
+                                    .blockhashes(height + 1, tip.height)
+                                    .map(move |hashes| stream::iter_ok(heights.zip(hashes)))
+                                    .flatten_stream(),
+                            )
+                        }
+                        None => {
+                            info!(
+                                "Starting to poll for Bitcoin blocks after block {}",
+                                tip.height
+                            );
+                            known.remember(tip.height, tip.hash);
+                            Box::new(stream::empty())
+                        }
+                    };
+                }
+
+                drop(known);
+                Box::new(
+                    branch_to_known_block(source, Arc::clone(&known_blocks), tip)
+                        .map(move |(branch, parent)| {
+                            let mut known = known_blocks.lock().unwrap();
+                            if let Some((height, _)) = branch.first() {
+                                known.rewind(*height, parent.as_ref().map(String::as_str));
+                            }
+                            stream::iter_ok(branch)
+                        })
+                        .flatten_stream(),
+                )
+            },
+        )
+        .flatten_stream();
+
+    Box::new(
+        block_ids
+            .and_then(move |(_, hash)| block_source.block(&hash))
+            .take_while(move |block| {
+                let extends_chain = emitted_blocks.lock().unwrap().extends(block);
+                if !extends_chain {
+                    debug!(
+                        "Bitcoin block {} no longer extends the chain, retrying on the next poll",
+                        block.blockhash()
+                    );
+                }
+                Ok(extends_chain)
+            })
+            .inspect(move |block| {
+                emitted_blocks
+                    .lock()
+                    .unwrap()
+                    .remember(u64::from(block.height), block.blockhash());
+            }),
+    )
+}
+
+/// Walks back from `tip` until the parent of a block is already known. Yields
+/// the blocks on the way, oldest first, together with the parent of the oldest
+/// one.
+fn branch_to_known_block(
+    source: BitcoindRpcBlockSource,
+    known_blocks: Arc<Mutex<KnownBlocks>>,
+    tip: BlockHeader,
+) -> Box<dyn Future<Item = (Branch, Option<String>), Error = Error> + Send> {
+    Box::new(future::loop_fn(
+        (Vec::new(), tip),
+        move |(mut branch, header): (Branch, BlockHeader)| -> Box<
+            dyn Future<Item = Loop<(Branch, Option<String>), (Branch, BlockHeader)>, Error = Error>
+                + Send,
+        > {
+            let parent = header.prev_blockhash.clone();
+            let reached_oldest_known = {
+                let known = known_blocks.lock().unwrap();
+
+                match known.oldest_height() {
+                    Some(oldest_height) if header.height <= oldest_height => {
+                        warn!(
+                            "Bitcoin reorg reaches below block {}, not rolling back any further",
+                            oldest_height
+                        );
+                        true
+                    }
+                    _ => parent
+                        .as_ref()
+                        .map_or(true, |parent| known.contains(parent)),
+                }
+            };
+
+            branch.push((header.height, header.hash));
+
+            match parent {
+                Some(parent) if !reached_oldest_known => Box::new(
+                    source
+                        .block_header(&parent)
+                        .map(move |header| Loop::Continue((branch, header))),
+                ),
+                parent => {
+                    branch.reverse();
+                    Box::new(future::ok(Loop::Break((branch, parent))))
+                }
+            }
+        },
+    ))
+}
+
+/// The heights and hashes of the latest blocks that were emitted, oldest
+/// first.
+#[derive(Debug, Default)]
+struct KnownBlocks(VecDeque<(u64, String)>);
+
+impl KnownBlocks {
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn tip(&self) -> Option<&str> {
+        self.0.back().map(|(_, hash)| hash.as_str())
+    }
+
+    fn oldest_height(&self) -> Option<u64> {
+        self.0.front().map(|(height, _)| *height)
+    }
+
+    fn contains(&self, blockhash: &str) -> bool {
+        self.0.iter().any(|(_, hash)| hash == blockhash)
+    }
+
+    fn extends(&self, block: &MinedBlock) -> bool {
+        self.tip()
+            .map_or(true, |tip| tip == block.prev_blockhash().as_str())
+    }
+
+    fn remember(&mut self, height: u64, blockhash: String) {
+        self.0.push_back((height, blockhash));
+        while self.0.len() > MAX_REORG_DEPTH {
+            self.0.pop_front();
+        }
+    }
+
+    /// Forgets all blocks from `height` onwards. If `parent` is not what is
+    /// left at the tip, the fork is deeper than what is remembered and
+    /// everything is forgotten.
+    fn rewind(&mut self, height: u64, parent: Option<&str>) {
+        self.0.retain(|(known_height, _)| *known_height < height);
+
+        if self.tip() != parent {
+            self.0.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    fn known_blocks(heights: std::ops::RangeInclusive<u64>) -> KnownBlocks {
+        let mut known_blocks = KnownBlocks::default();
+        for height in heights {
+            known_blocks.remember(height, format!("block-{}", height));
+        }
+        known_blocks
+    }
+
+    #[test]
+    fn given_fork_below_tip_rewind_forgets_replaced_blocks() {
+        let mut known_blocks = known_blocks(1..=5);
+
+        known_blocks.rewind(4, Some("block-3"));
+
+        assert_that(&known_blocks.tip()).is_equal_to(Some("block-3"));
+        assert_that(&known_blocks.contains("block-4")).is_false();
+    }
+
+    #[test]
+    fn given_fork_below_oldest_known_block_rewind_forgets_everything() {
+        let mut known_blocks = known_blocks(3..=5);
+
+        known_blocks.rewind(2, Some("block-1"));
+
+        assert_that(&known_blocks.is_empty()).is_true();
+    }
+
+    #[test]
+    fn remembers_at_most_max_reorg_depth_blocks() {
+        let known_blocks = known_blocks(1..=(MAX_REORG_DEPTH as u64 + 10));
+
+        assert_that(&known_blocks.oldest_height()).is_equal_to(Some(11));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::backfill::BlockSource;
+use bitcoin_rpc_client::{BitcoinCoreClient, BitcoinRpcApi, ClientError, RpcError};
+use bitcoin_support::{
+    serialize::{deserialize, BitcoinHash},
+    Block, BlockHash, BlockHeader, MinedBlock, Sha256dHash, Transaction,
+};
+use futures::{future, stream, Future, Stream};
+use std::sync::Arc;
+use tokio_threadpool::{blocking, BlockingError};
+
+#[derive(Debug)]
+pub enum Error {
+    Connection(ClientError),
+    Rpc(RpcError),
+    InvalidHex(hex::FromHexError),
+    InvalidTransaction(bitcoin_support::Error),
+    InvalidHeader(String),
+    InvalidBlockhash(String),
+    UnexpectedBlockhash {
+        expected: BlockHash,
+        actual: BlockHash,
+    },
+    Threadpool(BlockingError),
+}
+
+/// The parts of a block header that are needed to follow the chain.
+#[derive(Debug)]
+pub struct BlockHeader {
+    pub hash: String,
+    pub height: u64,
+    pub prev_blockhash: Option<String>,
+}
+
+/// Reads the blocks of the active chain through bitcoind's JSON-RPC
+/// interface. Requires bitcoind to run with `txindex=1`, as the transactions
+/// of a block are fetched one by one.
+#[derive(DebugStub, Clone)]
+pub struct BitcoindRpcBlockSource {
+    #[debug_stub = "BitcoinCoreClient"]
+    client: Arc<BitcoinCoreClient>,
+}
+
+impl BitcoindRpcBlockSource {
+    pub fn new(client: Arc<BitcoinCoreClient>) -> Self {
+        BitcoindRpcBlockSource { client }
+    }
+
+    pub fn best_block_hash(&self) -> impl Future<Item = String, Error = Error> {
+        let client = Arc::clone(&self.client);
+
+        in_blocking_section(move || {
+            rpc(client.get_best_block_hash()).map(|blockhash| format!("{:x}", blockhash))
+        })
+    }
+
+    pub fn block_header(&self, blockhash: &str) -> impl Future<Item = BlockHeader, Error = Error> {
+        let client = Arc::clone(&self.client);
+        let blockhash = blockhash.to_string();
+
+        in_blocking_section(move || {
+            let block = rpc(client.get_block(&parse_blockhash(&blockhash)?))?;
+
+            Ok(BlockHeader {
+                hash: blockhash.clone(),
+                height: u64::from(block.height),
+                prev_blockhash: block
+                    .previousblockhash
+                    .map(|prev_blockhash| format!("{:x}", prev_blockhash)),
+            })
+        })
+    }
+
+    pub fn block(&self, blockhash: &str) -> impl Future<Item = MinedBlock, Error = Error> {
+        let source = self.clone();
+        let blockhash = blockhash.to_string();
+
+        in_blocking_section(move || source.mined_block(&parse_blockhash(&blockhash)?))
+    }
+
+    /// The hashes of the blocks from height `from` to `to` on the active
+    /// chain, oldest first.
+    pub fn blockhashes(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> impl Future<Item = Vec<String>, Error = Error> {
+        let source = self.clone();
+
+        in_blocking_section(move || {
+            source.blockhashes_between(from, to).map(|blockhashes| {
+                blockhashes
+                    .iter()
+                    .map(|blockhash| format!("{:x}", blockhash))
+                    .collect()
+            })
+        })
+    }
+
+    /// Walks back from the tip, so the blocks of a fork bitcoind does not
+    /// consider active are never returned.
+    fn blockhashes_between(&self, from: u64, to: u64) -> Result<Vec<BlockHash>, Error> {
+        let mut blockhashes = Vec::new();
+        let mut next = Some(rpc(self.client.get_best_block_hash())?);
+
+        while let Some(blockhash) = next {
+            let block = rpc(self.client.get_block(&blockhash))?;
+            let height = u64::from(block.height);
+
+            if height <= to {
+                blockhashes.push(blockhash);
+            }
+            if height <= from {
+                break;
+            }
+            next = block.previousblockhash;
+        }
+
+        blockhashes.reverse();
+        Ok(blockhashes)
+    }
+
+    fn mined_block(&self, blockhash: &BlockHash) -> Result<MinedBlock, Error> {
+        let block = rpc(self.client.get_block(blockhash))?;
+
+        let header = BlockHeader {
+            version: block.version,
+            prev_blockhash: block.previousblockhash.unwrap_or_default(),
+            merkle_root: Sha256dHash::from_hex(block.merkleroot.as_str())
+                .map_err(|_| Error::InvalidHeader(block.merkleroot.clone()))?,
+            time: block.time as u32,
+            bits: u32::from_str_radix(block.bits.as_str(), 16)
+                .map_err(|_| Error::InvalidHeader(block.bits.clone()))?,
+            nonce: block.nonce,
+        };
+
+        let txdata = block
+            .tx
+            .iter()
+            .map(|tx_id| {
+                let serialized = rpc(self.client.get_raw_transaction_serialized(tx_id))?;
+                let bytes = hex::decode(serialized.0).map_err(Error::InvalidHex)?;
+
+                deserialize::<Transaction>(bytes.as_ref()).map_err(Error::InvalidTransaction)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mined_block = MinedBlock::new(Block { header, txdata }, block.height);
+
+        // Guards against a header that was put together from the wrong fields
+        let actual = mined_block.as_ref().bitcoin_hash();
+        if actual != *blockhash {
+            return Err(Error::UnexpectedBlockhash {
+                expected: *blockhash,
+                actual,
+            });
+        }
+
+        Ok(mined_block)
+    }
+}
+
+fn parse_blockhash(blockhash: &str) -> Result<BlockHash, Error> {
+    BlockHash::from_hex(blockhash).map_err(|_| Error::InvalidBlockhash(blockhash.to_string()))
+}
+
+fn rpc<T>(result: Result<Result<T, RpcError>, ClientError>) -> Result<T, Error> {
+    result.map_err(Error::Connection)?.map_err(Error::Rpc)
+}
+
+/// `BitcoinCoreClient` is synchronous, every call runs as a blocking section
+/// so it does not stall the other tasks of the runtime.
+fn in_blocking_section<T, F>(mut f: F) -> impl Future<Item = T, Error = Error>
+where
+    F: FnMut() -> Result<T, Error>,
+{
+    future::poll_fn(move || blocking(|| f()))
+        .map_err(Error::Threadpool)
+        .and_then(|result| result)
+}
+
+impl BlockSource<MinedBlock> for BitcoindRpcBlockSource {
+    fn blocks_between(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Box<dyn Stream<Item = MinedBlock, Error = ()> + Send> {
+        let source = self.clone();
+        let blockhashes = in_blocking_section(move || source.blockhashes_between(from, to));
+
+        let source = self.clone();
+        Box::new(
+            blockhashes
+                .map(stream::iter_ok)
+                .flatten_stream()
+                .and_then(move |blockhash| {
+                    let source = source.clone();
+                    in_blocking_section(move || source.mined_block(&blockhash))
+                })
+                .map_err(move |e| {
+                    error!("Could not read Bitcoin blocks {} to {}: {:?}", from, to, e)
+                }),
+        )
+    }
+}
@@ -1,4 +1,4 @@
-use bitcoin_support::{serialize::deserialize, MinedBlock};
+use bitcoin_support::{serialize::deserialize, MinedBlock, Transaction};
 use byteorder::{LittleEndian, ReadBytesExt};
 use futures::sync::mpsc::{self, UnboundedReceiver};
 use std::{io::Cursor, thread};
@@ -33,6 +33,39 @@ pub fn bitcoin_block_listener(endpoint: &str) -> Result<UnboundedReceiver<MinedB
     Ok(state_receiver)
 }
 
+/// Streams the transactions bitcoind accepts into its mempool. Needs bitcoind
+/// to be started with `-zmqpubrawtx`.
+pub fn bitcoin_transaction_listener(
+    endpoint: &str,
+) -> Result<UnboundedReceiver<Transaction>, zmq::Error> {
+    let context = Context::new()?;
+    let mut socket = context.socket(zmq::SUB)?;
+
+    socket.set_subscribe(b"rawtx")?;
+    socket.connect(endpoint)?;
+
+    info!(
+        "Connecting to {} to subscribe to unconfirmed Bitcoin transactions over ZeroMQ",
+        socket.get_last_endpoint().unwrap()
+    );
+
+    let (transaction_sender, transaction_receiver) = mpsc::unbounded();
+
+    thread::spawn(move || {
+        // we need this to keep the context alive
+        let _context = context;
+
+        loop {
+            let result = receive_transaction(&mut socket);
+
+            if let Ok(Some(transaction)) = result {
+                let _ = transaction_sender.unbounded_send(transaction);
+            }
+        }
+    });
+    Ok(transaction_receiver)
+}
+
 fn receive_block(socket: &mut Socket) -> Result<Option<MinedBlock>, zmq::Error> {
     let bytes = socket.recv_bytes(zmq::SNDMORE)?;
     let bytes: &[u8] = bytes.as_ref();
@@ -70,3 +103,34 @@ fn receive_block(socket: &mut Socket) -> Result<Option<MinedBlock>, zmq::Error>
         }
     }
 }
+
+fn receive_transaction(socket: &mut Socket) -> Result<Option<Transaction>, zmq::Error> {
+    let bytes = socket.recv_bytes(zmq::SNDMORE)?;
+    let bytes: &[u8] = bytes.as_ref();
+
+    match bytes {
+        b"rawtx" => {
+            let bytes = socket.recv_bytes(zmq::SNDMORE)?;
+            // The last part is the sequence number of the message
+            let _ = socket.recv_bytes(zmq::SNDMORE)?;
+
+            match deserialize(bytes.as_ref()) {
+                Ok(transaction) => {
+                    trace!("Got unconfirmed {:?}", transaction);
+                    Ok(Some(transaction))
+                }
+                Err(e) => {
+                    error!(
+                        "Got new transaction but failed to deserialize it because {:?}",
+                        e
+                    );
+                    Ok(None)
+                }
+            }
+        }
+        _ => {
+            error!("Unhandled message: {:?}", bytes);
+            Ok(None)
+        }
+    }
+}
@@ -1,32 +1,206 @@
-use crate::web3::{
-    self,
-    futures::{Future, Stream},
-    transports::Http,
-    types::{Block, BlockId, Transaction},
-    Web3,
+use crate::{
+    backfill::BlockSource,
+    ethereum::EthereumBlockWithReceipts,
+    web3::{
+        self,
+        api::BaseFilter,
+        futures::{future, stream, Future, Stream},
+        transports::Http,
+        types::{Block, BlockId, BlockNumber, Transaction, TransactionId, H256},
+        Web3,
+    },
 };
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::timer::Interval;
+
+type PendingTransactionsFilter = Arc<Mutex<Option<BaseFilter<Http, H256>>>>;
 
+/// Streams every new block. If `resume_after` is given, all blocks above that
+/// height which are already on the chain are replayed first, so nothing that
+/// was mined while the listener was down gets skipped.
 pub fn ethereum_block_listener(
     client: Arc<Web3<Http>>,
     polling_wait_time: Duration,
+    resume_after: Option<u64>,
 ) -> Result<Box<dyn Stream<Item = Block<Transaction>, Error = ()> + Send>, web3::Error> {
+    // The filter is created before looking up the height so that no block can
+    // fall between the replayed ones and the ones reported by the filter
     let filter = client.eth_filter().create_blocks_filter().wait()?;
+    let current_height = client.eth().block_number().wait()?.as_u64();
 
     info!(
         "Starting listener for Ethereum from block {} waiting for new blocks.",
-        client
-            .eth()
-            .block_number()
-            .wait()
-            .expect("Could not get block height from web3 client")
+        current_height
     );
 
+    let missed_heights = match resume_after {
+        Some(height) if height < current_height => {
+            info!(
+                "Replaying Ethereum blocks {} to {}",
+                height + 1,
+                current_height
+            );
+            (height + 1)..(current_height + 1)
+        }
+        _ => 0..0,
+    };
+
+    let replay_client = client.clone();
+    let missed_blocks = stream::iter_ok(missed_heights).and_then(move |height| {
+        replay_client
+            .eth()
+            .block_with_txs(BlockId::Number(BlockNumber::Number(height)))
+    });
+
+    let new_blocks = filter
+        .stream(polling_wait_time)
+        .and_then(move |block_hash| client.eth().block_with_txs(BlockId::from(block_hash)));
+
     Ok(Box::new(
-        filter
-            .stream(polling_wait_time)
-            .and_then(move |block_hash| client.eth().block_with_txs(BlockId::from(block_hash)))
+        missed_blocks
+            .chain(new_blocks)
             .filter_map(|item| item)
             .map_err(|error| error!("Could not read block: {:?}", error)),
     ))
 }
+
+/// Streams the transactions that enter the node's pending pool, polling for
+/// them every `polling_wait_time`. A failed request only costs the
+/// transactions it was about, the listener carries on with the next poll.
+pub fn ethereum_pending_transaction_listener(
+    client: Arc<Web3<Http>>,
+    polling_wait_time: Duration,
+) -> Result<Box<dyn Stream<Item = Transaction, Error = ()> + Send>, web3::Error> {
+    let filter = client
+        .eth_filter()
+        .create_pending_transactions_filter()
+        .wait()?;
+    let filter = Arc::new(Mutex::new(Some(filter)));
+
+    info!("Starting listener for pending Ethereum transactions");
+
+    let filter_client = client.clone();
+    Ok(Box::new(
+        Interval::new(Instant::now(), polling_wait_time)
+            .map_err(|e| error!("Stopped polling for pending transactions: {:?}", e))
+            .and_then(move |_| poll_pending_transactions_filter(&filter_client, &filter))
+            .map(stream::iter_ok)
+            .flatten()
+            .and_then(move |transaction_hash| {
+                client
+                    .eth()
+                    .transaction(TransactionId::Hash(transaction_hash))
+                    .then(move |result| {
+                        Ok(result.unwrap_or_else(|e| {
+                            warn!(
+                                "Could not read pending transaction {:?}: {:?}",
+                                transaction_hash, e
+                            );
+                            None
+                        }))
+                    })
+            })
+            .filter_map(|transaction| transaction),
+    ))
+}
+
+/// The hashes of the transactions that entered the pending pool since the
+/// last poll. Nodes forget filters that are not polled for a while and on
+/// restart, so a filter that can no longer be polled is replaced by a new one
+/// on the next call.
+fn poll_pending_transactions_filter(
+    client: &Web3<Http>,
+    filter: &PendingTransactionsFilter,
+) -> Box<dyn Future<Item = Vec<H256>, Error = ()> + Send> {
+    let installed_filter = filter.lock().unwrap();
+    let filter = Arc::clone(filter);
+
+    match &*installed_filter {
+        Some(installed_filter) => Box::new(installed_filter.poll().then(move |result| {
+            Ok(result.map(Option::unwrap_or_default).unwrap_or_else(|e| {
+                warn!("Replacing the pending transaction filter because {:?}", e);
+                *filter.lock().unwrap() = None;
+                Vec::new()
+            }))
+        })),
+        None => Box::new(
+            client
+                .eth_filter()
+                .create_pending_transactions_filter()
+                .then(move |result| {
+                    match result {
+                        Ok(new_filter) => *filter.lock().unwrap() = Some(new_filter),
+                        Err(e) => warn!("Could not create a pending transaction filter: {:?}", e),
+                    }
+                    Ok(Vec::new())
+                }),
+        ),
+    }
+}
+
+/// Fetches the receipts of all transactions in `block`. That is one request
+/// per transaction, so callers should skip it while nobody is interested in
+/// logs.
+pub fn ethereum_transaction_receipts(
+    client: &Web3<Http>,
+    block: Block<Transaction>,
+) -> Box<dyn Future<Item = EthereumBlockWithReceipts, Error = ()> + Send> {
+    let receipts: Vec<_> = block
+        .transactions
+        .iter()
+        .map(|transaction| client.eth().transaction_receipt(transaction.hash))
+        .collect();
+
+    Box::new(
+        future::join_all(receipts)
+            .map(move |receipts| EthereumBlockWithReceipts {
+                block,
+                receipts: receipts.into_iter().filter_map(|receipt| receipt).collect(),
+            })
+            .map_err(|error| error!("Could not read transaction receipts: {:?}", error)),
+    )
+}
+
+fn ethereum_block_at(
+    client: &Web3<Http>,
+    height: u64,
+) -> impl Future<Item = Option<Block<Transaction>>, Error = ()> {
+    client
+        .eth()
+        .block_with_txs(BlockId::Number(BlockNumber::Number(height)))
+        .map_err(move |e| error!("Could not read Ethereum block {}: {:?}", height, e))
+}
+
+impl BlockSource<Block<Transaction>> for Web3<Http> {
+    fn blocks_between(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Box<dyn Stream<Item = Block<Transaction>, Error = ()> + Send> {
+        let client = self.clone();
+
+        Box::new(
+            stream::iter_ok(from..=to)
+                .and_then(move |height| ethereum_block_at(&client, height))
+                .filter_map(|block| block),
+        )
+    }
+}
+
+impl BlockSource<EthereumBlockWithReceipts> for Web3<Http> {
+    fn blocks_between(
+        &self,
+        from: u64,
+        to: u64,
+    ) -> Box<dyn Stream<Item = EthereumBlockWithReceipts, Error = ()> + Send> {
+        let client = self.clone();
+
+        Box::new(
+            BlockSource::<Block<Transaction>>::blocks_between(self, from, to)
+                .and_then(move |block| ethereum_transaction_receipts(&client, block)),
+        )
+    }
+}
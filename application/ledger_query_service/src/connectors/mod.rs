@@ -1,2 +1,4 @@
+pub mod bitcoind_rpc_block_poller;
+pub mod bitcoind_rpc_block_source;
 pub mod bitcoind_zmq_listener;
 pub mod ethereum_web3_block_poller;
@@ -0,0 +1,43 @@
+use crate::{
+    disk_store::{self, read_json, write_json},
+    latest_block_repository::{LatestBlock, LatestBlockRepository},
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+/// Remembers the latest block across restarts, which tells a connector where
+/// to resume from.
+#[derive(Debug)]
+pub struct DiskLatestBlockRepository {
+    path: PathBuf,
+    latest_block: RwLock<Option<LatestBlock>>,
+}
+
+impl DiskLatestBlockRepository {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, disk_store::Error> {
+        let path = path.as_ref().to_path_buf();
+        let latest_block = read_json(&path)?;
+
+        Ok(DiskLatestBlockRepository {
+            path,
+            latest_block: RwLock::new(latest_block),
+        })
+    }
+}
+
+impl LatestBlockRepository for DiskLatestBlockRepository {
+    fn get(&self) -> Option<LatestBlock> {
+        *self.latest_block.read().unwrap()
+    }
+
+    fn set(&self, latest_block: LatestBlock) {
+        let mut current = self.latest_block.write().unwrap();
+
+        if let Err(e) = write_json(&self.path, &latest_block) {
+            error!("Could not persist latest block {:?}: {:?}", latest_block, e);
+        }
+        *current = Some(latest_block);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    disk_store::{self, read_json, write_json},
+    query_repository::{Error, QueryRepository},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(bound = "Q: Serialize + DeserializeOwned")]
+struct State<Q> {
+    queries: BTreeMap<u32, Q>,
+    next_index: u32,
+}
+
+impl<Q> Default for State<Q> {
+    fn default() -> Self {
+        State {
+            queries: BTreeMap::new(),
+            next_index: 1,
+        }
+    }
+}
+
+/// Keeps all queries of one kind in a single JSON file that is rewritten on
+/// every change. The next id is stored alongside, so ids handed out before a
+/// restart are never reused.
+#[derive(Debug)]
+pub struct DiskQueryRepository<Q> {
+    path: PathBuf,
+    state: RwLock<State<Q>>,
+}
+
+impl<Q: Serialize + DeserializeOwned> DiskQueryRepository<Q> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, disk_store::Error> {
+        let path = path.as_ref().to_path_buf();
+        let state = read_json(&path)?.unwrap_or_default();
+
+        Ok(DiskQueryRepository {
+            path,
+            state: RwLock::new(state),
+        })
+    }
+}
+
+impl<Q: Serialize + DeserializeOwned + Send + Sync + Clone + 'static> QueryRepository<Q>
+    for DiskQueryRepository<Q>
+{
+    fn all(&self) -> Box<dyn Iterator<Item = (u32, Q)>> {
+        let state = self.state.read().unwrap();
+
+        Box::new(state.queries.clone().into_iter())
+    }
+
+    fn get(&self, id: u32) -> Option<Q> {
+        let state = self.state.read().unwrap();
+
+        state.queries.get(&id).cloned()
+    }
+
+    fn save(&self, entity: Q) -> Result<u32, Error<Q>> {
+        let mut state = self.state.write().unwrap();
+
+        let id = state.next_index;
+        state.queries.insert(id, entity);
+        state.next_index += 1;
+
+        if let Err(e) = write_json(&self.path, &*state) {
+            error!("Could not persist query {}: {:?}", id, e);
+            state.next_index -= 1;
+            let entity = state.queries.remove(&id).expect("query was just inserted");
+
+            return Err(Error::FailedToStore(entity));
+        }
+
+        Ok(id)
+    }
+
+    fn delete(&self, id: u32) {
+        let mut state = self.state.write().unwrap();
+
+        if state.queries.remove(&id).is_some() {
+            if let Err(e) = write_json(&self.path, &*state) {
+                error!("Could not persist deletion of query {}: {:?}", id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn given_reopened_repository_keeps_queries_and_does_not_reuse_ids() {
+        let path = temp_dir().join(format!("lqs-queries-{}.json", rand::random::<u64>()));
+
+        {
+            let repository = DiskQueryRepository::open(&path).unwrap();
+            repository.save(String::from("first")).unwrap();
+            let second = repository.save(String::from("second")).unwrap();
+            repository.delete(second);
+        }
+
+        let repository = DiskQueryRepository::<String>::open(&path).unwrap();
+
+        assert_that(&repository.get(1))
+            .is_some()
+            .is_equal_to(String::from("first"));
+        assert_that(&repository.get(2)).is_none();
+        assert_that(&repository.save(String::from("third")))
+            .is_ok()
+            .is_equal_to(3);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{
+    disk_store::{self, append_json_line, open_for_appending, read_json_lines, write_json_lines},
+    query_result_repository::{QueryResult, QueryResultRepository},
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Mutex, RwLock},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+enum Change {
+    Add(u32, String),
+    Remove(u32, String),
+    Delete(u32),
+}
+
+/// Keeps the matches of all queries of one kind in a journal that every change
+/// is appended to. The journal is compacted whenever the repository is opened.
+#[derive(Debug)]
+pub struct DiskQueryResultRepository<Q> {
+    path: PathBuf,
+    storage: RwLock<BTreeMap<u32, QueryResult>>,
+    journal: Mutex<File>,
+    phantom: PhantomData<Q>,
+}
+
+impl<Q> DiskQueryResultRepository<Q> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, disk_store::Error> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut storage = BTreeMap::new();
+        for change in read_json_lines(&path)? {
+            apply(&mut storage, change);
+        }
+
+        let compacted: Vec<_> = storage
+            .iter()
+            .flat_map(|(id, query_result): (&u32, &QueryResult)| {
+                query_result
+                    .0
+                    .iter()
+                    .map(move |tx_id| Change::Add(*id, tx_id.clone()))
+            })
+            .collect();
+        write_json_lines(&path, &compacted)?;
+        let journal = open_for_appending(&path)?;
+
+        Ok(DiskQueryResultRepository {
+            path,
+            storage: RwLock::new(storage),
+            journal: Mutex::new(journal),
+            phantom: PhantomData,
+        })
+    }
+
+    /// Called with the storage locked, so the journal lists the changes in
+    /// the order they were applied.
+    fn record(&self, change: &Change) {
+        let mut journal = self.journal.lock().unwrap();
+
+        if let Err(e) = append_json_line(&mut journal, &self.path, change) {
+            error!("Could not persist {:?}: {:?}", change, e);
+        }
+    }
+}
+
+fn apply(storage: &mut BTreeMap<u32, QueryResult>, change: Change) {
+    match change {
+        Change::Add(id, tx_id) => storage.entry(id).or_default().0.push(tx_id),
+        Change::Remove(id, tx_id) => {
+            if let Some(query_result) = storage.get_mut(&id) {
+                query_result.0.retain(|result| *result != tx_id);
+            }
+        }
+        Change::Delete(id) => {
+            storage.remove(&id);
+        }
+    }
+}
+
+impl<Q: Send + Sync + Clone + 'static> QueryResultRepository<Q> for DiskQueryResultRepository<Q> {
+    fn get(&self, id: u32) -> Option<QueryResult> {
+        let storage = self.storage.read().unwrap();
+
+        storage.get(&id).cloned()
+    }
+
+    fn add_result(&self, id: u32, tx_id: String) {
+        let mut storage = self.storage.write().unwrap();
+
+        let change = Change::Add(id, tx_id);
+        self.record(&change);
+        apply(&mut storage, change);
+    }
+
+    fn remove_result(&self, id: u32, tx_id: &str) {
+        let mut storage = self.storage.write().unwrap();
+
+        let change = Change::Remove(id, tx_id.to_string());
+        self.record(&change);
+        apply(&mut storage, change);
+    }
+
+    fn delete(&self, id: u32) {
+        let mut storage = self.storage.write().unwrap();
+
+        if storage.contains_key(&id) {
+            let change = Change::Delete(id);
+            self.record(&change);
+            apply(&mut storage, change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn given_reopened_repository_keeps_results() {
+        let path = temp_dir().join(format!("lqs-results-{}.jsonl", rand::random::<u64>()));
+
+        {
+            let repository = DiskQueryResultRepository::<()>::open(&path).unwrap();
+            repository.add_result(1, String::from("foobar"));
+            repository.add_result(1, String::from("baz"));
+            repository.remove_result(1, "baz");
+        }
+
+        let repository = DiskQueryResultRepository::<()>::open(&path).unwrap();
+
+        assert_that(&repository.get(1).map(|result| result.0))
+            .is_some()
+            .is_equal_to(vec![String::from("foobar")]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn given_incomplete_last_change_keeps_the_earlier_ones() {
+        let path = temp_dir().join(format!("lqs-results-{}.jsonl", rand::random::<u64>()));
+
+        {
+            let repository = DiskQueryResultRepository::<()>::open(&path).unwrap();
+            repository.add_result(1, String::from("foobar"));
+        }
+        let mut journal = open_for_appending(&path).unwrap();
+        std::io::Write::write_all(&mut journal, b"{\"Add\":[1,\"ba").unwrap();
+
+        let repository = DiskQueryResultRepository::<()>::open(&path).unwrap();
+
+        assert_that(&repository.get(1).map(|result| result.0))
+            .is_some()
+            .is_equal_to(vec![String::from("foobar")]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
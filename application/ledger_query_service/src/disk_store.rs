@@ -0,0 +1,114 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(display = "Failed to access {:?}", _0)]
+    Io(PathBuf, #[cause] io::Error),
+    #[fail(display = "Contents of {:?} could not be (de)serialized", _0)]
+    Serialization(PathBuf, #[cause] serde_json::Error),
+}
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<Option<T>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Io(path.to_path_buf(), e)),
+    };
+
+    serde_json::from_reader(file)
+        .map(Some)
+        .map_err(|e| Error::Serialization(path.to_path_buf(), e))
+}
+
+/// Reads a file holding one JSON document per line. A crash in the middle of
+/// an append leaves an incomplete last line behind, which is skipped.
+pub fn read_json_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(Error::Io(path.to_path_buf(), e)),
+    };
+
+    let mut lines = BufReader::new(file).lines().peekable();
+    let mut values = Vec::new();
+
+    while let Some(line) = lines.next() {
+        let line = line.map_err(|e| Error::Io(path.to_path_buf(), e))?;
+
+        match serde_json::from_str(&line) {
+            Ok(value) => values.push(value),
+            Err(ref e) if lines.peek().is_none() => {
+                warn!("Skipping incomplete last line of {:?}: {:?}", path, e);
+            }
+            Err(e) => return Err(Error::Serialization(path.to_path_buf(), e)),
+        }
+    }
+
+    Ok(values)
+}
+
+/// Writes to a temporary file first and moves it into place afterwards so
+/// that a crash halfway through never leaves a truncated document behind.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    let bytes =
+        serde_json::to_vec(value).map_err(|e| Error::Serialization(path.to_path_buf(), e))?;
+
+    write_atomically(path, &bytes)
+}
+
+/// Replaces the file with one line per value, see `write_json`.
+pub fn write_json_lines<T: Serialize>(path: &Path, values: &[T]) -> Result<(), Error> {
+    let mut bytes = Vec::new();
+    for value in values {
+        serde_json::to_writer(&mut bytes, value)
+            .map_err(|e| Error::Serialization(path.to_path_buf(), e))?;
+        bytes.push(b'\n');
+    }
+
+    write_atomically(path, &bytes)
+}
+
+pub fn open_for_appending(path: &Path) -> Result<File, Error> {
+    OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+/// Appends `value` as a line of its own and waits until it hit the disk.
+pub fn append_json_line<T: Serialize>(
+    file: &mut File,
+    path: &Path,
+    value: &T,
+) -> Result<(), Error> {
+    let mut bytes =
+        serde_json::to_vec(value).map_err(|e| Error::Serialization(path.to_path_buf(), e))?;
+    bytes.push(b'\n');
+
+    file.write_all(&bytes)
+        .and_then(|_| file.sync_data())
+        .map_err(|e| Error::Io(path.to_path_buf(), e))
+}
+
+fn write_atomically(path: &Path, bytes: &[u8]) -> Result<(), Error> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory).map_err(|e| Error::Io(directory.to_path_buf(), e))?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = File::create(&tmp_path).map_err(|e| Error::Io(tmp_path.clone(), e))?;
+    file.write_all(bytes)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| Error::Io(tmp_path.clone(), e))?;
+
+    fs::rename(&tmp_path, path).map_err(|e| Error::Io(path.to_path_buf(), e))
+}
@@ -0,0 +1,90 @@
+use crate::{
+    block_processor::Query, query_repository::QueryRepository,
+    query_result_repository::QueryResultRepository,
+};
+
+/// Deletes every query that expires at or below `height`, together with its
+/// results. Returns the ids of the deleted queries.
+pub fn delete_expired_queries<O, Q: Query<O>>(
+    queries: &dyn QueryRepository<Q>,
+    results: &dyn QueryResultRepository<Q>,
+    height: u64,
+) -> Vec<u32> {
+    let expired_ids: Vec<u32> = queries
+        .all()
+        .filter(|(_, query)| {
+            query
+                .expires_at_block()
+                .map_or(false, |expires_at_block| expires_at_block <= height)
+        })
+        .map(|(id, _)| id)
+        .collect();
+
+    for id in &expired_ids {
+        debug!("Query {} expired at block {}, deleting it", id, height);
+        queries.delete(*id);
+        results.delete(*id);
+    }
+
+    expired_ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        block_processor::QueryMatchResult, in_memory_query_repository::InMemoryQueryRepository,
+        in_memory_query_result_repository::InMemoryQueryResultRepository,
+    };
+    use futures::Future;
+    use spectral::prelude::*;
+
+    #[derive(Clone, Debug)]
+    struct ExpiringQuery {
+        expires_at_block: Option<u64>,
+    }
+
+    impl Query<u8> for ExpiringQuery {
+        fn matches(&self, _: &u8) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send> {
+            Box::new(futures::future::ok(QueryMatchResult::yes()))
+        }
+
+        fn is_empty(&self) -> bool {
+            false
+        }
+
+        fn expires_at_block(&self) -> Option<u64> {
+            self.expires_at_block
+        }
+    }
+
+    #[test]
+    fn deletes_only_queries_that_expired_with_their_results() {
+        let queries = InMemoryQueryRepository::default();
+        let results = InMemoryQueryResultRepository::default();
+        let expired = queries
+            .save(ExpiringQuery {
+                expires_at_block: Some(10),
+            })
+            .unwrap();
+        let not_yet_expired = queries
+            .save(ExpiringQuery {
+                expires_at_block: Some(11),
+            })
+            .unwrap();
+        let never_expiring = queries
+            .save(ExpiringQuery {
+                expires_at_block: None,
+            })
+            .unwrap();
+        results.add_result(expired, String::from("tx"));
+
+        let deleted = delete_expired_queries(&queries, &results, 10);
+
+        assert_that(&deleted).is_equal_to(vec![expired]);
+        assert_that(&queries.get(expired).is_none()).is_true();
+        assert_that(&results.get(expired).is_none()).is_true();
+        assert_that(&queries.get(not_yet_expired).is_some()).is_true();
+        assert_that(&queries.get(never_expiring).is_some()).is_true();
+    }
+}
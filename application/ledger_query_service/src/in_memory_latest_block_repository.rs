@@ -0,0 +1,53 @@
+use crate::latest_block_repository::{LatestBlock, LatestBlockRepository};
+use std::sync::RwLock;
+
+#[derive(Debug, Default)]
+pub struct InMemoryLatestBlockRepository {
+    latest_block: RwLock<Option<LatestBlock>>,
+}
+
+impl LatestBlockRepository for InMemoryLatestBlockRepository {
+    fn get(&self) -> Option<LatestBlock> {
+        *self.latest_block.read().unwrap()
+    }
+
+    fn set(&self, latest_block: LatestBlock) {
+        let mut current = self.latest_block.write().unwrap();
+
+        *current = Some(latest_block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spectral::prelude::*;
+
+    #[test]
+    fn given_no_block_was_set_returns_none() {
+        let repository = InMemoryLatestBlockRepository::default();
+
+        assert_that(&repository.get()).is_none();
+    }
+
+    #[test]
+    fn given_two_blocks_returns_the_last_one() {
+        let repository = InMemoryLatestBlockRepository::default();
+
+        repository.set(LatestBlock {
+            height: 100,
+            timestamp: 1_544_000_000,
+        });
+        repository.set(LatestBlock {
+            height: 101,
+            timestamp: 1_544_000_600,
+        });
+
+        assert_that(&repository.get())
+            .is_some()
+            .is_equal_to(LatestBlock {
+                height: 101,
+                timestamp: 1_544_000_600,
+            });
+    }
+}
@@ -26,6 +26,14 @@ impl<Q: Send + Sync + Clone + 'static> QueryResultRepository<Q>
         storage.insert(id, query_result);
     }
 
+    fn remove_result(&self, id: u32, tx_id: &str) {
+        let mut storage = self.storage.write().unwrap();
+
+        if let Some(query_result) = storage.get_mut(&id) {
+            query_result.0.retain(|result| result != tx_id);
+        }
+    }
+
     fn delete(&self, id: u32) {
         let mut storage = self.storage.write().unwrap();
 
@@ -66,4 +74,18 @@ mod tests {
         query_results.contains(String::from("foobar"));
         query_results.contains(String::from("baz"));
     }
+
+    #[test]
+    fn given_existing_entry_removes_only_that_result() {
+        let repository = InMemoryQueryResultRepository::<()>::default();
+
+        repository.add_result(1, String::from("foobar"));
+        repository.add_result(1, String::from("baz"));
+        repository.remove_result(1, "foobar");
+
+        assert_that(&repository.get(1))
+            .is_some()
+            .map(|r| &r.0)
+            .is_equal_to(&vec![String::from("baz")]);
+    }
 }
@@ -0,0 +1,14 @@
+/// The most recent block that was handed to the block processor of a ledger.
+///
+/// Bitcoin blocks carry their height next to the block itself, Ethereum blocks
+/// know their own number. The timestamp is the one set by the miner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatestBlock {
+    pub height: u64,
+    pub timestamp: u64,
+}
+
+pub trait LatestBlockRepository: Send + Sync + 'static {
+    fn get(&self) -> Option<LatestBlock>;
+    fn set(&self, latest_block: LatestBlock);
+}
@@ -6,28 +6,54 @@ extern crate debug_stub_derive;
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
+extern crate failure;
+#[macro_use]
 extern crate log;
 
+mod backfill;
 mod block_processor;
 mod connectors;
+mod disk_latest_block_repository;
+mod disk_query_repository;
+mod disk_query_result_repository;
+pub mod disk_store;
+mod expired_queries;
+mod in_memory_latest_block_repository;
 mod in_memory_query_repository;
 mod in_memory_query_result_repository;
+mod latest_block_repository;
+mod notifying_query_result_repository;
 mod queries;
+mod query_index;
 mod query_repository;
 mod query_result_repository;
 pub mod route_factory;
 mod routes;
 pub mod settings;
+mod unconfirmed_matches;
 
 pub use crate::{
+    backfill::*,
     block_processor::*,
-    connectors::{bitcoind_zmq_listener::*, ethereum_web3_block_poller::*},
+    connectors::{
+        bitcoind_rpc_block_poller::*, bitcoind_rpc_block_source::*, bitcoind_zmq_listener::*,
+        ethereum_web3_block_poller::*,
+    },
+    disk_latest_block_repository::*,
+    disk_query_repository::*,
+    disk_query_result_repository::*,
+    expired_queries::*,
+    in_memory_latest_block_repository::*,
     in_memory_query_repository::*,
     in_memory_query_result_repository::*,
+    latest_block_repository::*,
+    notifying_query_result_repository::*,
     queries::*,
+    query_index::*,
     query_repository::*,
     query_result_repository::*,
     route_factory::*,
     routes::*,
+    unconfirmed_matches::*,
 };
 pub use ethereum_support::web3;
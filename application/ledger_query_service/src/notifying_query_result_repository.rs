@@ -0,0 +1,136 @@
+use crate::query_result_repository::{QueryResult, QueryResultRepository};
+use futures::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+/// A change to the results of a query, as told to its subscribers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum QueryResultEvent {
+    Match(String),
+    /// The match was retracted because the block it was found in got orphaned
+    Removal(String),
+}
+
+/// Forwards every new match and every retracted one to whoever subscribed to
+/// the query, so clients don't have to poll for them.
+#[derive(DebugStub)]
+pub struct NotifyingQueryResultRepository<Q> {
+    #[debug_stub = "Results"]
+    results: Arc<dyn QueryResultRepository<Q>>,
+    subscribers: Mutex<HashMap<u32, Vec<UnboundedSender<QueryResultEvent>>>>,
+}
+
+impl<Q> NotifyingQueryResultRepository<Q> {
+    pub fn new(results: Arc<dyn QueryResultRepository<Q>>) -> Self {
+        NotifyingQueryResultRepository {
+            results,
+            subscribers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Yields the matches found so far, followed by each change as it comes
+    /// in. The stream ends when the query is deleted.
+    pub fn subscribe(&self, id: u32) -> UnboundedReceiver<QueryResultEvent> {
+        let (sender, receiver) = mpsc::unbounded();
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        for tx_id in self.results.get(id).unwrap_or_default().0 {
+            let _ = sender.unbounded_send(QueryResultEvent::Match(tx_id));
+        }
+        subscribers.entry(id).or_insert_with(Vec::new).push(sender);
+
+        receiver
+    }
+
+    fn notify(
+        subscribers: &mut HashMap<u32, Vec<UnboundedSender<QueryResultEvent>>>,
+        id: u32,
+        event: &QueryResultEvent,
+    ) {
+        if let Some(senders) = subscribers.get_mut(&id) {
+            senders.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+        }
+    }
+}
+
+impl<Q: Send + Sync + 'static> QueryResultRepository<Q> for NotifyingQueryResultRepository<Q> {
+    fn get(&self, id: u32) -> Option<QueryResult> {
+        self.results.get(id)
+    }
+
+    fn add_result(&self, id: u32, tx_id: String) {
+        // Holding the lock while adding keeps a concurrent subscriber from
+        // receiving the same match twice
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        self.results.add_result(id, tx_id.clone());
+        Self::notify(&mut subscribers, id, &QueryResultEvent::Match(tx_id));
+    }
+
+    fn remove_result(&self, id: u32, tx_id: &str) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        self.results.remove_result(id, tx_id);
+        Self::notify(
+            &mut subscribers,
+            id,
+            &QueryResultEvent::Removal(tx_id.to_string()),
+        );
+    }
+
+    fn delete(&self, id: u32) {
+        let mut subscribers = self.subscribers.lock().unwrap();
+
+        self.results.delete(id);
+        subscribers.remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_query_result_repository::InMemoryQueryResultRepository;
+    use futures::Stream;
+    use spectral::prelude::*;
+
+    #[test]
+    fn given_subscription_receives_existing_and_new_matches_until_query_is_deleted() {
+        let repository = NotifyingQueryResultRepository::<()>::new(Arc::new(
+            InMemoryQueryResultRepository::default(),
+        ));
+        repository.add_result(1, String::from("existing"));
+
+        let matches = repository.subscribe(1);
+        repository.add_result(1, String::from("new"));
+        repository.add_result(2, String::from("other query"));
+        repository.delete(1);
+
+        assert_that(&matches.collect().wait())
+            .is_ok()
+            .is_equal_to(vec![
+                QueryResultEvent::Match(String::from("existing")),
+                QueryResultEvent::Match(String::from("new")),
+            ]);
+    }
+
+    #[test]
+    fn given_subscription_receives_retracted_matches() {
+        let repository = NotifyingQueryResultRepository::<()>::new(Arc::new(
+            InMemoryQueryResultRepository::default(),
+        ));
+
+        let events = repository.subscribe(1);
+        repository.add_result(1, String::from("orphaned"));
+        repository.remove_result(1, "orphaned");
+        repository.delete(1);
+
+        assert_that(&events.collect().wait())
+            .is_ok()
+            .is_equal_to(vec![
+                QueryResultEvent::Match(String::from("orphaned")),
+                QueryResultEvent::Removal(String::from("orphaned")),
+            ]);
+    }
+}
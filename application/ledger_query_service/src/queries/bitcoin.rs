@@ -1,11 +1,12 @@
 use crate::{
     block_processor::{Block, Query, QueryMatchResult, Transaction},
+    latest_block_repository::LatestBlock,
     query_result_repository::QueryResult,
     route_factory::{Error, ExpandResult, QueryParams, QueryType, ShouldExpand},
 };
 use bitcoin_rpc_client::{BitcoinCoreClient, BitcoinRpcApi};
 use bitcoin_support::{
-    serialize::BitcoinHash, Address, MinedBlock as BitcoinBlock, OutPoint, SpendsFrom,
+    serialize::BitcoinHash, Address, MinedBlock as BitcoinBlock, OutPoint, Script, SpendsFrom,
     SpendsFromWith, SpendsTo, SpendsWith, Transaction as BitcoinTransaction, TransactionId,
 };
 use futures::Future;
@@ -18,6 +19,12 @@ pub struct BitcoinTransactionQuery {
     pub unlock_script: Option<Vec<Vec<u8>>>,
     #[serde(default = "default_confirmations")]
     confirmations_needed: u32,
+    #[serde(default)]
+    from_block_height: Option<u64>,
+    #[serde(default)]
+    include_unconfirmed: bool,
+    #[serde(default)]
+    expires_at_block: Option<u64>,
 }
 
 impl QueryType for BitcoinTransactionQuery {
@@ -39,8 +46,8 @@ impl ExpandResult for BitcoinTransactionQuery {
     fn expand_result(
         result: &QueryResult,
         client: Arc<BitcoinCoreClient>,
-    ) -> Result<Vec<BitcoinTransaction>, Error> {
-        let mut expanded_result: Vec<BitcoinTransaction> = Vec::new();
+    ) -> Result<Vec<(BitcoinTransaction, Option<LatestBlock>)>, Error> {
+        let mut expanded_result = Vec::new();
         for tx_id in result.clone().0 {
             let tx_id = TransactionId::from_hex(tx_id.as_str()).map_err(|_| Error::InvalidHex)?;
 
@@ -48,7 +55,16 @@ impl ExpandResult for BitcoinTransactionQuery {
                 .get_raw_transaction_verbose(&tx_id)
                 .map_err(Error::BitcoinRpcConnection)?
                 .map_err(Error::BitcoinRpcResponse)?;
-            expanded_result.push(transaction.into());
+            let block = client
+                .get_block(&transaction.blockhash)
+                .map_err(Error::BitcoinRpcConnection)?
+                .map_err(Error::BitcoinRpcResponse)?;
+            let mined_in = LatestBlock {
+                height: u64::from(block.height),
+                timestamp: block.time,
+            };
+
+            expanded_result.push((transaction.into(), Some(mined_in)));
         }
         Ok(expanded_result)
     }
@@ -69,6 +85,7 @@ impl Query<BitcoinTransaction> for BitcoinTransactionQuery {
                 from_outpoint,
                 unlock_script,
                 confirmations_needed,
+                ..
             } => {
                 let mut result = true;
 
@@ -101,12 +118,52 @@ impl Query<BitcoinTransaction> for BitcoinTransactionQuery {
     fn is_empty(&self) -> bool {
         self.to_address.is_none() && self.from_outpoint.is_none() && self.unlock_script.is_none()
     }
+
+    fn from_block_height(&self) -> Option<u64> {
+        self.from_block_height
+    }
+
+    fn include_unconfirmed(&self) -> bool {
+        self.include_unconfirmed
+    }
+
+    fn expires_at_block(&self) -> Option<u64> {
+        self.expires_at_block
+    }
+
+    fn index_key(&self) -> Option<String> {
+        match (&self.from_outpoint, &self.to_address) {
+            (Some(from_outpoint), _) => Some(outpoint_key(from_outpoint)),
+            (None, Some(to_address)) => Some(script_pubkey_key(&to_address.script_pubkey())),
+            (None, None) => None,
+        }
+    }
+}
+
+fn outpoint_key(outpoint: &OutPoint) -> String {
+    format!("outpoint:{}:{}", outpoint.txid, outpoint.vout)
+}
+
+fn script_pubkey_key(script_pubkey: &Script) -> String {
+    format!("script_pubkey:{}", hex::encode(script_pubkey.as_bytes()))
 }
 
 impl Transaction for BitcoinTransaction {
     fn transaction_id(&self) -> String {
         self.txid().to_string()
     }
+
+    fn index_keys(&self) -> Vec<String> {
+        self.input
+            .iter()
+            .map(|input| outpoint_key(&input.previous_output))
+            .chain(
+                self.output
+                    .iter()
+                    .map(|output| script_pubkey_key(&output.script_pubkey)),
+            )
+            .collect()
+    }
 }
 
 impl Block for BitcoinBlock {
@@ -118,14 +175,28 @@ impl Block for BitcoinBlock {
     fn prev_blockhash(&self) -> String {
         format!("{:x}", self.as_ref().header.prev_blockhash)
     }
+    fn height(&self) -> u64 {
+        u64::from(self.height)
+    }
     fn transactions(&self) -> &[BitcoinTransaction] {
         self.as_ref().txdata.as_slice()
     }
 }
 
+impl<'a> From<&'a BitcoinBlock> for LatestBlock {
+    fn from(block: &'a BitcoinBlock) -> Self {
+        LatestBlock {
+            height: u64::from(block.height),
+            timestamp: u64::from(block.as_ref().header.time),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct BitcoinBlockQuery {
     pub min_height: Option<u32>,
+    #[serde(default)]
+    pub expires_at_block: Option<u64>,
 }
 
 impl QueryType for BitcoinBlockQuery {
@@ -144,7 +215,10 @@ impl ExpandResult for BitcoinBlockQuery {
     type Client = ();
     type Item = ();
 
-    fn expand_result(_result: &QueryResult, _client: Arc<()>) -> Result<Vec<Self::Item>, Error> {
+    fn expand_result(
+        _result: &QueryResult,
+        _client: Arc<()>,
+    ) -> Result<Vec<(Self::Item, Option<LatestBlock>)>, Error> {
         unimplemented!()
     }
 }
@@ -172,6 +246,10 @@ impl Query<BitcoinBlock> for BitcoinBlockQuery {
     fn is_empty(&self) -> bool {
         self.min_height.is_none()
     }
+
+    fn expires_at_block(&self) -> Option<u64> {
+        self.expires_at_block
+    }
 }
 
 #[cfg(test)]
@@ -223,6 +301,7 @@ mod tests {
 
         let query = BitcoinBlockQuery {
             min_height: Some(42),
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&block));
@@ -250,6 +329,7 @@ mod tests {
 
         let query = BitcoinBlockQuery {
             min_height: Some(42),
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&block));
@@ -277,6 +357,7 @@ mod tests {
 
         let query = BitcoinBlockQuery {
             min_height: Some(42),
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&block));
@@ -292,6 +373,9 @@ mod tests {
             from_outpoint: None,
             unlock_script: None,
             confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&tx));
@@ -311,6 +395,9 @@ mod tests {
             from_outpoint: None,
             unlock_script: Some(unlock_script),
             confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&tx));
@@ -328,6 +415,9 @@ mod tests {
             from_outpoint: None,
             unlock_script: Some(unlock_script),
             confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&tx));
@@ -351,12 +441,43 @@ mod tests {
             from_outpoint: Some(outpoint),
             unlock_script: Some(unlock_script),
             confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let result = exec_future(query.matches(&tx));
         assert_that(&result).is_equal_to(QueryMatchResult::yes());
     }
 
+    #[test]
+    fn given_transaction_with_to_then_index_key_of_to_address_query_is_among_its_keys() {
+        let tx = parse_raw_tx(WITNESS_TX);
+
+        let query = BitcoinTransactionQuery {
+            to_address: Some("329XTScM6cJgu8VZvaqYWpfuxT1eQDSJkP".parse().unwrap()),
+            ..BitcoinTransactionQuery::default()
+        };
+
+        assert_that(&tx.index_keys()).contains(query.index_key().unwrap());
+    }
+
+    #[test]
+    fn given_transaction_spending_outpoint_then_index_key_of_outpoint_query_is_among_its_keys() {
+        let tx = parse_raw_tx(WITNESS_TX);
+        let outpoint = create_outpoint(
+            "ad067ee417ee5518122374307d1fa494c67e30c75d38c7061d944b59e56fe024",
+            1u32,
+        );
+
+        let query = BitcoinTransactionQuery {
+            from_outpoint: Some(outpoint),
+            ..BitcoinTransactionQuery::default()
+        };
+
+        assert_that(&tx.index_keys()).contains(query.index_key().unwrap());
+    }
+
     fn exec_future(
         future: Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send>,
     ) -> QueryMatchResult {
@@ -1,21 +1,24 @@
 use crate::{
     block_processor::{Block, Query, QueryMatchResult, Transaction},
+    latest_block_repository::LatestBlock,
     query_result_repository::QueryResult,
     route_factory::{Error, ExpandResult, QueryParams, QueryType, ShouldExpand},
 };
 use ethereum_support::{
     web3::{
         transports::Http,
-        types::{H256, U256},
+        types::{BlockId, H256, U256},
         Web3,
     },
-    Address, Block as EthereumBlock, Bytes, Transaction as EthereumTransaction, TransactionId,
+    Address, Block as EthereumBlock, Bytes, Log, Transaction as EthereumTransaction, TransactionId,
+    TransactionReceipt,
 };
 use futures::{
-    future::Future,
+    future::{self, Either, Future},
     stream::{self, Stream},
 };
 use std::sync::Arc;
+use tiny_keccak::keccak256;
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct EthereumTransactionQuery {
@@ -24,6 +27,14 @@ pub struct EthereumTransactionQuery {
     is_contract_creation: Option<bool>,
     transaction_data: Option<Bytes>,
     transaction_data_length: Option<usize>,
+    #[serde(default = "default_confirmations")]
+    confirmations_needed: u32,
+    #[serde(default)]
+    from_block_height: Option<u64>,
+    #[serde(default)]
+    include_unconfirmed: bool,
+    #[serde(default)]
+    expires_at_block: Option<u64>,
 }
 
 impl QueryType for EthereumTransactionQuery {
@@ -38,6 +49,10 @@ impl ShouldExpand for EthereumTransactionQuery {
     }
 }
 
+fn default_confirmations() -> u32 {
+    1
+}
+
 fn clean_0x(s: &str) -> &str {
     if s.starts_with("0x") {
         &s[2..]
@@ -46,6 +61,69 @@ fn clean_0x(s: &str) -> &str {
     }
 }
 
+fn expand_transactions(
+    result: &QueryResult,
+    client: Arc<Web3<Http>>,
+) -> Result<Vec<(EthereumTransaction, Option<LatestBlock>)>, Error> {
+    let futures: Vec<_> = result
+        .0
+        .iter()
+        .filter_map(|tx_id| match hex::decode(clean_0x(tx_id)) {
+            Ok(bytes) => Some(bytes),
+            Err(e) => {
+                warn!("Skipping {} because it is not valid hex: {:?}", tx_id, e);
+                None
+            }
+        })
+        .map(|id| {
+            let client = Arc::clone(&client);
+
+            client
+                .eth()
+                .transaction(TransactionId::Hash(H256::from_slice(id.as_ref())))
+                .map_err(Error::Web3)
+                .and_then(move |transaction| match transaction {
+                    Some(transaction) => Either::A(
+                        mined_in(&client, &transaction)
+                            .map(move |mined_in| Some((transaction, mined_in))),
+                    ),
+                    None => Either::B(future::ok(None)),
+                })
+        })
+        .collect();
+
+    stream::futures_ordered(futures)
+        .filter_map(|item| item)
+        .collect()
+        .wait()
+}
+
+/// Pending transactions have no block yet, every other one is looked up by
+/// the hash of the block that included it.
+fn mined_in(
+    client: &Web3<Http>,
+    transaction: &EthereumTransaction,
+) -> impl Future<Item = Option<LatestBlock>, Error = Error> {
+    match transaction.block_hash {
+        Some(block_hash) => Either::A(
+            client
+                .eth()
+                .block(BlockId::Hash(block_hash))
+                .map_err(Error::Web3)
+                .map(|block| {
+                    block.map(|block| LatestBlock {
+                        height: block
+                            .number
+                            .map(|number| number.low_u64())
+                            .unwrap_or_default(),
+                        timestamp: block.timestamp.low_u64(),
+                    })
+                }),
+        ),
+        None => Either::B(future::ok(None)),
+    }
+}
+
 impl ExpandResult for EthereumTransactionQuery {
     type Client = Web3<Http>;
     type Item = EthereumTransaction;
@@ -53,29 +131,8 @@ impl ExpandResult for EthereumTransactionQuery {
     fn expand_result(
         result: &QueryResult,
         client: Arc<Web3<Http>>,
-    ) -> Result<Vec<Self::Item>, Error> {
-        let futures: Vec<_> = result
-            .0
-            .iter()
-            .filter_map(|tx_id| match hex::decode(clean_0x(tx_id)) {
-                Ok(bytes) => Some(bytes),
-                Err(e) => {
-                    warn!("Skipping {} because it is not valid hex: {:?}", tx_id, e);
-                    None
-                }
-            })
-            .map(|id| {
-                client
-                    .eth()
-                    .transaction(TransactionId::Hash(H256::from_slice(id.as_ref())))
-                    .map_err(Error::Web3)
-            })
-            .collect();
-
-        stream::futures_ordered(futures)
-            .filter_map(|item| item)
-            .collect()
-            .wait()
+    ) -> Result<Vec<(Self::Item, Option<LatestBlock>)>, Error> {
+        expand_transactions(result, client)
     }
 }
 
@@ -91,6 +148,7 @@ impl Query<EthereumTransaction> for EthereumTransactionQuery {
                 is_contract_creation: None,
                 transaction_data: None,
                 transaction_data_length: None,
+                ..
             } => Box::new(futures::future::ok(QueryMatchResult::no())),
             Self {
                 from_address,
@@ -98,6 +156,8 @@ impl Query<EthereumTransaction> for EthereumTransactionQuery {
                 is_contract_creation,
                 transaction_data,
                 transaction_data_length,
+                confirmations_needed,
+                ..
             } => {
                 let mut result = true;
 
@@ -123,7 +183,9 @@ impl Query<EthereumTransaction> for EthereumTransactionQuery {
                 }
 
                 if result {
-                    Box::new(futures::future::ok(QueryMatchResult::yes()))
+                    Box::new(futures::future::ok(
+                        QueryMatchResult::yes_with_confirmations(*confirmations_needed),
+                    ))
                 } else {
                     Box::new(futures::future::ok(QueryMatchResult::no()))
                 }
@@ -134,12 +196,154 @@ impl Query<EthereumTransaction> for EthereumTransactionQuery {
     fn is_empty(&self) -> bool {
         self.from_address.is_none() && self.to_address.is_none() && self.transaction_data.is_none()
     }
+
+    fn from_block_height(&self) -> Option<u64> {
+        self.from_block_height
+    }
+
+    fn include_unconfirmed(&self) -> bool {
+        self.include_unconfirmed
+    }
+
+    fn expires_at_block(&self) -> Option<u64> {
+        self.expires_at_block
+    }
+
+    fn index_key(&self) -> Option<String> {
+        match (&self.to_address, &self.transaction_data) {
+            (Some(to_address), _) => Some(to_address_key(to_address)),
+            // Contract creations have no recipient, the code they deploy is
+            // what identifies them
+            (None, Some(transaction_data)) => Some(transaction_data_key(transaction_data)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn to_address_key(to_address: &Address) -> String {
+    format!("to_address:{:x}", to_address)
+}
+
+fn transaction_data_key(transaction_data: &Bytes) -> String {
+    format!(
+        "transaction_data:{}",
+        hex::encode(keccak256(&transaction_data.0))
+    )
+}
+
+/// Matches transactions that emitted a log from `address` whose topics match
+/// `topics` position by position. A `None` topic matches anything, so e.g.
+/// `[Transfer, None, to]` finds every ERC20 transfer to `to`. If `data` is
+/// given, the log's non-indexed data has to be exactly that, e.g. the amount of
+/// a transfer.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct EthereumTransactionLogQuery {
+    address: Option<Address>,
+    #[serde(default)]
+    topics: Vec<Option<H256>>,
+    #[serde(default)]
+    data: Option<Bytes>,
+    #[serde(default = "default_confirmations")]
+    confirmations_needed: u32,
+    #[serde(default)]
+    from_block_height: Option<u64>,
+    #[serde(default)]
+    expires_at_block: Option<u64>,
+}
+
+impl QueryType for EthereumTransactionLogQuery {
+    fn route() -> &'static str {
+        "logs"
+    }
+}
+
+impl ShouldExpand for EthereumTransactionLogQuery {
+    fn should_expand(params: &QueryParams) -> bool {
+        params.expand_results
+    }
+}
+
+impl ExpandResult for EthereumTransactionLogQuery {
+    type Client = Web3<Http>;
+    type Item = EthereumTransaction;
+
+    fn expand_result(
+        result: &QueryResult,
+        client: Arc<Web3<Http>>,
+    ) -> Result<Vec<(Self::Item, Option<LatestBlock>)>, Error> {
+        expand_transactions(result, client)
+    }
+}
+
+impl EthereumTransactionLogQuery {
+    fn matches_log(&self, log: &Log) -> bool {
+        if let Some(address) = self.address {
+            if log.address != address {
+                return false;
+            }
+        }
+
+        if let Some(ref data) = self.data {
+            if log.data != *data {
+                return false;
+            }
+        }
+
+        self.topics
+            .iter()
+            .enumerate()
+            .all(|(index, topic)| match topic {
+                Some(topic) => log.topics.get(index) == Some(topic),
+                None => true,
+            })
+    }
+}
+
+impl Query<TransactionReceipt> for EthereumTransactionLogQuery {
+    fn matches(
+        &self,
+        receipt: &TransactionReceipt,
+    ) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send> {
+        if receipt.logs.iter().any(|log| self.matches_log(log)) {
+            Box::new(futures::future::ok(
+                QueryMatchResult::yes_with_confirmations(self.confirmations_needed),
+            ))
+        } else {
+            Box::new(futures::future::ok(QueryMatchResult::no()))
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.address.is_none() && self.topics.iter().all(Option::is_none)
+    }
+
+    fn from_block_height(&self) -> Option<u64> {
+        self.from_block_height
+    }
+
+    fn expires_at_block(&self) -> Option<u64> {
+        self.expires_at_block
+    }
 }
 
 impl Transaction for EthereumTransaction {
     fn transaction_id(&self) -> String {
         format!("{:?}", self.hash)
     }
+
+    fn index_keys(&self) -> Vec<String> {
+        self.to
+            .iter()
+            .map(to_address_key)
+            .chain(Some(transaction_data_key(&self.input)))
+            .collect()
+    }
+}
+
+impl Transaction for TransactionReceipt {
+    fn transaction_id(&self) -> String {
+        format!("{:?}", self.transaction_hash)
+    }
 }
 
 impl Block for EthereumBlock<EthereumTransaction> {
@@ -150,11 +354,50 @@ impl Block for EthereumBlock<EthereumTransaction> {
     fn prev_blockhash(&self) -> String {
         format!("{:x}", self.parent_hash)
     }
+    fn height(&self) -> u64 {
+        LatestBlock::from(self).height
+    }
     fn transactions(&self) -> &[Self::Transaction] {
         self.transactions.as_slice()
     }
 }
 
+/// Logs are only part of the transaction receipts, which have to be fetched
+/// separately from the block.
+#[derive(Debug, Clone)]
+pub struct EthereumBlockWithReceipts {
+    pub block: EthereumBlock<EthereumTransaction>,
+    pub receipts: Vec<TransactionReceipt>,
+}
+
+impl Block for EthereumBlockWithReceipts {
+    type Transaction = TransactionReceipt;
+    fn blockhash(&self) -> String {
+        self.block.blockhash()
+    }
+    fn prev_blockhash(&self) -> String {
+        self.block.prev_blockhash()
+    }
+    fn height(&self) -> u64 {
+        self.block.height()
+    }
+    fn transactions(&self) -> &[Self::Transaction] {
+        self.receipts.as_slice()
+    }
+}
+
+impl<'a> From<&'a EthereumBlock<EthereumTransaction>> for LatestBlock {
+    fn from(block: &'a EthereumBlock<EthereumTransaction>) -> Self {
+        LatestBlock {
+            height: block
+                .number
+                .map(|number| number.low_u64())
+                .unwrap_or_default(),
+            timestamp: block.timestamp.low_u64(),
+        }
+    }
+}
+
 impl Query<EthereumBlock<EthereumTransaction>> for EthereumBlockQuery {
     fn matches(
         &self,
@@ -178,11 +421,33 @@ impl Query<EthereumBlock<EthereumTransaction>> for EthereumBlockQuery {
     fn is_empty(&self) -> bool {
         self.min_timestamp_secs.is_none()
     }
+
+    fn expires_at_block(&self) -> Option<u64> {
+        self.expires_at_block
+    }
+}
+
+impl Query<EthereumBlockWithReceipts> for EthereumBlockQuery {
+    fn matches(
+        &self,
+        block: &EthereumBlockWithReceipts,
+    ) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send> {
+        Query::<EthereumBlock<EthereumTransaction>>::matches(self, &block.block)
+    }
+    fn is_empty(&self) -> bool {
+        self.min_timestamp_secs.is_none()
+    }
+
+    fn expires_at_block(&self) -> Option<u64> {
+        self.expires_at_block
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
 pub struct EthereumBlockQuery {
     pub min_timestamp_secs: Option<u64>,
+    #[serde(default)]
+    pub expires_at_block: Option<u64>,
 }
 
 impl QueryType for EthereumBlockQuery {
@@ -201,7 +466,10 @@ impl ExpandResult for EthereumBlockQuery {
     type Client = ();
     type Item = ();
 
-    fn expand_result(_result: &QueryResult, _client: Arc<()>) -> Result<Vec<Self::Item>, Error> {
+    fn expand_result(
+        _result: &QueryResult,
+        _client: Arc<()>,
+    ) -> Result<Vec<(Self::Item, Option<LatestBlock>)>, Error> {
         unimplemented!()
     }
 }
@@ -209,7 +477,10 @@ impl ExpandResult for EthereumBlockQuery {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::web3::types::{Bytes, Transaction, H256, U256};
+    use crate::{
+        query_index::QueryIndex,
+        web3::types::{Bytes, Transaction, H256, U256},
+    };
     use spectral::prelude::*;
 
     #[test]
@@ -222,6 +493,10 @@ mod tests {
             is_contract_creation: Some(true),
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -250,6 +525,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -280,6 +559,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -310,6 +593,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -340,6 +627,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -368,6 +659,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: Some(Bytes::from(vec![1, 2, 3, 4, 5])),
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let query_data_length = EthereumTransactionQuery {
@@ -376,6 +671,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: Some(5),
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let refund_query = EthereumTransactionQuery {
@@ -384,6 +683,10 @@ mod tests {
             is_contract_creation: Some(false),
             transaction_data: Some(Bytes::from(vec![])),
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -410,6 +713,33 @@ mod tests {
         assert_that(&result).is_equal_to(QueryMatchResult::no());
     }
 
+    #[test]
+    fn given_contract_creation_query_only_transactions_deploying_that_code_are_candidates() {
+        let query = EthereumTransactionQuery {
+            is_contract_creation: Some(true),
+            transaction_data: Some(Bytes::from(vec![1, 2, 3, 4, 5])),
+            ..EthereumTransactionQuery::default()
+        };
+        let index = QueryIndex::new(vec![(1, query)].into_iter());
+
+        let contract_creation = |input: Vec<u8>| Transaction {
+            hash: H256::from(123),
+            nonce: U256::from(1),
+            block_hash: None,
+            block_number: None,
+            transaction_index: None,
+            from: "0aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".parse().unwrap(),
+            to: None,
+            value: U256::from(0),
+            gas_price: U256::from(0),
+            gas: U256::from(0),
+            input: Bytes::from(input),
+        };
+
+        assert_that(&index.candidates(&contract_creation(vec![1, 2, 3, 4, 5]))).has_length(1);
+        assert_that(&index.candidates(&contract_creation(vec![5, 4, 3, 2, 1]))).is_empty();
+    }
+
     #[test]
     fn given_query_transaction_data_is_empty_transaction_matches() {
         let query_data = EthereumTransactionQuery {
@@ -418,6 +748,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: Some(Bytes::from(vec![])),
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let query_data_length = EthereumTransactionQuery {
@@ -426,6 +760,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: Some(0),
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -457,6 +795,10 @@ mod tests {
             is_contract_creation: None,
             transaction_data: None,
             transaction_data_length: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
         };
 
         let transaction = Transaction {
@@ -476,6 +818,193 @@ mod tests {
         assert_that(&result).is_equal_to(QueryMatchResult::no())
     }
 
+    #[test]
+    fn given_query_with_confirmations_needed_matching_transaction_waits_for_them() {
+        let to_address = "a00f2cac7bad9285ecfd59e8860f5b2d8622e099".parse().unwrap();
+
+        let query = EthereumTransactionQuery {
+            from_address: None,
+            to_address: Some(to_address),
+            is_contract_creation: None,
+            transaction_data: None,
+            transaction_data_length: None,
+            confirmations_needed: 12,
+            from_block_height: None,
+            include_unconfirmed: false,
+            expires_at_block: None,
+        };
+
+        let transaction = Transaction {
+            hash: H256::from(123),
+            nonce: U256::from(1),
+            block_hash: None,
+            block_number: None,
+            transaction_index: None,
+            from: "0aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".parse().unwrap(),
+            to: Some(to_address),
+            value: U256::from(0),
+            gas_price: U256::from(0),
+            gas: U256::from(0),
+            input: Bytes::from(vec![]),
+        };
+
+        let result = exec_future(query.matches(&transaction));
+        assert_that(&result).is_equal_to(QueryMatchResult::yes_with_confirmations(12));
+    }
+
+    #[test]
+    fn given_query_without_confirmations_needed_defaults_to_one() {
+        let query: EthereumTransactionQuery =
+            serde_json::from_str(r#"{"to_address":"0xa00f2cac7bad9285ecfd59e8860f5b2d8622e099"}"#)
+                .unwrap();
+
+        assert_that(&query.confirmations_needed).is_equal_to(1);
+    }
+
+    const TRANSFER_LOG_MSG: &str =
+        "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+    const TOKEN_CONTRACT: &str = "0xb97048628db6b661d4c2aa833e95dbe1a905b280";
+    const FROM_TOPIC: &str = "0x000000000000000000000000a00f2cac7bad9285ecfd59e8860f5b2d8622e099";
+    const TO_TOPIC: &str = "0x0000000000000000000000000bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+
+    fn receipt_with_transfer_log(to_topic: &str) -> TransactionReceipt {
+        serde_json::from_value(serde_json::json!({
+            "transactionHash": "0x000000000000000000000000000000000000000000000000000000000000007b",
+            "transactionIndex": "0x0",
+            "blockHash": "0x0000000000000000000000000000000000000000000000000000000000000001",
+            "blockNumber": "0x1",
+            "cumulativeGasUsed": "0xca13",
+            "gasUsed": "0xca13",
+            "contractAddress": null,
+            "status": "0x1",
+            "logsBloom": format!("0x{}", "0".repeat(512)),
+            "logs": [{
+                "address": TOKEN_CONTRACT,
+                "topics": [TRANSFER_LOG_MSG, FROM_TOPIC, to_topic],
+                "data": "0x0000000000000000000000000000000000000000000000000000000000000064",
+                "blockHash": "0x0000000000000000000000000000000000000000000000000000000000000001",
+                "blockNumber": "0x1",
+                "transactionHash": "0x000000000000000000000000000000000000000000000000000000000000007b",
+                "transactionIndex": "0x0",
+                "logIndex": "0x0",
+                "transactionLogIndex": "0x0",
+                "type": "mined",
+                "removed": false
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn given_log_query_for_transfers_to_address_transaction_with_such_transfer_matches() {
+        let query = EthereumTransactionLogQuery {
+            address: Some(TOKEN_CONTRACT.into()),
+            topics: vec![Some(TRANSFER_LOG_MSG.into()), None, Some(TO_TOPIC.into())],
+            data: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        let result = exec_future(query.matches(&receipt_with_transfer_log(TO_TOPIC)));
+        assert_that(&result).is_equal_to(QueryMatchResult::yes_with_confirmations(0));
+    }
+
+    #[test]
+    fn given_log_query_for_transfers_to_address_transfer_to_other_address_doesnt_match() {
+        let query = EthereumTransactionLogQuery {
+            address: Some(TOKEN_CONTRACT.into()),
+            topics: vec![Some(TRANSFER_LOG_MSG.into()), None, Some(TO_TOPIC.into())],
+            data: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        let result = exec_future(query.matches(&receipt_with_transfer_log(FROM_TOPIC)));
+        assert_that(&result).is_equal_to(QueryMatchResult::no());
+    }
+
+    #[test]
+    fn given_log_query_for_other_contract_transfer_doesnt_match() {
+        let query = EthereumTransactionLogQuery {
+            address: Some("0x0aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".into()),
+            topics: vec![Some(TRANSFER_LOG_MSG.into())],
+            data: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        let result = exec_future(query.matches(&receipt_with_transfer_log(TO_TOPIC)));
+        assert_that(&result).is_equal_to(QueryMatchResult::no());
+    }
+
+    #[test]
+    fn given_log_query_with_more_topics_than_the_log_doesnt_match() {
+        let query = EthereumTransactionLogQuery {
+            address: None,
+            topics: vec![None, None, None, Some(TO_TOPIC.into())],
+            data: None,
+            confirmations_needed: 0,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        let result = exec_future(query.matches(&receipt_with_transfer_log(TO_TOPIC)));
+        assert_that(&result).is_equal_to(QueryMatchResult::no());
+    }
+
+    #[test]
+    fn given_log_query_for_transfer_of_amount_transfer_of_that_amount_matches() {
+        let mut amount_100 = [0u8; 32];
+        amount_100[31] = 100;
+
+        let query = EthereumTransactionLogQuery {
+            address: Some(TOKEN_CONTRACT.into()),
+            topics: vec![Some(TRANSFER_LOG_MSG.into()), None, Some(TO_TOPIC.into())],
+            data: Some(Bytes::from(amount_100.to_vec())),
+            confirmations_needed: 0,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        let result = exec_future(query.matches(&receipt_with_transfer_log(TO_TOPIC)));
+        assert_that(&result).is_equal_to(QueryMatchResult::yes_with_confirmations(0));
+    }
+
+    #[test]
+    fn given_log_query_for_transfer_of_amount_smaller_transfer_doesnt_match() {
+        let mut amount_200 = [0u8; 32];
+        amount_200[31] = 200;
+
+        let query = EthereumTransactionLogQuery {
+            address: Some(TOKEN_CONTRACT.into()),
+            topics: vec![Some(TRANSFER_LOG_MSG.into()), None, Some(TO_TOPIC.into())],
+            data: Some(Bytes::from(amount_200.to_vec())),
+            confirmations_needed: 0,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        let result = exec_future(query.matches(&receipt_with_transfer_log(TO_TOPIC)));
+        assert_that(&result).is_equal_to(QueryMatchResult::no());
+    }
+
+    #[test]
+    fn given_log_query_without_address_or_topics_is_empty() {
+        let query = EthereumTransactionLogQuery {
+            address: None,
+            topics: vec![None, None],
+            data: None,
+            confirmations_needed: 1,
+            from_block_height: None,
+            expires_at_block: None,
+        };
+
+        assert_that(&query.is_empty()).is_true();
+    }
+
     fn exec_future(
         future: Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send>,
     ) -> QueryMatchResult {
@@ -0,0 +1,112 @@
+use crate::block_processor::{Query, Transaction};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+};
+
+/// Transaction queries filed under their index key, so a transaction only has
+/// to be checked against the queries filed under one of its own keys. Queries
+/// without a key could match anything and are checked against every
+/// transaction.
+#[derive(Debug)]
+pub struct QueryIndex<T, Q> {
+    indexed: HashMap<String, Vec<(u32, Q)>>,
+    unindexed: Vec<(u32, Q)>,
+    transaction_type: PhantomData<T>,
+}
+
+impl<T: Transaction, Q: Query<T>> QueryIndex<T, Q> {
+    pub fn new<I: Iterator<Item = (u32, Q)>>(queries: I) -> Self {
+        let mut indexed = HashMap::new();
+        let mut unindexed = Vec::new();
+
+        for (query_id, query) in queries {
+            match query.index_key() {
+                Some(key) => indexed
+                    .entry(key)
+                    .or_insert_with(Vec::new)
+                    .push((query_id, query)),
+                None => unindexed.push((query_id, query)),
+            }
+        }
+
+        QueryIndex {
+            indexed,
+            unindexed,
+            transaction_type: PhantomData,
+        }
+    }
+
+    /// The queries that `transaction` could possibly match.
+    pub fn candidates(&self, transaction: &T) -> Vec<&(u32, Q)> {
+        let keys: HashSet<String> = transaction.index_keys().into_iter().collect();
+
+        keys.iter()
+            .filter_map(|key| self.indexed.get(key))
+            .flatten()
+            .chain(self.unindexed.iter())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_processor::QueryMatchResult;
+    use futures::Future;
+    use spectral::prelude::*;
+
+    #[derive(Clone, Debug)]
+    struct KeyedQuery(Option<&'static str>);
+
+    impl Query<KeyedTransaction> for KeyedQuery {
+        fn matches(
+            &self,
+            _: &KeyedTransaction,
+        ) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send> {
+            Box::new(futures::future::ok(QueryMatchResult::yes()))
+        }
+
+        fn is_empty(&self) -> bool {
+            false
+        }
+
+        fn index_key(&self) -> Option<String> {
+            self.0.map(String::from)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct KeyedTransaction(Vec<&'static str>);
+
+    impl Transaction for KeyedTransaction {
+        fn transaction_id(&self) -> String {
+            String::from("tx")
+        }
+
+        fn index_keys(&self) -> Vec<String> {
+            self.0.iter().map(|key| String::from(*key)).collect()
+        }
+    }
+
+    #[test]
+    fn given_transaction_keys_candidates_are_queries_under_those_keys_and_unindexed_ones() {
+        let index = QueryIndex::new(
+            vec![
+                (1, KeyedQuery(Some("a"))),
+                (2, KeyedQuery(Some("b"))),
+                (3, KeyedQuery(None)),
+            ]
+            .into_iter(),
+        );
+
+        let mut candidates: Vec<u32> = index
+            .candidates(&KeyedTransaction(vec!["a", "a", "c"]))
+            .into_iter()
+            .map(|(query_id, _)| *query_id)
+            .collect();
+        candidates.sort();
+
+        assert_that(&candidates).is_equal_to(vec![1, 3]);
+    }
+}
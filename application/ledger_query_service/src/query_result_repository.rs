@@ -1,8 +1,9 @@
-#[derive(Debug, Serialize, Clone, Default)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct QueryResult(pub Vec<String>);
 
 pub trait QueryResultRepository<T>: Send + Sync + 'static {
     fn get(&self, id: u32) -> Option<QueryResult>;
     fn add_result(&self, id: u32, tx_id: String);
+    fn remove_result(&self, id: u32, tx_id: &str);
     fn delete(&self, id: u32);
 }
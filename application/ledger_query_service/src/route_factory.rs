@@ -1,8 +1,12 @@
 use crate::{
     block_processor::Query,
+    latest_block_repository::{LatestBlock, LatestBlockRepository},
+    notifying_query_result_repository::NotifyingQueryResultRepository,
     query_repository::QueryRepository,
-    query_result_repository::{QueryResult, QueryResultRepository},
-    routes, web3,
+    query_result_repository::QueryResult,
+    routes,
+    unconfirmed_matches::UnconfirmedMatches,
+    web3,
 };
 use serde::{de::DeserializeOwned, Serialize};
 use std::sync::Arc;
@@ -30,10 +34,12 @@ pub trait ExpandResult {
     type Client: 'static + Send + Sync;
     type Item: Serialize;
 
+    /// Fetches the matched transactions along with the block each of them
+    /// was mined in, as far as the node knows it.
     fn expand_result(
         result: &QueryResult,
         client: Arc<Self::Client>,
-    ) -> Result<Vec<Self::Item>, Error>;
+    ) -> Result<Vec<(Self::Item, Option<LatestBlock>)>, Error>;
 }
 
 pub trait ShouldExpand {
@@ -60,13 +66,14 @@ impl RouteFactory {
             + DeserializeOwned
             + Serialize
             + Send
+            + Sync
             + 'static,
-        QR: QueryRepository<Q>,
-        QRR: QueryResultRepository<Q>,
+        QR: QueryRepository<Q> + ?Sized,
     >(
         &self,
         query_repository: Arc<QR>,
-        query_result_repository: Arc<QRR>,
+        query_result_repository: Arc<NotifyingQueryResultRepository<Q>>,
+        unconfirmed_matches: Arc<UnconfirmedMatches<Q>>,
         client: Option<Arc<<Q as ExpandResult>::Client>>,
         ledger_name: &'static str,
     ) -> BoxedFilter<(impl Reply,)> {
@@ -80,6 +87,7 @@ impl RouteFactory {
         let external_url = warp::any().map(move || external_url.clone());
         let query_repository = warp::any().map(move || query_repository.clone());
         let query_result_repository = warp::any().map(move || query_result_repository.clone());
+        let unconfirmed_matches = warp::any().map(move || unconfirmed_matches.clone());
         let client = warp::any().map(move || client.clone());
 
         let json_body = warp::body::json().and_then(routes::non_empty_query);
@@ -92,9 +100,18 @@ impl RouteFactory {
             .and(json_body)
             .and_then(routes::create_query);
 
+        let stream = warp::get2()
+            .and(query_repository.clone())
+            .and(query_result_repository.clone())
+            .and(warp::path::param::<u32>())
+            .and(warp::path("events"))
+            .and(warp::path::end())
+            .and_then(routes::stream_query_results);
+
         let retrieve = warp::get2()
             .and(query_repository.clone())
             .and(query_result_repository.clone())
+            .and(unconfirmed_matches.clone())
             .and(client.clone())
             .and(warp::path::param::<u32>())
             .and(warp::query::<QueryParams>())
@@ -103,10 +120,29 @@ impl RouteFactory {
         let delete = warp::delete2()
             .and(query_repository)
             .and(query_result_repository)
+            .and(unconfirmed_matches)
             .and(warp::path::param::<u32>())
             .and_then(routes::delete_query);
 
-        path.and(create.or(retrieve).or(delete))
+        path.and(create.or(stream).or(retrieve).or(delete))
+            .recover(routes::customize_error)
+            .boxed()
+    }
+
+    pub fn create_latest_block_route<R: LatestBlockRepository + ?Sized>(
+        &self,
+        latest_block_repository: Arc<R>,
+        ledger_name: &'static str,
+    ) -> BoxedFilter<(impl Reply,)> {
+        let latest_block_repository = warp::any().map(move || latest_block_repository.clone());
+
+        warp::path("blocks")
+            .and(warp::path(ledger_name))
+            .and(warp::path("latest"))
+            .and(warp::path::end())
+            .and(warp::get2())
+            .and(latest_block_repository)
+            .and_then(routes::retrieve_latest_block)
             .recover(routes::customize_error)
             .boxed()
     }
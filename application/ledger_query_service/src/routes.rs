@@ -1,13 +1,20 @@
 use crate::{
     block_processor::Query,
+    latest_block_repository::{LatestBlock, LatestBlockRepository},
+    notifying_query_result_repository::{NotifyingQueryResultRepository, QueryResultEvent},
     query_repository::QueryRepository,
     query_result_repository::QueryResultRepository,
     route_factory::{ExpandResult, QueryParams, ShouldExpand},
+    unconfirmed_matches::UnconfirmedMatches,
 };
+use futures::Stream;
 use http_api_problem::{HttpApiProblem, HttpStatusCode};
-use hyper::StatusCode;
+use hyper::{
+    header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE},
+    Body, Response, StatusCode,
+};
 use serde::Serialize;
-use std::{error::Error as StdError, fmt, sync::Arc};
+use std::{error::Error as StdError, fmt, io, sync::Arc};
 use url::Url;
 use warp::{self, Rejection, Reply};
 
@@ -18,6 +25,7 @@ pub enum Error {
     DataExpansion,
     MissingClient,
     QueryNotFound,
+    LatestBlockUnknown,
 }
 
 #[derive(Debug)]
@@ -53,6 +61,9 @@ impl From<Error> for HttpApiProblem {
             QueryNotFound => HttpApiProblem::new("query-not-found")
                 .set_status(404)
                 .set_detail("The requested query does not exist"),
+            LatestBlockUnknown => HttpApiProblem::new("latest-block-unknown")
+                .set_status(404)
+                .set_detail("No block has been processed since the service was started"),
         }
     }
 }
@@ -84,7 +95,7 @@ pub fn non_empty_query<O, Q: Query<O>>(query: Q) -> Result<Q, Rejection> {
 }
 
 #[allow(clippy::needless_pass_by_value)]
-pub fn create_query<O, Q: Query<O> + Send, QR: QueryRepository<Q>>(
+pub fn create_query<O, Q: Query<O> + Send, QR: QueryRepository<Q> + ?Sized>(
     external_url: Url,
     query_repository: Arc<QR>,
     ledger_name: &'static str,
@@ -112,11 +123,12 @@ pub fn create_query<O, Q: Query<O> + Send, QR: QueryRepository<Q>>(
 pub fn retrieve_query<
     O,
     Q: Query<O> + Serialize + ShouldExpand + Send + ExpandResult,
-    QR: QueryRepository<Q>,
-    QRR: QueryResultRepository<Q>,
+    QR: QueryRepository<Q> + ?Sized,
+    QRR: QueryResultRepository<Q> + ?Sized,
 >(
     query_repository: Arc<QR>,
     query_result_repository: Arc<QRR>,
+    unconfirmed_matches: Arc<UnconfirmedMatches<Q>>,
     client: Option<Arc<<Q as ExpandResult>::Client>>,
     id: u32,
     query_params: QueryParams,
@@ -130,12 +142,15 @@ pub fn retrieve_query<
         Ok(query) => {
             let query_result = query_result_repository.get(id).unwrap_or_default();
             let mut result = ResponsePayload::TransactionIds(query_result.0.clone());
+            let mut mined_in = Vec::new();
 
             if Q::should_expand(&query_params) {
                 match client {
                     Some(client) => match Q::expand_result(&query_result, client) {
                         Ok(data) => {
-                            result = ResponsePayload::Transactions(data);
+                            let (transactions, blocks) = data.into_iter().unzip();
+                            result = ResponsePayload::Transactions(transactions);
+                            mined_in = blocks;
                         }
                         Err(e) => {
                             error!("Could not acquire expanded data: {:?}", e);
@@ -153,9 +168,18 @@ pub fn retrieve_query<
                 }
             }
 
+            let unconfirmed_matches = unconfirmed_matches
+                .get(id)
+                .into_iter()
+                .filter(|tx_id| !query_result.0.contains(tx_id))
+                .map(UnconfirmedMatch::new)
+                .collect();
+
             Ok(warp::reply::json(&RetrieveQueryResponse {
                 query,
                 matches: result,
+                mined_in,
+                unconfirmed_matches,
             }))
         }
         Err(e) => Err(e),
@@ -166,15 +190,17 @@ pub fn retrieve_query<
 pub fn delete_query<
     O,
     Q: Query<O> + Send,
-    QR: QueryRepository<Q>,
-    QRR: QueryResultRepository<Q>,
+    QR: QueryRepository<Q> + ?Sized,
+    QRR: QueryResultRepository<Q> + ?Sized,
 >(
     query_repository: Arc<QR>,
     query_result_repository: Arc<QRR>,
+    unconfirmed_matches: Arc<UnconfirmedMatches<Q>>,
     id: u32,
 ) -> Result<impl Reply, Rejection> {
     query_repository.delete(id);
     query_result_repository.delete(id);
+    unconfirmed_matches.delete(id);
 
     Ok(warp::reply::with_status(
         warp::reply(),
@@ -182,6 +208,61 @@ pub fn delete_query<
     ))
 }
 
+/// Streams the matches of a query as Server-Sent Events, one `match` event
+/// carrying the JSON encoded transaction id per match and one `removal` event
+/// per match that was retracted by a reorg.
+#[allow(clippy::needless_pass_by_value)]
+pub fn stream_query_results<O, Q: Query<O> + Send, QR: QueryRepository<Q> + ?Sized>(
+    query_repository: Arc<QR>,
+    query_result_repository: Arc<NotifyingQueryResultRepository<Q>>,
+    id: u32,
+) -> Result<impl Reply, Rejection> {
+    if query_repository.get(id).is_none() {
+        return Err(warp::reject::custom(HttpApiProblemStdError {
+            http_api_problem: Error::QueryNotFound.into(),
+        }));
+    }
+
+    let events = query_result_repository
+        .subscribe(id)
+        .map(|event| {
+            let (name, tx_id) = match event {
+                QueryResultEvent::Match(tx_id) => ("match", tx_id),
+                QueryResultEvent::Removal(tx_id) => ("removal", tx_id),
+            };
+            format!(
+                "event: {}\ndata: {}\n\n",
+                name,
+                serde_json::Value::String(tx_id)
+            )
+        })
+        .map_err(|()| io::Error::new(io::ErrorKind::Other, "subscription was dropped"));
+
+    let mut response = Response::new(Body::wrap_stream(events));
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+    response
+        .headers_mut()
+        .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+    Ok(response)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub fn retrieve_latest_block<R: LatestBlockRepository + ?Sized>(
+    latest_block_repository: Arc<R>,
+) -> Result<impl Reply, Rejection> {
+    latest_block_repository
+        .get()
+        .map(|latest_block| warp::reply::json(&latest_block))
+        .ok_or_else(|| {
+            warp::reject::custom(HttpApiProblemStdError {
+                http_api_problem: Error::LatestBlockUnknown.into(),
+            })
+        })
+}
+
 #[derive(Debug, Clone, Serialize)]
 #[serde(untagged)]
 enum ResponsePayload<T> {
@@ -195,8 +276,31 @@ impl<T> Default for ResponsePayload<T> {
     }
 }
 
+/// A match of a transaction that is not in a block yet. `confirmations` is
+/// always 0, it tells these apart from the confirmed `matches`.
+#[derive(Debug, Serialize, Clone)]
+struct UnconfirmedMatch {
+    id: String,
+    confirmations: u32,
+}
+
+impl UnconfirmedMatch {
+    fn new(id: String) -> Self {
+        UnconfirmedMatch {
+            id,
+            confirmations: 0,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Clone, Default)]
 pub struct RetrieveQueryResponse<Q, T> {
     query: Q,
     matches: ResponsePayload<T>,
+    /// The block each of the expanded `matches` was mined in, in the same
+    /// order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    mined_in: Vec<Option<LatestBlock>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unconfirmed_matches: Vec<UnconfirmedMatch>,
 }
@@ -1,14 +1,22 @@
 mod serde;
 
+use ::serde::de::{self, Deserialize, Deserializer};
 use bitcoin_support::Network;
 use config::{Config, ConfigError, File};
-use std::{ffi::OsStr, net::IpAddr, path::Path, time::Duration};
+use std::{
+    ffi::OsStr,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub http_api: HttpApi,
     pub bitcoin: Bitcoin,
     pub ethereum: Ethereum,
+    #[serde(default)]
+    pub storage: Storage,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -19,23 +27,101 @@ pub struct HttpApi {
     pub external_url: url::Url,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Clone)]
 pub struct Bitcoin {
     pub network: Network,
-    pub zmq_endpoint: String,
+    pub block_source: BitcoinBlockSource,
+    pub mempool: Option<BitcoinMempool>,
     // Below could be options
-    #[serde(with = "serde::url")]
     pub node_url: url::Url,
     pub node_username: String,
     pub node_password: String,
 }
 
+/// `Bitcoin` as it is written down. Configs from before `block_source` name
+/// the ZMQ endpoint to get blocks from as `zmq_endpoint` instead.
+#[derive(Deserialize)]
+struct BitcoinConfig {
+    network: Network,
+    #[serde(default)]
+    block_source: Option<BitcoinBlockSource>,
+    #[serde(default)]
+    zmq_endpoint: Option<String>,
+    #[serde(default)]
+    mempool: Option<BitcoinMempool>,
+    #[serde(with = "serde::url")]
+    node_url: url::Url,
+    node_username: String,
+    node_password: String,
+}
+
+impl<'de> Deserialize<'de> for Bitcoin {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = BitcoinConfig::deserialize(deserializer)?;
+
+        let block_source = match (config.block_source, config.zmq_endpoint) {
+            (Some(block_source), _) => block_source,
+            (None, Some(endpoint)) => BitcoinBlockSource::Zmq { endpoint },
+            (None, None) => return Err(de::Error::missing_field("block_source")),
+        };
+
+        Ok(Bitcoin {
+            network: config.network,
+            block_source,
+            mempool: config.mempool,
+            node_url: config.node_url,
+            node_username: config.node_username,
+            node_password: config.node_password,
+        })
+    }
+}
+
+/// How new blocks are learned about. `rpc_polling` only needs the RPC
+/// interface configured above, `zmq` requires bitcoind to publish `rawblock`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BitcoinBlockSource {
+    Zmq {
+        endpoint: String,
+    },
+    RpcPolling {
+        #[serde(with = "serde::duration")]
+        poll_interval_secs: Duration,
+    },
+}
+
+/// Feeds unconfirmed transactions to the queries that ask for them. bitcoind
+/// only publishes those over ZMQ, with `-zmqpubrawtx`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BitcoinMempool {
+    pub zmq_endpoint: String,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Ethereum {
     #[serde(with = "serde::url")]
     pub node_url: url::Url,
     #[serde(with = "serde::duration")]
     pub poll_interval_secs: Duration,
+    /// Whether to also poll for pending transactions, for the queries that
+    /// ask for unconfirmed matches.
+    #[serde(default)]
+    pub poll_mempool: bool,
+}
+
+/// Where registered queries, their matches and the latest block of each
+/// ledger are kept.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Storage {
+    InMemory,
+    Disk { path: PathBuf },
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Storage::InMemory
+    }
 }
 
 impl Settings {
@@ -69,4 +155,57 @@ mod tests {
         assert_that(&settings).is_ok();
     }
 
+    #[test]
+    fn storage_defaults_to_in_memory() {
+        let settings = Settings::create("./config/default.toml").unwrap();
+
+        assert_that(&settings.storage).is_equal_to(Storage::InMemory);
+    }
+
+    #[test]
+    fn can_read_rpc_polling_block_source() {
+        let mut config = Config::new();
+        config
+            .merge(File::from_str(
+                r#"
+                [block_source]
+                type = "rpc_polling"
+                poll_interval_secs = 10
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .unwrap();
+
+        let block_source = config.get::<BitcoinBlockSource>("block_source");
+
+        assert_that(&block_source)
+            .is_ok()
+            .is_equal_to(BitcoinBlockSource::RpcPolling {
+                poll_interval_secs: Duration::from_secs(10),
+            });
+    }
+
+    #[test]
+    fn given_only_zmq_endpoint_of_older_configs_block_source_is_zmq() {
+        let mut config = Config::new();
+        config
+            .merge(File::from_str(
+                r#"
+                [bitcoin]
+                network = "regtest"
+                node_url = "http://localhost:18443"
+                node_username = "bitcoin"
+                node_password = "password"
+                zmq_endpoint = "tcp://127.0.0.1:28332"
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .unwrap();
+
+        let bitcoin = config.get::<Bitcoin>("bitcoin").unwrap();
+
+        assert_that(&bitcoin.block_source).is_equal_to(BitcoinBlockSource::Zmq {
+            endpoint: String::from("tcp://127.0.0.1:28332"),
+        });
+    }
 }
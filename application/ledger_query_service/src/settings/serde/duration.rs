@@ -20,6 +20,19 @@ where
         {
             Ok(Duration::from_secs(value))
         }
+
+        // Values nested in internally tagged enums are buffered first, which
+        // turns the integers `config` hands out back into signed ones
+        fn visit_i64<E>(self, value: i64) -> Result<Duration, E>
+        where
+            E: de::Error,
+        {
+            if value < 0 {
+                return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+            }
+
+            Ok(Duration::from_secs(value as u64))
+        }
     }
 
     deserializer.deserialize_u64(Visitor)
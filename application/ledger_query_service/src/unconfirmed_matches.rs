@@ -0,0 +1,249 @@
+use crate::{
+    block_processor::{Block, Query, QueryMatchResult, Transaction},
+    query_repository::QueryRepository,
+};
+use futures::{future::join_all, Future};
+use std::{
+    collections::{HashMap, HashSet},
+    marker::PhantomData,
+    sync::{Arc, RwLock},
+};
+
+/// Matches of transactions that are still in the mempool. They are kept apart
+/// from the query results because such a transaction may be replaced or
+/// dropped before it is ever mined.
+#[derive(Debug)]
+pub struct UnconfirmedMatches<Q> {
+    /// The matching transactions along with the number of blocks mined since
+    /// they were seen
+    matches: RwLock<HashMap<u32, Vec<(String, u64)>>>,
+    last_mined: RwLock<HashSet<String>>,
+    max_age: Option<u64>,
+    phantom: PhantomData<Q>,
+}
+
+impl<Q> Default for UnconfirmedMatches<Q> {
+    fn default() -> Self {
+        UnconfirmedMatches {
+            matches: RwLock::new(HashMap::new()),
+            last_mined: RwLock::new(HashSet::new()),
+            max_age: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<Q> UnconfirmedMatches<Q> {
+    /// Drops a match once `max_age` blocks were mined without the transaction,
+    /// the mempool has most likely evicted or replaced it by then. Neither
+    /// bitcoind nor the Ethereum node tell us when that happens.
+    pub fn expiring_after(max_age: u64) -> Self {
+        UnconfirmedMatches {
+            max_age: Some(max_age),
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Vec<String> {
+        let matches = self.matches.read().unwrap();
+
+        matches
+            .get(&id)
+            .map(|tx_ids| tx_ids.iter().map(|(tx_id, _)| tx_id.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn add(&self, id: u32, tx_id: String) {
+        // bitcoind also announces the transactions of a new block as raw
+        // transactions, possibly after the block itself was processed
+        if self.last_mined.read().unwrap().contains(&tx_id) {
+            return;
+        }
+
+        let mut matches = self.matches.write().unwrap();
+        let tx_ids = matches.entry(id).or_insert_with(Vec::new);
+
+        if !tx_ids.iter().any(|(known, _)| *known == tx_id) {
+            tx_ids.push((tx_id, 0));
+        }
+    }
+
+    /// Drops the matches of all transactions in `block`, from now on they are
+    /// the block processor's business. Also drops those that waited too long.
+    pub fn remove_mined<B: Block>(&self, block: &B) {
+        let mined: HashSet<String> = block
+            .transactions()
+            .iter()
+            .map(Transaction::transaction_id)
+            .collect();
+        let max_age = self.max_age;
+
+        let mut matches = self.matches.write().unwrap();
+        for tx_ids in matches.values_mut() {
+            tx_ids.retain(|(tx_id, _)| !mined.contains(tx_id));
+            tx_ids.iter_mut().for_each(|(_, age)| *age += 1);
+            tx_ids.retain(|(_, age)| max_age.map_or(true, |max_age| *age < max_age));
+        }
+
+        *self.last_mined.write().unwrap() = mined;
+    }
+
+    pub fn delete(&self, id: u32) {
+        let mut matches = self.matches.write().unwrap();
+
+        matches.remove(&id);
+    }
+}
+
+/// Matches a transaction from the mempool against all queries that asked for
+/// unconfirmed matches.
+pub fn match_unconfirmed_transaction<T: Transaction, Q: Query<T> + Send + Sync>(
+    queries: &dyn QueryRepository<Q>,
+    unconfirmed_matches: Arc<UnconfirmedMatches<Q>>,
+    transaction: &T,
+) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+    trace!("Processing unconfirmed {:?}", transaction);
+    let tx_id = transaction.transaction_id();
+
+    let matches: Vec<_> = queries
+        .all()
+        .filter(|(_, query)| query.include_unconfirmed())
+        .map(|(query_id, query)| {
+            let tx_id = tx_id.clone();
+            let unconfirmed_matches = Arc::clone(&unconfirmed_matches);
+
+            query.matches(transaction).map(move |result| {
+                if let QueryMatchResult::Yes { .. } = result {
+                    trace!(
+                        "Unconfirmed transaction {} matches Query-ID: {:?}",
+                        tx_id,
+                        query_id
+                    );
+                    unconfirmed_matches.add(query_id, tx_id);
+                }
+            })
+        })
+        .collect();
+
+    Box::new(join_all(matches).map(|_| ()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::in_memory_query_repository::InMemoryQueryRepository;
+    use spectral::prelude::*;
+
+    #[derive(Clone, Debug)]
+    struct TransactionQuery {
+        transaction_id: u8,
+        include_unconfirmed: bool,
+    }
+
+    impl Query<TestTransaction> for TransactionQuery {
+        fn matches(
+            &self,
+            transaction: &TestTransaction,
+        ) -> Box<dyn Future<Item = QueryMatchResult, Error = ()> + Send> {
+            if self.transaction_id == transaction.0 {
+                Box::new(futures::future::ok(QueryMatchResult::yes()))
+            } else {
+                Box::new(futures::future::ok(QueryMatchResult::no()))
+            }
+        }
+
+        fn is_empty(&self) -> bool {
+            false
+        }
+
+        fn include_unconfirmed(&self) -> bool {
+            self.include_unconfirmed
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestTransaction(u8);
+
+    impl Transaction for TestTransaction {
+        fn transaction_id(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct TestBlock(Vec<TestTransaction>);
+
+    impl Block for TestBlock {
+        type Transaction = TestTransaction;
+
+        fn blockhash(&self) -> String {
+            String::from("block")
+        }
+        fn prev_blockhash(&self) -> String {
+            String::from("parent")
+        }
+        fn height(&self) -> u64 {
+            1
+        }
+        fn transactions(&self) -> &[TestTransaction] {
+            self.0.as_slice()
+        }
+    }
+
+    #[test]
+    fn given_query_including_unconfirmed_matches_transaction_until_mined() {
+        let queries = InMemoryQueryRepository::default();
+        let opted_in = queries
+            .save(TransactionQuery {
+                transaction_id: 1,
+                include_unconfirmed: true,
+            })
+            .unwrap();
+        let opted_out = queries
+            .save(TransactionQuery {
+                transaction_id: 1,
+                include_unconfirmed: false,
+            })
+            .unwrap();
+        let unconfirmed_matches = Arc::new(UnconfirmedMatches::default());
+
+        match_unconfirmed_transaction(
+            &queries,
+            Arc::clone(&unconfirmed_matches),
+            &TestTransaction(1),
+        )
+        .wait()
+        .unwrap();
+
+        assert_that(&unconfirmed_matches.get(opted_in)).is_equal_to(vec![String::from("1")]);
+        assert_that(&unconfirmed_matches.get(opted_out)).is_empty();
+
+        unconfirmed_matches.remove_mined(&TestBlock(vec![TestTransaction(1)]));
+
+        assert_that(&unconfirmed_matches.get(opted_in)).is_empty();
+    }
+
+    #[test]
+    fn given_transaction_stays_in_mempool_for_too_long_its_match_expires() {
+        let unconfirmed_matches = UnconfirmedMatches::<TransactionQuery>::expiring_after(2);
+
+        unconfirmed_matches.add(1, String::from("1"));
+        unconfirmed_matches.remove_mined(&TestBlock(vec![]));
+
+        assert_that(&unconfirmed_matches.get(1)).is_equal_to(vec![String::from("1")]);
+
+        unconfirmed_matches.remove_mined(&TestBlock(vec![]));
+
+        assert_that(&unconfirmed_matches.get(1)).is_empty();
+    }
+
+    #[test]
+    fn given_transaction_of_last_block_announced_late_it_is_not_added() {
+        let unconfirmed_matches = UnconfirmedMatches::<TransactionQuery>::default();
+
+        unconfirmed_matches.remove_mined(&TestBlock(vec![TestTransaction(1)]));
+        unconfirmed_matches.add(1, String::from("1"));
+
+        assert_that(&unconfirmed_matches.get(1)).is_empty();
+    }
+}